@@ -0,0 +1,384 @@
+//! Linux capabilities of thread, by capget/capset syscalls (no libcap)
+//!
+//! Ref [capabilities(7)](https://man7.org/linux/man-pages/man7/capabilities.7.html)
+
+use std::{
+    fmt::Debug,
+    ops::{BitAnd, BitOr, Sub},
+};
+
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    errno::{self, PosixError},
+    unistd::Pid,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/// _LINUX_CAPABILITY_VERSION_3, 64 bits capabilities in two u32
+const CAPABILITY_VERSION_3: u32 = 0x20080522;
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// CAP_XXX
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter)]
+#[derive_to_bits(u32)]
+#[repr(u32)]
+pub enum Capability {
+    Chown = 0,
+    /// Bypass file read, write, execute permission checks
+    DacOverride = 1,
+    DacReadSearch = 2,
+    Fowner = 3,
+    Fsetid = 4,
+    Kill = 5,
+    Setgid = 6,
+    Setuid = 7,
+    Setpcap = 8,
+    LinuxImmutable = 9,
+    /// Bind port below 1024
+    NetBindService = 10,
+    NetBroadcast = 11,
+    /// Configure interfaces, routes, firewall, ...
+    NetAdmin = 12,
+    /// RAW and PACKET sockets
+    NetRaw = 13,
+    IpcLock = 14,
+    IpcOwner = 15,
+    SysModule = 16,
+    SysRawio = 17,
+    SysChroot = 18,
+    SysPtrace = 19,
+    SysPacct = 20,
+    /// Mount, namespaces, sethostname, ...
+    SysAdmin = 21,
+    SysBoot = 22,
+    SysNice = 23,
+    SysResource = 24,
+    SysTime = 25,
+    SysTtyConfig = 26,
+    Mknod = 27,
+    Lease = 28,
+    AuditWrite = 29,
+    AuditControl = 30,
+    Setfcap = 31,
+    MacOverride = 32,
+    MacAdmin = 33,
+    Syslog = 34,
+    WakeAlarm = 35,
+    BlockSuspend = 36,
+    AuditRead = 37,
+    Perfmon = 38,
+    Bpf = 39,
+    CheckpointRestore = 40,
+}
+
+/// Set of `Capability`
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(u64)]
+#[repr(transparent)]
+pub struct CapSet(u64);
+
+/// Capability sets of thread
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// Used for permission check
+    pub effective: CapSet,
+    /// Limit of `effective` and `inheritable` (once dropped, it can't be
+    /// regained)
+    pub permitted: CapSet,
+    /// Preserved across execve (for program with the file capabilities)
+    pub inheritable: CapSet,
+}
+
+/// struct __user_cap_header_struct
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: i32,
+}
+
+/// struct __user_cap_data_struct
+#[derive(Default, Clone, Copy)]
+#[repr(C)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl Capability {
+    fn mask(self) -> u64 {
+        1 << self.to_bits()
+    }
+}
+
+impl CapSet {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    /// All capabilities known
+    pub fn all() -> Self {
+        Capability::iter().collect()
+    }
+
+    pub fn insert(&mut self, cap: Capability) {
+        self.0 |= cap.mask();
+    }
+
+    pub fn remove(&mut self, cap: Capability) {
+        self.0 &= !cap.mask();
+    }
+
+    pub fn contains(&self, cap: Capability) -> bool {
+        self.0 & cap.mask() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// All of `other` are in `self`
+    pub fn is_superset(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Capability> + '_ {
+        Capability::iter().filter(|cap| self.contains(*cap))
+    }
+}
+
+impl FromIterator<Capability> for CapSet {
+    fn from_iter<T: IntoIterator<Item = Capability>>(iter: T) -> Self {
+        let mut set = Self::new();
+
+        for cap in iter {
+            set.insert(cap);
+        }
+
+        set
+    }
+}
+
+impl From<Capability> for CapSet {
+    fn from(cap: Capability) -> Self {
+        Self(cap.mask())
+    }
+}
+
+impl BitOr<Capability> for CapSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Capability) -> Self::Output {
+        Self(self.0 | rhs.mask())
+    }
+}
+
+impl BitOr for Capability {
+    type Output = CapSet;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        CapSet(self.mask() | rhs.mask())
+    }
+}
+
+impl BitOr for CapSet {
+    type Output = Self;
+
+    /// Union
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for CapSet {
+    type Output = Self;
+
+    /// Intersection
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Sub for CapSet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+impl Debug for CapSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl Capabilities {
+    /// capget, of thread `pid` (`None` for the calling thread)
+    pub fn get(pid: Option<Pid>) -> errno::Result<Self> {
+        let mut header = CapUserHeader {
+            version: CAPABILITY_VERSION_3,
+            pid: pid.map(Pid::as_raw).unwrap_or(0),
+        };
+        let mut data = [CapUserData::default(); 2];
+
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_capget,
+                &mut header as *mut CapUserHeader,
+                data.as_mut_ptr(),
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        let join = |lo: u32, hi: u32| CapSet((hi as u64) << 32 | lo as u64);
+
+        Ok(Self {
+            effective: join(data[0].effective, data[1].effective),
+            permitted: join(data[0].permitted, data[1].permitted),
+            inheritable: join(data[0].inheritable, data[1].inheritable),
+        })
+    }
+
+    /// capset of the calling thread (only)
+    ///
+    /// EPERM if it adds capabilities to `permitted`, or `effective`,
+    /// `inheritable` exceed `permitted` (without CAP_SETPCAP for
+    /// `inheritable`).
+    pub fn set(&self) -> errno::Result<()> {
+        let mut header = CapUserHeader {
+            version: CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+
+        let split = |set: CapSet| (set.0 as u32, (set.0 >> 32) as u32);
+
+        let (e0, e1) = split(self.effective);
+        let (p0, p1) = split(self.permitted);
+        let (i0, i1) = split(self.inheritable);
+
+        let data = [
+            CapUserData {
+                effective: e0,
+                permitted: p0,
+                inheritable: i0,
+            },
+            CapUserData {
+                effective: e1,
+                permitted: p1,
+                inheritable: i1,
+            },
+        ];
+
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_capset,
+                &mut header as *mut CapUserHeader,
+                data.as_ptr(),
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(())
+    }
+
+    /// Drop all capabilities of the calling thread except `keep` (e.g.
+    /// CAP_NET_RAW of raw socket tool), which are made effective
+    ///
+    /// EPERM if any of `keep` isn't permitted.
+    pub fn keep_only(keep: CapSet) -> errno::Result<()> {
+        let caps = Self::get(None)?;
+
+        if !caps.permitted.is_superset(keep) {
+            Err(PosixError::EPERM)?
+        }
+
+        Self {
+            effective: keep,
+            permitted: keep,
+            inheritable: caps.inheritable & keep,
+        }
+        .set()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_cap_set() {
+        let mut set = Capability::NetRaw | Capability::NetAdmin;
+
+        assert!(set.contains(Capability::NetRaw));
+        assert!(!set.contains(Capability::SysAdmin));
+
+        set.remove(Capability::NetAdmin);
+
+        assert_eq!(set, CapSet::from(Capability::NetRaw));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [Capability::NetRaw]);
+        assert!(CapSet::all().is_superset(set));
+        assert_eq!(CapSet::all().iter().count(), 41);
+        assert_eq!(format!("{set:?}"), "{NetRaw}");
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let caps = Capabilities::get(None).unwrap();
+
+        println!("{caps:#?}");
+
+        assert!(caps.permitted.is_superset(caps.effective));
+        assert_eq!(Capabilities::get(Some(Pid::this())).unwrap(), caps);
+
+        // capabilities are per-thread
+        thread::spawn(move || {
+            let keep = CapSet::from(Capability::NetRaw);
+
+            if !caps.permitted.contains(Capability::NetRaw) {
+                assert_eq!(
+                    Capabilities::keep_only(keep),
+                    Err(PosixError::EPERM)
+                );
+                return;
+            }
+
+            Capabilities::keep_only(keep).unwrap();
+
+            let caps = Capabilities::get(None).unwrap();
+
+            assert_eq!(caps.permitted, keep);
+            assert_eq!(caps.effective, keep);
+
+            // dropped permanently
+            let regain = Capabilities {
+                permitted: keep | Capability::NetAdmin,
+                ..caps
+            };
+
+            assert_eq!(regain.set(), Err(PosixError::EPERM));
+        })
+        .join()
+        .unwrap();
+    }
+}
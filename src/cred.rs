@@ -0,0 +1,408 @@
+//! User and group credentials of process
+//!
+//! Ref [credentials(7)](https://man7.org/linux/man-pages/man7/credentials.7.html)
+
+use std::{
+    ffi::{CStr, CString, c_char, c_int},
+    mem::zeroed,
+    path::PathBuf,
+    ptr::null_mut,
+};
+
+use libc::{ERANGE, gid_t, group, passwd, uid_t};
+
+use crate::errno::{self, PosixError};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Entry of user database (/etc/passwd, ...)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub name: String,
+    pub uid: uid_t,
+    /// Primary group
+    pub gid: gid_t,
+    pub dir: PathBuf,
+    pub shell: PathBuf,
+}
+
+/// Entry of group database (/etc/group, ...)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    pub gid: gid_t,
+    pub members: Vec<String>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl User {
+    /// getpwnam_r, `None` if there is no such user
+    pub fn from_name(name: &str) -> errno::Result<Option<Self>> {
+        let name = CString::new(name).map_err(|_| PosixError::EINVAL)?;
+
+        lookup(|pwd: &mut passwd, buf, res| unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                res,
+            )
+        })
+    }
+
+    /// getpwuid_r
+    pub fn from_uid(uid: uid_t) -> errno::Result<Option<Self>> {
+        lookup(|pwd: &mut passwd, buf, res| unsafe {
+            libc::getpwuid_r(uid, pwd, buf.as_mut_ptr(), buf.len(), res)
+        })
+    }
+}
+
+impl From<&passwd> for User {
+    fn from(pwd: &passwd) -> Self {
+        unsafe {
+            Self {
+                name: from_c_str(pwd.pw_name),
+                uid: pwd.pw_uid,
+                gid: pwd.pw_gid,
+                dir: from_c_str(pwd.pw_dir).into(),
+                shell: from_c_str(pwd.pw_shell).into(),
+            }
+        }
+    }
+}
+
+impl Group {
+    /// getgrnam_r, `None` if there is no such group
+    pub fn from_name(name: &str) -> errno::Result<Option<Self>> {
+        let name = CString::new(name).map_err(|_| PosixError::EINVAL)?;
+
+        lookup(|grp: &mut group, buf, res| unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                grp,
+                buf.as_mut_ptr(),
+                buf.len(),
+                res,
+            )
+        })
+    }
+
+    /// getgrgid_r
+    pub fn from_gid(gid: gid_t) -> errno::Result<Option<Self>> {
+        lookup(|grp: &mut group, buf, res| unsafe {
+            libc::getgrgid_r(gid, grp, buf.as_mut_ptr(), buf.len(), res)
+        })
+    }
+}
+
+impl From<&group> for Group {
+    fn from(grp: &group) -> Self {
+        let mut members = vec![];
+
+        unsafe {
+            let mut p = grp.gr_mem;
+
+            while !p.is_null() && !(*p).is_null() {
+                members.push(from_c_str(*p));
+                p = p.add(1);
+            }
+
+            Self {
+                name: from_c_str(grp.gr_name),
+                gid: grp.gr_gid,
+                members,
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Real uid
+pub fn getuid() -> uid_t {
+    unsafe { libc::getuid() }
+}
+
+/// Effective uid, which is used for permission check
+pub fn geteuid() -> uid_t {
+    unsafe { libc::geteuid() }
+}
+
+pub fn getgid() -> gid_t {
+    unsafe { libc::getgid() }
+}
+
+pub fn getegid() -> gid_t {
+    unsafe { libc::getegid() }
+}
+
+/// Return (real, effective, saved set-user-ID)
+pub fn getresuid() -> errno::Result<(uid_t, uid_t, uid_t)> {
+    let (mut r, mut e, mut s) = (0, 0, 0);
+
+    let ret = unsafe { libc::getresuid(&mut r, &mut e, &mut s) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok((r, e, s))
+}
+
+/// Return (real, effective, saved set-group-ID)
+pub fn getresgid() -> errno::Result<(gid_t, gid_t, gid_t)> {
+    let (mut r, mut e, mut s) = (0, 0, 0);
+
+    let ret = unsafe { libc::getresgid(&mut r, &mut e, &mut s) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok((r, e, s))
+}
+
+/// Set all of real, effective, saved uid if caller is privileged
+/// (CAP_SETUID), else only effective uid (to real or saved one)
+pub fn setuid(uid: uid_t) -> errno::Result<()> {
+    let ret = unsafe { libc::setuid(uid) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Like `setuid` (CAP_SETGID)
+pub fn setgid(gid: gid_t) -> errno::Result<()> {
+    let ret = unsafe { libc::setgid(gid) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// `None` keeps it unchanged
+///
+/// It applies to all threads of process (synchronized by libc).
+pub fn setresuid(
+    ruid: Option<uid_t>,
+    euid: Option<uid_t>,
+    suid: Option<uid_t>,
+) -> errno::Result<()> {
+    let ret = unsafe {
+        libc::setresuid(
+            ruid.unwrap_or(uid_t::MAX),
+            euid.unwrap_or(uid_t::MAX),
+            suid.unwrap_or(uid_t::MAX),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// `None` keeps it unchanged
+pub fn setresgid(
+    rgid: Option<gid_t>,
+    egid: Option<gid_t>,
+    sgid: Option<gid_t>,
+) -> errno::Result<()> {
+    let ret = unsafe {
+        libc::setresgid(
+            rgid.unwrap_or(gid_t::MAX),
+            egid.unwrap_or(gid_t::MAX),
+            sgid.unwrap_or(gid_t::MAX),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Supplementary group ids
+pub fn getgroups() -> errno::Result<Vec<gid_t>> {
+    loop {
+        let n = unsafe { libc::getgroups(0, null_mut()) };
+
+        if n == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        let mut groups = vec![0; n as usize];
+
+        let ret = unsafe { libc::getgroups(n, groups.as_mut_ptr()) };
+
+        if ret == -1 {
+            match errno::last_os_error() {
+                // changed between two calls
+                PosixError::EINVAL => continue,
+                err => Err(err)?,
+            }
+        }
+
+        groups.truncate(ret as usize);
+
+        break Ok(groups);
+    }
+}
+
+/// Replace supplementary group ids (CAP_SETGID)
+pub fn setgroups(groups: &[gid_t]) -> errno::Result<()> {
+    let ret = unsafe { libc::setgroups(groups.len(), groups.as_ptr()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Drop root privileges permanently to `user` and `group`, e.g. after
+/// binding low ports
+///
+/// Supplementary groups are set to `group` only, then gid, then uid (which
+/// loses CAP_SETGID), all of real, effective and saved ids are changed.
+/// The drop is verified, and EPERM if privileges could be regained.
+///
+/// ENOENT if `user` or `group` doesn't exist.
+pub fn drop_privileges(user: &str, group: &str) -> errno::Result<()> {
+    let uid = User::from_name(user)?.ok_or(PosixError::ENOENT)?.uid;
+    let gid = Group::from_name(group)?.ok_or(PosixError::ENOENT)?.gid;
+
+    setgroups(&[gid])?;
+    setresgid(Some(gid), Some(gid), Some(gid))?;
+    setresuid(Some(uid), Some(uid), Some(uid))?;
+
+    if getresuid()? != (uid, uid, uid) || getresgid()? != (gid, gid, gid) {
+        Err(PosixError::EPERM)?
+    }
+
+    if uid != 0 && unsafe { libc::setuid(0) } != -1 {
+        Err(PosixError::EPERM)?
+    }
+
+    Ok(())
+}
+
+/// Retry `f` (getxxx_r) with larger buffer on ERANGE
+fn lookup<T, R>(
+    mut f: impl FnMut(&mut T, &mut [c_char], *mut *mut T) -> c_int,
+) -> errno::Result<Option<R>>
+where
+    R: for<'a> From<&'a T>,
+{
+    let mut buf = vec![0 as c_char; 1024];
+
+    loop {
+        let mut ent: T = unsafe { zeroed() };
+        let mut res: *mut T = null_mut();
+
+        match f(&mut ent, &mut buf, &mut res) {
+            0 => (),
+            ERANGE => {
+                let len = buf.len() * 2;
+                buf.resize(len, 0);
+                continue;
+            }
+            code => {
+                Err(PosixError::try_from(code).unwrap_or(PosixError::EIO))?
+            }
+        }
+
+        break Ok((!res.is_null()).then(|| R::from(&ent)));
+    }
+}
+
+unsafe fn from_c_str(p: *const c_char) -> String {
+    if p.is_null() {
+        return String::new();
+    }
+
+    unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{ForkResult, WaitFlags, WaitStatus, fork, waitpid};
+
+    #[test]
+    fn test_lookup() {
+        let root = User::from_uid(0).unwrap().unwrap();
+
+        assert_eq!(root.name, "root");
+        assert_eq!(User::from_name("root").unwrap(), Some(root.clone()));
+        assert_eq!(User::from_name("lxtest-no-such-user").unwrap(), None);
+        assert_eq!(User::from_name("a\0b"), Err(PosixError::EINVAL));
+
+        let group = Group::from_gid(root.gid).unwrap().unwrap();
+
+        assert_eq!(Group::from_name(&group.name).unwrap(), Some(group));
+
+        let (r, e, _) = getresuid().unwrap();
+
+        assert_eq!((r, e), (getuid(), geteuid()));
+        assert!(getgroups().is_ok());
+    }
+
+    #[test]
+    fn test_drop_privileges() {
+        let Some(nobody) = User::from_name("nobody").unwrap()
+        else {
+            return;
+        };
+        let group = Group::from_gid(nobody.gid).unwrap().unwrap();
+
+        if geteuid() != 0 {
+            assert_eq!(
+                drop_privileges(&nobody.name, &group.name),
+                Err(PosixError::EPERM)
+            );
+            return;
+        }
+
+        // credentials are process wide, drop them in child, which only
+        // makes async-signal-safe calls (ids are resolved before fork)
+        let (uid, gid) = (nobody.uid, group.gid);
+
+        let child = match unsafe { fork() }.unwrap() {
+            ForkResult::Parent { child } => child,
+            ForkResult::Child => unsafe {
+                let mut groups = [0; 2];
+
+                let ok = setgroups(&[gid]).is_ok()
+                    && setresgid(Some(gid), Some(gid), Some(gid)).is_ok()
+                    && setresuid(Some(uid), Some(uid), Some(uid)).is_ok()
+                    && getresuid() == Ok((uid, uid, uid))
+                    && libc::getgroups(2, groups.as_mut_ptr()) == 1
+                    && groups[0] == gid
+                    && setgroups(&[0]) == Err(PosixError::EPERM);
+
+                libc::_exit(if ok { 0 } else { 1 })
+            },
+        };
+
+        assert_eq!(
+            waitpid(Some(child), WaitFlags::new()),
+            Ok(Some(WaitStatus::Exited(child, 0)))
+        );
+    }
+}
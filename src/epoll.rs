@@ -1,24 +1,52 @@
+pub mod reactor;
+
 use std::{
     ffi::{c_int, c_void},
     fmt::Debug,
     ops::{BitAnd, BitOr, BitOrAssign},
-    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    ptr::null,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
 };
 
 use libc::{
-    EPOLL_CLOEXEC, EPOLL_CTL_ADD, epoll_event,
+    EPOLL_CLOEXEC, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD,
+    SYS_epoll_pwait2, epoll_event, timespec,
 };
 use m6tobytes::derive_to_bits;
 use strum::{EnumIter, IntoEnumIterator};
 
-use crate::{errno, signal::SignalSet};
+use crate::{
+    errno::{self, PosixError},
+    signal::SignalSet,
+};
 
 
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/// High 2 bits of `EpollData` as tag of `EpollToken`
+const TOKEN_TAG_SHIFT: u32 = 62;
+const TOKEN_VALUE_MASK: u64 = (1 << TOKEN_TAG_SHIFT) - 1;
+
+const TOKEN_TAG_U64: u64 = 0;
+const TOKEN_TAG_FD: u64 = 1;
+const TOKEN_TAG_INDEX: u64 = 2;
+
+/// sizeof kernel sigset_t (_NSIG / 8), not the one of libc
+const KERNEL_SIGSET_SIZE: usize = 8;
+
+/// Set once epoll_pwait2 returns ENOSYS (before Linux 5.11)
+static PWAIT2_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
 ////////////////////////////////////////////////////////////////////////////////
 //// Structures
 
+/// struct epoll_event, packed on x86 like kernel does
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
+#[cfg_attr(any(target_arch = "x86", target_arch = "x86_64"), repr(packed))]
 pub struct EpollEvent {
     pub events: EpollEvents,
     pub data: EpollData,
@@ -71,17 +99,55 @@ pub union EpollData {
     pub ptr: *mut c_void,
 }
 
+/// Tagged user data, encoded into `EpollData` (tag in high 2 bits)
+///
+/// `U64` and `Index` have only 62 bits available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EpollToken {
+    Fd(c_int),
+    U64(u64),
+    /// e.g. index of slab of handlers
+    Index(usize),
+}
+
+/// EPOLL_XXX of epoll_create1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(i32)]
+#[repr(i32)]
+pub enum EpollCreateFlag {
+    CloExec = EPOLL_CLOEXEC,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(i32)]
+#[repr(transparent)]
+pub struct EpollCreateFlags(i32);
+
+#[derive(Debug)]
 pub struct Epoll {
     epfd: OwnedFd,
 }
 
+/// Owned buffer of `Epoll::wait_events`, capacity is max events each wait
+#[derive(Debug, Clone)]
+pub struct Events {
+    buf: Vec<EpollEvent>,
+    /// Number of fired events of last wait
+    len: usize,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 //// Implementations
 
 impl Epoll {
     /// create with EPOLL_CLOEXEC flag
     pub fn create() -> errno::Result<Self> {
-        let ret = unsafe { libc::epoll_create1(EPOLL_CLOEXEC) };
+        Self::create_with(EpollCreateFlag::CloExec.into())
+    }
+
+    /// Empty `flags` to be inherited across exec
+    pub fn create_with(flags: EpollCreateFlags) -> errno::Result<Self> {
+        let ret = unsafe { libc::epoll_create1(flags.to_bits()) };
 
         if ret == -1 {
             Err(errno::last_os_error())?
@@ -92,13 +158,52 @@ impl Epoll {
         })
     }
 
+    /// `fd` should refer to an epoll instance
+    pub fn from_owned_fd(epfd: OwnedFd) -> Self {
+        Self { epfd }
+    }
+
+    pub fn into_owned_fd(self) -> OwnedFd {
+        self.epfd
+    }
+
     pub fn insert(&mut self, fd: BorrowedFd, event: EpollEvent) -> errno::Result<()> {
+        self.ctl(EPOLL_CTL_ADD, fd, Some(event))
+    }
+
+    /// EPOLL_CTL_MOD, replace events and data of registered `fd` (re-arm
+    /// `EpollFlag::Oneshot` one)
+    pub fn modify(
+        &mut self,
+        fd: BorrowedFd,
+        event: EpollEvent,
+    ) -> errno::Result<()> {
+        self.ctl(EPOLL_CTL_MOD, fd, Some(event))
+    }
+
+    /// EPOLL_CTL_DEL
+    ///
+    /// Registration is removed automatically only if all fds refer to the
+    /// open file (e.g. dup-ed) are closed, so remove it before closing.
+    pub fn remove(&mut self, fd: BorrowedFd) -> errno::Result<()> {
+        self.ctl(EPOLL_CTL_DEL, fd, None)
+    }
+
+    fn ctl(
+        &mut self,
+        op: c_int,
+        fd: BorrowedFd,
+        event: Option<EpollEvent>,
+    ) -> errno::Result<()> {
+        // ignored by EPOLL_CTL_DEL
+        let mut event = event.unwrap_or_default();
+
         let ret = unsafe {
             libc::epoll_ctl(
                 self.epfd.as_raw_fd(),
-                EPOLL_CTL_ADD,
+                op,
                 fd.as_raw_fd(),
-                &event as *const EpollEvent as *mut epoll_event,
+                &mut event as *mut EpollEvent as *mut epoll_event,
             )
         };
 
@@ -109,6 +214,72 @@ impl Epoll {
         Ok(())
     }
 
+    /// timeout: ms, -1 for infinite
+    pub fn wait<'a>(
+        &self,
+        events: &'a mut [EpollEvent],
+        timeout: c_int,
+    ) -> errno::Result<&'a [EpollEvent]> {
+        epoll_wait(self.epfd.as_fd(), events, timeout)
+    }
+
+    /// `wait` with `None` for infinite, round up to ms
+    pub fn wait_timeout<'a>(
+        &self,
+        events: &'a mut [EpollEvent],
+        timeout: Option<Duration>,
+    ) -> errno::Result<&'a [EpollEvent]> {
+        self.wait(events, duration_to_ms(timeout))
+    }
+
+    /// `wait_timeout` into `events`, return number of fired events
+    pub fn wait_events(
+        &self,
+        events: &mut Events,
+        timeout: Option<Duration>,
+    ) -> errno::Result<usize> {
+        events.len = 0;
+        events.len = self.wait_timeout(&mut events.buf, timeout)?.len();
+
+        Ok(events.len)
+    }
+
+    /// `wait_timeout` retried on EINTR with the remaining timeout
+    pub fn wait_uninterrupted<'a>(
+        &self,
+        events: &'a mut [EpollEvent],
+        timeout: Option<Duration>,
+    ) -> errno::Result<&'a [EpollEvent]> {
+        let n = retry_eintr(timeout, |remaining| {
+            Ok(self.wait_timeout(events, remaining)?.len())
+        })?;
+
+        Ok(&events[..n])
+    }
+
+    /// `pwait2` retried on EINTR with the remaining timeout
+    pub fn pwait2_uninterrupted<'a>(
+        &self,
+        events: &'a mut [EpollEvent],
+        timeout: Option<Duration>,
+        sigmask: Option<SignalSet>,
+    ) -> errno::Result<&'a [EpollEvent]> {
+        let n = retry_eintr(timeout, |remaining| {
+            Ok(self.pwait2(events, remaining, sigmask)?.len())
+        })?;
+
+        Ok(&events[..n])
+    }
+
+    /// `wait_events` retried on EINTR with the remaining timeout
+    pub fn wait_events_uninterrupted(
+        &self,
+        events: &mut Events,
+        timeout: Option<Duration>,
+    ) -> errno::Result<usize> {
+        retry_eintr(timeout, |remaining| self.wait_events(events, remaining))
+    }
+
     /// timeout:  ms
     pub fn pwait<'a>(
         &self,
@@ -118,6 +289,156 @@ impl Epoll {
     ) -> errno::Result<&'a [EpollEvent]> {
         epoll_pwait(self.epfd.as_fd(), events, timeout, sigmask)
     }
+
+    /// `pwait` with ns timeout, `None` for infinite
+    pub fn pwait2<'a>(
+        &self,
+        events: &'a mut [EpollEvent],
+        timeout: Option<Duration>,
+        sigmask: Option<SignalSet>,
+    ) -> errno::Result<&'a [EpollEvent]> {
+        epoll_pwait2(self.epfd.as_fd(), events, timeout, sigmask)
+    }
+}
+
+impl Events {
+    /// At least 1
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: vec![EpollEvent::default(); capacity.max(1)],
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Resize capacity (at least 1), fired events are cleared
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.buf.resize(capacity.max(1), EpollEvent::default());
+        self.len = 0;
+    }
+
+    /// Whether last wait filled all capacity (more events may be ready)
+    pub fn is_full(&self) -> bool {
+        self.len == self.buf.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn as_slice(&self) -> &[EpollEvent] {
+        &self.buf[..self.len]
+    }
+
+    /// Fired events of last wait
+    pub fn iter(&self) -> std::slice::Iter<'_, EpollEvent> {
+        self.as_slice().iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Events {
+    type Item = &'a EpollEvent;
+    type IntoIter = std::slice::Iter<'a, EpollEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl From<OwnedFd> for Epoll {
+    fn from(epfd: OwnedFd) -> Self {
+        Self::from_owned_fd(epfd)
+    }
+}
+
+impl From<Epoll> for OwnedFd {
+    fn from(epoll: Epoll) -> Self {
+        epoll.into_owned_fd()
+    }
+}
+
+impl AsRawFd for Epoll {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epfd.as_raw_fd()
+    }
+}
+
+impl AsFd for Epoll {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.epfd.as_fd()
+    }
+}
+
+impl EpollEvent {
+    /// EOVERFLOW if `token` doesn't fit in 62 bits
+    pub fn with_token(
+        events: EpollEvents,
+        token: EpollToken,
+    ) -> errno::Result<Self> {
+        Ok(Self {
+            events,
+            data: token.try_into()?,
+        })
+    }
+
+    /// `None` if data isn't encoded from `EpollToken`
+    pub fn token(&self) -> Option<EpollToken> {
+        { self.data }.token()
+    }
+}
+
+impl EpollCreateFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<EpollCreateFlag> for EpollCreateFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: EpollCreateFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitAnd<EpollCreateFlag> for &EpollCreateFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: EpollCreateFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<EpollCreateFlags> for EpollCreateFlag {
+    fn into(self) -> EpollCreateFlags {
+        EpollCreateFlags(self.to_bits())
+    }
+}
+
+impl Debug for EpollCreateFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in EpollCreateFlag::iter().filter(|e| self & *e).enumerate()
+        {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for EpollEvent {
@@ -144,25 +465,25 @@ impl BitAnd<EpollFlag> for &EpollEvent {
 
 impl PartialEq<EpollFlag> for EpollEvent {
     fn eq(&self, other: &EpollFlag) -> bool {
-        self.events.eq(other)
+        { self.events }.eq(other)
     }
 }
 
 impl PartialOrd<EpollFlag> for EpollEvent {
     fn partial_cmp(&self, other: &EpollFlag) -> Option<std::cmp::Ordering> {
-        self.events.partial_cmp(other)
+        { self.events }.partial_cmp(other)
     }
 }
 
 impl PartialEq<EpollFlag> for &EpollEvent {
     fn eq(&self, other: &EpollFlag) -> bool {
-        self.events.eq(other)
+        { self.events }.eq(other)
     }
 }
 
 impl PartialOrd<EpollFlag> for &EpollEvent {
     fn partial_cmp(&self, other: &EpollFlag) -> Option<std::cmp::Ordering> {
-        self.events.partial_cmp(other)
+        { self.events }.partial_cmp(other)
     }
 }
 
@@ -184,6 +505,39 @@ impl EpollData {
     pub fn new_as_fd(fd: c_int) -> Self {
         Self { fd }
     }
+
+    /// Decode `EpollToken`, `None` for unknown tag
+    pub fn token(&self) -> Option<EpollToken> {
+        let raw = unsafe { self.u64 };
+        let value = raw & TOKEN_VALUE_MASK;
+
+        Some(match raw >> TOKEN_TAG_SHIFT {
+            TOKEN_TAG_U64 => EpollToken::U64(value),
+            TOKEN_TAG_FD => EpollToken::Fd(value as u32 as c_int),
+            TOKEN_TAG_INDEX => EpollToken::Index(value as usize),
+            _ => None?,
+        })
+    }
+}
+
+impl TryFrom<EpollToken> for EpollData {
+    type Error = PosixError;
+
+    fn try_from(token: EpollToken) -> Result<Self, Self::Error> {
+        let (tag, value) = match token {
+            EpollToken::Fd(fd) => (TOKEN_TAG_FD, fd as u32 as u64),
+            EpollToken::U64(v) => (TOKEN_TAG_U64, v),
+            EpollToken::Index(i) => (TOKEN_TAG_INDEX, i as u64),
+        };
+
+        if value > TOKEN_VALUE_MASK {
+            Err(PosixError::EOVERFLOW)?
+        }
+
+        Ok(Self {
+            u64: tag << TOKEN_TAG_SHIFT | value,
+        })
+    }
 }
 
 impl Debug for EpollData {
@@ -338,6 +692,27 @@ impl Debug for EpollEvents {
 ////////////////////////////////////////////////////////////////////////////////
 //// Functions
 
+pub fn epoll_wait<'a>(
+    epfd: BorrowedFd,
+    events: &'a mut [EpollEvent],
+    timeout: c_int,
+) -> errno::Result<&'a [EpollEvent]> {
+    let ret = unsafe {
+        libc::epoll_wait(
+            epfd.as_raw_fd(),
+            events.as_mut_ptr() as *mut epoll_event,
+            events.len() as c_int,
+            timeout,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(&events[..ret as usize])
+}
+
 pub fn epoll_pwait<'a>(
     epfd: BorrowedFd,
     events: &'a mut [EpollEvent],
@@ -361,3 +736,340 @@ pub fn epoll_pwait<'a>(
     Ok(&events[..ret as usize])
 }
 
+/// Fallback to `epoll_pwait` (timeout rounded up to ms) if epoll_pwait2
+/// isn't supported
+pub fn epoll_pwait2<'a>(
+    epfd: BorrowedFd,
+    events: &'a mut [EpollEvent],
+    timeout: Option<Duration>,
+    sigmask: Option<SignalSet>,
+) -> errno::Result<&'a [EpollEvent]> {
+    if PWAIT2_UNSUPPORTED.load(Ordering::Relaxed) {
+        return epoll_pwait(epfd, events, duration_to_ms(timeout), sigmask);
+    }
+
+    let ts = timeout.map(|timeout| timespec {
+        tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as _,
+        tv_nsec: timeout.subsec_nanos() as _,
+    });
+
+    let ret = unsafe {
+        libc::syscall(
+            SYS_epoll_pwait2,
+            epfd.as_raw_fd(),
+            events.as_mut_ptr() as *mut epoll_event,
+            events.len() as c_int,
+            ts.as_ref()
+                .map(|ts| ts as *const timespec)
+                .unwrap_or(null()),
+            sigmask
+                .as_ref()
+                .map(|sigmask| sigmask.as_ptr())
+                .unwrap_or(null()),
+            KERNEL_SIGSET_SIZE,
+        )
+    };
+
+    if ret == -1 {
+        match errno::last_os_error() {
+            PosixError::ENOSYS => {
+                PWAIT2_UNSUPPORTED.store(true, Ordering::Relaxed);
+
+                return epoll_pwait(
+                    epfd,
+                    events,
+                    duration_to_ms(timeout),
+                    sigmask,
+                );
+            }
+            err => Err(err)?,
+        }
+    }
+
+    Ok(&events[..ret as usize])
+}
+
+/// Call `f` with remaining timeout until it doesn't fail with EINTR
+fn retry_eintr(
+    timeout: Option<Duration>,
+    mut f: impl FnMut(Option<Duration>) -> errno::Result<usize>,
+) -> errno::Result<usize> {
+    // `None` if it's infinite (or too far to be represented)
+    let deadline =
+        timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    let mut remaining = timeout;
+
+    loop {
+        match f(remaining) {
+            Err(PosixError::EINTR) => {
+                if let Some(deadline) = deadline {
+                    remaining = Some(
+                        deadline.saturating_duration_since(Instant::now()),
+                    );
+                }
+            }
+            res => break res,
+        }
+    }
+}
+
+/// -1 for `None`, round up to not wake before `timeout`, saturate at
+/// c_int::MAX
+fn duration_to_ms(timeout: Option<Duration>) -> c_int {
+    match timeout {
+        Some(timeout) => timeout
+            .as_nanos()
+            .div_ceil(1_000_000)
+            .min(c_int::MAX as u128) as c_int,
+        None => -1,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        signal::Signal,
+        socket::{AddressFamily, SocketType, send, socketpair},
+        unistd::read,
+    };
+
+    #[test]
+    fn test_epoll_ctl() {
+        let (a, b) = socketpair(
+            AddressFamily::UNIX,
+            SocketType::STREAM,
+            Default::default(),
+        )
+        .unwrap();
+
+        let mut epoll = Epoll::create().unwrap();
+        let mut events = [EpollEvent::default(); 4];
+
+        epoll
+            .insert(
+                a.as_fd(),
+                EpollEvent {
+                    events: EpollFlag::In.into(),
+                    data: EpollData::new_as_fd(a.as_raw_fd()),
+                },
+            )
+            .unwrap();
+
+        assert!(epoll.pwait(&mut events, 0, None).unwrap().is_empty());
+
+        send(b.as_fd(), b"x", Default::default()).unwrap();
+
+        let fired = epoll.pwait(&mut events, 0, None).unwrap();
+
+        assert_eq!(fired.len(), 1);
+        assert!(fired[0] & EpollFlag::In);
+        assert_eq!(unsafe { fired[0].data.fd }, a.as_raw_fd());
+
+        epoll
+            .modify(
+                a.as_fd(),
+                EpollEvent {
+                    events: EpollFlag::Out.into(),
+                    data: EpollData { u64: 7 },
+                },
+            )
+            .unwrap();
+
+        let fired = epoll.pwait(&mut events, 0, None).unwrap();
+
+        assert!(fired[0] & EpollFlag::Out);
+        assert!(!(fired[0] & EpollFlag::In));
+        assert_eq!(unsafe { fired[0].data.u64 }, 7);
+
+        epoll.remove(a.as_fd()).unwrap();
+
+        assert!(epoll.pwait(&mut events, 0, None).unwrap().is_empty());
+        assert_eq!(epoll.remove(a.as_fd()), Err(PosixError::ENOENT));
+    }
+
+    #[test]
+    fn test_epoll_wait() {
+        let (a, b) = socketpair(
+            AddressFamily::UNIX,
+            SocketType::STREAM,
+            Default::default(),
+        )
+        .unwrap();
+
+        let mut epoll = Epoll::create().unwrap();
+        let mut events = [EpollEvent::default(); 4];
+
+        epoll
+            .insert(
+                a.as_fd(),
+                EpollEvent {
+                    events: EpollFlag::In.into(),
+                    data: EpollData::new_as_fd(a.as_raw_fd()),
+                },
+            )
+            .unwrap();
+
+        assert!(
+            epoll
+                .wait_timeout(&mut events, Some(Duration::from_micros(10)))
+                .unwrap()
+                .is_empty()
+        );
+
+        send(b.as_fd(), b"x", Default::default()).unwrap();
+
+        assert_eq!(epoll.wait(&mut events, -1).unwrap().len(), 1);
+        assert_eq!(epoll.wait_timeout(&mut events, None).unwrap().len(), 1);
+
+        assert_eq!(duration_to_ms(Some(Duration::from_micros(1))), 1);
+        assert_eq!(duration_to_ms(Some(Duration::MAX)), c_int::MAX);
+        assert_eq!(
+            epoll
+                .wait_uninterrupted(&mut events, Some(Duration::MAX))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // drain
+        let mut buf = [0u8; 1];
+        read(a.as_fd(), &mut buf, 1).unwrap();
+
+        assert!(
+            epoll
+                .pwait2(&mut events, Some(Duration::from_micros(100)), None)
+                .unwrap()
+                .is_empty()
+        );
+
+        send(b.as_fd(), b"x", Default::default()).unwrap();
+
+        assert_eq!(
+            epoll
+                .pwait2(&mut events, None, Some(Signal::SIGUSR2.into()))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_epoll_token() {
+        for token in [
+            EpollToken::Fd(3),
+            EpollToken::Fd(-1),
+            EpollToken::U64(TOKEN_VALUE_MASK),
+            EpollToken::Index(42),
+        ] {
+            let event =
+                EpollEvent::with_token(EpollFlag::In.into(), token).unwrap();
+
+            assert_eq!(event.token(), Some(token));
+        }
+
+        assert_eq!(
+            EpollData::try_from(EpollToken::U64(u64::MAX)).unwrap_err(),
+            PosixError::EOVERFLOW
+        );
+        assert_eq!(EpollData { u64: u64::MAX }.token(), None);
+    }
+
+    #[test]
+    fn test_epoll_events() {
+        let (a, b) = socketpair(
+            AddressFamily::UNIX,
+            SocketType::STREAM,
+            Default::default(),
+        )
+        .unwrap();
+
+        let mut epoll = Epoll::create().unwrap();
+        let mut events = Events::with_capacity(0);
+
+        assert_eq!(events.capacity(), 1);
+
+        for (i, fd) in [a.as_fd(), b.as_fd()].into_iter().enumerate() {
+            epoll
+                .insert(
+                    fd,
+                    EpollEvent::with_token(
+                        EpollFlag::Out.into(),
+                        EpollToken::Index(i),
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(epoll.wait_events(&mut events, None), Ok(1));
+        assert!(events.is_full());
+
+        events.set_capacity(8);
+
+        assert!(events.is_empty());
+        assert_eq!(epoll.wait_events(&mut events, None), Ok(2));
+
+        let mut tokens = events
+            .iter()
+            .filter_map(EpollEvent::token)
+            .collect::<Vec<_>>();
+
+        tokens.sort_by_key(|token| match token {
+            EpollToken::Index(i) => *i,
+            _ => unreachable!(),
+        });
+
+        assert_eq!(tokens, [EpollToken::Index(0), EpollToken::Index(1)]);
+        assert!(!events.is_full());
+    }
+
+    #[test]
+    fn test_epoll_create() {
+        let epoll = Epoll::create_with(EpollCreateFlags::new()).unwrap();
+        let fd_flags =
+            unsafe { libc::fcntl(epoll.as_raw_fd(), libc::F_GETFD) };
+
+        assert_eq!(fd_flags & libc::FD_CLOEXEC, 0);
+
+        let epoll = Epoll::create().unwrap();
+        let fd_flags =
+            unsafe { libc::fcntl(epoll.as_raw_fd(), libc::F_GETFD) };
+
+        assert_ne!(fd_flags & libc::FD_CLOEXEC, 0);
+
+        let raw = epoll.as_raw_fd();
+        let epoll = Epoll::from(epoll.into_owned_fd());
+
+        assert_eq!(epoll.as_raw_fd(), raw);
+        assert!(epoll.wait(&mut [EpollEvent::default()], 0).is_ok());
+    }
+
+    #[test]
+    fn test_retry_eintr() {
+        let mut calls = vec![];
+
+        let ret = retry_eintr(Some(Duration::from_secs(60)), |remaining| {
+            calls.push(remaining.unwrap());
+
+            if calls.len() < 3 {
+                Err(PosixError::EINTR)
+            }
+            else {
+                Ok(calls.len())
+            }
+        });
+
+        assert_eq!(ret, Ok(3));
+        assert!(calls.is_sorted_by(|a, b| a >= b));
+
+        assert_eq!(
+            retry_eintr(None, |remaining| {
+                assert!(remaining.is_none());
+                Err(PosixError::EBADF)
+            }),
+            Err(PosixError::EBADF)
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Token based registrations on top of `Epoll`
+//!
+//! User data of each registration is a `Token` chosen by caller, so no
+//! `EpollData` union is read by caller.
+
+use std::{
+    collections::HashMap,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    time::Duration,
+};
+
+use crate::{
+    epoll::{Epoll, EpollData, EpollEvent, EpollEvents, Events},
+    errno::{self, PosixError},
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Identify a registration of `Reactor`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Token(pub usize);
+
+struct Registration {
+    fd: RawFd,
+    interest: EpollEvents,
+}
+
+pub struct Reactor {
+    epoll: Epoll,
+    registrations: HashMap<Token, Registration>,
+    events: Events,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl Reactor {
+    /// Up to 64 events each poll
+    pub fn new() -> errno::Result<Self> {
+        Self::with_capacity(64)
+    }
+
+    /// Up to `capacity` events each poll
+    pub fn with_capacity(capacity: usize) -> errno::Result<Self> {
+        Ok(Self {
+            epoll: Epoll::create()?,
+            registrations: HashMap::new(),
+            events: Events::with_capacity(capacity),
+        })
+    }
+
+    /// EEXIST if `token` is in use
+    ///
+    /// Only raw `fd` is kept, it should be deregistered before it's closed
+    /// (see `reregister`).
+    pub fn register(
+        &mut self,
+        fd: BorrowedFd,
+        token: Token,
+        interest: EpollEvents,
+    ) -> errno::Result<()> {
+        if self.registrations.contains_key(&token) {
+            Err(PosixError::EEXIST)?
+        }
+
+        self.epoll.insert(fd, event(token, interest))?;
+
+        self.registrations.insert(
+            token,
+            Registration {
+                fd: fd.as_raw_fd(),
+                interest,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Replace interest set of `token`, ENOENT if it isn't registered
+    ///
+    /// # Safety
+    ///
+    /// `fd` registered with `token` should be still open, otherwise the
+    /// same number may be reused by other file, which would be modified.
+    /// So as `rearm`, `deregister`.
+    pub unsafe fn reregister(
+        &mut self,
+        token: Token,
+        interest: EpollEvents,
+    ) -> errno::Result<()> {
+        let reg = self
+            .registrations
+            .get_mut(&token)
+            .ok_or(PosixError::ENOENT)?;
+
+        self.epoll.modify(
+            unsafe { BorrowedFd::borrow_raw(reg.fd) },
+            event(token, interest),
+        )?;
+
+        reg.interest = interest;
+
+        Ok(())
+    }
+
+    /// Re-arm `EpollFlag::Oneshot` registration with the same interest set
+    ///
+    /// # Safety
+    ///
+    /// See `reregister`.
+    pub unsafe fn rearm(&mut self, token: Token) -> errno::Result<()> {
+        let interest = self
+            .registrations
+            .get(&token)
+            .ok_or(PosixError::ENOENT)?
+            .interest;
+
+        unsafe { self.reregister(token, interest) }
+    }
+
+    /// Registration is kept if EPOLL_CTL_DEL fails
+    ///
+    /// # Safety
+    ///
+    /// See `reregister`.
+    pub unsafe fn deregister(&mut self, token: Token) -> errno::Result<()> {
+        let reg = self.registrations.get(&token).ok_or(PosixError::ENOENT)?;
+
+        self.epoll.remove(unsafe { BorrowedFd::borrow_raw(reg.fd) })?;
+
+        self.registrations.remove(&token);
+
+        Ok(())
+    }
+
+    /// Interest set of `token`
+    pub fn interest(&self, token: Token) -> Option<EpollEvents> {
+        self.registrations.get(&token).map(|reg| reg.interest)
+    }
+
+    pub fn len(&self) -> usize {
+        self.registrations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+
+    /// Wait readiness, `None` for infinite
+    ///
+    /// EINTR is returned as it is.
+    pub fn poll(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> errno::Result<impl Iterator<Item = (Token, EpollEvents)> + '_> {
+        self.epoll.wait_events(&mut self.events, timeout)?;
+
+        Ok(self.events.iter().map(|event| {
+            (Token(unsafe { event.data.u64 } as usize), event.events)
+        }))
+    }
+
+    /// `poll` and call `f` for each ready registration, return the number
+    /// of them
+    pub fn dispatch(
+        &mut self,
+        timeout: Option<Duration>,
+        mut f: impl FnMut(Token, EpollEvents),
+    ) -> errno::Result<usize> {
+        let mut n = 0;
+
+        for (token, events) in self.poll(timeout)? {
+            f(token, events);
+            n += 1;
+        }
+
+        Ok(n)
+    }
+}
+
+impl AsFd for Reactor {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.epoll.as_fd()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+fn event(token: Token, interest: EpollEvents) -> EpollEvent {
+    EpollEvent {
+        events: interest,
+        data: EpollData {
+            u64: token.0 as u64,
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        epoll::EpollFlag,
+        socket::{AddressFamily, SocketType, send, socketpair},
+    };
+
+    #[test]
+    fn test_reactor() {
+        let (a, b) = socketpair(
+            AddressFamily::UNIX,
+            SocketType::STREAM,
+            Default::default(),
+        )
+        .unwrap();
+
+        let mut reactor = Reactor::with_capacity(4).unwrap();
+        let interest = EpollEvents::new().epoll_in().epoll_oneshot();
+
+        reactor.register(a.as_fd(), Token(1), interest).unwrap();
+        reactor
+            .register(b.as_fd(), Token(2), EpollFlag::Out.into())
+            .unwrap();
+
+        assert_eq!(
+            reactor.register(a.as_fd(), Token(1), interest),
+            Err(PosixError::EEXIST)
+        );
+
+        send(b.as_fd(), b"x", Default::default()).unwrap();
+
+        let mut fired = reactor
+            .poll(Some(Duration::ZERO))
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        fired.sort_by_key(|(token, _)| *token);
+
+        assert_eq!(fired.len(), 2);
+        assert_eq!(fired[0].0, Token(1));
+        assert!(fired[0].1 & EpollFlag::In);
+        assert!(fired[1].1 & EpollFlag::Out);
+
+        // `b` is still open
+        unsafe { reactor.deregister(Token(2)) }.unwrap();
+
+        // oneshot is disabled after fired
+        assert_eq!(reactor.dispatch(Some(Duration::ZERO), |_, _| ()), Ok(0));
+
+        assert!(reactor.interest(Token(1)).unwrap() & EpollFlag::Oneshot);
+        unsafe { reactor.rearm(Token(1)) }.unwrap();
+
+        let mut tokens = vec![];
+
+        reactor
+            .dispatch(Some(Duration::ZERO), |token, _| tokens.push(token))
+            .unwrap();
+
+        assert_eq!(tokens, [Token(1)]);
+        assert_eq!(
+            unsafe { reactor.rearm(Token(2)) },
+            Err(PosixError::ENOENT)
+        );
+    }
+}
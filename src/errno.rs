@@ -1,3 +1,5 @@
+use std::io;
+
 use derive_more::derive::Error;
 use int_enum::IntEnum;
 use libc::__errno_location;
@@ -542,3 +544,10 @@ pub(crate) fn last_os_error() -> PosixError {
         PosixError::try_from(errno).unwrap()
     }
 }
+
+/// EIO for error not from OS (std::fs, ...)
+pub(crate) fn from_io_error(err: io::Error) -> PosixError {
+    err.raw_os_error()
+        .and_then(|code| PosixError::try_from(code).ok())
+        .unwrap_or(PosixError::EIO)
+}
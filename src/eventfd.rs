@@ -0,0 +1,233 @@
+//! Event counter notified through file descriptor, e.g. wake `Epoll` loop
+//! from other threads
+//!
+//! Ref [eventfd(2)](https://man7.org/linux/man-pages/man2/eventfd.2.html)
+
+use std::{
+    fmt::Debug,
+    ops::{BitAnd, BitOr},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use libc::c_uint;
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::errno::{self, PosixError};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// EFD_XXX
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(i32)]
+#[repr(i32)]
+pub enum EventFdFlag {
+    /// `read` decreases counter by 1 instead of resetting it
+    Semaphore = 0x1,
+    NonBlock = 0o4000,
+    CloExec = 0o2000000,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(i32)]
+#[repr(transparent)]
+pub struct EventFdFlags(i32);
+
+/// Cloned through `try_clone` to share between threads (or by reference,
+/// it's `Sync`)
+#[derive(Debug)]
+pub struct EventFd {
+    fd: OwnedFd,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl EventFdFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<EventFdFlag> for EventFdFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: EventFdFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for EventFdFlag {
+    type Output = EventFdFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        EventFdFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<EventFdFlag> for &EventFdFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: EventFdFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<EventFdFlags> for EventFdFlag {
+    fn into(self) -> EventFdFlags {
+        EventFdFlags(self.to_bits())
+    }
+}
+
+impl Debug for EventFdFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in EventFdFlag::iter().filter(|e| self & *e).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl EventFd {
+    pub fn new(initval: c_uint, flags: EventFdFlags) -> errno::Result<Self> {
+        let ret = unsafe { libc::eventfd(initval, flags.to_bits()) };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(ret) },
+        })
+    }
+
+    /// Add `value` to counter
+    ///
+    /// EAGAIN if it would overflow (non-blocking), EINVAL for u64::MAX.
+    pub fn write(&self, value: u64) -> errno::Result<()> {
+        let ret = unsafe {
+            libc::write(
+                self.fd.as_raw_fd(),
+                &value as *const u64 as _,
+                size_of::<u64>(),
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(())
+    }
+
+    /// Take counter (reset to 0), or 1 for `EventFdFlag::Semaphore`
+    ///
+    /// EAGAIN if counter is 0 (non-blocking).
+    pub fn read(&self) -> errno::Result<u64> {
+        let mut value = 0u64;
+
+        let ret = unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                &mut value as *mut u64 as _,
+                size_of::<u64>(),
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(value)
+    }
+
+    /// `write(1)`
+    pub fn notify(&self) -> errno::Result<()> {
+        self.write(1)
+    }
+
+    /// Refer to the same counter
+    pub fn try_clone(&self) -> errno::Result<Self> {
+        Ok(Self {
+            fd: self.fd.try_clone().map_err(errno::from_io_error)?,
+        })
+    }
+}
+
+impl AsFd for EventFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for EventFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::epoll::{Epoll, EpollEvent, EpollFlag, EpollToken};
+
+    #[test]
+    fn test_eventfd() {
+        let efd =
+            EventFd::new(0, EventFdFlag::NonBlock | EventFdFlag::CloExec)
+                .unwrap();
+
+        assert_eq!(efd.read(), Err(PosixError::EAGAIN));
+        assert_eq!(efd.write(u64::MAX), Err(PosixError::EINVAL));
+
+        let mut epoll = Epoll::create().unwrap();
+        let mut events = [EpollEvent::default(); 1];
+
+        epoll
+            .insert(
+                efd.as_fd(),
+                EpollEvent::with_token(
+                    EpollFlag::In.into(),
+                    EpollToken::U64(9),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let waker = efd.try_clone().unwrap();
+
+        thread::spawn(move || {
+            waker.write(2).unwrap();
+            waker.notify().unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let fired = epoll.wait(&mut events, 1000).unwrap();
+
+        assert_eq!(fired[0].token(), Some(EpollToken::U64(9)));
+        assert_eq!(efd.read(), Ok(3));
+
+        let sem = EventFd::new(
+            2,
+            EventFdFlag::Semaphore
+                | EventFdFlag::NonBlock
+                | EventFdFlag::CloExec,
+        )
+        .unwrap();
+
+        assert_eq!(sem.read(), Ok(1));
+        assert_eq!(sem.read(), Ok(1));
+        assert_eq!(sem.read(), Err(PosixError::EAGAIN));
+    }
+}
@@ -0,0 +1,304 @@
+//! File descriptor flags and advisory record locks
+//!
+//! Ref [fcntl(2)](https://man7.org/linux/man-pages/man2/fcntl.2.html)
+
+use std::{
+    ffi::{c_int, c_short},
+    mem::zeroed,
+    os::fd::{AsRawFd, BorrowedFd},
+};
+
+use libc::{
+    F_GETFD, F_GETFL, F_GETLK, F_OFD_GETLK, F_OFD_SETLK, F_OFD_SETLKW,
+    F_RDLCK, F_SETFD, F_SETFL, F_SETLK, F_SETLKW, F_UNLCK, F_WRLCK,
+    FD_CLOEXEC, O_NONBLOCK, flock, off_t,
+};
+
+use crate::{
+    errno::{self, PosixError},
+    unistd::{Pid, Whence},
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// F_XXLCK
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockKind {
+    /// Shared
+    Read,
+    /// Exclusive
+    Write,
+    Unlock,
+}
+
+/// struct flock, lock of byte range `start..start + len` (0 `len` for
+/// till EOF, even it grows)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileLock {
+    pub kind: LockKind,
+    pub whence: Whence,
+    pub start: off_t,
+    pub len: off_t,
+    /// Holder of conflicting lock returned by `get_lock` (`None` for OFD
+    /// lock)
+    pub pid: Option<Pid>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl FileLock {
+    /// Lock whole file
+    pub fn whole(kind: LockKind) -> Self {
+        Self {
+            kind,
+            whence: Whence::Set,
+            start: 0,
+            len: 0,
+            pid: None,
+        }
+    }
+
+    pub fn range(kind: LockKind, start: off_t, len: off_t) -> Self {
+        Self {
+            start,
+            len,
+            ..Self::whole(kind)
+        }
+    }
+
+    fn to_flock(&self) -> flock {
+        let mut raw: flock = unsafe { zeroed() };
+
+        raw.l_type = match self.kind {
+            LockKind::Read => F_RDLCK,
+            LockKind::Write => F_WRLCK,
+            LockKind::Unlock => F_UNLCK,
+        } as c_short;
+        raw.l_whence = self.whence.to_bits() as c_short;
+        raw.l_start = self.start;
+        raw.l_len = self.len;
+
+        raw
+    }
+
+    fn from_flock(raw: &flock) -> Self {
+        Self {
+            kind: match raw.l_type as c_int {
+                F_RDLCK => LockKind::Read,
+                F_WRLCK => LockKind::Write,
+                _ => LockKind::Unlock,
+            },
+            // kernel returns it as SEEK_SET
+            whence: Whence::Set,
+            start: raw.l_start,
+            len: raw.l_len,
+            pid: (raw.l_pid > 0).then_some(Pid::from_raw(raw.l_pid)),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// F_GETFD, FD_CLOEXEC
+pub fn get_fd_flags(fd: BorrowedFd) -> errno::Result<c_int> {
+    fcntl_int(fd, F_GETFD, 0)
+}
+
+pub fn set_fd_flags(fd: BorrowedFd, flags: c_int) -> errno::Result<()> {
+    fcntl_int(fd, F_SETFD, flags)?;
+
+    Ok(())
+}
+
+/// F_GETFL, access mode and file status flags (O_XXX)
+pub fn get_fl_flags(fd: BorrowedFd) -> errno::Result<c_int> {
+    fcntl_int(fd, F_GETFL, 0)
+}
+
+/// Only O_APPEND, O_ASYNC, O_DIRECT, O_NOATIME, O_NONBLOCK can be changed
+pub fn set_fl_flags(fd: BorrowedFd, flags: c_int) -> errno::Result<()> {
+    fcntl_int(fd, F_SETFL, flags)?;
+
+    Ok(())
+}
+
+/// Toggle O_NONBLOCK
+pub fn set_nonblocking(
+    fd: BorrowedFd,
+    nonblocking: bool,
+) -> errno::Result<()> {
+    let flags = get_fl_flags(fd)?;
+
+    let flags = if nonblocking {
+        flags | O_NONBLOCK
+    }
+    else {
+        flags & !O_NONBLOCK
+    };
+
+    set_fl_flags(fd, flags)
+}
+
+/// Toggle FD_CLOEXEC
+pub fn set_cloexec(fd: BorrowedFd, cloexec: bool) -> errno::Result<()> {
+    let flags = get_fd_flags(fd)?;
+
+    let flags = if cloexec {
+        flags | FD_CLOEXEC
+    }
+    else {
+        flags & !FD_CLOEXEC
+    };
+
+    set_fd_flags(fd, flags)
+}
+
+/// F_SETLK, EAGAIN (or EACCES) if it conflicts with lock of other process
+///
+/// Process associated lock is released when any fd of the file is closed
+/// by the process, and isn't inherited by fork.
+pub fn set_lock(fd: BorrowedFd, lock: &FileLock) -> errno::Result<()> {
+    fcntl_lock(fd, F_SETLK, &mut lock.to_flock())
+}
+
+/// F_SETLKW, wait for conflicting lock to be released (EINTR if
+/// interrupted, EDEADLK if deadlock is detected)
+pub fn set_lock_wait(fd: BorrowedFd, lock: &FileLock) -> errno::Result<()> {
+    fcntl_lock(fd, F_SETLKW, &mut lock.to_flock())
+}
+
+/// F_GETLK, lock that would prevent `lock`, `None` if it could be placed
+pub fn get_lock(
+    fd: BorrowedFd,
+    lock: &FileLock,
+) -> errno::Result<Option<FileLock>> {
+    get_lock_with(fd, F_GETLK, lock)
+}
+
+/// F_OFD_SETLK, lock owned by open file description (shared by dup-ed
+/// fds and fork), so it conflicts with lock of other open of the file in
+/// the same process (e.g. other threads)
+pub fn ofd_set_lock(fd: BorrowedFd, lock: &FileLock) -> errno::Result<()> {
+    fcntl_lock(fd, F_OFD_SETLK, &mut lock.to_flock())
+}
+
+/// F_OFD_SETLKW
+pub fn ofd_set_lock_wait(
+    fd: BorrowedFd,
+    lock: &FileLock,
+) -> errno::Result<()> {
+    fcntl_lock(fd, F_OFD_SETLKW, &mut lock.to_flock())
+}
+
+/// F_OFD_GETLK
+pub fn ofd_get_lock(
+    fd: BorrowedFd,
+    lock: &FileLock,
+) -> errno::Result<Option<FileLock>> {
+    get_lock_with(fd, F_OFD_GETLK, lock)
+}
+
+fn get_lock_with(
+    fd: BorrowedFd,
+    cmd: c_int,
+    lock: &FileLock,
+) -> errno::Result<Option<FileLock>> {
+    let mut raw = lock.to_flock();
+
+    fcntl_lock(fd, cmd, &mut raw)?;
+
+    Ok((raw.l_type as c_int != F_UNLCK).then(|| FileLock::from_flock(&raw)))
+}
+
+fn fcntl_lock(
+    fd: BorrowedFd,
+    cmd: c_int,
+    raw: &mut flock,
+) -> errno::Result<()> {
+    let ret = unsafe { libc::fcntl(fd.as_raw_fd(), cmd, raw as *mut flock) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+fn fcntl_int(fd: BorrowedFd, cmd: c_int, arg: c_int) -> errno::Result<c_int> {
+    let ret = unsafe { libc::fcntl(fd.as_raw_fd(), cmd, arg) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret)
+}
+
+/// EAGAIN and EACCES are both used for conflicting lock
+pub fn is_lock_conflict(err: PosixError) -> bool {
+    matches!(err, PosixError::EAGAIN | PosixError::EACCES)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, os::fd::AsFd, process};
+
+    use super::*;
+
+    #[test]
+    fn test_fd_flags() {
+        let (a, _b) = crate::unistd::pipe2(Default::default()).unwrap();
+
+        assert_eq!(get_fd_flags(a.as_fd()).unwrap() & FD_CLOEXEC, 0);
+
+        set_cloexec(a.as_fd(), true).unwrap();
+        set_nonblocking(a.as_fd(), true).unwrap();
+
+        assert_ne!(get_fd_flags(a.as_fd()).unwrap() & FD_CLOEXEC, 0);
+        assert_ne!(get_fl_flags(a.as_fd()).unwrap() & O_NONBLOCK, 0);
+
+        set_nonblocking(a.as_fd(), false).unwrap();
+
+        assert_eq!(get_fl_flags(a.as_fd()).unwrap() & O_NONBLOCK, 0);
+    }
+
+    #[test]
+    fn test_file_lock() {
+        let path = std::env::temp_dir()
+            .join(format!("lxtest-fcntl-{}", process::id()));
+
+        let f1 = File::create(&path).unwrap();
+        let f2 = File::options().write(true).open(&path).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        let wr = FileLock::whole(LockKind::Write);
+
+        // process associated locks never conflict inside the process
+        set_lock(f1.as_fd(), &wr).unwrap();
+        assert_eq!(get_lock(f2.as_fd(), &wr), Ok(None));
+        set_lock(f1.as_fd(), &FileLock::whole(LockKind::Unlock)).unwrap();
+
+        ofd_set_lock(f1.as_fd(), &FileLock::range(LockKind::Write, 0, 10))
+            .unwrap();
+
+        let held = ofd_get_lock(f2.as_fd(), &wr).unwrap().unwrap();
+
+        assert_eq!(held.kind, LockKind::Write);
+        assert_eq!((held.start, held.len), (0, 10));
+        assert_eq!(held.pid, None);
+
+        let err = ofd_set_lock(f2.as_fd(), &wr).unwrap_err();
+
+        assert!(is_lock_conflict(err));
+
+        // out of range
+        ofd_set_lock(f2.as_fd(), &FileLock::range(LockKind::Read, 10, 0))
+            .unwrap();
+    }
+}
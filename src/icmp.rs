@@ -0,0 +1,372 @@
+//! ICMP Echo (ping)
+//!
+//! Ref [RFC-792](https://datatracker.ietf.org/doc/html/rfc792),
+//! [RFC-4443](https://datatracker.ietf.org/doc/html/rfc4443)
+//!
+//! `Dgram` kind needs no privilege but sysctl `net.ipv4.ping_group_range`
+//! should cover our gid; `Raw` kind needs CAP_NET_RAW.
+
+use std::{
+    ffi::c_int,
+    io::IoSliceMut,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd},
+    time::{Duration, Instant},
+};
+
+use libc::{IPPROTO_ICMP, IPPROTO_ICMPV6};
+
+use crate::{
+    epoll::{Epoll, EpollData, EpollEvent, EpollFlag},
+    errno::{self, PosixError},
+    socket::{
+        AddressFamily, ExtraBehavior, SockAddr, SockAddrIn, SockAddrIn6,
+        SocketProtocol, SocketType, recvmsg, sendto, socket,
+    },
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+pub const ICMP_ECHO_REPLY: u8 = 0;
+pub const ICMP_ECHO_REQUEST: u8 = 8;
+pub const ICMPV6_ECHO_REQUEST: u8 = 128;
+pub const ICMPV6_ECHO_REPLY: u8 = 129;
+
+/// type(1) + code(1) + checksum(2) + identifier(2) + sequence(2)
+pub const ICMP_ECHO_HDR_LEN: usize = 8;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IcmpSocketKind {
+    /// SOCK_RAW, receive every ICMP packet (IPv4 with IP header)
+    Raw,
+    /// SOCK_DGRAM "ping socket", kernel manages identifier and filtering
+    Dgram,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EchoReply {
+    pub from: IpAddr,
+    pub seq: u16,
+    /// ICMP message length (header included)
+    pub len: usize,
+    /// Only available for IPv4 `Raw` kind
+    pub ttl: Option<u8>,
+    pub rtt: Duration,
+}
+
+/// Echo request/reply on one ICMP socket (one outstanding request at a time)
+pub struct Pinger {
+    sock: OwnedFd,
+    kind: IcmpSocketKind,
+    v6: bool,
+    id: u16,
+    seq: u16,
+    epoll: Epoll,
+    buf: Vec<u8>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl Pinger {
+    pub fn new_v4(kind: IcmpSocketKind) -> errno::Result<Self> {
+        Self::new(kind, false)
+    }
+
+    pub fn new_v6(kind: IcmpSocketKind) -> errno::Result<Self> {
+        Self::new(kind, true)
+    }
+
+    fn new(kind: IcmpSocketKind, v6: bool) -> errno::Result<Self> {
+        let (domain, protocol) = if v6 {
+            (AddressFamily::INET6, IPPROTO_ICMPV6)
+        }
+        else {
+            (AddressFamily::INET, IPPROTO_ICMP)
+        };
+
+        let sock = socket(
+            domain,
+            match kind {
+                IcmpSocketKind::Raw => SocketType::RAW,
+                IcmpSocketKind::Dgram => SocketType::DGRAM,
+            },
+            ExtraBehavior::new().non_block().close_on_exec(),
+            SocketProtocol::from_raw_ip(protocol as u8),
+        )?;
+
+        let mut epoll = Epoll::create()?;
+
+        epoll.insert(
+            sock.as_fd(),
+            EpollEvent {
+                events: EpollFlag::In.into(),
+                data: EpollData::new_as_fd(sock.as_raw_fd()),
+            },
+        )?;
+
+        Ok(Self {
+            sock,
+            kind,
+            v6,
+            id: std::process::id() as u16,
+            seq: 0,
+            epoll,
+            buf: vec![0; u16::MAX as usize],
+        })
+    }
+
+    pub fn kind(&self) -> IcmpSocketKind {
+        self.kind
+    }
+
+    /// Send one echo request with `payload_len` bytes payload and wait reply
+    ///
+    /// Return `None` on timeout
+    pub fn ping(
+        &mut self,
+        dst: IpAddr,
+        payload_len: usize,
+        timeout: Duration,
+    ) -> errno::Result<Option<EchoReply>> {
+        let addr: SockAddr = match dst {
+            IpAddr::V4(ip) if !self.v6 => SockAddrIn::from(ip).into(),
+            IpAddr::V6(ip) if self.v6 => SockAddrIn6::from(ip).into(),
+            _ => Err(PosixError::EAFNOSUPPORT)?,
+        };
+
+        self.seq = self.seq.wrapping_add(1);
+
+        let payload: Vec<u8> = (0..payload_len).map(|i| i as u8).collect();
+        let req = if self.v6 {
+            echo_request_v6(self.id, self.seq, &payload)
+        }
+        else {
+            echo_request_v4(self.id, self.seq, &payload)
+        };
+
+        let start = Instant::now();
+
+        sendto(self.sock.as_fd(), &req, Default::default(), Some(addr))?;
+
+        loop {
+            let elapsed = start.elapsed();
+
+            if elapsed >= timeout {
+                return Ok(None);
+            }
+
+            let remain_ms =
+                (timeout - elapsed).as_millis().clamp(1, c_int::MAX as u128)
+                    as c_int;
+            let mut events = [EpollEvent::default(); 1];
+
+            match self.epoll.pwait(&mut events, remain_ms, None) {
+                Ok(fired) if fired.is_empty() => continue,
+                Ok(_) => (),
+                Err(PosixError::EINTR) => continue,
+                Err(err) => Err(err)?,
+            }
+
+            while let Some(reply) = self.recv_one(start)? {
+                if reply.seq == self.seq && reply.from == dst {
+                    return Ok(Some(reply));
+                }
+            }
+        }
+    }
+
+    /// Ok(None) for nothing left to read
+    fn recv_one(
+        &mut self,
+        start: Instant,
+    ) -> errno::Result<Option<EchoReply>> {
+        loop {
+            let msg = match recvmsg(
+                self.sock.as_fd(),
+                &mut [IoSliceMut::new(&mut self.buf)],
+                None,
+                Default::default(),
+            ) {
+                Ok(msg) => msg,
+                Err(PosixError::EAGAIN) => return Ok(None),
+                Err(PosixError::EINTR) => continue,
+                Err(err) => Err(err)?,
+            };
+
+            let from = match msg.addr() {
+                Some(SockAddr::Inet(sin)) => {
+                    IpAddr::V4(Into::<Ipv4Addr>::into(sin.addr))
+                }
+                Some(SockAddr::Inet6(sin6)) => {
+                    IpAddr::V6(Into::<Ipv6Addr>::into(sin6.addr))
+                }
+                _ => continue,
+            };
+
+            let mut pkt = &self.buf[..msg.len];
+            let mut ttl = None;
+
+            // raw IPv4 socket receive IP header
+            if !self.v6 && self.kind == IcmpSocketKind::Raw {
+                let Some(first) = pkt.first()
+                else {
+                    continue;
+                };
+
+                let ihl = ((first & 0x0F) as usize) * 4;
+
+                if pkt.len() < ihl + ICMP_ECHO_HDR_LEN {
+                    continue;
+                }
+
+                ttl = Some(pkt[8]);
+                pkt = &pkt[ihl..];
+            }
+
+            let Some((ty, id, seq)) = parse_echo_hdr(pkt)
+            else {
+                continue;
+            };
+
+            let reply_ty = if self.v6 {
+                ICMPV6_ECHO_REPLY
+            }
+            else {
+                ICMP_ECHO_REPLY
+            };
+
+            if ty != reply_ty {
+                continue;
+            }
+
+            // DGRAM socket identifier is rewritten by kernel
+            if self.kind == IcmpSocketKind::Raw && id != self.id {
+                continue;
+            }
+
+            return Ok(Some(EchoReply {
+                from,
+                seq,
+                len: pkt.len(),
+                ttl,
+                rtt: start.elapsed(),
+            }));
+        }
+    }
+}
+
+impl AsFd for Pinger {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.sock.as_fd()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Internet checksum (RFC-1071), one's complement of one's complement sum
+pub fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+
+    let mut chunks = data.chunks_exact(2);
+
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/// ICMP echo request with checksum filled
+pub fn echo_request_v4(id: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    let mut pkt = echo_hdr(ICMP_ECHO_REQUEST, id, seq, payload);
+
+    let sum = checksum(&pkt);
+    pkt[2..4].copy_from_slice(&sum.to_be_bytes());
+
+    pkt
+}
+
+/// ICMPv6 echo request, checksum (need pseudo header) is left for kernel
+pub fn echo_request_v6(id: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    echo_hdr(ICMPV6_ECHO_REQUEST, id, seq, payload)
+}
+
+fn echo_hdr(ty: u8, id: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(ICMP_ECHO_HDR_LEN + payload.len());
+
+    pkt.extend_from_slice(&[ty, 0, 0, 0]);
+    pkt.extend_from_slice(&id.to_be_bytes());
+    pkt.extend_from_slice(&seq.to_be_bytes());
+    pkt.extend_from_slice(payload);
+
+    pkt
+}
+
+/// (type, identifier, sequence)
+fn parse_echo_hdr(pkt: &[u8]) -> Option<(u8, u16, u16)> {
+    if pkt.len() < ICMP_ECHO_HDR_LEN {
+        return None;
+    }
+
+    Some((
+        pkt[0],
+        u16::from_be_bytes([pkt[4], pkt[5]]),
+        u16::from_be_bytes([pkt[6], pkt[7]]),
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        let pkt = echo_request_v4(0x1234, 1, b"abcdefgh");
+
+        // checksum over a packet with valid checksum is zero
+        assert_eq!(checksum(&pkt), 0);
+        assert_eq!(parse_echo_hdr(&pkt), Some((ICMP_ECHO_REQUEST, 0x1234, 1)));
+
+        // odd length
+        assert_eq!(checksum(&[0x01]), !0x0100);
+    }
+
+    #[test]
+    fn test_ping_localhost() {
+        let mut pinger = match Pinger::new_v4(IcmpSocketKind::Dgram) {
+            Ok(pinger) => pinger,
+            Err(err) => {
+                // gid out of sysctl net.ipv4.ping_group_range
+                assert_eq!(err, PosixError::EACCES);
+                return;
+            }
+        };
+
+        let reply = pinger
+            .ping(IpAddr::V4(Ipv4Addr::LOCALHOST), 56, Duration::from_secs(1))
+            .unwrap();
+
+        println!("{reply:?}");
+
+        let reply = reply.unwrap();
+
+        assert_eq!(reply.from, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(reply.seq, 1);
+    }
+}
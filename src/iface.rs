@@ -2,28 +2,38 @@
 //!
 
 use std::{
-    ffi::{CStr, c_int},
+    collections::HashMap,
+    ffi::{CStr, CString, c_int, c_uint, c_void},
     fmt::Debug,
-    net::{Ipv4Addr, Ipv6Addr},
-    ops::BitAnd,
-    os::fd::AsFd,
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::{BitAnd, BitOr},
+    os::fd::{AsFd, BorrowedFd, OwnedFd},
     ptr::null_mut,
 };
 
 use derive_more::derive::{Deref, DerefMut};
 use ifstructs::ifreq;
 use int_enum::IntEnum;
-use libc::{freeifaddrs, getifaddrs, sockaddr_in, sockaddr_in6};
-use m6tobytes::derive_to_bits;
+use libc::{
+    IF_NAMESIZE, c_char, c_short, freeifaddrs, getifaddrs, sockaddr,
+    sockaddr_in, sockaddr_in6,
+};
+use m6tobytes::{derive_from_bits, derive_to_bits};
 use osimodel::datalink::Mac;
 use strum::{EnumIter, IntoEnumIterator};
 
 use crate::{
-    errno::{self, PosixError},
+    errno::{self, PosixError, from_io_error},
     ioctl::{IoctlOpcode, ioctl},
+    netlink::{
+        Address, Link, get_link, get_links,
+        monitor::{NetlinkEvent, NetlinkMonitor, RtnlGroup},
+        nl80211::Nl80211,
+    },
     socket::{
-        AddressFamily, InAddr, SaFamily, SockAddrIn, SockAddrLL, SocketType,
-        socket,
+        AddressFamily, ExtraBehavior, InAddr, SaFamily, SockAddr, SockAddrIn,
+        SockAddrLL, SocketType, socket,
     },
 };
 
@@ -45,6 +55,10 @@ pub enum IfAddr {
         name: String,
         addr: Ipv4Addr,
         mask: Ipv4Addr,
+        /// For `IfFlag::Broadcast` interface
+        broadcast: Option<Ipv4Addr>,
+        /// Destination address of `IfFlag::PointToPoint` interface
+        peer: Option<Ipv4Addr>,
         flags: IfFlags,
     },
     Inet6 {
@@ -107,8 +121,9 @@ pub enum IfFlag {
     Dynamic = 0x8000,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Default, Clone, Copy)]
 #[derive_to_bits(u32)]
+#[derive_from_bits(u32)]
 #[repr(transparent)]
 pub struct IfFlags(u32);
 
@@ -143,6 +158,41 @@ pub struct RtnlLinkStats {
     rx_nohandler: u32,
 }
 
+/// struct rtnl_link_stats64, 64-bit counters won't wrap on busy link
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[repr(C)]
+pub struct RtnlLinkStats64 {
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    pub multicast: u64,
+    pub collisions: u64,
+    /* detailed rx_errors: */
+    pub rx_length_errors: u64,
+    pub rx_over_errors: u64,
+    pub rx_crc_errors: u64,
+    pub rx_frame_errors: u64,
+    pub rx_fifo_errors: u64,
+    pub rx_missed_errors: u64,
+    /* detailed tx_errors */
+    pub tx_aborted_errors: u64,
+    pub tx_carrier_errors: u64,
+    pub tx_fifo_errors: u64,
+    pub tx_heartbeat_errors: u64,
+    pub tx_window_errors: u64,
+    /* for cslip etc */
+    pub rx_compressed: u64,
+    pub tx_compressed: u64,
+    pub rx_nohandler: u64,
+    /// Since Linux 5.19
+    pub rx_otherhost_dropped: u64,
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct HwAddr {
     pub ty: HwType,
@@ -164,6 +214,148 @@ pub enum HwType {
     IEEE80211 = 801,
 }
 
+/// Interface change reported by `IfaceWatcher`
+#[derive(Debug)]
+pub enum IfaceEvent {
+    Added(Link),
+    Removed(Link),
+    /// `link.flags` is the new one
+    FlagsChanged {
+        link: Link,
+        old: IfFlags,
+    },
+    /// IPv4/IPv6 address added or removed
+    AddrChanged {
+        addr: Address,
+        removed: bool,
+    },
+}
+
+/// Interface change stream, see `watch`
+///
+/// Blocking by default, for epoll integration, `set_nonblocking` and
+/// iterate until `None` after readable.
+pub struct IfaceWatcher {
+    monitor: NetlinkMonitor,
+    /// Known links, used to tell added from changed
+    flags: HashMap<c_int, IfFlags>,
+}
+
+/// Interface ioctl (SIOCGIFXXX/SIOCSIFXXX) handle
+///
+/// Reuse one AF_INET socket for many requests, the `get_ifxxx`/`set_ifxxx`
+/// functions create one for each call.
+pub struct IfaceCtl {
+    sock: OwnedFd,
+}
+
+/// HWTSTAMP_TX_XXX
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, IntEnum)]
+#[repr(i32)]
+pub enum HwTstampTx {
+    Off = 0,
+    /// Timestamp all outgoing packets (with SOF_TIMESTAMPING_TX_HARDWARE)
+    On = 1,
+    /// Also insert timestamp into PTP Sync messages
+    OnestepSync = 2,
+    /// Also insert timestamp into PTP Pdelay_Resp messages
+    OnestepP2p = 3,
+}
+
+/// HWTSTAMP_FILTER_XXX, incoming packets to timestamp
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, IntEnum)]
+#[repr(i32)]
+pub enum HwTstampRxFilter {
+    None = 0,
+    All = 1,
+    /// Driver specific (only for get)
+    Some = 2,
+    PtpV1L4Event = 3,
+    PtpV1L4Sync = 4,
+    PtpV1L4DelayReq = 5,
+    PtpV2L4Event = 6,
+    PtpV2L4Sync = 7,
+    PtpV2L4DelayReq = 8,
+    PtpV2L2Event = 9,
+    PtpV2L2Sync = 10,
+    PtpV2L2DelayReq = 11,
+    /// PTP v2 over any transport
+    PtpV2Event = 12,
+    PtpV2Sync = 13,
+    PtpV2DelayReq = 14,
+    NtpAll = 15,
+}
+
+/// Hardware timestamping config of NIC (SIOCGHWTSTAMP/SIOCSHWTSTAMP)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct HwTstampConfig {
+    pub tx_type: HwTstampTx,
+    pub rx_filter: HwTstampRxFilter,
+}
+
+/// struct hwtstamp_config
+#[derive(Default)]
+#[repr(C)]
+struct HwTstampConfigRaw {
+    /// reserved, must be zero
+    flags: c_int,
+    tx_type: c_int,
+    rx_filter: c_int,
+}
+
+/// Multicast group joined on interface (`ip maddr`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct McastMembership {
+    pub ifindex: c_int,
+    pub name: String,
+    pub group: IpAddr,
+    /// Number of sockets (and kernel itself) joined
+    pub users: u32,
+}
+
+/// struct iw_point of wireless extensions
+#[repr(C)]
+struct IwPoint {
+    pointer: *mut c_void,
+    length: u16,
+    flags: u16,
+}
+
+/// struct iwreq, union iwreq_data is 16 bytes
+#[repr(C)]
+struct IwReq {
+    name: [c_char; IF_NAMESIZE],
+    point: IwPoint,
+    _pad: [u8; 16 - size_of::<IwPoint>()],
+}
+
+/// Entry of SIOCGIFCONF
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IfConfEntry {
+    /// With alias label (e.g. `eth0:1`)
+    pub name: String,
+    pub addr: Ipv4Addr,
+}
+
+/// struct ifconf
+#[repr(C)]
+struct IfConf {
+    /// Size of buffer in bytes
+    len: c_int,
+    /// NULL to query the needed size
+    req: *mut ifreq,
+}
+
+/// Cached name <-> ifindex mapping, kept in sync by RTM_NEWLINK/RTM_DELLINK
+///
+/// Changes are applied when `refresh` (or a lookup) is called, register it
+/// to epoll to know when there are pending ones.
+pub struct IfaceRegistry {
+    monitor: NetlinkMonitor,
+    by_name: HashMap<String, c_int>,
+    by_index: HashMap<c_int, String>,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 //// Implementations
 
@@ -177,6 +369,53 @@ impl IntoIterator for IfAddrTbl {
     }
 }
 
+impl RtnlLinkStats64 {
+    /// Missing trailing fields (older kernel) are zero
+    pub(crate) fn from_bytes(data: &[u8]) -> Self {
+        let mut stats = Self::default();
+
+        let len = data.len().min(size_of::<Self>());
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                &mut stats as *mut Self as *mut u8,
+                len,
+            );
+        }
+
+        stats
+    }
+}
+
+impl IfFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<IfFlag> for IfFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: IfFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for IfFlag {
+    type Output = IfFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        IfFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl Into<IfFlags> for IfFlag {
+    fn into(self) -> IfFlags {
+        IfFlags(self.to_bits())
+    }
+}
+
 impl BitAnd<IfFlag> for IfFlags {
     type Output = bool;
 
@@ -207,17 +446,551 @@ impl Debug for IfFlags {
     }
 }
 
+impl IfaceRegistry {
+    pub fn new() -> errno::Result<Self> {
+        // subscribe before dump, or changes between them would be lost
+        let monitor = NetlinkMonitor::new(RtnlGroup::Link.into())?;
+
+        monitor.set_nonblocking(true)?;
+
+        let mut this = Self {
+            monitor,
+            by_name: HashMap::new(),
+            by_index: HashMap::new(),
+        };
+
+        this.reload()?;
+
+        Ok(this)
+    }
+
+    /// Apply pending link events
+    pub fn refresh(&mut self) -> errno::Result<()> {
+        loop {
+            match self.monitor.next_event() {
+                Ok(Some(
+                    NetlinkEvent::LinkUp(link) | NetlinkEvent::LinkDown(link),
+                )) => self.insert(link.ifindex, link.name),
+                Ok(Some(NetlinkEvent::LinkRemoved(link))) => {
+                    self.remove(link.ifindex)
+                }
+                Ok(Some(_)) => (),
+                Ok(None) => break Ok(()),
+                // events were dropped, re-synchronize
+                Err(PosixError::ENOBUFS) => self.reload()?,
+                Err(err) => break Err(err),
+            }
+        }
+    }
+
+    /// Like `if_nametoindex`, ENODEV if not found
+    pub fn index(&mut self, name: &str) -> errno::Result<c_int> {
+        self.refresh()?;
+
+        self.by_name.get(name).copied().ok_or(PosixError::ENODEV)
+    }
+
+    /// Like `if_indextoname`, ENXIO if not found
+    pub fn name(&mut self, ifindex: c_int) -> errno::Result<&str> {
+        self.refresh()?;
+
+        self.by_index
+            .get(&ifindex)
+            .map(|name| name.as_str())
+            .ok_or(PosixError::ENXIO)
+    }
+
+    /// Cached (ifindex, name) pairs, call `refresh` before it if needed
+    pub fn iter(&self) -> impl Iterator<Item = (c_int, &str)> {
+        self.by_index
+            .iter()
+            .map(|(ifindex, name)| (*ifindex, name.as_str()))
+    }
+
+    /// Drop cache and dump all links
+    fn reload(&mut self) -> errno::Result<()> {
+        // drain queued events, the dump is newer
+        while let Ok(Some(_)) = self.monitor.next_event() {}
+
+        self.by_name.clear();
+        self.by_index.clear();
+
+        for link in get_links()? {
+            self.insert(link.ifindex, link.name);
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, ifindex: c_int, name: String) {
+        // renamed
+        if let Some(old) = self.by_index.insert(ifindex, name.clone()) {
+            self.by_name.remove(&old);
+        }
+
+        self.by_name.insert(name, ifindex);
+    }
+
+    fn remove(&mut self, ifindex: c_int) {
+        if let Some(name) = self.by_index.remove(&ifindex) {
+            self.by_name.remove(&name);
+        }
+    }
+}
+
+impl IfaceWatcher {
+    pub fn set_nonblocking(&self, nonblocking: bool) -> errno::Result<()> {
+        self.monitor.set_nonblocking(nonblocking)
+    }
+
+    /// Next event, `Ok(None)` if it would block (non-blocking mode)
+    pub fn next_event(&mut self) -> errno::Result<Option<IfaceEvent>> {
+        loop {
+            let Some(event) = self.monitor.next_event()?
+            else {
+                return Ok(None);
+            };
+
+            let event = match event {
+                NetlinkEvent::LinkUp(link) | NetlinkEvent::LinkDown(link) => {
+                    match self.flags.insert(link.ifindex, link.flags) {
+                        None => IfaceEvent::Added(link),
+                        Some(old) if old.to_bits() != link.flags.to_bits() => {
+                            IfaceEvent::FlagsChanged { link, old }
+                        }
+                        // other attributes (mtu, name, ...) changed
+                        Some(_) => continue,
+                    }
+                }
+                NetlinkEvent::LinkRemoved(link) => {
+                    self.flags.remove(&link.ifindex);
+
+                    IfaceEvent::Removed(link)
+                }
+                NetlinkEvent::AddrAdded(addr) => IfaceEvent::AddrChanged {
+                    addr,
+                    removed: false,
+                },
+                NetlinkEvent::AddrRemoved(addr) => IfaceEvent::AddrChanged {
+                    addr,
+                    removed: true,
+                },
+                _ => continue,
+            };
+
+            return Ok(Some(event));
+        }
+    }
+}
+
+impl Iterator for IfaceWatcher {
+    type Item = errno::Result<IfaceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+impl AsFd for IfaceWatcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.monitor.as_fd()
+    }
+}
+
+impl AsFd for IfaceRegistry {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.monitor.as_fd()
+    }
+}
+
+impl IfaceCtl {
+    pub fn new() -> errno::Result<Self> {
+        let sock = socket(
+            AddressFamily::INET,
+            SocketType::DGRAM,
+            ExtraBehavior::new().close_on_exec(),
+            Default::default(),
+        )?;
+
+        Ok(Self { sock })
+    }
+
+    pub fn index(&self, name: &str) -> errno::Result<c_int> {
+        let mut ifr = ifreq(name)?;
+
+        self.ioctl(IoctlOpcode::GetIfaceIndex, &mut ifr)?;
+
+        Ok(unsafe { ifr.ifr_ifru.ifr_ifindex })
+    }
+
+    pub fn hwaddr(&self, name: &str) -> errno::Result<HwAddr> {
+        let mut ifr = ifreq(name)?;
+
+        self.ioctl(IoctlOpcode::GetIfaceHwAddr, &mut ifr)?;
+
+        let ty =
+            HwType::try_from(unsafe { ifr.ifr_ifru.ifr_hwaddr.sa_family })
+                .unwrap();
+        let addr = Mac::from(unsafe { ifr.ifr_ifru.ifr_hwaddr.sa_data });
+
+        Ok(HwAddr { ty, addr })
+    }
+
+    pub fn mtu(&self, name: &str) -> errno::Result<c_int> {
+        let mut ifr = ifreq(name)?;
+
+        self.ioctl(IoctlOpcode::GetIfMTU, &mut ifr)?;
+
+        Ok(unsafe { ifr.ifr_ifru.ifr_mtu })
+    }
+
+    pub fn ip(&self, name: &str) -> errno::Result<InAddr> {
+        let mut ifr = ifreq(name)?;
+
+        self.ioctl(IoctlOpcode::GetIfaceAddr, &mut ifr)?;
+
+        Ok(SockAddrIn::from(unsafe { ifr.ifr_ifru.ifr_addr }).addr)
+    }
+
+    pub fn netmask(&self, name: &str) -> errno::Result<InAddr> {
+        let mut ifr = ifreq(name)?;
+
+        self.ioctl(IoctlOpcode::GetIfaceNetmask, &mut ifr)?;
+
+        Ok(SockAddrIn::from(unsafe { ifr.ifr_ifru.ifr_netmask }).addr)
+    }
+
+    pub fn broadcast(&self, name: &str) -> errno::Result<InAddr> {
+        let mut ifr = ifreq(name)?;
+
+        self.ioctl(IoctlOpcode::GetIfaceBrdAddr, &mut ifr)?;
+
+        Ok(SockAddrIn::from(unsafe { ifr.ifr_ifru.ifr_broadaddr }).addr)
+    }
+
+    /// SIOCGIFCONF, all interfaces with IPv4 address in one ioctl
+    pub fn conf(&self) -> errno::Result<Vec<IfConfEntry>> {
+        let mut ifc = IfConf {
+            len: 0,
+            req: null_mut(),
+        };
+
+        loop {
+            // query needed size
+            ifc.req = null_mut();
+            ioctl(
+                self.sock.as_fd(),
+                IoctlOpcode::GetIfaceConf,
+                Some(&mut ifc),
+            )?;
+
+            // one more slot to detect address added between the two calls
+            let cap = ifc.len as usize / size_of::<ifreq>() + 1;
+            let mut reqs: Vec<ifreq> =
+                (0..cap).map(|_| unsafe { std::mem::zeroed() }).collect();
+
+            ifc.len = (cap * size_of::<ifreq>()) as c_int;
+            ifc.req = reqs.as_mut_ptr();
+            ioctl(
+                self.sock.as_fd(),
+                IoctlOpcode::GetIfaceConf,
+                Some(&mut ifc),
+            )?;
+
+            let n = ifc.len as usize / size_of::<ifreq>();
+
+            if n == cap {
+                continue;
+            }
+
+            break Ok(reqs[..n]
+                .iter()
+                .map(|ifr| IfConfEntry {
+                    name: unsafe {
+                        CStr::from_ptr(ifr.ifr_name.as_ptr() as *const c_char)
+                    }
+                    .to_string_lossy()
+                    .into_owned(),
+                    addr: SockAddrIn::from(unsafe { ifr.ifr_ifru.ifr_addr })
+                        .addr
+                        .into(),
+                })
+                .collect());
+        }
+    }
+
+    /// SIOCGHWTSTAMP, EOPNOTSUPP if driver doesn't support it
+    pub fn hwtstamp(&self, name: &str) -> errno::Result<HwTstampConfig> {
+        self.hwtstamp_ioctl(
+            name,
+            IoctlOpcode::GetHwTstamp,
+            HwTstampConfigRaw::default(),
+        )
+    }
+
+    /// SIOCSHWTSTAMP (need CAP_NET_ADMIN), return the applied config
+    ///
+    /// Driver may timestamp more than requested (e.g. `All` for a PTP
+    /// filter), or ERANGE if it's unsupported.
+    pub fn set_hwtstamp(
+        &self,
+        name: &str,
+        config: HwTstampConfig,
+    ) -> errno::Result<HwTstampConfig> {
+        self.hwtstamp_ioctl(
+            name,
+            IoctlOpcode::SetHwTstamp,
+            HwTstampConfigRaw {
+                flags: 0,
+                tx_type: config.tx_type as c_int,
+                rx_filter: config.rx_filter as c_int,
+            },
+        )
+    }
+
+    pub fn flags(&self, name: &str) -> errno::Result<IfFlags> {
+        let mut ifr = ifreq(name)?;
+
+        self.ioctl(IoctlOpcode::GetIfaceFlags, &mut ifr)?;
+
+        // ifr_flags is short
+        Ok(IfFlags(unsafe { ifr.ifr_ifru.ifr_flags } as u16 as u32))
+    }
+
+    /// See `set_ifip`
+    pub fn set_ip(&self, name: &str, ip: Ipv4Addr) -> errno::Result<()> {
+        self.set_sockaddr(name, IoctlOpcode::SetIfaceAddr, ip)
+    }
+
+    pub fn set_netmask(
+        &self,
+        name: &str,
+        mask: Ipv4Addr,
+    ) -> errno::Result<()> {
+        let bits = mask.to_bits();
+
+        // contiguous
+        if bits.leading_ones() + bits.trailing_zeros() != 32 {
+            Err(PosixError::EINVAL)?
+        }
+
+        self.set_sockaddr(name, IoctlOpcode::SetIfaceNetmask, mask)
+    }
+
+    pub fn set_broadcast(
+        &self,
+        name: &str,
+        broadcast: Ipv4Addr,
+    ) -> errno::Result<()> {
+        self.set_sockaddr(name, IoctlOpcode::SetIfaceBrdAddr, broadcast)
+    }
+
+    pub fn set_mtu(&self, name: &str, mtu: c_int) -> errno::Result<()> {
+        if mtu <= 0 {
+            Err(PosixError::EINVAL)?
+        }
+
+        let mut ifr = ifreq(name)?;
+
+        ifr.ifr_ifru.ifr_mtu = mtu;
+
+        self.ioctl(IoctlOpcode::SetIfMTU, &mut ifr)
+    }
+
+    /// See `set_ifhwaddr`
+    pub fn set_hwaddr(&self, name: &str, addr: Mac) -> errno::Result<()> {
+        if self.flags(name)? & IfFlag::Up {
+            Err(PosixError::EBUSY)?
+        }
+
+        let mut ifr = ifreq(name)?;
+
+        unsafe {
+            ifr.ifr_ifru.ifr_hwaddr.sa_family = HwType::Ether as u16;
+
+            for (dst, src) in ifr
+                .ifr_ifru
+                .ifr_hwaddr
+                .sa_data
+                .iter_mut()
+                .zip(&addr.into_arr8()[..6])
+            {
+                *dst = *src as c_char;
+            }
+        }
+
+        self.ioctl(IoctlOpcode::SetIfaceHwAddr, &mut ifr)
+    }
+
+    /// See `modify_ifflags`
+    pub fn modify_flags(
+        &self,
+        name: &str,
+        set: IfFlags,
+        clear: IfFlags,
+    ) -> errno::Result<()> {
+        let mut ifr = ifreq(name)?;
+
+        self.ioctl(IoctlOpcode::GetIfaceFlags, &mut ifr)?;
+
+        unsafe {
+            let flags = ifr.ifr_ifru.ifr_flags as u16 as u32;
+
+            ifr.ifr_ifru.ifr_flags =
+                ((flags | set.to_bits()) & !clear.to_bits()) as u16 as c_short;
+        }
+
+        self.ioctl(IoctlOpcode::SetIfaceFlags, &mut ifr)
+    }
+
+    pub fn set_up(&self, name: &str, up: bool) -> errno::Result<()> {
+        if up {
+            self.modify_flags(name, IfFlag::Up.into(), IfFlags::new())
+        }
+        else {
+            self.modify_flags(name, IfFlags::new(), IfFlag::Up.into())
+        }
+    }
+
+    pub fn set_promisc(&self, name: &str, on: bool) -> errno::Result<()> {
+        if on {
+            self.modify_flags(name, IfFlag::Promisc.into(), IfFlags::new())
+        }
+        else {
+            self.modify_flags(name, IfFlags::new(), IfFlag::Promisc.into())
+        }
+    }
+
+    fn set_sockaddr(
+        &self,
+        name: &str,
+        op: IoctlOpcode,
+        ip: Ipv4Addr,
+    ) -> errno::Result<()> {
+        let mut ifr = ifreq(name)?;
+
+        // ifr_addr, ifr_netmask and ifr_broadaddr share the same place
+        ifr.ifr_ifru.ifr_addr =
+            Into::<SockAddr>::into(SockAddrIn::from(ip)).address();
+
+        self.ioctl(op, &mut ifr)
+    }
+
+    fn hwtstamp_ioctl(
+        &self,
+        name: &str,
+        op: IoctlOpcode,
+        mut raw: HwTstampConfigRaw,
+    ) -> errno::Result<HwTstampConfig> {
+        let mut ifr = ifreq(name)?;
+
+        ifr.ifr_ifru.ifr_data = &mut raw as *mut HwTstampConfigRaw as _;
+
+        self.ioctl(op, &mut ifr)?;
+
+        Ok(HwTstampConfig {
+            tx_type: HwTstampTx::try_from(raw.tx_type)
+                .map_err(|_| PosixError::EINVAL)?,
+            rx_filter: HwTstampRxFilter::try_from(raw.rx_filter)
+                .map_err(|_| PosixError::EINVAL)?,
+        })
+    }
+
+    /// SIOCGIWESSID, `None` if not associated
+    fn wext_ssid(&self, name: &str) -> errno::Result<Option<String>> {
+        // IW_ESSID_MAX_SIZE
+        let mut essid = [0u8; 32];
+
+        let len =
+            self.wext_point(name, IoctlOpcode::GetIwEssid, &mut essid)?;
+
+        Ok((len > 0).then(|| {
+            String::from_utf8_lossy(&essid[..len.min(essid.len())])
+                .into_owned()
+        }))
+    }
+
+    /// SIOCGIWSTATS, signal level in dBm
+    fn wext_signal(&self, name: &str) -> errno::Result<Option<i8>> {
+        // struct iw_statistics
+        let mut stats = [0u8; 32];
+
+        self.wext_point(name, IoctlOpcode::GetIwStats, &mut stats)?;
+
+        // struct iw_quality {qual, level, noise, updated} at offset 2
+        let level = stats[3];
+        let updated = stats[5];
+
+        // IW_QUAL_LEVEL_INVALID, IW_QUAL_DBM
+        Ok(
+            (updated & 0x20 == 0 && updated & 0x08 != 0)
+                .then_some(level as i8),
+        )
+    }
+
+    /// Wireless extensions ioctl with struct iw_point to `buf`, return
+    /// filled length
+    fn wext_point(
+        &self,
+        name: &str,
+        op: IoctlOpcode,
+        buf: &mut [u8],
+    ) -> errno::Result<usize> {
+        let ifr = ifreq(name)?;
+
+        let mut iwr = IwReq {
+            name: [0; IF_NAMESIZE],
+            point: IwPoint {
+                pointer: buf.as_mut_ptr() as *mut c_void,
+                length: buf.len() as u16,
+                flags: 0,
+            },
+            _pad: Default::default(),
+        };
+
+        for (dst, src) in iwr.name.iter_mut().zip(ifr.ifr_name.iter()) {
+            *dst = *src as c_char;
+        }
+
+        ioctl(self.sock.as_fd(), op, Some(&mut iwr))?;
+
+        Ok(iwr.point.length as usize)
+    }
+
+    fn ioctl(&self, op: IoctlOpcode, ifr: &mut ifreq) -> errno::Result<()> {
+        ioctl(self.sock.as_fd(), op, Some(ifr))?;
+
+        Ok(())
+    }
+}
+
+impl AsFd for IfaceCtl {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.sock.as_fd()
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 //// Functions
 
 pub fn get_ifaddrtbl() -> errno::Result<IfAddrTbl> {
     unsafe {
-        let mut ifa = null_mut();
+        let mut head = null_mut();
 
-        getifaddrs(&mut ifa);
+        if getifaddrs(&mut head) == -1 {
+            Err(errno::last_os_error())?
+        }
 
+        let mut ifa = head;
         let mut items = vec![];
 
+        // NULL for absent address (e.g. netmask of tun without address)
+        let ipv4 = |sa: *mut sockaddr| -> Option<Ipv4Addr> {
+            (!sa.is_null()).then(|| {
+                InAddr::from((*(sa as *mut sockaddr_in)).sin_addr).into()
+            })
+        };
+
         while !ifa.is_null() {
             if (*ifa).ifa_addr.is_null() {
                 ifa = (*ifa).ifa_next;
@@ -232,16 +1005,26 @@ pub fn get_ifaddrtbl() -> errno::Result<IfAddrTbl> {
             let flags = IfFlags((*ifa).ifa_flags);
 
             let item = if family == SaFamily::Inet {
+                // ifa_ifu is ifu_broadaddr or ifu_dstaddr depend on flags
+                let ifu = ipv4((*ifa).ifa_ifu);
+
                 IfAddr::Inet {
                     name,
-                    addr: InAddr::from(
-                        (*((*ifa).ifa_addr as *mut sockaddr_in)).sin_addr,
-                    )
-                    .into(),
-                    mask: InAddr::from(
-                        (*((*ifa).ifa_addr as *mut sockaddr_in)).sin_addr,
-                    )
-                    .into(),
+                    addr: ipv4((*ifa).ifa_addr).unwrap(),
+                    mask: ipv4((*ifa).ifa_netmask)
+                        .unwrap_or(Ipv4Addr::UNSPECIFIED),
+                    broadcast: if flags & IfFlag::Broadcast {
+                        ifu
+                    }
+                    else {
+                        None
+                    },
+                    peer: if flags & IfFlag::PointToPoint {
+                        ifu
+                    }
+                    else {
+                        None
+                    },
                     flags,
                 }
             }
@@ -285,7 +1068,7 @@ pub fn get_ifaddrtbl() -> errno::Result<IfAddrTbl> {
             ifa = (*ifa).ifa_next;
         }
 
-        freeifaddrs(ifa);
+        freeifaddrs(head);
 
         Ok(IfAddrTbl(items))
     }
@@ -321,70 +1104,286 @@ pub(crate) fn ifreq(name: &str) -> errno::Result<ifreq> {
     ifreq::from_name(name).map_err(|_| PosixError::EINVAL)
 }
 
-pub fn get_ifindex(name: &str) -> errno::Result<c_int> {
-    let mut ifr = ifreq(name)?;
-
-    let fd = socket(
-        AddressFamily::INET,
-        SocketType::DGRAM,
-        Default::default(),
-        Default::default(),
+/// Watch link and address changes of all interfaces
+pub fn watch() -> errno::Result<IfaceWatcher> {
+    // subscribe before dump, or changes between them would be lost
+    let monitor = NetlinkMonitor::new(
+        RtnlGroup::Link | RtnlGroup::Ipv4IfAddr | RtnlGroup::Ipv6IfAddr,
     )?;
 
-    ioctl(fd.as_fd(), IoctlOpcode::GetIfaceIndex, Some(&mut ifr))?;
+    let flags = get_links()?
+        .into_iter()
+        .map(|link| (link.ifindex, link.flags))
+        .collect();
 
-    Ok(unsafe { ifr.ifr_ifru.ifr_ifindex })
+    Ok(IfaceWatcher { monitor, flags })
 }
 
-pub fn get_ifhwaddr(name: &str) -> errno::Result<HwAddr> {
-    let mut ifr = ifreq(name)?;
+/// IPv4 and IPv6 multicast groups of all interfaces (`/proc/net/igmp`,
+/// `/proc/net/igmp6`)
+pub fn get_mcast_groups() -> errno::Result<Vec<McastMembership>> {
+    let mut groups = parse_igmp(
+        &fs::read_to_string("/proc/net/igmp").map_err(from_io_error)?,
+    );
 
-    let fd = socket(
-        AddressFamily::INET,
-        SocketType::DGRAM,
-        Default::default(),
-        Default::default(),
-    )?;
+    // IPv6 is disabled
+    if let Ok(igmp6) = fs::read_to_string("/proc/net/igmp6") {
+        groups.extend(parse_igmp6(&igmp6));
+    }
+
+    Ok(groups)
+}
 
-    ioctl(fd.as_fd(), IoctlOpcode::GetIfaceHwAddr, Some(&mut ifr))?;
+/// ```text
+/// Idx	Device    : Count Querier	Group    Users Timer	Reporter
+/// 1	lo        :     1      V3
+/// 				010000E0     1 0:00000000		0
+/// ```
+fn parse_igmp(content: &str) -> Vec<McastMembership> {
+    let mut groups = vec![];
+    let mut dev = None;
 
-    let ty = HwType::try_from(unsafe { ifr.ifr_ifru.ifr_hwaddr.sa_family })
-        .unwrap();
-    let addr = Mac::from(unsafe { ifr.ifr_ifru.ifr_hwaddr.sa_data });
+    for line in content.lines().skip(1) {
+        let mut fields = line.split_whitespace();
 
-    Ok(HwAddr { ty, addr })
+        if line.starts_with('\t') {
+            let Some((ifindex, name)) = &dev
+            else {
+                continue;
+            };
+
+            // __be32 printed by %08X
+            let (Some(group), Some(users)) = (
+                fields.next().and_then(|s| u32::from_str_radix(s, 16).ok()),
+                fields.next().and_then(|s| s.parse().ok()),
+            )
+            else {
+                continue;
+            };
+
+            groups.push(McastMembership {
+                ifindex: *ifindex,
+                name: name.clone(),
+                group: IpAddr::V4(Ipv4Addr::from_octets(group.to_ne_bytes())),
+                users,
+            });
+        }
+        else {
+            // "%-10s:", colon sticks to name of 10+ characters
+            dev = fields
+                .next()
+                .and_then(|s| s.parse::<c_int>().ok())
+                .zip(
+                    fields.next().map(|s| s.trim_end_matches(':').to_owned()),
+                );
+        }
+    }
+
+    groups
 }
 
-pub fn get_ifmtu(name: &str) -> errno::Result<c_int> {
-    let mut ifr = ifreq(name)?;
+/// ```text
+/// 1    lo              ff020000000000000000000000000001     1 0000000C 0
+/// ```
+fn parse_igmp6(content: &str) -> Vec<McastMembership> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+
+            let ifindex = fields.next()?.parse().ok()?;
+            let name = fields.next()?.to_owned();
+            let hex = fields.next()?;
+            let users = fields.next()?.parse().ok()?;
+
+            if hex.len() != 32 {
+                return None;
+            }
 
-    let fd = socket(
-        AddressFamily::INET,
-        SocketType::DGRAM,
-        Default::default(),
-        Default::default(),
-    )?;
+            let mut group = [0u8; 16];
 
-    ioctl(fd.as_fd(), IoctlOpcode::GetIfMTU, Some(&mut ifr))?;
+            for (i, byte) in group.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+            }
 
-    Ok(unsafe { ifr.ifr_ifru.ifr_mtu })
+            Some(McastMembership {
+                ifindex,
+                name,
+                group: IpAddr::V6(Ipv6Addr::from_octets(group)),
+                users,
+            })
+        })
+        .collect()
+}
+
+/// if_nametoindex(3), no socket needed
+pub fn if_nametoindex(name: &str) -> errno::Result<c_int> {
+    let name = CString::new(name).map_err(|_| PosixError::EINVAL)?;
+
+    let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+
+    if ifindex == 0 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ifindex as c_int)
+}
+
+/// if_indextoname(3)
+pub fn if_indextoname(ifindex: c_int) -> errno::Result<String> {
+    let mut buf = [0 as c_char; IF_NAMESIZE];
+
+    let ret =
+        unsafe { libc::if_indextoname(ifindex as c_uint, buf.as_mut_ptr()) };
+
+    if ret.is_null() {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(unsafe { CStr::from_ptr(buf.as_ptr()) }
+        .to_string_lossy()
+        .into_owned())
+}
+
+pub fn get_ifindex(name: &str) -> errno::Result<c_int> {
+    IfaceCtl::new()?.index(name)
+}
+
+pub fn get_ifhwaddr(name: &str) -> errno::Result<HwAddr> {
+    IfaceCtl::new()?.hwaddr(name)
+}
+
+pub fn get_ifmtu(name: &str) -> errno::Result<c_int> {
+    IfaceCtl::new()?.mtu(name)
 }
 
 pub fn get_ifip(name: &str) -> errno::Result<InAddr> {
-    let mut ifr = ifreq(name)?;
+    IfaceCtl::new()?.ip(name)
+}
 
-    let fd = socket(
-        AddressFamily::INET,
-        SocketType::DGRAM,
-        Default::default(),
-        Default::default(),
-    )?;
+pub fn get_ifnetmask(name: &str) -> errno::Result<InAddr> {
+    IfaceCtl::new()?.netmask(name)
+}
+
+pub fn get_ifbroadcast(name: &str) -> errno::Result<InAddr> {
+    IfaceCtl::new()?.broadcast(name)
+}
+
+/// SIOCSIFADDR, replace primary IPv4 address (need CAP_NET_ADMIN)
+///
+/// Netmask is reset to the classful one, set it after.
+pub fn set_ifip(name: &str, ip: Ipv4Addr) -> errno::Result<()> {
+    IfaceCtl::new()?.set_ip(name, ip)
+}
+
+/// SIOCSIFNETMASK (need CAP_NET_ADMIN)
+pub fn set_ifnetmask(name: &str, mask: Ipv4Addr) -> errno::Result<()> {
+    IfaceCtl::new()?.set_netmask(name, mask)
+}
+
+/// SIOCSIFBRDADDR (need CAP_NET_ADMIN)
+pub fn set_ifbroadcast(name: &str, broadcast: Ipv4Addr) -> errno::Result<()> {
+    IfaceCtl::new()?.set_broadcast(name, broadcast)
+}
+
+/// SIOCSIFMTU (need CAP_NET_ADMIN)
+pub fn set_ifmtu(name: &str, mtu: c_int) -> errno::Result<()> {
+    IfaceCtl::new()?.set_mtu(name, mtu)
+}
+
+/// SIOCSIFHWADDR, Ethernet address (need CAP_NET_ADMIN)
+///
+/// Interface should be down (most drivers refuse it), EBUSY otherwise.
+pub fn set_ifhwaddr(name: &str, addr: Mac) -> errno::Result<()> {
+    IfaceCtl::new()?.set_hwaddr(name, addr)
+}
+
+/// 64-bit statistics by RTM_GETLINK (IFLA_STATS64)
+pub fn get_ifstats(name: &str) -> errno::Result<RtnlLinkStats64> {
+    get_link(name)?.stats.ok_or(PosixError::ENODATA)
+}
+
+/// SIOCGIFCONF, lighter than `get_ifaddrtbl` if only names and IPv4
+/// addresses are needed
+pub fn get_ifconf() -> errno::Result<Vec<IfConfEntry>> {
+    IfaceCtl::new()?.conf()
+}
+
+/// SIOCGHWTSTAMP, see `IfaceCtl::hwtstamp`
+pub fn get_ifhwtstamp(name: &str) -> errno::Result<HwTstampConfig> {
+    IfaceCtl::new()?.hwtstamp(name)
+}
+
+/// SIOCSHWTSTAMP, see `IfaceCtl::set_hwtstamp`
+///
+/// Enable it before SO_TIMESTAMPING with hardware flags
+/// (`TimestampingFlags::hardware`).
+pub fn set_ifhwtstamp(
+    name: &str,
+    config: HwTstampConfig,
+) -> errno::Result<HwTstampConfig> {
+    IfaceCtl::new()?.set_hwtstamp(name, config)
+}
+
+/// SSID of connected Wi-Fi network, `None` if not connected
+///
+/// By nl80211, or wireless extensions (SIOCGIWESSID) if it's unavailable.
+/// Err for wired interface (ENODEV or EOPNOTSUPP).
+pub fn get_wifi_ssid(name: &str) -> errno::Result<Option<String>> {
+    let ifindex = if_nametoindex(name)?;
+
+    match Nl80211::new() {
+        Ok(mut nl) => match nl.interface(ifindex)?.ssid {
+            Some(ssid) => Ok(Some(ssid)),
+            // old kernel doesn't report SSID of station
+            None => Ok(IfaceCtl::new()?.wext_ssid(name).unwrap_or(None)),
+        },
+        Err(PosixError::ENOENT) => IfaceCtl::new()?.wext_ssid(name),
+        Err(err) => Err(err),
+    }
+}
 
-    ioctl(fd.as_fd(), IoctlOpcode::GetIfaceAddr, Some(&mut ifr))?;
+/// Signal of the associated AP in dBm, `None` if not connected (see
+/// `get_wifi_ssid`)
+pub fn get_wifi_signal(name: &str) -> errno::Result<Option<i8>> {
+    let ifindex = if_nametoindex(name)?;
 
-    Ok(SockAddrIn::from(unsafe { ifr.ifr_ifru.ifr_addr }).addr)
+    match Nl80211::new() {
+        Ok(mut nl) => {
+            // check it's wireless
+            nl.interface(ifindex)?;
+            nl.signal(ifindex)
+        }
+        Err(PosixError::ENOENT) => IfaceCtl::new()?.wext_signal(name),
+        Err(err) => Err(err),
+    }
 }
 
+pub fn get_ifflags(name: &str) -> errno::Result<IfFlags> {
+    IfaceCtl::new()?.flags(name)
+}
+
+/// Read-modify-write interface flags (SIOCGIFFLAGS/SIOCSIFFLAGS), need
+/// CAP_NET_ADMIN
+///
+/// Only the low 16 bits flags could be changed.
+pub fn modify_ifflags(
+    name: &str,
+    set: IfFlags,
+    clear: IfFlags,
+) -> errno::Result<()> {
+    IfaceCtl::new()?.modify_flags(name, set, clear)
+}
+
+/// `ip link set <name> up/down`
+pub fn set_iface_up(name: &str, up: bool) -> errno::Result<()> {
+    IfaceCtl::new()?.set_up(name, up)
+}
+
+/// `ip link set <name> promisc on/off`
+pub fn set_promisc(name: &str, on: bool) -> errno::Result<()> {
+    IfaceCtl::new()?.set_promisc(name, on)
+}
 
 #[cfg(test)]
 mod tests {
@@ -396,9 +1395,260 @@ mod tests {
 
         println!("{tbl:#?}");
 
+        let lo = tbl
+            .iter()
+            .find_map(|ifaddr| match ifaddr {
+                IfAddr::Inet {
+                    name, mask, peer, ..
+                } if name == "lo" => Some((*mask, *peer)),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(lo, (Ipv4Addr::new(255, 0, 0, 0), None));
+
         println!("{:?}", get_available_ipv4_ifname());
     }
 
+    #[test]
+    fn test_ifflags() {
+        let flags = get_ifflags("lo").unwrap();
+
+        assert!(flags & IfFlag::Loopback);
+        assert!(flags & IfFlag::Up);
+
+        assert_eq!(
+            get_ifflags("lxtest-noexist").unwrap_err(),
+            PosixError::ENODEV
+        );
+
+        // need CAP_NET_ADMIN, lo is up already
+        match set_iface_up("lo", true) {
+            Ok(()) => {
+                set_promisc("lo", true).unwrap();
+                assert!(get_ifflags("lo").unwrap() & IfFlag::Promisc);
+
+                set_promisc("lo", false).unwrap();
+                assert!(!(get_ifflags("lo").unwrap() & IfFlag::Promisc));
+            }
+            Err(err) => println!("set_iface_up: {err:?}"),
+        }
+
+        assert_eq!(
+            format!("{:?}", IfFlag::Up | IfFlag::Running),
+            "[Up, Running]"
+        );
+    }
+
+    #[test]
+    fn test_set_ifip() {
+        assert_eq!(get_ifip("lo").unwrap(), Ipv4Addr::LOCALHOST.into());
+        assert_eq!(
+            get_ifnetmask("lo").unwrap(),
+            Ipv4Addr::new(255, 0, 0, 0).into()
+        );
+
+        assert_eq!(
+            set_ifnetmask("lo", Ipv4Addr::new(255, 0, 255, 0)),
+            Err(PosixError::EINVAL)
+        );
+
+        // need CAP_NET_ADMIN, set the same address
+        match set_ifip("lo", Ipv4Addr::LOCALHOST) {
+            Ok(()) => {
+                set_ifnetmask("lo", Ipv4Addr::new(255, 0, 0, 0)).unwrap();
+            }
+            Err(err) => println!("set_ifip: {err:?}"),
+        }
+
+        // lo isn't broadcast interface
+        println!("{:?}", get_ifbroadcast("lo"));
+    }
+
+    #[test]
+    fn test_set_ifmtu() {
+        let mtu = get_ifmtu("lo").unwrap();
+
+        assert_eq!(set_ifmtu("lo", 0), Err(PosixError::EINVAL));
+
+        // lo is up
+        assert_eq!(
+            set_ifhwaddr("lo", Mac::from_bytes(&[0x02, 0, 0, 0, 0, 1])),
+            Err(PosixError::EBUSY)
+        );
+
+        // need CAP_NET_ADMIN
+        match set_ifmtu("lo", mtu) {
+            Ok(()) => assert_eq!(get_ifmtu("lo").unwrap(), mtu),
+            Err(err) => println!("set_ifmtu: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_if_nametoindex() {
+        let ifindex = if_nametoindex("lo").unwrap();
+
+        assert_eq!(ifindex, get_ifindex("lo").unwrap());
+        assert_eq!(if_indextoname(ifindex).unwrap(), "lo");
+        assert_eq!(
+            if_nametoindex("lxtest-noexist").unwrap_err(),
+            PosixError::ENODEV
+        );
+
+        let mut registry = IfaceRegistry::new().unwrap();
+
+        assert_eq!(registry.index("lo").unwrap(), ifindex);
+        assert_eq!(registry.name(ifindex).unwrap(), "lo");
+        assert_eq!(registry.index("lxtest-noexist"), Err(PosixError::ENODEV));
+
+        for (ifindex, name) in registry.iter() {
+            println!("{ifindex}: {name}");
+        }
+    }
+
+    #[test]
+    fn test_watch() {
+        let mut watcher = watch().unwrap();
+
+        watcher.set_nonblocking(true).unwrap();
+
+        // nothing happened probably
+        for event in watcher.by_ref().take(8) {
+            println!("{:?}", event.unwrap());
+        }
+
+        // need CAP_NET_ADMIN
+        match crate::netlink::create_link(
+            "lxtest-watch0",
+            &crate::netlink::LinkKind::Dummy,
+        ) {
+            Ok(()) => {
+                let ifindex = if_nametoindex("lxtest-watch0").unwrap();
+
+                crate::netlink::del_link(ifindex).unwrap();
+
+                let events = watcher.by_ref().flatten().collect::<Vec<_>>();
+
+                assert!(events.iter().any(|event| matches!(
+                    event,
+                    IfaceEvent::Added(link) if link.ifindex == ifindex
+                )));
+                assert!(events.iter().any(|event| matches!(
+                    event,
+                    IfaceEvent::Removed(link) if link.ifindex == ifindex
+                )));
+            }
+            Err(err) => println!("create_link: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_hwtstamp() {
+        // lo has no hardware clock
+        assert!(get_ifhwtstamp("lo").is_err());
+
+        for name in ["enp3s0", "eth0"] {
+            println!("{name}: {:?}", get_ifhwtstamp(name));
+        }
+    }
+
+    #[test]
+    fn test_wifi() {
+        assert!(get_wifi_ssid("lo").is_err());
+        assert!(get_wifi_signal("lo").is_err());
+
+        for name in ["wlp2s0", "wlan0"] {
+            println!(
+                "{name}: {:?} {:?}",
+                get_wifi_ssid(name),
+                get_wifi_signal(name)
+            );
+        }
+    }
+
+    #[test]
+    fn test_mcast_groups() {
+        let igmp = "Idx\tDevice    : Count Querier\tGroup    Users Timer\tReporter\n\
+                    1\tlo        :     1      V3\n\
+                    \t\t\t\t010000E0     1 0:00000000\t\t0\n\
+                    5\tlxtest-br0:     1      V3\n\
+                    \t\t\t\t010000E0     1 0:00000000\t\t0\n";
+
+        assert_eq!(
+            parse_igmp(igmp),
+            [
+                McastMembership {
+                    ifindex: 1,
+                    name: "lo".to_owned(),
+                    group: IpAddr::V4(Ipv4Addr::new(224, 0, 0, 1)),
+                    users: 1,
+                },
+                McastMembership {
+                    ifindex: 5,
+                    name: "lxtest-br0".to_owned(),
+                    group: IpAddr::V4(Ipv4Addr::new(224, 0, 0, 1)),
+                    users: 1,
+                }
+            ]
+        );
+
+        let igmp6 = "1    lo              ff020000000000000000000000000001     1 0000000C 0\n";
+
+        assert_eq!(
+            parse_igmp6(igmp6)[0].group,
+            IpAddr::V6(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1))
+        );
+
+        let groups = get_mcast_groups().unwrap();
+
+        println!("{groups:#?}");
+
+        // all-hosts group is joined on every multicast interface
+        assert!(groups.iter().any(|m| m.name == "lo"
+            && m.group == IpAddr::V4(Ipv4Addr::new(224, 0, 0, 1))));
+    }
+
+    #[test]
+    fn test_ifstats() {
+        let stats = get_ifstats("lo").unwrap();
+
+        println!("{stats:#?}");
+
+        assert_eq!(get_ifstats("lxtest-noexist"), Err(PosixError::ENODEV));
+
+        let mut data = [0u8; 16];
+        data[..8].copy_from_slice(&u64::MAX.to_ne_bytes());
+
+        let stats = RtnlLinkStats64::from_bytes(&data);
+
+        assert_eq!(stats.rx_packets, u64::MAX);
+        assert_eq!(stats.rx_bytes, 0);
+    }
+
+    #[test]
+    fn test_ifconf() {
+        let conf = get_ifconf().unwrap();
+
+        println!("{conf:#?}");
+
+        assert!(conf.contains(&IfConfEntry {
+            name: "lo".to_owned(),
+            addr: Ipv4Addr::LOCALHOST,
+        }));
+    }
+
+    #[test]
+    fn test_iface_ctl() {
+        let ctl = IfaceCtl::new().unwrap();
+
+        assert_eq!(ctl.index("lo").unwrap(), if_nametoindex("lo").unwrap());
+        assert_eq!(ctl.ip("lo").unwrap(), Ipv4Addr::LOCALHOST.into());
+        assert!(ctl.flags("lo").unwrap() & IfFlag::Loopback);
+        assert_eq!(ctl.mtu("lo").unwrap(), get_ifmtu("lo").unwrap());
+        assert_eq!(ctl.hwaddr("lo").unwrap().ty, HwType::Loopback);
+        assert_eq!(ctl.index("lxtest-noexist"), Err(PosixError::ENODEV));
+    }
+
     #[test]
     fn test_getifaddrs() {
         let name = "enp3s0";
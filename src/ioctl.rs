@@ -27,6 +27,42 @@ pub enum IoctlOpcode {
     GetIfaceAddr = 0x00008915,
     /// get ethernet MTU
     GetIfMTU = 0x00008921,
+    /// get interface flags (IFF_XXX)
+    GetIfaceFlags = 0x00008913,
+    /// get interface list (IPv4 configured ones)
+    GetIfaceConf = 0x00008912,
+    /// set interface flags (IFF_XXX)
+    SetIfaceFlags = 0x00008914,
+    /// set ipv4 address
+    SetIfaceAddr = 0x00008916,
+    /// get ipv4 broadcast address
+    GetIfaceBrdAddr = 0x00008919,
+    /// set ipv4 broadcast address
+    SetIfaceBrdAddr = 0x0000891a,
+    /// get ipv4 netmask
+    GetIfaceNetmask = 0x0000891b,
+    /// set ipv4 netmask
+    SetIfaceNetmask = 0x0000891c,
+    /// set MTU
+    SetIfMTU = 0x00008922,
+    /// set hardware address
+    SetIfaceHwAddr = 0x00008924,
+    /// set hardware timestamping config
+    SetHwTstamp = 0x000089b0,
+    /// get hardware timestamping config
+    GetHwTstamp = 0x000089b1,
+    /// get wireless statistics (wext)
+    GetIwStats = 0x00008b0f,
+    /// get ESSID (wext)
+    GetIwEssid = 0x00008b1b,
+    /// TUNSETIFF, attach TUN/TAP device
+    TunSetIff = 0x400454ca,
+    /// TUNSETPERSIST (by value)
+    TunSetPersist = 0x400454cb,
+    /// TUNSETOWNER (by value)
+    TunSetOwner = 0x400454cc,
+    /// TUNSETGROUP (by value)
+    TunSetGroup = 0x400454ce,
     
 }
 
@@ -56,3 +92,21 @@ pub fn ioctl(fd: BorrowedFd, op: IoctlOpcode, anydata: Option<&mut dyn Any>) ->
         }
     }
 }
+
+/// For request taking integer argument by value instead of pointer
+pub fn ioctl_val(
+    fd: BorrowedFd,
+    op: IoctlOpcode,
+    val: usize,
+) -> errno::Result<c_int> {
+    let ret = unsafe {
+        libc::ioctl(fd.as_raw_fd(), Into::<usize>::into(op) as _, val)
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())
+    }
+    else {
+        Ok(ret)
+    }
+}
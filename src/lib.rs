@@ -12,3 +12,17 @@ pub mod signal;
 pub mod netdb;
 pub mod unistd;
 pub mod netlink;
+pub mod icmp;
+pub mod tun;
+pub mod timerfd;
+pub mod signalfd;
+pub mod eventfd;
+pub mod process;
+pub mod pidfd;
+pub mod fcntl;
+pub mod cred;
+pub mod resource;
+pub mod sched;
+pub mod prctl;
+pub mod caps;
+pub mod mount;
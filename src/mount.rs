@@ -0,0 +1,558 @@
+//! Mount and unmount filesystems, by mount(2) or the new mount API
+//! (fsopen, fsconfig, fsmount, move_mount)
+//!
+//! All of them need CAP_SYS_ADMIN (of user namespace owning the mount
+//! namespace), usually after `unshare(CloneFlag::NewNs)` for sandbox.
+//!
+//! Ref [mount(2)](https://man7.org/linux/man-pages/man2/mount.2.html),
+//! [fsopen(2)](https://man7.org/linux/man-pages/man2/fsopen.2.html)
+
+use std::{
+    ffi::{CString, c_char, c_int, c_ulong},
+    fmt::Debug,
+    ops::{BitAnd, BitOr},
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+    ptr::null,
+};
+
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::errno::{self, PosixError};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+const FSOPEN_CLOEXEC: u32 = 0x1;
+const FSMOUNT_CLOEXEC: u32 = 0x1;
+const MOVE_MOUNT_F_EMPTY_PATH: u32 = 0x4;
+
+const FSCONFIG_SET_FLAG: u32 = 0;
+const FSCONFIG_SET_STRING: u32 = 1;
+const FSCONFIG_CMD_CREATE: u32 = 6;
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// MS_XXX of mount
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(u64)]
+#[repr(u64)]
+pub enum MsFlag {
+    ReadOnly = 1,
+    NoSuid = 2,
+    NoDev = 4,
+    NoExec = 8,
+    Synchronous = 16,
+    /// Change flags (and data) of existing mount
+    Remount = 32,
+    DirSync = 128,
+    NoSymfollow = 256,
+    NoAtime = 1024,
+    NoDiratime = 2048,
+    /// Make `source` (file or directory) visible at `target` too
+    Bind = 4096,
+    /// Move existing mount `source` to `target`
+    Move = 8192,
+    /// Apply to submounts too (with `Bind` or propagation type)
+    Rec = 16384,
+    Silent = 32768,
+    /// Propagation type, can't be bind mounted
+    Unbindable = 1 << 17,
+    /// Propagation type, no event is propagated
+    Private = 1 << 18,
+    /// Propagation type, receive events only
+    Slave = 1 << 19,
+    /// Propagation type, send and receive events with peer group
+    Shared = 1 << 20,
+    RelAtime = 1 << 21,
+    StrictAtime = 1 << 24,
+    LazyTime = 1 << 25,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(u64)]
+#[repr(transparent)]
+pub struct MsFlags(u64);
+
+/// MNT_XXX, UMOUNT_XXX of umount2
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(i32)]
+#[repr(i32)]
+pub enum UmountFlag {
+    /// Abort pending requests (NFS)
+    Force = 1,
+    /// Lazy unmount, detach now and clean up when it isn't busy
+    Detach = 2,
+    /// Mark it expired (EAGAIN), unmount by the second call if unused
+    Expire = 4,
+    /// Don't dereference `target` if it's symbolic link
+    NoFollow = 8,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(i32)]
+#[repr(transparent)]
+pub struct UmountFlags(i32);
+
+/// MOUNT_ATTR_XXX of fsmount
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(u32)]
+#[repr(u32)]
+pub enum MountAttr {
+    ReadOnly = 0x1,
+    NoSuid = 0x2,
+    NoDev = 0x4,
+    NoExec = 0x8,
+    NoAtime = 0x10,
+    StrictAtime = 0x20,
+    NoDiratime = 0x80,
+    NoSymfollow = 0x200000,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(u32)]
+#[repr(transparent)]
+pub struct MountAttrs(u32);
+
+/// Filesystem context of fsopen, configured by `set_xxx` then `create`
+/// and `mount`
+#[derive(Debug)]
+pub struct FsContext {
+    fd: OwnedFd,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl MsFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<MsFlag> for MsFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: MsFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for MsFlag {
+    type Output = MsFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        MsFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<MsFlag> for &MsFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: MsFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<MsFlags> for MsFlag {
+    fn into(self) -> MsFlags {
+        MsFlags(self.to_bits())
+    }
+}
+
+impl Debug for MsFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in MsFlag::iter().filter(|e| self & *e).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UmountFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<UmountFlag> for UmountFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: UmountFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for UmountFlag {
+    type Output = UmountFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        UmountFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<UmountFlag> for &UmountFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: UmountFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<UmountFlags> for UmountFlag {
+    fn into(self) -> UmountFlags {
+        UmountFlags(self.to_bits())
+    }
+}
+
+impl Debug for UmountFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in UmountFlag::iter().filter(|e| self & *e).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MountAttrs {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<MountAttr> for MountAttrs {
+    type Output = Self;
+
+    fn bitor(self, rhs: MountAttr) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for MountAttr {
+    type Output = MountAttrs;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        MountAttrs(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<MountAttr> for &MountAttrs {
+    type Output = bool;
+
+    fn bitand(self, rhs: MountAttr) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<MountAttrs> for MountAttr {
+    fn into(self) -> MountAttrs {
+        MountAttrs(self.to_bits())
+    }
+}
+
+impl Debug for MountAttrs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in MountAttr::iter().filter(|e| self & *e).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FsContext {
+    /// fsopen (Linux 5.2), e.g. "tmpfs", "proc"
+    pub fn open(fstype: &str) -> errno::Result<Self> {
+        let fstype = to_cstring(fstype.as_bytes())?;
+
+        let ret = unsafe {
+            libc::syscall(libc::SYS_fsopen, fstype.as_ptr(), FSOPEN_CLOEXEC)
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(ret as RawFd) },
+        })
+    }
+
+    /// Boolean option, e.g. "ro"
+    pub fn set_flag(&self, key: &str) -> errno::Result<()> {
+        let key = to_cstring(key.as_bytes())?;
+
+        self.fsconfig(FSCONFIG_SET_FLAG, key.as_ptr(), null(), 0)
+    }
+
+    /// Option with value, e.g. "size" = "64M", "source" = "none"
+    pub fn set_string(&self, key: &str, value: &str) -> errno::Result<()> {
+        let key = to_cstring(key.as_bytes())?;
+        let value = to_cstring(value.as_bytes())?;
+
+        self.fsconfig(FSCONFIG_SET_STRING, key.as_ptr(), value.as_ptr(), 0)
+    }
+
+    /// Create superblock with options set
+    pub fn create(&self) -> errno::Result<()> {
+        self.fsconfig(FSCONFIG_CMD_CREATE, null(), null(), 0)
+    }
+
+    /// fsmount, return detached mount (not attached to any path yet) to
+    /// be placed by `move_mount`
+    pub fn mount(&self, attrs: MountAttrs) -> errno::Result<OwnedFd> {
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_fsmount,
+                self.fd.as_raw_fd(),
+                FSMOUNT_CLOEXEC,
+                attrs.to_bits(),
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(unsafe { OwnedFd::from_raw_fd(ret as RawFd) })
+    }
+
+    fn fsconfig(
+        &self,
+        cmd: u32,
+        key: *const c_char,
+        value: *const c_char,
+        aux: c_int,
+    ) -> errno::Result<()> {
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_fsconfig,
+                self.fd.as_raw_fd(),
+                cmd,
+                key,
+                value,
+                aux,
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(())
+    }
+}
+
+impl AsFd for FsContext {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// `source`: device, directory (`MsFlag::Bind`, `MsFlag::Move`) or
+/// anything for pseudo filesystem (e.g. "proc", "tmpfs")
+///
+/// `fstype`, `source` and `data` are ignored for remount, bind, move and
+/// changing propagation type.
+pub fn mount(
+    source: Option<&Path>,
+    target: &Path,
+    fstype: Option<&str>,
+    flags: MsFlags,
+    data: Option<&str>,
+) -> errno::Result<()> {
+    let source = source
+        .map(|p| to_cstring(p.as_os_str().as_bytes()))
+        .transpose()?;
+    let target = to_cstring(target.as_os_str().as_bytes())?;
+    let fstype = fstype.map(|s| to_cstring(s.as_bytes())).transpose()?;
+    let data = data.map(|s| to_cstring(s.as_bytes())).transpose()?;
+
+    let ret = unsafe {
+        libc::mount(
+            source.as_ref().map(|s| s.as_ptr()).unwrap_or(null()),
+            target.as_ptr(),
+            fstype.as_ref().map(|s| s.as_ptr()).unwrap_or(null()),
+            flags.to_bits() as c_ulong,
+            data.as_ref()
+                .map(|s| s.as_ptr() as *const _)
+                .unwrap_or(null()),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Bind mount `source` at `target` (with submounts if `recursive`)
+pub fn bind_mount(
+    source: &Path,
+    target: &Path,
+    recursive: bool,
+) -> errno::Result<()> {
+    let mut flags = MsFlags::new() | MsFlag::Bind;
+
+    if recursive {
+        flags = flags | MsFlag::Rec;
+    }
+
+    mount(Some(source), target, None, flags, None)
+}
+
+/// Change propagation type of mount `target` to private, so mounts inside
+/// the namespace don't leak out (e.g. "/" after unshare)
+pub fn make_private(target: &Path, recursive: bool) -> errno::Result<()> {
+    let mut flags = MsFlags::new() | MsFlag::Private;
+
+    if recursive {
+        flags = flags | MsFlag::Rec;
+    }
+
+    mount(None, target, None, flags, None)
+}
+
+/// EBUSY if it's in use (without `UmountFlag::Detach`), EINVAL if
+/// `target` isn't a mount point
+pub fn umount2(target: &Path, flags: UmountFlags) -> errno::Result<()> {
+    let target = to_cstring(target.as_os_str().as_bytes())?;
+
+    let ret = unsafe { libc::umount2(target.as_ptr(), flags.to_bits()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Attach detached mount `mnt` (of `FsContext::mount`) at `target`
+pub fn move_mount(mnt: BorrowedFd, target: &Path) -> errno::Result<()> {
+    let target = to_cstring(target.as_os_str().as_bytes())?;
+
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_move_mount,
+            mnt.as_raw_fd(),
+            c"".as_ptr(),
+            libc::AT_FDCWD,
+            target.as_ptr(),
+            MOVE_MOUNT_F_EMPTY_PATH,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+fn to_cstring(bytes: &[u8]) -> errno::Result<CString> {
+    CString::new(bytes).map_err(|_| PosixError::EINVAL)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process, thread};
+
+    use super::*;
+    use crate::sched::{CloneFlag, unshare};
+
+    #[test]
+    fn test_mount() {
+        let dir = std::env::temp_dir()
+            .join(format!("lxtest-mount-{}", process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        let dir2 = dir.clone();
+
+        // mount namespace of spawned thread only
+        thread::spawn(move || {
+            let dir = dir2;
+
+            match unshare(CloneFlag::NewNs.into()) {
+                Ok(()) => (),
+                Err(err) => {
+                    assert_eq!(err, PosixError::EPERM);
+                    return;
+                }
+            }
+
+            make_private(Path::new("/"), true).unwrap();
+
+            mount(
+                Some(Path::new("tmpfs")),
+                &dir,
+                Some("tmpfs"),
+                MsFlag::NoSuid | MsFlag::NoDev,
+                Some("size=1M"),
+            )
+            .unwrap();
+
+            fs::write(dir.join("a"), b"tmp").unwrap();
+
+            umount2(&dir, UmountFlags::new()).unwrap();
+            assert!(!dir.join("a").exists());
+
+            // new mount API
+            let ctx = match FsContext::open("tmpfs") {
+                Ok(ctx) => ctx,
+                // kernel older than 5.2
+                Err(err) => {
+                    assert_eq!(err, PosixError::ENOSYS);
+                    return;
+                }
+            };
+
+            ctx.set_string("size", "1M").unwrap();
+            ctx.create().unwrap();
+
+            let mnt = ctx.mount(MountAttr::NoExec.into()).unwrap();
+
+            move_mount(mnt.as_fd(), &dir).unwrap();
+
+            fs::write(dir.join("b"), b"tmp").unwrap();
+
+            umount2(&dir, UmountFlag::Detach.into()).unwrap();
+
+            assert!(!dir.join("b").exists());
+            assert_eq!(
+                umount2(&dir, UmountFlags::new()),
+                Err(PosixError::EINVAL)
+            );
+        })
+        .join()
+        .unwrap();
+
+        fs::remove_dir(&dir).unwrap();
+    }
+}
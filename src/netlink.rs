@@ -1,25 +1,102 @@
 //! Refer [RFC-3549](https://datatracker.ietf.org/doc/html/rfc3549)
 
+pub mod genl;
+pub mod monitor;
+pub mod netns;
+pub mod nfqueue;
+pub mod nl80211;
+pub mod sock_diag;
+pub mod tc;
+
 use std::{
+    collections::{HashMap, VecDeque},
     ffi::c_int,
+    fmt::Debug,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    ops::BitOr,
-    os::fd::AsFd,
+    ops::{BitAnd, BitOr},
+    os::fd::{AsFd, BorrowedFd, OwnedFd},
+    sync::atomic::{AtomicU32, Ordering},
 };
 
 use int_enum::IntEnum;
-use libc::size_t;
+use libc::{
+    IFNAMSIZ, NETLINK_EXT_ACK, NETLINK_GET_STRICT_CHK, SOL_NETLINK, size_t,
+};
 use m6io::rawbuf::{AlignedRawBufRef, RawBufRef};
 use m6tobytes::derive_to_bits;
-use osimodel::network::ip::ToS;
-use strum::EnumIter;
+use osimodel::{datalink::Mac, network::ip::ToS};
+use strum::{EnumIter, IntoEnumIterator};
 
-use crate::{errno, iface::get_ifindex, socket::*};
+use crate::{
+    errno::{self, PosixError},
+    iface::{IfFlag, IfFlags, RtnlLinkStats64, get_ifindex},
+    socket::*,
+};
 
 
 pub const NLMSG_ALIGNTO: usize = 4;
 pub const RTA_ALIGNTO: usize = 4;
 
+/* IFLA_XXX (struct ifinfomsg attributes) */
+const IFLA_ADDRESS: u16 = 1;
+const IFLA_IFNAME: u16 = 3;
+const IFLA_MTU: u16 = 4;
+const IFLA_LINK: u16 = 5;
+const IFLA_MASTER: u16 = 10;
+const IFLA_OPERSTATE: u16 = 16;
+const IFLA_LINKINFO: u16 = 18;
+const IFLA_STATS64: u16 = 23;
+const IFLA_INFO_KIND: u16 = 1;
+const IFLA_INFO_DATA: u16 = 2;
+/// IFLA_INFO_DATA of veth
+const VETH_INFO_PEER: u16 = 1;
+/// IFLA_INFO_DATA of vlan
+const IFLA_VLAN_ID: u16 = 1;
+const IFLA_VLAN_EGRESS_QOS: u16 = 3;
+const IFLA_VLAN_INGRESS_QOS: u16 = 4;
+/// Entry of IFLA_VLAN_EGRESS_QOS/IFLA_VLAN_INGRESS_QOS
+const IFLA_VLAN_QOS_MAPPING: u16 = 1;
+/// VLAN ID 0 and 4095 are reserved
+const VLAN_VID_MASK: u16 = 0x0fff;
+/// IFLA_INFO_DATA of macvlan
+const IFLA_MACVLAN_MODE: u16 = 1;
+/// IFLA_INFO_DATA of ipvlan
+const IFLA_IPVLAN_MODE: u16 = 1;
+
+/* IFA_XXX (struct ifaddrmsg attributes) */
+const IFA_ADDRESS: u16 = 1;
+const IFA_LOCAL: u16 = 2;
+const IFA_LABEL: u16 = 3;
+const IFA_FLAGS: u16 = 8;
+
+/* NDA_XXX (struct ndmsg attributes) */
+const NDA_DST: u16 = 1;
+const NDA_LLADDR: u16 = 2;
+/// NTF_XXX (ndmsg.flags), proxy (published) entry
+const NTF_PROXY: u8 = 0x08;
+
+/* IFAL_XXX (struct ifaddrlblmsg attributes) */
+const IFAL_ADDRESS: u16 = 1;
+const IFAL_LABEL: u16 = 2;
+
+/* RTAX_XXX (RTA_METRICS nested attributes) */
+const RTAX_MTU: u16 = 2;
+const RTAX_WINDOW: u16 = 3;
+const RTAX_RTT: u16 = 4;
+const RTAX_ADVMSS: u16 = 8;
+const RTAX_HOPLIMIT: u16 = 10;
+const RTAX_INITCWND: u16 = 11;
+
+/* NLMSGERR_ATTR_XXX (extended ACK TLVs) */
+const NLMSGERR_ATTR_MSG: u16 = 1;
+const NLMSGERR_ATTR_OFFS: u16 = 2;
+
+/// Receive buffer for dump (kernel dump messages fit in it)
+const NL_DUMP_BUF_SIZE: usize = 32 * 1024;
+
+/// Sequence number shared by all requests of this process
+static NL_SEQ: AtomicU32 = AtomicU32::new(1);
+
 ////////////////////////////////////////////////////////////////////////////////
 //// Traits
 
@@ -31,6 +108,16 @@ pub trait FillBuf {
     fn fill_buf(&self, buf: &mut [u8]);
 }
 
+/// Netlink message payload of a request (family header and attributes)
+pub trait NlPayload: FillBuf {
+    fn msg_type(&self) -> NlMsgType;
+
+    /// NLM_F_REQUEST and NLM_F_ACK are added by `NetlinkSocket::request`
+    fn msg_flags(&self) -> NlMsgFlags {
+        NlMsgFlags::default()
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 //// Structures
 
@@ -77,11 +164,28 @@ pub enum NlMsgCtrlType {
 #[repr(u16)]
 #[non_exhaustive]
 pub enum NlMsgRouteType {
+    NewLink = 16,
+    DelLink = 17,
+    GetLink = 18,
+    SetLink = 19,
+    NewAddr = 20,
+    DelAddr = 21,
+    GetAddr = 22,
     NewRoute = 24,
+    DelRoute = 25,
     GetRoute = 26,
+    NewNeigh = 28,
+    DelNeigh = 29,
+    GetNeigh = 30,
+    NewQdisc = 36,
+    DelQdisc = 37,
+    GetQdisc = 38,
+    NewAddrLabel = 72,
+    DelAddrLabel = 73,
+    GetAddrLabel = 74,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 #[derive_to_bits(u16)]
 #[repr(transparent)]
 pub struct NlMsgFlags(u16);
@@ -116,6 +220,17 @@ pub enum NlMsgGetFlag {
     Dump = 0x300,
 }
 
+/// Flags of NLMSG_ERROR (ACK) message
+#[derive(Clone, Copy, PartialEq, Eq, Debug, IntEnum)]
+#[derive_to_bits(u16)]
+#[repr(u16)]
+pub enum NlMsgAckFlag {
+    /// Request payload was capped (only request header echoed)
+    Capped = 0x100,
+    /// Extended ACK TLVs were included
+    AckTlvs = 0x200,
+}
+
 /// For Creation/Modification
 #[derive(Clone, Copy, PartialEq, Eq, Debug, IntEnum)]
 #[derive_to_bits(u16)]
@@ -231,9 +346,17 @@ pub struct RtAttrType(u16);
 #[repr(u16)]
 #[non_exhaustive]
 pub enum RtAttrKind {
+    Dst = 1,
+    Src = 2,
     Iif = 3,
     Oif = 4,
     Gateway = 5,
+    Priority = 6,
+    PrefSrc = 7,
+    Metrics = 8,
+    Multipath = 9,
+    CacheInfo = 12,
+    Table = 15,
     Oth(u16),
 }
 
@@ -244,16 +367,86 @@ pub enum RtReqAttr {
     OIf(c_int),
     /// Input Inetrface
     IIf(c_int),
+    /// Destination prefix
+    Dst(IpAddr),
+    Gateway(IpAddr),
+    /// Route metric
+    Priority(u32),
+    /// Routing table id (for id beyond u8 of `RtMsgTable`)
+    Table(u32),
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 #[non_exhaustive]
 pub enum RtRespAttr {
+    Dst(IpAddr),
+    /// Source prefix (policy routing)
+    Src(IpAddr),
     Gateway(IpAddr),
     OIf(c_int),
+    IIf(c_int),
+    /// Preferred source address
+    PrefSrc(IpAddr),
+    Priority(u32),
+    Table(u32),
+    Metrics(RouteMetrics),
+    /// ECMP next hops
+    Multipath(Vec<NextHop>),
+    CacheInfo(RtCacheInfo),
     Oth,
 }
 
+/// struct rtnexthop, followed by attributes of the next hop
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct RtNextHopHdr {
+    /// Header included
+    pub len: u16,
+    /// RTNH_F_XXX
+    pub flags: u8,
+    /// Weight - 1
+    pub hops: u8,
+    pub ifindex: c_int,
+}
+
+/// Next hop of multipath route (`nexthop via ... dev ... weight ...`)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NextHop {
+    pub ifindex: c_int,
+    pub gateway: Option<IpAddr>,
+    pub weight: u16,
+    /// RTNH_F_XXX
+    pub flags: u8,
+}
+
+/// struct rta_cacheinfo
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct RtCacheInfo {
+    pub clntref: u32,
+    /// Jiffies
+    pub lastuse: u32,
+    /// Remaining jiffies, 0 for never
+    pub expires: i32,
+    pub error: u32,
+    pub used: u32,
+    pub id: u32,
+    pub ts: u32,
+    pub tsage: u32,
+}
+
+/// RTA_METRICS, `None` for unset (kernel default)
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RouteMetrics {
+    pub mtu: Option<u32>,
+    pub window: Option<u32>,
+    /// Milliseconds
+    pub rtt: Option<u32>,
+    pub advmss: Option<u32>,
+    pub hoplimit: Option<u32>,
+    pub initcwnd: Option<u32>,
+}
+
 // pub struct NetlinkResponse {
 //     pub hdr: NlMessageHeader,
 //     pub payload: Option<NlMessagePayload>,
@@ -264,11 +457,354 @@ pub enum RtRespAttr {
 //     Oth(Vec<u8>),
 // }
 
+/// struct ifinfomsg
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct IfInfoMsgHdr {
+    /// AF_UNSPEC
+    pub family: u8,
+    pub _pad: u8,
+    /// ARPHRD_XXX
+    pub ty: u16,
+    pub index: c_int,
+    /// IFF_XXX
+    pub flags: u32,
+    /// IFF_XXX change mask
+    pub change: u32,
+}
+
+/// IF_OPER_XXX (RFC-2863 operational status)
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, IntEnum)]
+#[repr(u8)]
+pub enum OperState {
+    #[default]
+    Unknown = 0,
+    NotPresent = 1,
+    Down = 2,
+    LowerLayerDown = 3,
+    Testing = 4,
+    Dormant = 5,
+    Up = 6,
+}
+
+/// Virtual link type (IFLA_INFO_KIND) to create
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LinkKind {
+    /// Ethernet pair, `peer` is the name of the other end
+    Veth {
+        peer: String,
+    },
+    Dummy,
+    Bridge,
+    /// Bonding (mode is round-robin by default, configurable by sysfs)
+    Bond,
+    /// 802.1Q VLAN `id` on top of link `parent` (ifindex)
+    Vlan {
+        id: u16,
+        parent: c_int,
+        /// VLAN priority (PCP) of received frame -> skb priority
+        ingress_qos: Vec<VlanQosMapping>,
+        /// skb priority -> VLAN priority (PCP) of sent frame
+        egress_qos: Vec<VlanQosMapping>,
+    },
+    /// Virtual MAC address on top of link `parent`
+    Macvlan {
+        parent: c_int,
+        mode: MacvlanMode,
+    },
+    /// Share MAC address of link `parent`, dispatch by IP address
+    Ipvlan {
+        parent: c_int,
+        mode: IpvlanMode,
+    },
+}
+
+/// MACVLAN_MODE_XXX
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum MacvlanMode {
+    /// No communication between macvlans of the same parent
+    Private = 1,
+    /// Communicate through the external switch (hairpin)
+    Vepa = 2,
+    /// Communicate directly through the parent
+    #[default]
+    Bridge = 4,
+    /// Take over the parent (only one macvlan)
+    Passthru = 8,
+}
+
+/// IPVLAN_MODE_XXX
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u16)]
+pub enum IpvlanMode {
+    #[default]
+    L2 = 0,
+    L3 = 1,
+    /// L3 with netfilter (conntrack) support
+    L3s = 2,
+}
+
+/// struct ifla_vlan_qos_mapping
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct VlanQosMapping {
+    pub from: u32,
+    pub to: u32,
+}
+
+/// Network interface (RTM_NEWLINK)
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub ifindex: c_int,
+    pub name: String,
+    pub mtu: u32,
+    pub flags: IfFlags,
+    /// Only for Ethernet like (6 bytes) link layer address
+    pub mac: Option<Mac>,
+    pub oper_state: OperState,
+    /// IFLA_INFO_KIND (veth, bridge, ...), `None` for physical device
+    pub link_kind: Option<String>,
+    /// IFLA_STATS64
+    pub stats: Option<RtnlLinkStats64>,
+    /// Bridge or bond it's enslaved to (IFLA_MASTER)
+    pub master: Option<c_int>,
+}
+
+/// struct ifaddrmsg
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct IfAddrMsgHdr {
+    /// AF_INET, AF_INET6, AF_UNSPEC for all (dump)
+    pub family: u8,
+    pub prefixlen: u8,
+    /// IFA_F_XXX (low 8 bits)
+    pub flags: u8,
+    /// RT_SCOPE_XXX
+    pub scope: u8,
+    pub index: u32,
+}
+
+/// IFA_F_XXX
+#[derive(Clone, Copy, PartialEq, Eq, Debug, IntEnum, EnumIter)]
+#[derive_to_bits(u32)]
+#[repr(u32)]
+pub enum IfaFlag {
+    Secondary = 0x01,
+    /// Skip Duplicate Address Detection (IPv6)
+    NoDad = 0x02,
+    Optimistic = 0x04,
+    DadFailed = 0x08,
+    HomeAddress = 0x10,
+    Deprecated = 0x20,
+    Tentative = 0x40,
+    Permanent = 0x80,
+    ManageTempAddr = 0x100,
+    /// Don't add prefix route automatically
+    NoPrefixRoute = 0x200,
+    McAutoJoin = 0x400,
+    StablePrivacy = 0x800,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+#[derive_to_bits(u32)]
+#[repr(transparent)]
+pub struct IfaFlags(u32);
+
+/// Interface address (RTM_NEWADDR)
+#[derive(Debug, Clone)]
+pub struct Address {
+    pub ifindex: c_int,
+    /// IFA_LOCAL (IPv4) or IFA_ADDRESS
+    pub addr: IpAddr,
+    pub prefix_len: u8,
+    pub flags: IfaFlags,
+    pub scope: RtMsgScope,
+    /// IFA_LABEL (IPv4 alias like `eth0:1`)
+    pub label: Option<String>,
+}
+
+/// struct ndmsg
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct NdMsgHdr {
+    pub family: u8,
+    pub _pad1: u8,
+    pub _pad2: u16,
+    pub ifindex: c_int,
+    /// NUD_XXX
+    pub state: u16,
+    /// NTF_XXX
+    pub flags: u8,
+    /// RTN_XXX
+    pub ty: u8,
+}
+
+/// NUD_XXX (Neighbor Unreachability Detection state)
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, IntEnum)]
+#[repr(u16)]
+pub enum NudState {
+    #[default]
+    None = 0x00,
+    Incomplete = 0x01,
+    Reachable = 0x02,
+    Stale = 0x04,
+    Delay = 0x08,
+    Probe = 0x10,
+    Failed = 0x20,
+    /// Device without ARP
+    NoArp = 0x40,
+    /// Static entry
+    Permanent = 0x80,
+}
+
+/// ARP (IPv4) / NDP (IPv6) cache entry
+#[derive(Debug, Clone)]
+pub struct Neighbor {
+    pub ifindex: c_int,
+    pub ip: IpAddr,
+    /// `None` for incomplete/failed entry
+    pub lladdr: Option<Mac>,
+    pub state: NudState,
+    /// Proxy entry (NTF_PROXY), kernel answers ARP/NDP for `ip`
+    pub proxy: bool,
+}
+
+/// struct ifaddrlblmsg
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct IfAddrLblMsgHdr {
+    /// AF_INET6 only
+    pub family: u8,
+    pub _res: u8,
+    pub prefixlen: u8,
+    pub flags: u8,
+    pub index: u32,
+    pub seq: u32,
+}
+
+/// IPv6 address label (RFC 6724 policy table entry, `ip addrlabel`)
+///
+/// Source address with the same label as destination is preferred.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AddrLabel {
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    /// 0 for any interface
+    pub ifindex: c_int,
+    pub label: u32,
+}
+
+/// Route to be added or deleted (`ip route add/del`)
+#[derive(Clone, Copy, Debug)]
+pub struct RouteSpec {
+    /// Destination prefix
+    pub dst: IpAddr,
+    /// Prefix length, 0 for default route
+    pub dst_len: u8,
+    pub gateway: Option<IpAddr>,
+    /// Output interface
+    pub oif: Option<c_int>,
+    /// RTA_PRIORITY
+    pub metric: Option<u32>,
+    pub table: RtMsgTable,
+}
+
+/// Route dump filter, `None` matches all
+///
+/// Applied by kernel if strict checking (NETLINK_GET_STRICT_CHK) is
+/// supported, and by us anyway.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct RouteFilter {
+    pub family: Option<RtFamily>,
+    /// Output interface
+    pub oif: Option<c_int>,
+    pub table: Option<u32>,
+}
+
+/// Address dump filter, `None` matches all (see `RouteFilter`)
+#[derive(Default, Clone, Copy, Debug)]
+pub struct AddrFilter {
+    pub family: Option<RtFamily>,
+    pub ifindex: Option<c_int>,
+}
+
+/// struct nlmsgerr (NLMSG_ERROR payload) with extended ACK
+#[derive(Debug, Clone)]
+pub struct NlMsgErr {
+    /// 0 for ACK or negative errno
+    pub error: i32,
+    /// Header of the request that caused it
+    pub req: NlMsgHdr,
+    /// NLMSGERR_ATTR_MSG, human readable error message
+    pub msg: Option<String>,
+    /// NLMSGERR_ATTR_OFFS, offset of the invalid attribute in request
+    pub offset: Option<u32>,
+}
+
+/// Request of family header `H` (e.g. `RtMsgHdr`) followed by attributes
+#[derive(Debug, Clone)]
+pub struct NlRequest<H> {
+    pub ty: NlMsgType,
+    pub flags: NlMsgFlags,
+    pub hdr: H,
+    /// Encoded attributes (RTA aligned)
+    pub attrs: Vec<u8>,
+}
+
+/// Owned netlink message of reply
+#[derive(Debug, Clone)]
+pub struct NlMsg {
+    pub hdr: NlMsgHdr,
+    pub payload: Vec<u8>,
+}
+
+/// Netlink socket bound to kernel for request/response
+pub struct NetlinkSocket {
+    sock: OwnedFd,
+    portid: u32,
+    buf: Vec<u8>,
+}
+
+/// Streaming reader of netlink messages on socket `sock`
+///
+/// Receive buffer grows to fit every datagram, so large dumps aren't
+/// truncated. It ends after NLMSG_DONE.
+pub struct NlMsgReader<F> {
+    sock: F,
+    buf: Vec<u8>,
+    /// messages of last datagram not yet taken
+    pending: VecDeque<NlMsg>,
+    done: bool,
+}
+
+/// Attributes (struct rtattr) encoder, every attribute is RTA aligned
+#[derive(Debug, Default, Clone)]
+pub struct AttrBuilder {
+    buf: Vec<u8>,
+}
+
 pub(crate) struct NlMsgRaw {
     pub hdr: NlMsgHdr,
     pub payload: AlignedRawBufRef,
 }
 
+/// `RtMsgHdr` as received, `family`, `scope` and `ty` aren't validated
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct RtMsgHdrRaw {
+    family: u8,
+    dst_len: u8,
+    src_len: u8,
+    tos: ToS,
+    table: RtMsgTable,
+    protocol: RtMsgProto,
+    scope: u8,
+    ty: u8,
+    flags: RtMsgFlags,
+}
+
 pub(crate) struct RtMsgRaw {
     pub hdr: RtMsgHdr,
     pub attrs: Vec<RtAttrRaw>,
@@ -279,11 +815,40 @@ pub(crate) struct RtAttrRaw {
     pub payload: RawBufRef,
 }
 
+#[derive(Debug)]
 pub struct RtMsg {
     pub hdr: RtMsgHdr,
     pub attrs: Vec<RtRespAttr>,
 }
 
+/// Routing table entry (one line of `ip route show table all`)
+#[derive(Debug, Clone)]
+pub struct RouteEntry {
+    pub family: RtFamily,
+    /// Destination prefix, unspecified address for default route
+    pub dst: IpAddr,
+    pub dst_len: u8,
+    /// Source prefix of policy routing
+    pub src: Option<IpAddr>,
+    pub src_len: u8,
+    pub gateway: Option<IpAddr>,
+    /// Output interface
+    pub oif: Option<c_int>,
+    pub oif_name: Option<String>,
+    pub prefsrc: Option<IpAddr>,
+    /// RTA_PRIORITY
+    pub metric: Option<u32>,
+    /// RTA_TABLE if presents (table id beyond u8)
+    pub table: u32,
+    pub protocol: RtMsgProto,
+    pub scope: RtMsgScope,
+    pub ty: RtType,
+    pub metrics: RouteMetrics,
+    /// Empty for single path route
+    pub multipath: Vec<NextHop>,
+    pub cacheinfo: Option<RtCacheInfo>,
+}
+
 pub(crate) struct RtRespMsg {
     pub hdr: RtMsgHdr,
     pub attrs: Vec<RtRespAttr>,
@@ -297,7 +862,7 @@ impl RtAttrType {
         let x = self.to_bits();
 
         match x {
-            3 | 4 | 5 => unsafe { core::mem::transmute(x as u32) },
+            1..=9 | 12 | 15 => unsafe { core::mem::transmute(x as u32) },
             _ => RtAttrKind::Oth(x),
         }
     }
@@ -320,36 +885,180 @@ impl RtRespAttr {
         let RtAttrRaw { hdr, payload } = rta;
 
         match hdr.ty.to_kind() {
-            RtAttrKind::Iif => todo!(),
+            RtAttrKind::Iif => {
+                Self::IIf(payload.cast::<i32>().read_unaligned())
+            }
             RtAttrKind::Oif => {
                 Self::OIf(payload.cast::<i32>().read_unaligned())
             }
-            RtAttrKind::Gateway => Self::Gateway(match rth.family {
-                RtFamily::Unspec => unimplemented!(),
-                RtFamily::IPv4 => IpAddr::V4(Ipv4Addr::from_octets(
-                    payload.head_slice().try_into().unwrap(),
-                )),
-                RtFamily::IPv6 => IpAddr::V6(Ipv6Addr::from_octets(
-                    payload.head_slice().try_into().unwrap(),
-                )),
-            }),
+            RtAttrKind::Dst => {
+                rta_ip(rth.family, &payload).map_or(Self::Oth, Self::Dst)
+            }
+            RtAttrKind::Src => {
+                rta_ip(rth.family, &payload).map_or(Self::Oth, Self::Src)
+            }
+            RtAttrKind::Gateway => {
+                rta_ip(rth.family, &payload).map_or(Self::Oth, Self::Gateway)
+            }
+            RtAttrKind::PrefSrc => {
+                rta_ip(rth.family, &payload).map_or(Self::Oth, Self::PrefSrc)
+            }
+            RtAttrKind::Priority => {
+                Self::Priority(payload.cast::<u32>().read_unaligned())
+            }
+            RtAttrKind::Table => {
+                Self::Table(payload.cast::<u32>().read_unaligned())
+            }
+            RtAttrKind::Metrics => {
+                Self::Metrics(RouteMetrics::parse(payload.head_slice()))
+            }
+            RtAttrKind::Multipath => {
+                Self::Multipath(NextHop::parse_all(rth, payload.head_slice()))
+            }
+            RtAttrKind::CacheInfo => {
+                if payload.head_slice().len() < size_of::<RtCacheInfo>() {
+                    Self::Oth
+                }
+                else {
+                    Self::CacheInfo(
+                        payload.cast::<RtCacheInfo>().read_unaligned(),
+                    )
+                }
+            }
             RtAttrKind::Oth(_) => Self::Oth,
         }
     }
 }
 
-impl NlMsgHdr {
-    pub const fn payload_len(&self) -> usize {
-        if (self.len as usize) < size_of::<Self>() {
-            0
+impl NextHop {
+    /// Payload of RTA_MULTIPATH, list of rtnexthop
+    fn parse_all(rth: RtMsgHdr, buf: &[u8]) -> Vec<Self> {
+        let mut buf = AlignedRawBufRef::from_slice(buf, RTA_ALIGNTO);
+        let mut nexthops = vec![];
+
+        while buf.rem_len() >= size_of::<RtNextHopHdr>() {
+            let rtnh = buf.consume::<RtNextHopHdr>().read();
+
+            let Some(attrs_len) =
+                (rtnh.len as usize).checked_sub(size_of::<RtNextHopHdr>())
+            else {
+                break;
+            };
+
+            if attrs_len > buf.rem_len() {
+                break;
+            }
+
+            let gateway = parse_rta_raw(buf.consume_bytes(attrs_len))
+                .into_iter()
+                .find(|rta| rta.hdr.ty.to_kind() == RtAttrKind::Gateway)
+                .and_then(|rta| rta_ip(rth.family, &rta.payload));
+
+            nexthops.push(Self {
+                ifindex: rtnh.ifindex,
+                gateway,
+                weight: rtnh.hops as u16 + 1,
+                flags: rtnh.flags,
+            });
         }
-        else {
-            self.len as usize - size_of::<Self>()
+
+        nexthops
+    }
+}
+
+impl RouteMetrics {
+    fn parse(buf: &[u8]) -> Self {
+        let mut metrics = Self::default();
+
+        for rta in
+            parse_rta_raw(AlignedRawBufRef::from_slice(buf, RTA_ALIGNTO))
+        {
+            let v = Some(rta.payload.cast::<u32>().read_unaligned());
+
+            match rta.hdr.ty.to_bits() {
+                RTAX_MTU => metrics.mtu = v,
+                RTAX_WINDOW => metrics.window = v,
+                RTAX_RTT => metrics.rtt = v,
+                RTAX_ADVMSS => metrics.advmss = v,
+                RTAX_HOPLIMIT => metrics.hoplimit = v,
+                RTAX_INITCWND => metrics.initcwnd = v,
+                _ => (),
+            }
         }
+
+        metrics
     }
 }
 
-impl RtAttrHdr {
+impl RouteEntry {
+    /// `oif_name` is left `None`
+    fn parse(rth: RtMsgHdr, attrs: &[RtRespAttr]) -> Self {
+        let mut route = Self {
+            family: rth.family,
+            dst: match rth.family {
+                RtFamily::IPv6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                _ => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            },
+            dst_len: rth.dst_len,
+            src: None,
+            src_len: rth.src_len,
+            gateway: None,
+            oif: None,
+            oif_name: None,
+            prefsrc: None,
+            metric: None,
+            table: rth.table.0 as u32,
+            protocol: rth.protocol,
+            scope: rth.scope,
+            ty: rth.ty,
+            metrics: RouteMetrics::default(),
+            multipath: vec![],
+            cacheinfo: None,
+        };
+
+        for attr in attrs {
+            match *attr {
+                RtRespAttr::Dst(ip) => route.dst = ip,
+                RtRespAttr::Src(ip) => route.src = Some(ip),
+                RtRespAttr::Gateway(ip) => route.gateway = Some(ip),
+                RtRespAttr::OIf(ifindex) => route.oif = Some(ifindex),
+                RtRespAttr::PrefSrc(ip) => route.prefsrc = Some(ip),
+                RtRespAttr::Priority(metric) => route.metric = Some(metric),
+                RtRespAttr::Table(table) => route.table = table,
+                RtRespAttr::Metrics(metrics) => route.metrics = metrics,
+                RtRespAttr::Multipath(ref nexthops) => {
+                    route.multipath = nexthops.clone()
+                }
+                RtRespAttr::CacheInfo(cacheinfo) => {
+                    route.cacheinfo = Some(cacheinfo)
+                }
+                RtRespAttr::IIf(_) | RtRespAttr::Oth => (),
+            }
+        }
+
+        route
+    }
+}
+
+impl RouteFilter {
+    pub fn matches(&self, route: &RouteEntry) -> bool {
+        self.family.is_none_or(|family| family == route.family)
+            && self.oif.is_none_or(|oif| Some(oif) == route.oif)
+            && self.table.is_none_or(|table| table == route.table)
+    }
+}
+
+impl AddrFilter {
+    pub fn matches(&self, addr: &Address) -> bool {
+        self.family.is_none_or(|family| match family {
+            RtFamily::IPv4 => addr.addr.is_ipv4(),
+            RtFamily::IPv6 => addr.addr.is_ipv6(),
+            RtFamily::Unspec => true,
+        }) && self.ifindex.is_none_or(|ifindex| ifindex == addr.ifindex)
+    }
+}
+
+impl NlMsgHdr {
     pub const fn payload_len(&self) -> usize {
         if (self.len as usize) < size_of::<Self>() {
             0
@@ -360,383 +1069,2130 @@ impl RtAttrHdr {
     }
 }
 
-impl RtReqAttr {
-    pub fn kind(&self) -> RtAttrKind {
-        use RtAttrKind::*;
+impl NlMsgErr {
+    /// hdr: header of NLMSG_ERROR message, payload: its payload
+    pub(crate) fn parse(
+        hdr: &NlMsgHdr,
+        mut payload: AlignedRawBufRef,
+    ) -> Option<Self> {
+        if payload.rem_len() < size_of::<i32>() + size_of::<NlMsgHdr>() {
+            return None;
+        }
 
-        match self {
-            RtReqAttr::OIf(..) => Oif,
-            RtReqAttr::IIf(..) => Iif,
+        let error = payload.consume::<i32>().read();
+        let req = payload.consume::<NlMsgHdr>().read();
+
+        let mut err = Self {
+            error,
+            req,
+            msg: None,
+            offset: None,
+        };
+
+        let flags = hdr.flags.to_bits();
+
+        // whole request is echoed unless capped
+        if flags & NlMsgAckFlag::Capped.to_bits() == 0 {
+            let req_len = nlmsg_align(req.payload_len());
+
+            if payload.rem_len() < req_len {
+                return Some(err);
+            }
+
+            payload.consume_bytes(req_len);
         }
-    }
 
-    pub fn header(&self, data_len: usize) -> RtAttrHdr {
-        RtAttrHdr {
-            len: rta_len(data_len) as _,
-            ty: self.kind().into(),
+        if flags & NlMsgAckFlag::AckTlvs.to_bits() != 0 {
+            for rta in parse_rta_raw(payload) {
+                let data = rta.payload.head_slice();
+
+                match rta.hdr.ty.to_bits() {
+                    NLMSGERR_ATTR_MSG => err.msg = Some(attr_str(data)),
+                    NLMSGERR_ATTR_OFFS if data.len() >= 4 => {
+                        err.offset = Some(u32::from_ne_bytes(
+                            data[..4].try_into().unwrap(),
+                        ))
+                    }
+                    _ => (),
+                }
+            }
         }
+
+        Some(err)
+    }
+
+    pub fn is_ack(&self) -> bool {
+        self.error == 0
+    }
+
+    /// `Ok(())` for ACK, kernel error otherwise
+    pub fn to_result(&self) -> errno::Result<()> {
+        nlmsg_err(self.error)
     }
 }
 
-impl FillBuf for RtReqAttr {
+impl<H: Copy> FillBuf for NlRequest<H> {
     fn buf_len(&self) -> usize {
-        use RtReqAttr::*;
-
-        match self {
-            OIf(..) | IIf(..) => rta_len(4),
-        }
+        nlmsg_align(size_of::<H>()) + self.attrs.len()
     }
 
     fn fill_buf(&self, buf: &mut [u8]) {
         assert!(buf.len() >= self.buf_len());
 
-        todo!()
+        let hdr_len = nlmsg_align(size_of::<H>());
+
+        AlignedRawBufRef::from_slice(buf, NLMSG_ALIGNTO)
+            .consume::<H>()
+            .write(self.hdr);
+
+        buf[size_of::<H>()..hdr_len].fill(0);
+        buf[hdr_len..self.buf_len()].copy_from_slice(&self.attrs);
     }
 }
 
-impl RtMsg {
-    /// Assume gateway address is IPv4
-    pub fn get_gateway(&self) -> Option<IpAddr> {
-        self.attrs.iter().find_map(|attr| {
-            if let RtRespAttr::Gateway(ip) = attr {
-                Some(*ip)
-            }
-            else {
-                None
-            }
-        })
+impl<H: Copy> NlPayload for NlRequest<H> {
+    fn msg_type(&self) -> NlMsgType {
+        self.ty
     }
-}
 
-impl PartialEq<RtAttrKind> for &RtAttrKind {
-    fn eq(&self, other: &RtAttrKind) -> bool {
-        *self == other
+    fn msg_flags(&self) -> NlMsgFlags {
+        self.flags
     }
 }
 
-impl PartialEq<&Self> for RtAttrKind {
-    fn eq(&self, other: &&Self) -> bool {
-        self == *other
+impl NlMsg {
+    /// Payload for parsing (family header and attributes)
+    pub fn payload_ref(&self) -> AlignedRawBufRef {
+        AlignedRawBufRef::from_slice(&self.payload, NLMSG_ALIGNTO)
     }
 }
 
-impl BitOr<RtMsgFlag> for RtMsgFlags {
-    type Output = Self;
+impl NetlinkSocket {
+    pub fn new(protocol: SocketProtocol) -> errno::Result<Self> {
+        let sock = socket(
+            AddressFamily::NETLINK,
+            SocketType::RAW,
+            ExtraBehavior::new().close_on_exec(),
+            protocol,
+        )?;
 
-    fn bitor(self, rhs: RtMsgFlag) -> Self::Output {
-        Self(self.to_bits() | rhs.to_bits())
-    }
-}
+        bind(sock.as_fd(), SockAddrNL::default().into())?;
+        set_ext_ack(sock.as_fd(), true)?;
 
-impl BitOr<RtMsgFlag> for RtMsgFlag {
-    type Output = RtMsgFlags;
+        let portid = nl_portid(sock.as_fd())?;
 
-    fn bitor(self, rhs: RtMsgFlag) -> Self::Output {
-        RtMsgFlags(self.to_bits() | rhs.to_bits())
+        Ok(Self {
+            sock,
+            portid,
+            buf: vec![0; NL_DUMP_BUF_SIZE],
+        })
     }
-}
 
-impl RtMsgProto {
-    pub const UNSPEC: Self = Self(0);
-    pub const REDIRECT: Self = Self(0);
-    pub const KERNEL: Self = Self(0);
-    pub const BOOT: Self = Self(0);
-    pub const STATIC: Self = Self(0);
+    /// NETLINK_ROUTE (strict checking enabled if supported)
+    pub fn route() -> errno::Result<Self> {
+        let this = Self::new(SocketProtocol::NetlinkRoute)?;
 
-    pub fn custom(v: u8) -> Self {
-        assert!(v >= 5);
+        try_strict_check(this.sock.as_fd())?;
 
-        Self(v)
+        Ok(this)
     }
-}
-
-impl RtMsgTable {
-    pub const UNSPEC: Self = Self(0);
-    pub const COMPAT: Self = Self(252);
-    pub const DEFAULT: Self = Self(253);
-    pub const MAIN: Self = Self(254);
-    pub const LOCAL: Self = Self(255);
 
-    pub fn custom(v: u8) -> Self {
-        assert!(v > 0 && v <= 251);
+    /// Send `msg` and collect replies until ACK (or NLMSG_DONE for dump)
+    ///
+    /// Kernel error is returned as `Err`, replies of other requests are
+    /// dropped.
+    pub fn request<T: NlPayload>(
+        &mut self,
+        msg: &T,
+    ) -> errno::Result<Vec<NlMsg>> {
+        self.request_with(msg, |_| ())
+    }
+
+    /// `request`, but messages which aren't the reply (e.g. multicast
+    /// events, queued packets) are passed to `other` rather than dropped
+    pub fn request_with<T: NlPayload>(
+        &mut self,
+        msg: &T,
+        mut other: impl FnMut(NlMsg),
+    ) -> errno::Result<Vec<NlMsg>> {
+        let seq = self.send_with_flags(
+            msg,
+            msg.msg_flags() | NlMsgStdFlag::Request | NlMsgStdFlag::Ack,
+        )?;
+
+        let mut replies = vec![];
+
+        loop {
+            let len = recv_datagram(self.sock.as_fd(), &mut self.buf)?;
+
+            let mut nlbuf =
+                AlignedRawBufRef::from_slice(&self.buf[..len], NLMSG_ALIGNTO);
+
+            while nlmsg_ok(&nlbuf) {
+                let hdr = nlbuf.consume::<NlMsgHdr>().read();
+                let payload = nlbuf.consume_bytes(hdr.payload_len());
+
+                if !is_reply(&hdr, seq, self.portid) {
+                    let payload: RawBufRef = payload.into();
+
+                    other(NlMsg {
+                        hdr,
+                        payload: payload.head_slice().to_vec(),
+                    });
+                    continue;
+                }
+
+                if hdr.ty == NlMsgCtrlType::Done {
+                    return Ok(replies);
+                }
+
+                if hdr.ty == NlMsgCtrlType::Error {
+                    match NlMsgErr::parse(&hdr, payload) {
+                        Some(err) => err.to_result()?,
+                        None => Err(PosixError::EBADMSG)?,
+                    }
+
+                    return Ok(replies);
+                }
+
+                let payload: RawBufRef = payload.into();
+
+                replies.push(NlMsg {
+                    hdr,
+                    payload: payload.head_slice().to_vec(),
+                });
+            }
+        }
+    }
+
+    /// Send `msg` (NLM_F_REQUEST) without waiting reply, return its seq
+    pub fn send<T: NlPayload>(&self, msg: &T) -> errno::Result<u32> {
+        self.send_with_flags(msg, msg.msg_flags() | NlMsgStdFlag::Request)
+    }
+
+    /// Receive messages of one datagram (unsolicited ones included)
+    pub fn recv(&mut self) -> errno::Result<Vec<NlMsg>> {
+        let len = recv_datagram(self.sock.as_fd(), &mut self.buf)?;
+
+        Ok(parse_nlmsgs(&self.buf[..len]))
+    }
+
+    fn send_with_flags<T: NlPayload>(
+        &self,
+        msg: &T,
+        flags: NlMsgFlags,
+    ) -> errno::Result<u32> {
+        let len = nlmsg_length(msg.buf_len());
+        let mut req = vec![0u8; len];
+
+        let seq = write_nlmsg_hdr(&mut req, len, msg.msg_type(), flags);
+        msg.fill_buf(&mut req[nlmsg_length(0)..]);
+
+        send_all(self.sock.as_fd(), &req, Default::default())?;
+
+        Ok(seq)
+    }
+}
+
+impl<F: AsFd> NlMsgReader<F> {
+    pub fn new(sock: F) -> Self {
+        Self {
+            sock,
+            buf: vec![0; NL_DUMP_BUF_SIZE],
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Next message, `Ok(None)` after NLMSG_DONE or if it would block
+    /// (non-blocking socket)
+    ///
+    /// ENOBUFS means kernel dropped messages (receive buffer overrun).
+    pub fn next_msg(&mut self) -> errno::Result<Option<NlMsg>> {
+        while self.pending.is_empty() {
+            if self.done {
+                return Ok(None);
+            }
+
+            let len = match recv_datagram(self.sock.as_fd(), &mut self.buf) {
+                Ok(len) => len,
+                Err(PosixError::EAGAIN) => return Ok(None),
+                Err(err) => Err(err)?,
+            };
+
+            self.pending.extend(parse_nlmsgs(&self.buf[..len]));
+        }
+
+        let msg = self.pending.pop_front().unwrap();
+
+        if msg.hdr.ty == NlMsgCtrlType::Done {
+            self.done = true;
+            self.pending.clear();
+
+            return Ok(None);
+        }
+
+        Ok(Some(msg))
+    }
+
+    pub fn into_inner(self) -> F {
+        self.sock
+    }
+}
+
+impl<F: AsFd> Iterator for NlMsgReader<F> {
+    type Item = errno::Result<NlMsg>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_msg().transpose()
+    }
+}
+
+impl AsFd for NetlinkSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.sock.as_fd()
+    }
+}
+
+impl RtAttrHdr {
+    pub const fn payload_len(&self) -> usize {
+        if (self.len as usize) < size_of::<Self>() {
+            0
+        }
+        else {
+            self.len as usize - size_of::<Self>()
+        }
+    }
+}
+
+impl RtReqAttr {
+    pub fn kind(&self) -> RtAttrKind {
+        use RtAttrKind::*;
+
+        match self {
+            RtReqAttr::OIf(..) => Oif,
+            RtReqAttr::IIf(..) => Iif,
+            RtReqAttr::Dst(..) => Dst,
+            RtReqAttr::Gateway(..) => Gateway,
+            RtReqAttr::Priority(..) => Priority,
+            RtReqAttr::Table(..) => Table,
+        }
+    }
+
+    pub fn header(&self, data_len: usize) -> RtAttrHdr {
+        RtAttrHdr {
+            len: rta_len(data_len) as _,
+            ty: self.kind().into(),
+        }
+    }
+}
+
+impl FillBuf for RtReqAttr {
+    fn buf_len(&self) -> usize {
+        use RtReqAttr::*;
+
+        match self {
+            OIf(..) | IIf(..) | Priority(..) | Table(..) => rta_len(4),
+            Dst(IpAddr::V4(..)) | Gateway(IpAddr::V4(..)) => rta_len(4),
+            Dst(IpAddr::V6(..)) | Gateway(IpAddr::V6(..)) => rta_len(16),
+        }
+    }
+
+    fn fill_buf(&self, buf: &mut [u8]) {
+        use RtReqAttr::*;
+
+        assert!(buf.len() >= self.buf_len());
+
+        let mut buf_ref = AlignedRawBufRef::from_slice(buf, RTA_ALIGNTO);
+        let ty = self.kind().into();
+
+        match *self {
+            OIf(ifindex) | IIf(ifindex) => {
+                push_attr_u32(&mut buf_ref, ty, ifindex as _)
+            }
+            Priority(v) | Table(v) => push_attr_u32(&mut buf_ref, ty, v),
+            Dst(ip) | Gateway(ip) => push_attr_ip(&mut buf_ref, ty, ip),
+        }
+    }
+}
+
+impl AttrBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append attribute `ty` with raw payload `data`
+    pub fn bytes(mut self, ty: impl Into<RtAttrType>, data: &[u8]) -> Self {
+        let ty: RtAttrType = ty.into();
+        let len = rta_len(data.len());
+
+        self.buf.extend_from_slice(&(len as u16).to_ne_bytes());
+        self.buf.extend_from_slice(&ty.to_bits().to_ne_bytes());
+        self.buf.extend_from_slice(data);
+        self.buf.resize(rta_align(self.buf.len()), 0);
+
+        self
+    }
+
+    pub fn u8(self, ty: impl Into<RtAttrType>, v: u8) -> Self {
+        self.bytes(ty, &[v])
+    }
+
+    /// native order
+    pub fn u16(self, ty: impl Into<RtAttrType>, v: u16) -> Self {
+        self.bytes(ty, &v.to_ne_bytes())
+    }
+
+    /// native order
+    pub fn u32(self, ty: impl Into<RtAttrType>, v: u32) -> Self {
+        self.bytes(ty, &v.to_ne_bytes())
+    }
+
+    /// 4 bytes (IPv4) or 16 bytes (IPv6) in network order
+    pub fn ip(self, ty: impl Into<RtAttrType>, ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(ip) => self.bytes(ty, &ip.octets()),
+            IpAddr::V6(ip) => self.bytes(ty, &ip.octets()),
+        }
+    }
+
+    /// NUL terminated string
+    pub fn str(self, ty: impl Into<RtAttrType>, s: &str) -> Self {
+        let mut data = s.as_bytes().to_vec();
+        data.push(0);
+
+        self.bytes(ty, &data)
+    }
+
+    /// Attribute whose payload is attributes of `attrs`
+    pub fn nested(
+        self,
+        ty: impl Into<RtAttrType>,
+        attrs: AttrBuilder,
+    ) -> Self {
+        self.bytes(ty, &attrs.buf)
+    }
+
+    /// Append encoded attribute (e.g. `RtReqAttr`)
+    pub fn attr(mut self, attr: &impl FillBuf) -> Self {
+        let off = self.buf.len();
+
+        self.buf.resize(off + rta_align(attr.buf_len()), 0);
+        attr.fill_buf(&mut self.buf[off..]);
+
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl FillBuf for AttrBuilder {
+    fn buf_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn fill_buf(&self, buf: &mut [u8]) {
+        buf[..self.buf.len()].copy_from_slice(&self.buf);
+    }
+}
+
+impl From<u16> for RtAttrType {
+    fn from(value: u16) -> Self {
+        Self(value)
+    }
+}
+
+impl LinkKind {
+    /// IFLA_INFO_KIND
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Veth { .. } => "veth",
+            Self::Dummy => "dummy",
+            Self::Bridge => "bridge",
+            Self::Bond => "bond",
+            Self::Vlan { .. } => "vlan",
+            Self::Macvlan { .. } => "macvlan",
+            Self::Ipvlan { .. } => "ipvlan",
+        }
+    }
+
+    /// Lower link (IFLA_LINK)
+    pub fn parent(&self) -> Option<c_int> {
+        match self {
+            Self::Vlan { parent, .. }
+            | Self::Macvlan { parent, .. }
+            | Self::Ipvlan { parent, .. } => Some(*parent),
+            Self::Veth { .. } | Self::Dummy | Self::Bridge | Self::Bond => {
+                None
+            }
+        }
+    }
+
+    /// Payload of IFLA_INFO_DATA
+    fn info_data(&self) -> Option<AttrBuilder> {
+        match self {
+            Self::Veth { peer } => {
+                // struct ifinfomsg of peer followed by its attributes
+                let mut peer_msg = vec![0u8; size_of::<IfInfoMsgHdr>()];
+
+                peer_msg
+                    .extend(AttrBuilder::new().str(IFLA_IFNAME, peer).build());
+
+                Some(AttrBuilder::new().bytes(VETH_INFO_PEER, &peer_msg))
+            }
+            Self::Vlan {
+                id,
+                ingress_qos,
+                egress_qos,
+                ..
+            } => {
+                let mut data = AttrBuilder::new().u16(IFLA_VLAN_ID, *id);
+
+                if !ingress_qos.is_empty() {
+                    data = data.nested(
+                        IFLA_VLAN_INGRESS_QOS,
+                        vlan_qos_attrs(ingress_qos),
+                    );
+                }
+
+                if !egress_qos.is_empty() {
+                    data = data.nested(
+                        IFLA_VLAN_EGRESS_QOS,
+                        vlan_qos_attrs(egress_qos),
+                    );
+                }
+
+                Some(data)
+            }
+            Self::Macvlan { mode, .. } => {
+                Some(AttrBuilder::new().u32(IFLA_MACVLAN_MODE, *mode as u32))
+            }
+            Self::Ipvlan { mode, .. } => {
+                Some(AttrBuilder::new().u16(IFLA_IPVLAN_MODE, *mode as u16))
+            }
+            Self::Dummy | Self::Bridge | Self::Bond => None,
+        }
+    }
+}
+
+impl RtMsg {
+    /// Assume gateway address is IPv4
+    pub fn get_gateway(&self) -> Option<IpAddr> {
+        self.attrs.iter().find_map(|attr| {
+            if let RtRespAttr::Gateway(ip) = attr {
+                Some(*ip)
+            }
+            else {
+                None
+            }
+        })
+    }
+}
+
+impl PartialEq<RtAttrKind> for &RtAttrKind {
+    fn eq(&self, other: &RtAttrKind) -> bool {
+        *self == other
+    }
+}
+
+impl PartialEq<&Self> for RtAttrKind {
+    fn eq(&self, other: &&Self) -> bool {
+        self == *other
+    }
+}
+
+impl BitOr<RtMsgFlag> for RtMsgFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: RtMsgFlag) -> Self::Output {
+        Self(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitOr<RtMsgFlag> for RtMsgFlag {
+    type Output = RtMsgFlags;
+
+    fn bitor(self, rhs: RtMsgFlag) -> Self::Output {
+        RtMsgFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl RtMsgProto {
+    pub const UNSPEC: Self = Self(0);
+    pub const REDIRECT: Self = Self(1);
+    pub const KERNEL: Self = Self(2);
+    pub const BOOT: Self = Self(3);
+    pub const STATIC: Self = Self(4);
+
+    pub fn custom(v: u8) -> Self {
+        assert!(v >= 5);
 
         Self(v)
     }
 }
 
-impl BitOr<NlMsgStdFlag> for NlMsgFlags {
-    type Output = Self;
+impl RouteSpec {
+    /// Route to `dst`/`dst_len` in main table
+    pub fn new(dst: IpAddr, dst_len: u8) -> Self {
+        Self {
+            dst,
+            dst_len,
+            gateway: None,
+            oif: None,
+            metric: None,
+            table: RtMsgTable::MAIN,
+        }
+    }
+
+    pub fn gateway(mut self, gateway: IpAddr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    pub fn oif(mut self, ifindex: c_int) -> Self {
+        self.oif = Some(ifindex);
+        self
+    }
+
+    pub fn metric(mut self, metric: u32) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+
+    pub fn table(mut self, table: RtMsgTable) -> Self {
+        self.table = table;
+        self
+    }
+
+    fn family(&self) -> RtFamily {
+        match self.dst {
+            IpAddr::V4(..) => RtFamily::IPv4,
+            IpAddr::V6(..) => RtFamily::IPv6,
+        }
+    }
+}
+
+impl Link {
+    fn parse(ifi: IfInfoMsgHdr, attrs: Vec<RtAttrRaw>) -> Self {
+        let mut link = Self {
+            ifindex: ifi.index,
+            name: String::new(),
+            mtu: 0,
+            flags: IfFlags::from_bits(ifi.flags),
+            mac: None,
+            oper_state: OperState::Unknown,
+            link_kind: None,
+            stats: None,
+            master: None,
+        };
+
+        for RtAttrRaw { hdr, payload } in attrs {
+            let data = payload.head_slice();
+
+            match hdr.ty.to_bits() {
+                IFLA_IFNAME => link.name = attr_str(data),
+                IFLA_MTU if data.len() >= 4 => {
+                    link.mtu =
+                        u32::from_ne_bytes(data[..4].try_into().unwrap())
+                }
+                IFLA_ADDRESS if data.len() == 6 => {
+                    link.mac = Some(Mac::from_bytes(data))
+                }
+                IFLA_OPERSTATE if !data.is_empty() => {
+                    link.oper_state =
+                        OperState::try_from(data[0]).unwrap_or_default()
+                }
+                IFLA_MASTER if data.len() >= 4 => {
+                    link.master = Some(c_int::from_ne_bytes(
+                        data[..4].try_into().unwrap(),
+                    ))
+                }
+                IFLA_STATS64 => {
+                    link.stats = Some(RtnlLinkStats64::from_bytes(data))
+                }
+                IFLA_LINKINFO => {
+                    let nested = parse_rta_raw(AlignedRawBufRef::from_slice(
+                        data,
+                        RTA_ALIGNTO,
+                    ));
+
+                    link.link_kind = nested.into_iter().find_map(|rta| {
+                        (rta.hdr.ty.to_bits() == IFLA_INFO_KIND)
+                            .then(|| attr_str(rta.payload.head_slice()))
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        link
+    }
+}
+
+impl Address {
+    fn parse(ifa: IfAddrMsgHdr, attrs: Vec<RtAttrRaw>) -> Option<Self> {
+        let mut local = None;
+        let mut address = None;
+        let mut label = None;
+        let mut flags = IfaFlags(ifa.flags as u32);
+
+        for RtAttrRaw { hdr, payload } in attrs {
+            let data = payload.head_slice();
+
+            match hdr.ty.to_bits() {
+                IFA_LOCAL => local = attr_ip(data),
+                IFA_ADDRESS => address = attr_ip(data),
+                IFA_LABEL => label = Some(attr_str(data)),
+                IFA_FLAGS if data.len() >= 4 => {
+                    flags = IfaFlags(u32::from_ne_bytes(
+                        data[..4].try_into().unwrap(),
+                    ))
+                }
+                _ => (),
+            }
+        }
+
+        Some(Self {
+            ifindex: ifa.index as _,
+            // IFA_ADDRESS is peer address for point-to-point IPv4 link
+            addr: local.or(address)?,
+            prefix_len: ifa.prefixlen,
+            flags,
+            scope: RtMsgScope::try_from(ifa.scope).unwrap_or_default(),
+            label,
+        })
+    }
+}
+
+impl Neighbor {
+    fn parse(ndm: NdMsgHdr, attrs: Vec<RtAttrRaw>) -> Option<Self> {
+        let mut ip = None;
+        let mut lladdr = None;
+
+        for RtAttrRaw { hdr, payload } in attrs {
+            let data = payload.head_slice();
+
+            match hdr.ty.to_bits() {
+                NDA_DST => ip = attr_ip(data),
+                NDA_LLADDR if data.len() == 6 => {
+                    lladdr = Some(Mac::from_bytes(data))
+                }
+                _ => (),
+            }
+        }
+
+        Some(Self {
+            ifindex: ndm.ifindex,
+            ip: ip?,
+            lladdr,
+            state: NudState::try_from(ndm.state).unwrap_or_default(),
+            proxy: ndm.flags & NTF_PROXY != 0,
+        })
+    }
+}
+
+impl AddrLabel {
+    fn parse(ifal: IfAddrLblMsgHdr, attrs: Vec<RtAttrRaw>) -> Option<Self> {
+        let mut prefix = None;
+        let mut label = None;
+
+        for RtAttrRaw { hdr, payload } in attrs {
+            let data = payload.head_slice();
+
+            match hdr.ty.to_bits() {
+                IFAL_ADDRESS => {
+                    prefix = data.try_into().ok().map(Ipv6Addr::from_octets)
+                }
+                IFAL_LABEL => {
+                    label = data.try_into().ok().map(u32::from_ne_bytes)
+                }
+                _ => (),
+            }
+        }
+
+        Some(Self {
+            prefix: prefix?,
+            prefix_len: ifal.prefixlen,
+            ifindex: ifal.index as c_int,
+            label: label?,
+        })
+    }
+}
+
+impl IfaFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<IfaFlag> for IfaFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: IfaFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for IfaFlag {
+    type Output = IfaFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        IfaFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<IfaFlag> for &IfaFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: IfaFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<IfaFlags> for IfaFlag {
+    fn into(self) -> IfaFlags {
+        IfaFlags(self.to_bits())
+    }
+}
+
+impl Debug for IfaFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut builder = &mut f.debug_list();
+
+        for flag in IfaFlag::iter() {
+            if self & flag {
+                builder = builder.entry(&flag);
+            }
+        }
+
+        builder.finish()
+    }
+}
+
+impl RtMsgTable {
+    pub const UNSPEC: Self = Self(0);
+    pub const COMPAT: Self = Self(252);
+    pub const DEFAULT: Self = Self(253);
+    pub const MAIN: Self = Self(254);
+    pub const LOCAL: Self = Self(255);
+
+    pub fn custom(v: u8) -> Self {
+        assert!(v > 0 && v <= 251);
+
+        Self(v)
+    }
+}
+
+impl BitOr<NlMsgStdFlag> for NlMsgFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: NlMsgStdFlag) -> Self::Output {
+        Self(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitOr<NlMsgGetFlag> for NlMsgFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: NlMsgGetFlag) -> Self::Output {
+        Self(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitOr<NlMsgNewFlag> for NlMsgFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: NlMsgNewFlag) -> Self::Output {
+        Self(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitOr<NlMsgStdFlag> for NlMsgStdFlag {
+    type Output = NlMsgFlags;
+
+    fn bitor(self, rhs: NlMsgStdFlag) -> Self::Output {
+        NlMsgFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitOr<NlMsgGetFlag> for NlMsgStdFlag {
+    type Output = NlMsgFlags;
+
+    fn bitor(self, rhs: NlMsgGetFlag) -> Self::Output {
+        NlMsgFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitOr<NlMsgNewFlag> for NlMsgStdFlag {
+    type Output = NlMsgFlags;
+
+    fn bitor(self, rhs: NlMsgNewFlag) -> Self::Output {
+        NlMsgFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl NlMsgType {
+    pub fn to_kind(&self) -> NlMsgTypeKind {
+        (*self).into()
+    }
+}
+
+impl std::fmt::Debug for NlMsgType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.to_kind())
+    }
+}
+
+impl PartialEq<NlMsgCtrlType> for NlMsgType {
+    fn eq(&self, other: &NlMsgCtrlType) -> bool {
+        self.to_bits() == (*other).into()
+    }
+}
+
+impl Into<NlMsgType> for NlMsgRouteType {
+    fn into(self) -> NlMsgType {
+        NlMsgType(self.into())
+    }
+}
+
+impl From<NlMsgType> for NlMsgTypeKind {
+    fn from(value: NlMsgType) -> Self {
+        use NlMsgTypeKind::*;
+
+        let v = value.to_bits();
+
+        match v {
+            0..=4 => Ctrl(NlMsgCtrlType::try_from(v).unwrap()),
+            16.. => NlMsgRouteType::try_from(v).map(Route).unwrap_or(Oth(v)),
+            _ => Oth(v),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// C macro NLMSG_ALIGN
+///
+/// 4 bytes align
+pub const fn nlmsg_align(len: size_t) -> size_t {
+    (len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+
+///
+/// C macro NLMSG_LENGTH
+///
+/// Given the payload size, size, this macro returns the aligned size to store in the nlmsg_len
+/// field of the nlmsghdr.
+///
+pub const fn nlmsg_length(size: size_t) -> usize {
+    nlmsg_align(core::mem::size_of::<NlMsgHdr>()) + size
+}
+
+// /// forward ptr to nlmsg data
+// pub const fn nlmsg_data(buf: &mut AlignedRawBufRef) {
+//     buf.forward_bytes(core::mem::size_of::<NlMessageHeader>());
+// }
+
+pub const fn nlmsg_ok(buf: &AlignedRawBufRef) -> bool {
+    let len = buf.rem_len();
+
+    if len >= size_of::<NlMsgHdr>() {
+        let nlh_len = buf.cast_ref::<NlMsgHdr>().len as usize;
+
+        nlh_len >= size_of::<NlMsgHdr>() && nlh_len <= len
+    }
+    else {
+        false
+    }
+}
+
+
+// /// forward ptr to rtmsg header
+// pub const fn rtm_rta(buf: &mut AlignedRawBufRef) {
+//     buf.forward_bytes(core::mem::size_of::<RtMessageHeader>());
+// }
+
+pub const fn rta_ok(buf: &AlignedRawBufRef) -> bool {
+    let len = buf.rem_len();
+
+    if len >= size_of::<RtAttrHdr>() {
+        let rta_len = buf.cast_ref::<RtAttrHdr>().len as usize;
+
+        rta_len >= size_of::<RtAttrHdr>() && rta_len <= len
+    }
+    else {
+        false
+    }
+}
+
+/// C macro RTA_ALIGN
+pub const fn rta_align(len: size_t) -> size_t {
+    (len + RTA_ALIGNTO - 1) & !(RTA_ALIGNTO - 1)
+}
+
+///
+/// C macro style RTA_LENGTH
+///
+/// size: rta payload length
+///
+pub fn rta_len(size: size_t) -> size_t {
+    size_of::<RtAttrHdr>() + size
+}
+
+/// ```no_main
+/// ┌───────────────────┐
+/// │ nlmsghdr (16)     │
+/// ├───────────────────┤
+/// │ rtmsg (12)        │
+/// ├───────────────────┤
+/// │ Attribute 1       │
+/// │  - rta header (4) │
+/// │  - data (4)       │
+/// ├───────────────────┤
+/// │ Attribute 2       │
+/// └───────────────────┘
+/// ```
+/// Low-level Netlink implementation
+pub fn get_gateway_ipv4_by_ifname(
+    ifname: &str,
+) -> errno::Result<Option<Ipv4Addr>> {
+    let ifindex = get_ifindex(ifname)?;
+
+    let rth = RtMsgHdr {
+        family: RtFamily::IPv4,
+        dst_len: Default::default(),
+        src_len: Default::default(),
+        tos: ToS::default(),
+        table: RtMsgTable::MAIN,
+        protocol: RtMsgProto::UNSPEC,
+        scope: RtMsgScope::Universe,
+        ty: RtType::Unspec,
+        flags: RtMsgFlags::default(),
+    };
+
+
+    let replies = NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::GetRoute.into(),
+        flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+        hdr: rth,
+        attrs: AttrBuilder::new().attr(&RtReqAttr::OIf(ifindex)).build(),
+    })?;
+
+    let nlmsgs = replies
+        .iter()
+        .map(|msg| NlMsgRaw {
+            hdr: msg.hdr,
+            payload: msg.payload_ref(),
+        })
+        .collect();
+
+    let rtmsgs_resp = parse_rtm_resp(parse_rtm_raw(nlmsgs)?);
+
+    for RtRespMsg { hdr: rtmh, attrs } in rtmsgs_resp {
+        if rtmh.family != RtFamily::IPv4 {
+            continue
+        }
+
+        let Some(outifindex) = attrs.iter().find_map(|attr| {
+            if let RtRespAttr::OIf(ifindex) = attr {
+                Some(*ifindex)
+            }
+            else {
+                None
+            }
+        })
+        else {
+            continue;
+        };
+
+        if ifindex != outifindex {
+            continue
+        }
+
+        if let Some(ip) = attrs.iter().find_map(|attr| {
+            if let RtRespAttr::Gateway(ipaddr) = attr {
+                Some(match ipaddr {
+                    IpAddr::V4(ipv4_addr) => *ipv4_addr,
+                    IpAddr::V6(_ipv6_addr) => unreachable!(),
+                })
+            }
+            else {
+                None
+            }
+        }) {
+            return Ok(Some(ip));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Output interface and gateway of IPv4 default route (0.0.0.0/0) in main
+/// table, the one of lowest metric if there are many
+///
+/// Default route without gateway (e.g. `default dev wg0`) is ignored.
+pub fn get_default_route() -> errno::Result<Option<(String, Ipv4Addr)>> {
+    let routes = get_routes_filtered(&RouteFilter {
+        family: Some(RtFamily::IPv4),
+        oif: None,
+        table: Some(RtMsgTable::MAIN.0 as u32),
+    })?;
+
+    Ok(routes
+        .into_iter()
+        .filter(|route| route.dst_len == 0 && route.ty == RtType::Unicast)
+        .filter_map(|route| {
+            let Some(IpAddr::V4(gateway)) = route.gateway
+            else {
+                return None;
+            };
+
+            Some((route.metric.unwrap_or(0), route.oif_name?, gateway))
+        })
+        .min_by_key(|(metric, ..)| *metric)
+        .map(|(_, name, gateway)| (name, gateway)))
+}
+
+/// RTM_GETROUTE dump, IPv4 and IPv6 routes of all tables
+pub fn get_routes() -> errno::Result<Vec<RouteEntry>> {
+    get_routes_filtered(&RouteFilter::default())
+}
+
+/// RTM_GETROUTE dump of routes matching `filter`
+pub fn get_routes_filtered(
+    filter: &RouteFilter,
+) -> errno::Result<Vec<RouteEntry>> {
+    // header fields except family, table, protocol and type should be zero
+    // for strict checking
+    let rth = RtMsgHdr {
+        family: filter.family.unwrap_or(RtFamily::Unspec),
+        dst_len: 0,
+        src_len: 0,
+        tos: ToS::default(),
+        table: RtMsgTable::UNSPEC,
+        protocol: RtMsgProto::UNSPEC,
+        scope: RtMsgScope::Universe,
+        ty: RtType::Unspec,
+        flags: RtMsgFlags::default(),
+    };
+
+    let mut attrs = AttrBuilder::new();
+
+    if let Some(oif) = filter.oif {
+        attrs = attrs.attr(&RtReqAttr::OIf(oif));
+    }
+
+    if let Some(table) = filter.table {
+        attrs = attrs.attr(&RtReqAttr::Table(table));
+    }
+
+    let replies = NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::GetRoute.into(),
+        flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+        hdr: rth,
+        attrs: attrs.build(),
+    })?;
+
+    let names: HashMap<c_int, String> = get_links()?
+        .into_iter()
+        .map(|link| (link.ifindex, link.name))
+        .collect();
+
+    let mut routes = vec![];
+
+    for msg in replies.iter() {
+        if msg.hdr.ty.to_kind()
+            != NlMsgTypeKind::Route(NlMsgRouteType::NewRoute)
+        {
+            continue;
+        }
+
+        let mut payload = msg.payload_ref();
+        let Some(rth) = read_rtmsghdr(&mut payload)
+        else {
+            continue;
+        };
+
+        let attrs: Vec<RtRespAttr> = parse_rta_raw(payload)
+            .into_iter()
+            .map(|rta| RtRespAttr::parse_from_raw_rta(rth, rta))
+            .collect();
+
+        let mut route = RouteEntry::parse(rth, &attrs);
+
+        if !filter.matches(&route) {
+            continue;
+        }
+
+        route.oif_name =
+            route.oif.and_then(|ifindex| names.get(&ifindex).cloned());
+
+        routes.push(route);
+    }
+
+    Ok(routes)
+}
+
+/// RTM_GETLINK dump, all interfaces (including the ones without address)
+pub fn get_links() -> errno::Result<Vec<Link>> {
+    let sock = route_socket()?;
 
-    fn bitor(self, rhs: NlMsgStdFlag) -> Self::Output {
-        Self(self.to_bits() | rhs.to_bits())
+    let mut buf = [0u8; 64];
+    let mut buf_ref = AlignedRawBufRef::from_slice(&mut buf, NLMSG_ALIGNTO);
+
+    buf_ref.consume::<NlMsgHdr>().write(NlMsgHdr {
+        len: nlmsg_length(size_of::<IfInfoMsgHdr>()) as _,
+        ty: NlMsgRouteType::GetLink.into(),
+        flags: NlMsgStdFlag::Request | NlMsgGetFlag::Dump,
+        seq: next_seq(),
+        pid: 0,
+    });
+    buf_ref
+        .consume::<IfInfoMsgHdr>()
+        .write(IfInfoMsgHdr::default());
+
+    let len = buf_ref.consumed_slice().len();
+    let mut links = vec![];
+
+    dump(
+        sock.as_fd(),
+        &buf[..len],
+        |NlMsgRaw { hdr, mut payload }| {
+            if hdr.ty.to_kind()
+                != NlMsgTypeKind::Route(NlMsgRouteType::NewLink)
+            {
+                return;
+            }
+
+            let ifi = payload.consume::<IfInfoMsgHdr>().read();
+
+            links.push(Link::parse(ifi, parse_rta_raw(payload)));
+        },
+    )?;
+
+    Ok(links)
+}
+
+/// RTM_GETLINK of link `name`, ENODEV if not found
+pub fn get_link(name: &str) -> errno::Result<Link> {
+    if name.is_empty() || name.len() >= IFNAMSIZ {
+        Err(PosixError::EINVAL)?
     }
+
+    let replies = NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::GetLink.into(),
+        flags: NlMsgFlags::default(),
+        hdr: IfInfoMsgHdr::default(),
+        attrs: AttrBuilder::new().str(IFLA_IFNAME, name).build(),
+    })?;
+
+    let msg = replies
+        .iter()
+        .find(|msg| {
+            msg.hdr.ty.to_kind()
+                == NlMsgTypeKind::Route(NlMsgRouteType::NewLink)
+        })
+        .ok_or(PosixError::ENODEV)?;
+
+    let mut payload = msg.payload_ref();
+    let ifi = payload.consume::<IfInfoMsgHdr>().read();
+
+    Ok(Link::parse(ifi, parse_rta_raw(payload)))
 }
 
-impl BitOr<NlMsgGetFlag> for NlMsgFlags {
-    type Output = Self;
+/// RTM_NEWLINK with NLM_F_CREATE | NLM_F_EXCL, create virtual link `name`
+/// (need CAP_NET_ADMIN)
+///
+/// New link is down, see `set_link_up`.
+pub fn create_link(name: &str, kind: &LinkKind) -> errno::Result<()> {
+    new_link(name, kind, AttrBuilder::new())
+}
 
-    fn bitor(self, rhs: NlMsgGetFlag) -> Self::Output {
-        Self(self.to_bits() | rhs.to_bits())
+/// `extra`: attributes appended to the RTM_NEWLINK request
+fn new_link(
+    name: &str,
+    kind: &LinkKind,
+    extra: AttrBuilder,
+) -> errno::Result<()> {
+    if name.is_empty() || name.len() >= IFNAMSIZ {
+        Err(PosixError::EINVAL)?
     }
-}
 
-impl BitOr<NlMsgNewFlag> for NlMsgFlags {
-    type Output = Self;
+    let mut attrs = extra.str(IFLA_IFNAME, name);
 
-    fn bitor(self, rhs: NlMsgNewFlag) -> Self::Output {
-        Self(self.to_bits() | rhs.to_bits())
+    if let Some(parent) = kind.parent() {
+        attrs = attrs.u32(IFLA_LINK, parent as u32);
     }
-}
 
-impl BitOr<NlMsgStdFlag> for NlMsgStdFlag {
-    type Output = NlMsgFlags;
+    let mut linkinfo = AttrBuilder::new().str(IFLA_INFO_KIND, kind.kind());
 
-    fn bitor(self, rhs: NlMsgStdFlag) -> Self::Output {
-        NlMsgFlags(self.to_bits() | rhs.to_bits())
+    if let Some(data) = kind.info_data() {
+        linkinfo = linkinfo.nested(IFLA_INFO_DATA, data);
     }
+
+    NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::NewLink.into(),
+        flags: NlMsgFlags::default()
+            | NlMsgNewFlag::Create
+            | NlMsgNewFlag::Exec,
+        hdr: IfInfoMsgHdr::default(),
+        attrs: attrs.nested(IFLA_LINKINFO, linkinfo).build(),
+    })?;
+
+    Ok(())
 }
 
-impl BitOr<NlMsgGetFlag> for NlMsgStdFlag {
-    type Output = NlMsgFlags;
+/// Create VLAN `id` on `parent` named `<parent>.<id>` (like `vconfig add`),
+/// return the name
+pub fn add_vlan(parent: &str, id: u16) -> errno::Result<String> {
+    add_vlan_qos(parent, id, &[], &[])
+}
 
-    fn bitor(self, rhs: NlMsgGetFlag) -> Self::Output {
-        NlMsgFlags(self.to_bits() | rhs.to_bits())
+/// `add_vlan` with priority mapping, see `LinkKind::Vlan`
+pub fn add_vlan_qos(
+    parent: &str,
+    id: u16,
+    ingress_qos: &[VlanQosMapping],
+    egress_qos: &[VlanQosMapping],
+) -> errno::Result<String> {
+    if id == 0 || id >= VLAN_VID_MASK {
+        Err(PosixError::EINVAL)?
     }
+
+    let name = format!("{parent}.{id}");
+
+    create_link(
+        &name,
+        &LinkKind::Vlan {
+            id,
+            parent: get_ifindex(parent)?,
+            ingress_qos: ingress_qos.to_vec(),
+            egress_qos: egress_qos.to_vec(),
+        },
+    )?;
+
+    Ok(name)
 }
 
-impl BitOr<NlMsgNewFlag> for NlMsgStdFlag {
-    type Output = NlMsgFlags;
+/// Delete VLAN (any link actually) `name`
+pub fn del_vlan(name: &str) -> errno::Result<()> {
+    del_link(get_ifindex(name)?)
+}
 
-    fn bitor(self, rhs: NlMsgNewFlag) -> Self::Output {
-        NlMsgFlags(self.to_bits() | rhs.to_bits())
-    }
+/// RTM_DELLINK (peer of veth is deleted too)
+pub fn del_link(ifindex: c_int) -> errno::Result<()> {
+    NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::DelLink.into(),
+        flags: NlMsgFlags::default(),
+        hdr: IfInfoMsgHdr {
+            index: ifindex,
+            ..Default::default()
+        },
+        attrs: vec![],
+    })?;
+
+    Ok(())
 }
 
-impl NlMsgType {
-    pub fn to_kind(&self) -> NlMsgTypeKind {
-        (*self).into()
-    }
+/// RTM_SETLINK, bring interface up (`ip link set up`, need CAP_NET_ADMIN)
+pub fn set_link_up(ifindex: c_int) -> errno::Result<()> {
+    set_link(
+        ifindex,
+        IfFlag::Up.to_bits(),
+        IfFlag::Up.to_bits(),
+        AttrBuilder::new(),
+    )
 }
 
-impl std::fmt::Debug for NlMsgType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.to_kind())
-    }
+/// RTM_SETLINK, bring interface down
+pub fn set_link_down(ifindex: c_int) -> errno::Result<()> {
+    set_link(ifindex, 0, IfFlag::Up.to_bits(), AttrBuilder::new())
 }
 
-impl PartialEq<NlMsgCtrlType> for NlMsgType {
-    fn eq(&self, other: &NlMsgCtrlType) -> bool {
-        self.to_bits() == (*other).into()
+/// RTM_SETLINK with IFLA_MTU
+pub fn set_link_mtu(ifindex: c_int, mtu: u32) -> errno::Result<()> {
+    set_link(ifindex, 0, 0, AttrBuilder::new().u32(IFLA_MTU, mtu))
+}
+
+/// RTM_SETLINK with IFLA_ADDRESS (some drivers require link down)
+pub fn set_link_mac(ifindex: c_int, mac: Mac) -> errno::Result<()> {
+    set_link(
+        ifindex,
+        0,
+        0,
+        AttrBuilder::new().bytes(IFLA_ADDRESS, &mac.into_arr8()[..6]),
+    )
+}
+
+/// RTM_SETLINK with IFLA_IFNAME (link must be down), EINVAL if `name` is
+/// too long
+pub fn set_link_name(ifindex: c_int, name: &str) -> errno::Result<()> {
+    if name.is_empty() || name.len() >= IFNAMSIZ {
+        Err(PosixError::EINVAL)?
     }
+
+    set_link(ifindex, 0, 0, AttrBuilder::new().str(IFLA_IFNAME, name))
 }
 
-impl Into<NlMsgType> for NlMsgRouteType {
-    fn into(self) -> NlMsgType {
-        NlMsgType(self.into())
+/// RTM_SETLINK with IFLA_MASTER, add link to bridge or bond `master`
+/// (`ip link set <link> master <master>`)
+///
+/// Bond slave should be down.
+pub fn set_link_master(ifindex: c_int, master: c_int) -> errno::Result<()> {
+    set_link(
+        ifindex,
+        0,
+        0,
+        AttrBuilder::new().u32(IFLA_MASTER, master as u32),
+    )
+}
+
+/// RTM_SETLINK with IFLA_MASTER 0, remove link from its bridge or bond
+/// (`ip link set <link> nomaster`)
+pub fn unset_link_master(ifindex: c_int) -> errno::Result<()> {
+    set_link(ifindex, 0, 0, AttrBuilder::new().u32(IFLA_MASTER, 0))
+}
+
+/// Links enslaved to bridge or bond `master`
+pub fn get_link_slaves(master: c_int) -> errno::Result<Vec<Link>> {
+    Ok(get_links()?
+        .into_iter()
+        .filter(|link| link.master == Some(master))
+        .collect())
+}
+
+/// RTM_SETLINK, `change` is the mask of IFF_XXX `flags` to change
+fn set_link(
+    ifindex: c_int,
+    flags: u32,
+    change: u32,
+    attrs: AttrBuilder,
+) -> errno::Result<()> {
+    NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::SetLink.into(),
+        flags: NlMsgFlags::default(),
+        hdr: IfInfoMsgHdr {
+            index: ifindex,
+            flags,
+            change,
+            ..Default::default()
+        },
+        attrs: attrs.build(),
+    })?;
+
+    Ok(())
+}
+
+/// RTM_NEWROUTE with NLM_F_CREATE | NLM_F_EXCL (need CAP_NET_ADMIN)
+pub fn add_route(spec: &RouteSpec) -> errno::Result<()> {
+    modify_route(
+        spec,
+        NlMsgRouteType::NewRoute,
+        NlMsgStdFlag::Request
+            | NlMsgStdFlag::Ack
+            | NlMsgNewFlag::Create
+            | NlMsgNewFlag::Exec,
+    )
+}
+
+/// RTM_DELROUTE, route is matched by fields of `spec`
+pub fn del_route(spec: &RouteSpec) -> errno::Result<()> {
+    modify_route(
+        spec,
+        NlMsgRouteType::DelRoute,
+        NlMsgStdFlag::Request | NlMsgStdFlag::Ack,
+    )
+}
+
+fn modify_route(
+    spec: &RouteSpec,
+    ty: NlMsgRouteType,
+    flags: NlMsgFlags,
+) -> errno::Result<()> {
+    let sock = route_socket()?;
+
+    // same as iproute2
+    let (protocol, scope, rtype) = if ty == NlMsgRouteType::DelRoute {
+        (RtMsgProto::UNSPEC, RtMsgScope::Nowhere, RtType::Unspec)
+    }
+    else if spec.gateway.is_some() {
+        (RtMsgProto::BOOT, RtMsgScope::Universe, RtType::Unicast)
+    }
+    else {
+        (RtMsgProto::BOOT, RtMsgScope::Link, RtType::Unicast)
+    };
+
+    let rth = RtMsgHdr {
+        family: spec.family(),
+        dst_len: spec.dst_len,
+        src_len: 0,
+        tos: ToS::default(),
+        table: spec.table,
+        protocol,
+        scope,
+        ty: rtype,
+        flags: RtMsgFlags::default(),
+    };
+
+    let mut buf = [0u8; 1024];
+    let mut buf_ref = AlignedRawBufRef::from_slice(&mut buf, NLMSG_ALIGNTO);
+
+    // length is filled at last
+    buf_ref.consume::<NlMsgHdr>();
+    buf_ref.consume::<RtMsgHdr>().write(rth);
+
+    if spec.dst_len > 0 {
+        push_attr_ip(&mut buf_ref, RtAttrKind::Dst.into(), spec.dst);
+    }
+
+    if let Some(gateway) = spec.gateway {
+        push_attr_ip(&mut buf_ref, RtAttrKind::Gateway.into(), gateway);
+    }
+
+    if let Some(ifindex) = spec.oif {
+        push_attr_u32(&mut buf_ref, RtAttrKind::Oif.into(), ifindex as _);
+    }
+
+    if let Some(metric) = spec.metric {
+        push_attr_u32(&mut buf_ref, RtAttrKind::Priority.into(), metric);
     }
+
+    let len = buf_ref.consumed_slice().len();
+
+    let seq = write_nlmsg_hdr(&mut buf, len, ty.into(), flags);
+
+    send_all(sock.as_fd(), &buf[..len], Default::default())?;
+
+    recv_ack(sock.as_fd(), seq)
 }
 
-impl From<NlMsgType> for NlMsgTypeKind {
-    fn from(value: NlMsgType) -> Self {
-        use NlMsgTypeKind::*;
+/// RTM_GETADDR dump, IPv4 and IPv6 addresses of all interfaces
+pub fn get_addrs() -> errno::Result<Vec<Address>> {
+    get_addrs_filtered(&AddrFilter::default())
+}
 
-        let v = value.to_bits();
+/// RTM_GETADDR dump of addresses matching `filter`
+pub fn get_addrs_filtered(filter: &AddrFilter) -> errno::Result<Vec<Address>> {
+    let replies = NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::GetAddr.into(),
+        flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+        hdr: IfAddrMsgHdr {
+            family: filter.family.unwrap_or(RtFamily::Unspec) as u8,
+            index: filter.ifindex.unwrap_or(0) as u32,
+            ..Default::default()
+        },
+        attrs: vec![],
+    })?;
+
+    let mut addrs = vec![];
+
+    for msg in replies.iter() {
+        if msg.hdr.ty.to_kind()
+            != NlMsgTypeKind::Route(NlMsgRouteType::NewAddr)
+        {
+            continue;
+        }
 
-        match v {
-            0..=4 => Ctrl(NlMsgCtrlType::try_from(v).unwrap()),
-            26 => Route(NlMsgRouteType::GetRoute),
-            _ => Oth(v),
+        let mut payload = msg.payload_ref();
+        let ifa = payload.consume::<IfAddrMsgHdr>().read();
+
+        if let Some(addr) = Address::parse(ifa, parse_rta_raw(payload)) {
+            if filter.matches(&addr) {
+                addrs.push(addr);
+            }
         }
     }
-}
 
-////////////////////////////////////////////////////////////////////////////////
-//// Functions
+    Ok(addrs)
+}
 
-/// C macro NLMSG_ALIGN
+/// RTM_NEWADDR (`ip addr add addr/prefix_len dev ifindex`)
 ///
-/// 4 bytes align
-pub const fn nlmsg_align(len: size_t) -> size_t {
-    (len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+/// Flags like `IfaFlag::NoDad` are applied, need CAP_NET_ADMIN.
+pub fn add_addr(
+    ifindex: c_int,
+    addr: IpAddr,
+    prefix_len: u8,
+    flags: IfaFlags,
+) -> errno::Result<()> {
+    modify_addr(
+        NlMsgRouteType::NewAddr,
+        NlMsgStdFlag::Request
+            | NlMsgStdFlag::Ack
+            | NlMsgNewFlag::Create
+            | NlMsgNewFlag::Exec,
+        ifindex,
+        addr,
+        prefix_len,
+        flags,
+    )
 }
 
-///
-/// C macro NLMSG_LENGTH
-///
-/// Given the payload size, size, this macro returns the aligned size to store in the nlmsg_len
-/// field of the nlmsghdr.
-///
-pub const fn nlmsg_length(size: size_t) -> usize {
-    nlmsg_align(core::mem::size_of::<NlMsgHdr>()) + size
+/// RTM_DELADDR
+pub fn del_addr(
+    ifindex: c_int,
+    addr: IpAddr,
+    prefix_len: u8,
+) -> errno::Result<()> {
+    modify_addr(
+        NlMsgRouteType::DelAddr,
+        NlMsgStdFlag::Request | NlMsgStdFlag::Ack,
+        ifindex,
+        addr,
+        prefix_len,
+        IfaFlags::new(),
+    )
 }
 
-// /// forward ptr to nlmsg data
-// pub const fn nlmsg_data(buf: &mut AlignedRawBufRef) {
-//     buf.forward_bytes(core::mem::size_of::<NlMessageHeader>());
-// }
+fn modify_addr(
+    ty: NlMsgRouteType,
+    nlflags: NlMsgFlags,
+    ifindex: c_int,
+    addr: IpAddr,
+    prefix_len: u8,
+    flags: IfaFlags,
+) -> errno::Result<()> {
+    let sock = route_socket()?;
+
+    let ifa = IfAddrMsgHdr {
+        family: match addr {
+            IpAddr::V4(..) => RtFamily::IPv4,
+            IpAddr::V6(..) => RtFamily::IPv6,
+        } as u8,
+        prefixlen: prefix_len,
+        // the rest bits go IFA_FLAGS
+        flags: flags.to_bits() as u8,
+        scope: if addr.is_loopback() {
+            RtMsgScope::Host
+        }
+        else {
+            RtMsgScope::Universe
+        } as u8,
+        index: ifindex as _,
+    };
 
-pub const fn nlmsg_ok(buf: &AlignedRawBufRef) -> bool {
-    let len = buf.rem_len();
+    let mut buf = [0u8; 256];
+    let mut buf_ref = AlignedRawBufRef::from_slice(&mut buf, NLMSG_ALIGNTO);
 
-    if len >= size_of::<NlMsgHdr>() {
-        let nlh_len = buf.cast_ref::<NlMsgHdr>().len as usize;
+    buf_ref.consume::<NlMsgHdr>();
+    buf_ref.consume::<IfAddrMsgHdr>().write(ifa);
 
-        nlh_len >= size_of::<NlMsgHdr>() && nlh_len <= len
+    push_attr_ip(&mut buf_ref, RtAttrType(IFA_LOCAL), addr);
+    push_attr_ip(&mut buf_ref, RtAttrType(IFA_ADDRESS), addr);
+
+    if flags.to_bits() > 0xFF {
+        push_attr_u32(&mut buf_ref, RtAttrType(IFA_FLAGS), flags.to_bits());
     }
-    else {
-        false
+
+    let len = buf_ref.consumed_slice().len();
+
+    let seq = write_nlmsg_hdr(&mut buf, len, ty.into(), nlflags);
+
+    send_all(sock.as_fd(), &buf[..len], Default::default())?;
+
+    recv_ack(sock.as_fd(), seq)
+}
+
+/// RTM_GETNEIGH dump, ARP and NDP tables
+pub fn get_neighbors() -> errno::Result<Vec<Neighbor>> {
+    dump_neighbors(0)
+}
+
+/// RTM_GETNEIGH dump of proxy entries (`ip neigh show proxy`)
+pub fn get_proxy_neighbors() -> errno::Result<Vec<Neighbor>> {
+    dump_neighbors(NTF_PROXY)
+}
+
+/// `ntf_flags`: NTF_PROXY for proxy table
+fn dump_neighbors(ntf_flags: u8) -> errno::Result<Vec<Neighbor>> {
+    let replies = NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::GetNeigh.into(),
+        flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+        hdr: NdMsgHdr {
+            flags: ntf_flags,
+            ..Default::default()
+        },
+        attrs: vec![],
+    })?;
+
+    let mut neighbors = vec![];
+
+    for msg in replies.iter() {
+        if msg.hdr.ty.to_kind()
+            != NlMsgTypeKind::Route(NlMsgRouteType::NewNeigh)
+        {
+            continue;
+        }
+
+        let mut payload = msg.payload_ref();
+        let ndm = payload.consume::<NdMsgHdr>().read();
+
+        if let Some(neigh) = Neighbor::parse(ndm, parse_rta_raw(payload)) {
+            neighbors.push(neigh);
+        }
     }
+
+    Ok(neighbors)
+}
+
+/// RTM_NEWNEIGH, static (NUD_PERMANENT) entry `ip lladdr` on `ifindex`
+pub fn add_neighbor(
+    ifindex: c_int,
+    ip: IpAddr,
+    lladdr: Mac,
+) -> errno::Result<()> {
+    modify_neighbor(
+        NlMsgRouteType::NewNeigh,
+        NlMsgStdFlag::Request
+            | NlMsgStdFlag::Ack
+            | NlMsgNewFlag::Create
+            | NlMsgNewFlag::Exec,
+        ifindex,
+        ip,
+        Some(lladdr),
+        0,
+    )
 }
 
+/// RTM_DELNEIGH
+pub fn del_neighbor(ifindex: c_int, ip: IpAddr) -> errno::Result<()> {
+    modify_neighbor(
+        NlMsgRouteType::DelNeigh,
+        NlMsgStdFlag::Request | NlMsgStdFlag::Ack,
+        ifindex,
+        ip,
+        None,
+        0,
+    )
+}
 
-// /// forward ptr to rtmsg header
-// pub const fn rtm_rta(buf: &mut AlignedRawBufRef) {
-//     buf.forward_bytes(core::mem::size_of::<RtMessageHeader>());
-// }
+/// RTM_NEWNEIGH with NTF_PROXY, answer ARP (IPv4) / NDP (IPv6, need
+/// `net.ipv6.conf.<dev>.proxy_ndp`) for `ip` on `ifindex` with our MAC
+/// (`ip neigh add proxy`)
+pub fn add_proxy_neighbor(ifindex: c_int, ip: IpAddr) -> errno::Result<()> {
+    modify_neighbor(
+        NlMsgRouteType::NewNeigh,
+        NlMsgStdFlag::Request
+            | NlMsgStdFlag::Ack
+            | NlMsgNewFlag::Create
+            | NlMsgNewFlag::Exec,
+        ifindex,
+        ip,
+        None,
+        NTF_PROXY,
+    )
+}
 
-pub const fn rta_ok(buf: &AlignedRawBufRef) -> bool {
-    let len = buf.rem_len();
+/// RTM_DELNEIGH with NTF_PROXY
+pub fn del_proxy_neighbor(ifindex: c_int, ip: IpAddr) -> errno::Result<()> {
+    modify_neighbor(
+        NlMsgRouteType::DelNeigh,
+        NlMsgStdFlag::Request | NlMsgStdFlag::Ack,
+        ifindex,
+        ip,
+        None,
+        NTF_PROXY,
+    )
+}
 
-    if len >= size_of::<RtAttrHdr>() {
-        let rta_len = buf.cast_ref::<RtAttrHdr>().len as usize;
+/// `ntf_flags`: NTF_PROXY for proxy entry
+fn modify_neighbor(
+    ty: NlMsgRouteType,
+    nlflags: NlMsgFlags,
+    ifindex: c_int,
+    ip: IpAddr,
+    lladdr: Option<Mac>,
+    ntf_flags: u8,
+) -> errno::Result<()> {
+    let sock = route_socket()?;
+
+    let ndm = NdMsgHdr {
+        family: match ip {
+            IpAddr::V4(..) => RtFamily::IPv4,
+            IpAddr::V6(..) => RtFamily::IPv6,
+        } as u8,
+        ifindex,
+        state: NudState::Permanent as u16,
+        flags: ntf_flags,
+        ..Default::default()
+    };
 
-        rta_len >= size_of::<RtAttrHdr>() && rta_len <= len
+    let mut buf = [0u8; 256];
+    let mut buf_ref = AlignedRawBufRef::from_slice(&mut buf, NLMSG_ALIGNTO);
+
+    buf_ref.consume::<NlMsgHdr>();
+    buf_ref.consume::<NdMsgHdr>().write(ndm);
+
+    push_attr_ip(&mut buf_ref, RtAttrType(NDA_DST), ip);
+
+    if let Some(lladdr) = lladdr {
+        buf_ref.consume::<RtAttrHdr>().write(RtAttrHdr {
+            len: rta_len(6) as _,
+            ty: RtAttrType(NDA_LLADDR),
+        });
+        // zero padded to RTA_ALIGNTO
+        buf_ref.consume::<[u8; 8]>().write(lladdr.into_arr8());
     }
-    else {
-        false
+
+    let len = buf_ref.consumed_slice().len();
+
+    let seq = write_nlmsg_hdr(&mut buf, len, ty.into(), nlflags);
+
+    send_all(sock.as_fd(), &buf[..len], Default::default())?;
+
+    recv_ack(sock.as_fd(), seq)
+}
+
+/// RTM_GETADDRLABEL dump, IPv6 address label table
+pub fn get_addr_labels() -> errno::Result<Vec<AddrLabel>> {
+    let replies = NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::GetAddrLabel.into(),
+        flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+        hdr: IfAddrLblMsgHdr {
+            family: RtFamily::IPv6 as u8,
+            ..Default::default()
+        },
+        attrs: vec![],
+    })?;
+
+    let mut labels = vec![];
+
+    for msg in replies.iter() {
+        if msg.hdr.ty.to_kind()
+            != NlMsgTypeKind::Route(NlMsgRouteType::NewAddrLabel)
+        {
+            continue;
+        }
+
+        let mut payload = msg.payload_ref();
+        let ifal = payload.consume::<IfAddrLblMsgHdr>().read();
+
+        if let Some(label) = AddrLabel::parse(ifal, parse_rta_raw(payload)) {
+            labels.push(label);
+        }
     }
+
+    Ok(labels)
 }
 
-///
-/// C macro style RTA_LENGTH
-///
-/// size: rta payload length
-///
-pub fn rta_len(size: size_t) -> size_t {
-    size_of::<RtAttrHdr>() + size
+/// RTM_NEWADDRLABEL, add or replace label of the prefix (need
+/// CAP_NET_ADMIN)
+pub fn set_addr_label(label: &AddrLabel) -> errno::Result<()> {
+    modify_addr_label(
+        NlMsgRouteType::NewAddrLabel,
+        NlMsgFlags::default() | NlMsgNewFlag::Create | NlMsgNewFlag::Replace,
+        label,
+    )
 }
 
-/// ```no_main
-/// ┌───────────────────┐
-/// │ nlmsghdr (16)     │
-/// ├───────────────────┤
-/// │ rtmsg (12)        │
-/// ├───────────────────┤
-/// │ Attribute 1       │
-/// │  - rta header (4) │
-/// │  - data (4)       │
-/// ├───────────────────┤
-/// │ Attribute 2       │
-/// └───────────────────┘
-/// ```
-/// Low-level Netlink implementation
-pub fn get_gateway_ipv4_by_ifname(
-    ifname: &str,
-) -> errno::Result<Option<Ipv4Addr>> {
-    let ifindex = get_ifindex(ifname)?;
+/// RTM_DELADDRLABEL
+pub fn del_addr_label(label: &AddrLabel) -> errno::Result<()> {
+    modify_addr_label(
+        NlMsgRouteType::DelAddrLabel,
+        NlMsgFlags::default(),
+        label,
+    )
+}
+
+fn modify_addr_label(
+    ty: NlMsgRouteType,
+    flags: NlMsgFlags,
+    label: &AddrLabel,
+) -> errno::Result<()> {
+    if label.prefix_len > 128 {
+        Err(PosixError::EINVAL)?
+    }
+
+    NetlinkSocket::route()?.request(&NlRequest {
+        ty: ty.into(),
+        flags,
+        hdr: IfAddrLblMsgHdr {
+            family: RtFamily::IPv6 as u8,
+            prefixlen: label.prefix_len,
+            index: label.ifindex as u32,
+            ..Default::default()
+        },
+        attrs: AttrBuilder::new()
+            .ip(IFAL_ADDRESS, IpAddr::V6(label.prefix))
+            .u32(IFAL_LABEL, label.label)
+            .build(),
+    })?;
+
+    Ok(())
+}
 
+/// NETLINK_ROUTE socket bound to kernel
+fn route_socket() -> errno::Result<OwnedFd> {
     let sock = socket(
         AddressFamily::NETLINK,
         SocketType::RAW,
-        ExtraBehavior::new().non_block(),
+        ExtraBehavior::new().close_on_exec(),
         SocketProtocol::NetlinkRoute,
     )?;
 
-    // 3. Bind socket to kernel
-    let addr = SockAddrNL::default();
-
-    bind(sock.as_fd(), addr.into())?;
+    bind(sock.as_fd(), SockAddrNL::default().into())?;
+    set_ext_ack(sock.as_fd(), true)?;
+    try_strict_check(sock.as_fd())?;
 
-    // 4. Build route request message
-
-    let mut nlh = NlMsgHdr {
-        len: 0,
-        ty: NlMsgRouteType::GetRoute.into(),
-        flags: NlMsgStdFlag::Request | NlMsgGetFlag::Dump,
-        seq: Default::default(),
-        pid: Default::default(),
-    };
+    Ok(sock)
+}
 
-    let rth = RtMsgHdr {
-        family: RtFamily::IPv4,
-        dst_len: Default::default(),
-        src_len: Default::default(),
-        tos: ToS::default(),
-        table: RtMsgTable::MAIN,
-        protocol: RtMsgProto::UNSPEC,
-        scope: RtMsgScope::Universe,
-        ty: RtType::Unspec,
-        flags: RtMsgFlags::default(),
-    };
+/// NETLINK_EXT_ACK, kernel attach error message (NLMSGERR_ATTR_MSG) to
+/// NLMSG_ERROR
+pub fn set_ext_ack(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, SOL_NETLINK, NETLINK_EXT_ACK, &(enable as c_int))
+}
+
+/// NETLINK_GET_STRICT_CHK, kernel validates dump request strictly and
+/// filters dump by its header and attributes (since Linux 4.20)
+pub fn set_strict_check(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(
+        sock,
+        SOL_NETLINK,
+        NETLINK_GET_STRICT_CHK,
+        &(enable as c_int),
+    )
+}
 
-    let oif_attr = RtReqAttr::OIf(ifindex).header(size_of::<u32>());
+/// Enable strict checking, ignore it for old kernel
+fn try_strict_check(sock: BorrowedFd) -> errno::Result<()> {
+    match set_strict_check(sock, true) {
+        Ok(()) | Err(PosixError::ENOPROTOOPT) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
 
-    nlh.len =
-        nlmsg_length(size_of::<RtMsgHdr>() + oif_attr.len as size_t) as _;
+/// Wait NLMSG_ERROR (error code 0 for ACK) of request `seq`
+fn recv_ack(sock: BorrowedFd, seq: u32) -> errno::Result<()> {
+    let portid = nl_portid(sock)?;
+    let mut buf = vec![0u8; 1024];
 
-    let mut buf = [0u8; 1024];
-    let mut buf_ref = AlignedRawBufRef::from_slice(&mut buf, NLMSG_ALIGNTO);
+    loop {
+        let len = recv_datagram(sock, &mut buf)?;
 
-    buf_ref.consume::<NlMsgHdr>().write(nlh);
-    buf_ref.consume::<RtMsgHdr>().write(rth);
+        for NlMsgRaw { hdr, payload } in parse_nlm_raw(&buf[..len]) {
+            if hdr.ty != NlMsgCtrlType::Error || !is_reply(&hdr, seq, portid) {
+                continue;
+            }
+
+            return match NlMsgErr::parse(&hdr, payload) {
+                Some(err) => err.to_result(),
+                None => Err(PosixError::EBADMSG),
+            };
+        }
+    }
+}
 
-    buf_ref.consume::<RtAttrHdr>().write(oif_attr);
-    // native order u32
-    buf_ref.consume::<u32>().write(ifindex as _);
+/// Receive one datagram into `buf`, grow it if datagram is truncated
+fn recv_datagram(sock: BorrowedFd, buf: &mut Vec<u8>) -> errno::Result<usize> {
+    loop {
+        // MSG_TRUNC: return the real length of datagram
+        match recv(sock, buf, Flags::default() | Msg::PEEK | Msg::TRUNC) {
+            Ok(len) if len > buf.len() => buf.resize(len, 0),
+            Ok(..) => (),
+            Err(PosixError::EINTR) => continue,
+            Err(err) => Err(err)?,
+        }
 
-    // 5. Send
+        match recv(sock, buf, Default::default()) {
+            Ok(len) => break Ok(len),
+            Err(PosixError::EINTR) => continue,
+            Err(err) => Err(err)?,
+        }
+    }
+}
 
-    send_all(sock.as_fd(), buf_ref.consumed_slice(), Default::default())?;
+/// Fill netlink header of the `len` bytes message built in `buf`, return
+/// the sequence number assigned
+fn write_nlmsg_hdr(
+    buf: &mut [u8],
+    len: usize,
+    ty: NlMsgType,
+    flags: NlMsgFlags,
+) -> u32 {
+    let seq = next_seq();
+
+    AlignedRawBufRef::from_slice(buf, NLMSG_ALIGNTO)
+        .consume::<NlMsgHdr>()
+        .write(NlMsgHdr {
+            len: len as _,
+            ty,
+            flags,
+            seq,
+            pid: 0,
+        });
 
-    // 6 Recv
+    seq
+}
 
-    buf.fill(0);
+/// Next request sequence number (never 0, which is used by notifications)
+pub fn next_seq() -> u32 {
+    loop {
+        let seq = NL_SEQ.fetch_add(1, Ordering::Relaxed);
 
-    let rev_len = recv_all(sock.as_fd(), &mut buf, Default::default())?;
+        if seq != 0 {
+            break seq;
+        }
+    }
+}
 
-    // 6. Parse route response message
+/// Port id assigned by kernel to the bound netlink socket
+fn nl_portid(sock: BorrowedFd) -> errno::Result<u32> {
+    match getsockname(sock)? {
+        SockAddr::Netlink(addr) => Ok(addr.portid as u32),
+        _ => Err(PosixError::EINVAL),
+    }
+}
 
-    let nlmsgs = parse_nlm_raw(&buf[..rev_len]);
-    let rtmsgs_raw = parse_rtm_raw(nlmsgs);
-    let rtmsgs_resp = parse_rtm_resp(rtmsgs_raw);
+/// Reply of our request `seq` (kernel echoes seq and sets pid to our port)
+fn is_reply(hdr: &NlMsgHdr, seq: u32, portid: u32) -> bool {
+    hdr.seq == seq && hdr.pid == portid
+}
 
-    for RtRespMsg { hdr: rtmh, attrs } in rtmsgs_resp {
-        if rtmh.family != RtFamily::IPv4 {
-            continue
+fn push_attr_ip(buf_ref: &mut AlignedRawBufRef, ty: RtAttrType, ip: IpAddr) {
+    match ip {
+        IpAddr::V4(ip) => {
+            buf_ref.consume::<RtAttrHdr>().write(RtAttrHdr {
+                len: rta_len(4) as _,
+                ty,
+            });
+            buf_ref.consume::<[u8; 4]>().write(ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            buf_ref.consume::<RtAttrHdr>().write(RtAttrHdr {
+                len: rta_len(16) as _,
+                ty,
+            });
+            buf_ref.consume::<[u8; 16]>().write(ip.octets());
         }
+    }
+}
 
-        let Some(outifindex) = attrs.iter().find_map(|attr| {
-            if let RtRespAttr::OIf(ifindex) = attr {
-                Some(*ifindex)
-            }
-            else {
-                None
-            }
-        })
-        else {
-            continue;
-        };
+/// native order
+fn push_attr_u32(buf_ref: &mut AlignedRawBufRef, ty: RtAttrType, v: u32) {
+    buf_ref.consume::<RtAttrHdr>().write(RtAttrHdr {
+        len: rta_len(4) as _,
+        ty,
+    });
+    buf_ref.consume::<u32>().write(v);
+}
 
-        if ifindex != outifindex {
-            continue
+/// IPv4 (4 bytes) or IPv6 (16 bytes) address attribute
+fn attr_ip(data: &[u8]) -> Option<IpAddr> {
+    match data.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::from_octets(data.try_into().unwrap()))),
+        16 => {
+            Some(IpAddr::V6(Ipv6Addr::from_octets(data.try_into().unwrap())))
         }
+        _ => None,
+    }
+}
 
-        if let Some(ip) = attrs.iter().find_map(|attr| {
-            if let RtRespAttr::Gateway(ipaddr) = attr {
-                Some(match ipaddr {
-                    IpAddr::V4(ipv4_addr) => *ipv4_addr,
-                    IpAddr::V6(_ipv6_addr) => unreachable!(),
-                })
+/// Send dump request and feed every reply message to `f` until NLMSG_DONE
+///
+/// Messages not belong to the request (other sequence or port) are
+/// dropped.
+fn dump(
+    sock: BorrowedFd,
+    req: &[u8],
+    mut f: impl FnMut(NlMsgRaw),
+) -> errno::Result<()> {
+    let seq = AlignedRawBufRef::from_slice(req, NLMSG_ALIGNTO)
+        .cast_ref::<NlMsgHdr>()
+        .seq;
+    let portid = nl_portid(sock)?;
+
+    send_all(sock, req, Default::default())?;
+
+    let mut buf = vec![0u8; NL_DUMP_BUF_SIZE];
+
+    loop {
+        let len = recv_datagram(sock, &mut buf)?;
+
+        let mut nlbuf =
+            AlignedRawBufRef::from_slice(&buf[..len], NLMSG_ALIGNTO);
+
+        while nlmsg_ok(&nlbuf) {
+            let hdr = nlbuf.consume::<NlMsgHdr>().read();
+            let payload = nlbuf.consume_bytes(hdr.payload_len());
+
+            if !is_reply(&hdr, seq, portid) {
+                continue;
             }
-            else {
-                None
+
+            if hdr.ty == NlMsgCtrlType::Done {
+                return Ok(());
             }
-        }) {
-            return Ok(Some(ip));
+
+            if hdr.ty == NlMsgCtrlType::Error {
+                match NlMsgErr::parse(&hdr, payload) {
+                    Some(err) => err.to_result()?,
+                    None => Err(PosixError::EBADMSG)?,
+                }
+                continue;
+            }
+
+            f(NlMsgRaw { hdr, payload });
         }
     }
+}
 
-    Ok(None)
+/// nlmsgerr.error: 0 or negative errno
+fn nlmsg_err(code: i32) -> errno::Result<()> {
+    if code == 0 {
+        Ok(())
+    }
+    else {
+        Err(PosixError::try_from(-code).unwrap_or(PosixError::EIO))
+    }
+}
+
+/// IFLA_VLAN_QOS_MAPPING of each `from` -> `to` priority mapping
+fn vlan_qos_attrs(mappings: &[VlanQosMapping]) -> AttrBuilder {
+    mappings.iter().fold(AttrBuilder::new(), |attrs, mapping| {
+        let mut data = [0u8; 8];
+
+        data[..4].copy_from_slice(&mapping.from.to_ne_bytes());
+        data[4..].copy_from_slice(&mapping.to.to_ne_bytes());
+
+        attrs.bytes(IFLA_VLAN_QOS_MAPPING, &data)
+    })
+}
+
+/// NUL terminated string attribute
+fn attr_str(data: &[u8]) -> String {
+    let len = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+
+    String::from_utf8_lossy(&data[..len]).into_owned()
+}
+
+/// Split netlink messages of a datagram (NLMSG_DONE included)
+pub fn parse_nlmsgs(buf: &[u8]) -> Vec<NlMsg> {
+    let mut nlbuf = AlignedRawBufRef::from_slice(buf, NLMSG_ALIGNTO);
+    let mut msgs = vec![];
+
+    while nlmsg_ok(&nlbuf) {
+        let hdr = nlbuf.consume::<NlMsgHdr>().read();
+        let payload: RawBufRef = nlbuf.consume_bytes(hdr.payload_len()).into();
+
+        msgs.push(NlMsg {
+            hdr,
+            payload: payload.head_slice().to_vec(),
+        });
+    }
+
+    msgs
 }
 
 pub(crate) fn parse_nlm_raw<'a>(buf: &'a [u8]) -> Vec<NlMsgRaw> {
@@ -759,32 +3215,95 @@ pub(crate) fn parse_nlm_raw<'a>(buf: &'a [u8]) -> Vec<NlMsgRaw> {
     nlmsgs
 }
 
-pub(crate) fn parse_rtm_raw<'a>(nlmsgs: Vec<NlMsgRaw>) -> Vec<RtMsgRaw> {
+/// Kernel error (NLMSG_ERROR) in replies is returned
+pub(crate) fn parse_rtm_raw<'a>(
+    nlmsgs: Vec<NlMsgRaw>,
+) -> errno::Result<Vec<RtMsgRaw>> {
     let mut rtmsgs = vec![];
 
     for NlMsgRaw {
-        hdr: _nlh,
+        hdr: nlh,
         payload: mut buf,
     } in nlmsgs
     {
-        let rtmh = buf.consume::<RtMsgHdr>().read();
+        if nlh.ty == NlMsgCtrlType::Error {
+            if let Some(err) = NlMsgErr::parse(&nlh, buf) {
+                err.to_result()?;
+            }
+
+            continue;
+        }
+
+        if !matches!(nlh.ty.to_kind(), NlMsgTypeKind::Route(_)) {
+            continue;
+        }
+
+        let Some(rtmh) = read_rtmsghdr(&mut buf)
+        else {
+            continue;
+        };
 
         // let attrs_len = nlh.payload_len() - size_of::<RtMsgHdr>();
-        let mut attrs = vec![];
+        let attrs = parse_rta_raw(buf);
+
+        rtmsgs.push(RtMsgRaw { hdr: rtmh, attrs });
+    }
+
+    Ok(rtmsgs)
+}
 
-        while rta_ok(&buf) {
-            let rtah = buf.consume::<RtAttrHdr>().read();
+/// Route message header of IPv4 or IPv6, `None` for other families (e.g.
+/// IPMR, IP6MR, MPLS of AF_UNSPEC dump) or unknown scope, type
+pub(crate) fn read_rtmsghdr(buf: &mut AlignedRawBufRef) -> Option<RtMsgHdr> {
+    let raw = buf.consume::<RtMsgHdrRaw>().read();
 
-            attrs.push(RtAttrRaw {
-                hdr: rtah,
-                payload: buf.consume_bytes(rtah.payload_len()).into(),
-            });
+    let family = match RtFamily::try_from(raw.family) {
+        Ok(family @ (RtFamily::IPv4 | RtFamily::IPv6)) => family,
+        _ => return None,
+    };
+
+    Some(RtMsgHdr {
+        family,
+        dst_len: raw.dst_len,
+        src_len: raw.src_len,
+        tos: raw.tos,
+        table: raw.table,
+        protocol: raw.protocol,
+        scope: RtMsgScope::try_from(raw.scope).ok()?,
+        ty: RtType::try_from(raw.ty).ok()?,
+        flags: raw.flags,
+    })
+}
+
+/// Address attribute of route message of `family`
+fn rta_ip(family: RtFamily, payload: &RawBufRef) -> Option<IpAddr> {
+    let data = payload.head_slice();
+
+    match family {
+        RtFamily::IPv4 => {
+            Some(IpAddr::V4(Ipv4Addr::from_octets(data.try_into().ok()?)))
+        }
+        RtFamily::IPv6 => {
+            Some(IpAddr::V6(Ipv6Addr::from_octets(data.try_into().ok()?)))
         }
+        RtFamily::Unspec => None,
+    }
+}
 
-        rtmsgs.push(RtMsgRaw { hdr: rtmh, attrs });
+/// Attributes follow the family specific header
+pub(crate) fn parse_rta_raw(mut buf: AlignedRawBufRef) -> Vec<RtAttrRaw> {
+    let mut attrs = vec![];
+
+    while rta_ok(&buf) {
+        let rtah = buf.consume::<RtAttrHdr>().read();
+
+        attrs.push(RtAttrRaw {
+            hdr: rtah,
+            payload: buf.consume_bytes(rtah.payload_len()).into(),
+        });
     }
 
-    rtmsgs
+    attrs
 }
 
 pub(crate) fn parse_rtm_resp<'a>(raw_rtmsgs: Vec<RtMsgRaw>) -> Vec<RtRespMsg> {
@@ -805,7 +3324,59 @@ pub(crate) fn parse_rtm_resp<'a>(raw_rtmsgs: Vec<RtMsgRaw>) -> Vec<RtRespMsg> {
 
 #[cfg(test)]
 mod tests {
-    use crate::netlink::get_gateway_ipv4_by_ifname;
+    use super::*;
+    use crate::iface::IfFlag;
+
+    #[test]
+    fn test_nlmsg_err() {
+        let mut buf = [0u8; 128];
+        let mut buf_ref =
+            AlignedRawBufRef::from_slice(&mut buf, NLMSG_ALIGNTO);
+
+        buf_ref.consume::<NlMsgHdr>();
+        buf_ref.consume::<i32>().write(-(PosixError::EINVAL as i32));
+        buf_ref.consume::<NlMsgHdr>().write(NlMsgHdr {
+            len: nlmsg_length(size_of::<RtMsgHdr>()) as _,
+            ty: NlMsgRouteType::NewRoute.into(),
+            flags: NlMsgFlags::default() | NlMsgStdFlag::Request,
+            seq: 1,
+            pid: 0,
+        });
+        buf_ref.consume::<RtAttrHdr>().write(RtAttrHdr {
+            len: rta_len(4) as _,
+            ty: RtAttrType(NLMSGERR_ATTR_MSG),
+        });
+        buf_ref.consume::<[u8; 4]>().write(*b"bad\0");
+
+        let len = buf_ref.consumed_slice().len();
+
+        write_nlmsg_hdr(
+            &mut buf,
+            len,
+            NlMsgType(NlMsgCtrlType::Error.into()),
+            NlMsgFlags(
+                NlMsgAckFlag::Capped.to_bits()
+                    | NlMsgAckFlag::AckTlvs.to_bits(),
+            ),
+        );
+
+        let nlmsgs = parse_nlm_raw(&buf[..len]);
+        assert_eq!(nlmsgs.len(), 1);
+
+        let NlMsgRaw { hdr, payload } = nlmsgs.into_iter().next().unwrap();
+        let err = NlMsgErr::parse(&hdr, payload).unwrap();
+
+        println!("{err:?}");
+
+        assert_eq!(err.msg.as_deref(), Some("bad"));
+        assert_eq!(err.to_result(), Err(PosixError::EINVAL));
+
+        // kernel error instead of empty route list
+        assert_eq!(
+            parse_rtm_raw(parse_nlm_raw(&buf[..len])).err(),
+            Some(PosixError::EINVAL)
+        );
+    }
 
     #[test]
     fn test_get_gateway() {
@@ -813,4 +3384,547 @@ mod tests {
 
         println!("{ip_maybe:?}");
     }
+
+    #[test]
+    fn test_get_routes() {
+        let routes = get_routes().unwrap();
+
+        for route in routes.iter() {
+            println!("{route:?}");
+        }
+
+        // local table: 127.0.0.1 dev lo
+        assert!(routes.iter().any(|route| {
+            route.table == RtMsgTable::LOCAL.0 as u32
+                && route.dst == IpAddr::V4(Ipv4Addr::LOCALHOST)
+                && route.oif_name.as_deref() == Some("lo")
+        }));
+    }
+
+    #[test]
+    fn test_default_route() {
+        // there may be no default route in sandbox
+        if let Some((name, gateway)) = get_default_route().unwrap() {
+            println!("default via {gateway} dev {name}");
+
+            assert_eq!(
+                get_gateway_ipv4_by_ifname(&name).unwrap(),
+                Some(gateway)
+            );
+        }
+    }
+
+    #[test]
+    fn test_multipath() {
+        let rth = RtMsgHdr {
+            family: RtFamily::IPv4,
+            dst_len: 24,
+            src_len: 0,
+            tos: ToS::default(),
+            table: RtMsgTable::MAIN,
+            protocol: RtMsgProto::BOOT,
+            scope: RtMsgScope::Universe,
+            ty: RtType::Unicast,
+            flags: RtMsgFlags::default(),
+        };
+
+        let mut buf = vec![];
+
+        for (ifindex, gateway) in [(2, [10, 0, 0, 1]), (3, [10, 0, 1, 1])] {
+            let gateway = AttrBuilder::new()
+                .bytes(RtAttrKind::Gateway, &gateway)
+                .build();
+
+            buf.extend(((8 + gateway.len()) as u16).to_ne_bytes());
+            buf.extend([0, 1]);
+            buf.extend((ifindex as c_int).to_ne_bytes());
+            buf.extend(gateway);
+        }
+
+        let nexthops = NextHop::parse_all(rth, &buf);
+
+        assert_eq!(nexthops.len(), 2);
+        assert_eq!(nexthops[1].ifindex, 3);
+        assert_eq!(nexthops[1].weight, 2);
+        assert_eq!(
+            nexthops[1].gateway,
+            Some(IpAddr::V4(Ipv4Addr::new(10, 0, 1, 1)))
+        );
+
+        // truncated
+        assert_eq!(NextHop::parse_all(rth, &buf[..12]).len(), 0);
+        assert_eq!(RtAttrType(12).to_kind(), RtAttrKind::CacheInfo);
+    }
+
+    #[test]
+    fn test_filtered_dump() {
+        let lo = get_ifindex("lo").unwrap();
+
+        let routes = get_routes_filtered(&RouteFilter {
+            family: Some(RtFamily::IPv4),
+            oif: Some(lo),
+            table: Some(RtMsgTable::LOCAL.0 as u32),
+        })
+        .unwrap();
+
+        assert!(!routes.is_empty());
+        assert!(routes.iter().all(|route| route.oif == Some(lo)));
+
+        let addrs = get_addrs_filtered(&AddrFilter {
+            family: Some(RtFamily::IPv4),
+            ifindex: Some(lo),
+        })
+        .unwrap();
+
+        assert_eq!(addrs.len(), 1);
+        assert_eq!(addrs[0].addr, IpAddr::V4(Ipv4Addr::LOCALHOST));
+    }
+
+    #[test]
+    fn test_add_del_route() {
+        let lo = get_ifindex("lo").unwrap();
+
+        // TEST-NET-2
+        let spec =
+            RouteSpec::new(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 0)), 24)
+                .oif(lo)
+                .metric(1024);
+
+        // need CAP_NET_ADMIN
+        match add_route(&spec) {
+            Ok(()) => {
+                assert_eq!(add_route(&spec), Err(PosixError::EEXIST));
+                del_route(&spec).unwrap();
+                assert_eq!(del_route(&spec), Err(PosixError::ESRCH));
+            }
+            Err(err) => println!("add_route: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_get_links() {
+        let links = get_links().unwrap();
+
+        let lo = links.iter().find(|link| link.name == "lo").unwrap();
+
+        assert!(&lo.flags & IfFlag::Loopback);
+        assert_eq!(lo.ifindex, get_ifindex("lo").unwrap());
+
+        for link in links {
+            println!("{link:?}");
+        }
+    }
+
+    #[test]
+    fn test_create_link() {
+        let find = |name: &str| {
+            get_links()
+                .unwrap()
+                .into_iter()
+                .find(|link| link.name == name)
+        };
+
+        // need CAP_NET_ADMIN
+        match create_link("lxtest-dummy0", &LinkKind::Dummy) {
+            Ok(()) => {
+                let link = find("lxtest-dummy0").unwrap();
+
+                assert_eq!(link.link_kind.as_deref(), Some("dummy"));
+
+                create_link(
+                    "lxtest-vlan0",
+                    &LinkKind::Vlan {
+                        id: 100,
+                        parent: link.ifindex,
+                        ingress_qos: vec![],
+                        egress_qos: vec![VlanQosMapping { from: 6, to: 5 }],
+                    },
+                )
+                .unwrap();
+
+                create_link(
+                    "lxtest-veth0",
+                    &LinkKind::Veth {
+                        peer: "lxtest-veth1".to_owned(),
+                    },
+                )
+                .unwrap();
+
+                assert!(find("lxtest-veth1").is_some());
+
+                create_link(
+                    "lxtest-macvlan0",
+                    &LinkKind::Macvlan {
+                        parent: link.ifindex,
+                        mode: MacvlanMode::Private,
+                    },
+                )
+                .unwrap();
+
+                // ipvlan module may be unavailable
+                if let Err(err) = create_link(
+                    "lxtest-ipvlan0",
+                    &LinkKind::Ipvlan {
+                        parent: link.ifindex,
+                        mode: IpvlanMode::L3,
+                    },
+                ) {
+                    println!("create ipvlan: {err:?}");
+                }
+
+                del_link(find("lxtest-veth0").unwrap().ifindex).unwrap();
+                del_link(find("lxtest-vlan0").unwrap().ifindex).unwrap();
+                del_link(find("lxtest-macvlan0").unwrap().ifindex).unwrap();
+                del_link(link.ifindex).unwrap();
+
+                assert!(find("lxtest-veth1").is_none());
+            }
+            Err(err) => println!("create_link: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_add_vlan() {
+        assert_eq!(add_vlan("lo", 0), Err(PosixError::EINVAL));
+        assert_eq!(add_vlan("lo", 4095), Err(PosixError::EINVAL));
+
+        // need CAP_NET_ADMIN
+        match create_link("lxtest-d1", &LinkKind::Dummy) {
+            Ok(()) => {
+                let name = add_vlan_qos(
+                    "lxtest-d1",
+                    100,
+                    &[VlanQosMapping { from: 3, to: 1 }],
+                    &[VlanQosMapping { from: 1, to: 3 }],
+                )
+                .unwrap();
+
+                assert_eq!(name, "lxtest-d1.100");
+                assert_eq!(
+                    get_link(&name).unwrap().link_kind.as_deref(),
+                    Some("vlan")
+                );
+
+                del_vlan(&name).unwrap();
+                del_link(get_ifindex("lxtest-d1").unwrap()).unwrap();
+            }
+            Err(err) => println!("create_link: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_link_master() {
+        // need CAP_NET_ADMIN
+        match create_link("lxtest-br0", &LinkKind::Bridge) {
+            Ok(()) => {
+                let br = get_ifindex("lxtest-br0").unwrap();
+
+                create_link("lxtest-d2", &LinkKind::Dummy).unwrap();
+                let dummy = get_ifindex("lxtest-d2").unwrap();
+
+                set_link_master(dummy, br).unwrap();
+
+                assert_eq!(get_link("lxtest-d2").unwrap().master, Some(br));
+                assert_eq!(get_link_slaves(br).unwrap()[0].ifindex, dummy);
+
+                unset_link_master(dummy).unwrap();
+
+                assert!(get_link_slaves(br).unwrap().is_empty());
+
+                // bonding module may be unavailable
+                match create_link("lxtest-bond0", &LinkKind::Bond) {
+                    Ok(()) => {
+                        let bond = get_ifindex("lxtest-bond0").unwrap();
+
+                        set_link_master(dummy, bond).unwrap();
+                        assert_eq!(
+                            get_link("lxtest-d2").unwrap().master,
+                            Some(bond)
+                        );
+
+                        del_link(bond).unwrap();
+                    }
+                    Err(err) => println!("create bond: {err:?}"),
+                }
+
+                del_link(dummy).unwrap();
+                del_link(br).unwrap();
+            }
+            Err(err) => println!("create_link: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_set_link() {
+        let lo = get_links()
+            .unwrap()
+            .into_iter()
+            .find(|link| link.name == "lo")
+            .unwrap();
+
+        assert_eq!(
+            set_link_name(lo.ifindex, "name-longer-than-ifnamsiz"),
+            Err(PosixError::EINVAL)
+        );
+
+        // need CAP_NET_ADMIN, set to the same values
+        match set_link_mtu(lo.ifindex, lo.mtu) {
+            Ok(()) => {
+                set_link_up(lo.ifindex).unwrap();
+                assert_eq!(
+                    set_link_mtu(c_int::MAX, lo.mtu),
+                    Err(PosixError::ENODEV)
+                );
+            }
+            Err(err) => println!("set_link_mtu: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_attr_builder() {
+        let attrs = AttrBuilder::new()
+            .attr(&RtReqAttr::Dst(IpAddr::V4(Ipv4Addr::new(10, 1, 0, 0))))
+            .attr(&RtReqAttr::OIf(1))
+            .attr(&RtReqAttr::Table(1000))
+            .nested(
+                IFLA_LINKINFO,
+                AttrBuilder::new().str(IFLA_INFO_KIND, "veth"),
+            )
+            .u8(100, 7)
+            .build();
+
+        assert_eq!(attrs.len() % RTA_ALIGNTO, 0);
+
+        let rtas =
+            parse_rta_raw(AlignedRawBufRef::from_slice(&attrs, RTA_ALIGNTO));
+
+        let kinds = rtas
+            .iter()
+            .map(|rta| rta.hdr.ty.to_kind())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            [
+                RtAttrKind::Dst,
+                RtAttrKind::Oif,
+                RtAttrKind::Table,
+                RtAttrKind::Oth(IFLA_LINKINFO),
+                RtAttrKind::Oth(100)
+            ]
+        );
+
+        assert_eq!(rtas[0].payload.head_slice(), [10, 1, 0, 0]);
+        assert_eq!(rtas[2].payload.head_slice(), 1000u32.to_ne_bytes());
+        // unpadded length
+        assert_eq!(rtas[4].hdr.len as usize, rta_len(1));
+
+        let nested = parse_rta_raw(AlignedRawBufRef::from_slice(
+            rtas[3].payload.head_slice(),
+            RTA_ALIGNTO,
+        ));
+
+        assert_eq!(attr_str(nested[0].payload.head_slice()), "veth");
+    }
+
+    #[test]
+    fn test_netlink_socket() {
+        let mut sock = NetlinkSocket::route().unwrap();
+        let lo = get_ifindex("lo").unwrap();
+
+        let mut req = NlRequest {
+            ty: NlMsgRouteType::GetLink.into(),
+            flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+            hdr: IfInfoMsgHdr::default(),
+            attrs: vec![],
+        };
+
+        let links = sock.request(&req).unwrap();
+
+        assert!(links.iter().any(|msg| {
+            msg.payload_ref().consume::<IfInfoMsgHdr>().read().index == lo
+        }));
+
+        // single reply followed by ACK
+        req.flags = NlMsgFlags::default();
+        req.hdr.index = lo;
+
+        assert_eq!(sock.request(&req).unwrap().len(), 1);
+
+        // no such device
+        req.hdr.index = c_int::MAX;
+
+        assert_eq!(sock.request(&req).unwrap_err(), PosixError::ENODEV);
+    }
+
+    #[test]
+    fn test_nlmsg_reader() {
+        let sock = NetlinkSocket::route().unwrap();
+
+        sock.send(&NlRequest {
+            ty: NlMsgRouteType::GetLink.into(),
+            flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+            hdr: IfInfoMsgHdr::default(),
+            attrs: vec![],
+        })
+        .unwrap();
+
+        // ends after NLMSG_DONE
+        let msgs = NlMsgReader::new(&sock)
+            .collect::<errno::Result<Vec<NlMsg>>>()
+            .unwrap();
+
+        assert_eq!(msgs.len(), get_links().unwrap().len());
+        assert!(msgs.iter().all(|msg| msg.hdr.ty.to_kind()
+            == NlMsgTypeKind::Route(NlMsgRouteType::NewLink)));
+    }
+
+    #[test]
+    fn test_seq_correlation() {
+        let sock = route_socket().unwrap();
+        let lo = get_ifindex("lo").unwrap();
+
+        // single RTM_GETLINK whose reply is left unread in the socket
+        let mut buf = [0u8; 64];
+        let mut buf_ref =
+            AlignedRawBufRef::from_slice(&mut buf, NLMSG_ALIGNTO);
+
+        buf_ref.consume::<NlMsgHdr>();
+        buf_ref.consume::<IfInfoMsgHdr>().write(IfInfoMsgHdr {
+            index: lo,
+            ..Default::default()
+        });
+
+        let len = buf_ref.consumed_slice().len();
+        let stale_seq = write_nlmsg_hdr(
+            &mut buf,
+            len,
+            NlMsgRouteType::GetLink.into(),
+            NlMsgFlags::default() | NlMsgStdFlag::Request,
+        );
+
+        send_all(sock.as_fd(), &buf[..len], Default::default()).unwrap();
+
+        let seq = write_nlmsg_hdr(
+            &mut buf,
+            len,
+            NlMsgRouteType::GetLink.into(),
+            NlMsgStdFlag::Request | NlMsgGetFlag::Dump,
+        );
+
+        assert_ne!(seq, stale_seq);
+
+        let mut n = 0;
+
+        dump(
+            sock.as_fd(),
+            &buf[..len],
+            |NlMsgRaw { hdr, mut payload }| {
+                assert_eq!(hdr.seq, seq);
+
+                if payload.consume::<IfInfoMsgHdr>().read().index == lo {
+                    n += 1;
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn test_addrs() {
+        let lo = get_ifindex("lo").unwrap();
+
+        let addrs = get_addrs().unwrap();
+
+        assert!(addrs.iter().any(|addr| addr.ifindex == lo
+            && addr.addr == IpAddr::V4(Ipv4Addr::LOCALHOST)));
+
+        for addr in addrs.iter() {
+            println!("{addr:?}");
+        }
+
+        // TEST-NET-3, need CAP_NET_ADMIN
+        let ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+
+        match add_addr(lo, ip, 32, IfaFlag::NoPrefixRoute.into()) {
+            Ok(()) => {
+                assert!(
+                    get_addrs().unwrap().iter().any(|addr| addr.addr == ip)
+                );
+                del_addr(lo, ip, 32).unwrap();
+            }
+            Err(err) => println!("add_addr: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_addr_labels() {
+        let labels = match get_addr_labels() {
+            Ok(labels) => labels,
+            // IPv6 disabled
+            Err(err) => {
+                println!("get_addr_labels: {err:?}");
+                return;
+            }
+        };
+
+        for label in labels.iter() {
+            println!("{label:?}");
+        }
+
+        // documentation prefix
+        let label = AddrLabel {
+            prefix: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0),
+            prefix_len: 32,
+            ifindex: 0,
+            label: 100,
+        };
+
+        // need CAP_NET_ADMIN
+        match set_addr_label(&label) {
+            Ok(()) => {
+                assert!(get_addr_labels().unwrap().contains(&label));
+                del_addr_label(&label).unwrap();
+                assert!(!get_addr_labels().unwrap().contains(&label));
+            }
+            Err(err) => println!("set_addr_label: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_neighbors() {
+        for neigh in get_neighbors().unwrap() {
+            println!("{neigh:?}");
+        }
+
+        let lo = get_ifindex("lo").unwrap();
+        let ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 8));
+        let mac = Mac::from_bytes(&[0x02, 0, 0, 0, 0, 0x08]);
+
+        // need CAP_NET_ADMIN (and ARP capable device)
+        match add_neighbor(lo, ip, mac) {
+            Ok(()) => {
+                assert!(get_neighbors().unwrap().iter().any(|neigh| {
+                    neigh.ip == ip && neigh.state == NudState::Permanent
+                }));
+                del_neighbor(lo, ip).unwrap();
+            }
+            Err(err) => println!("add_neighbor: {err:?}"),
+        }
+
+        match add_proxy_neighbor(lo, ip) {
+            Ok(()) => {
+                assert!(
+                    get_proxy_neighbors()
+                        .unwrap()
+                        .iter()
+                        .any(|neigh| neigh.ip == ip && neigh.proxy)
+                );
+                del_proxy_neighbor(lo, ip).unwrap();
+            }
+            Err(err) => println!("add_proxy_neighbor: {err:?}"),
+        }
+    }
 }
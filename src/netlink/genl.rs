@@ -0,0 +1,505 @@
+//! Generic Netlink (NETLINK_GENERIC)
+//!
+//! Family id is allocated dynamically, resolve it by name through the
+//! controller (`nlctrl`) first.
+//!
+//! Ref [genetlink](https://docs.kernel.org/userspace-api/netlink/intro.html)
+
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+
+use m6io::rawbuf::{AlignedRawBufRef, RawBufRef};
+
+use crate::{
+    errno::{self, PosixError},
+    netlink::{
+        FillBuf, NL_DUMP_BUF_SIZE, NLMSG_ALIGNTO, NlMsgCtrlType, NlMsgFlags,
+        NlMsgGetFlag, NlMsgHdr, NlMsgStdFlag, NlMsgType, RtAttrHdr,
+        nlmsg_align, nlmsg_err, nlmsg_length, nlmsg_ok, parse_rta_raw,
+        recv_datagram, rta_len,
+    },
+    socket::{
+        AddressFamily, ExtraBehavior, SockAddrNL, SocketProtocol, SocketType,
+        bind, send_all, socket,
+    },
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/// Family id of the controller (`nlctrl`)
+pub const GENL_ID_CTRL: u16 = 0x10;
+
+const NLA_F_NESTED: u16 = 0x8000;
+const NLA_F_NET_BYTEORDER: u16 = 0x4000;
+const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+
+/* CTRL_CMD_XXX */
+const CTRL_CMD_NEWFAMILY: u8 = 1;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+
+/* CTRL_ATTR_XXX */
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const CTRL_ATTR_VERSION: u16 = 3;
+const CTRL_ATTR_HDRSIZE: u16 = 4;
+const CTRL_ATTR_MAXATTR: u16 = 5;
+const CTRL_ATTR_MCAST_GROUPS: u16 = 7;
+
+/* CTRL_ATTR_MCAST_GRP_XXX */
+const CTRL_ATTR_MCAST_GRP_NAME: u16 = 1;
+const CTRL_ATTR_MCAST_GRP_ID: u16 = 2;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// struct genlmsghdr
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct GenlMsgHdr {
+    pub cmd: u8,
+    pub version: u8,
+    pub _reserved: u16,
+}
+
+/// Netlink attribute (struct nlattr) with owned payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenlAttr {
+    /// Attribute type without NLA_F_XXX flags
+    pub ty: u16,
+    /// NLA_F_NESTED
+    pub nested: bool,
+    pub payload: Vec<u8>,
+}
+
+/// Generic netlink message (genlmsghdr and attributes)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenlMsg {
+    pub cmd: u8,
+    pub version: u8,
+    pub attrs: Vec<GenlAttr>,
+}
+
+/// Multicast group of a family
+#[derive(Debug, Clone)]
+pub struct GenlMcastGroup {
+    pub name: String,
+    pub id: u32,
+}
+
+/// Reply of CTRL_CMD_GETFAMILY
+#[derive(Debug, Clone)]
+pub struct GenlFamily {
+    pub id: u16,
+    pub name: String,
+    pub version: u32,
+    /// Size of family specific header follows genlmsghdr
+    pub hdr_size: u32,
+    pub max_attr: u32,
+    pub mcast_groups: Vec<GenlMcastGroup>,
+}
+
+/// NETLINK_GENERIC socket bound to kernel
+pub struct GenlSocket {
+    sock: OwnedFd,
+    buf: Vec<u8>,
+    seq: u32,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl GenlAttr {
+    pub fn bytes(ty: u16, payload: &[u8]) -> Self {
+        Self {
+            ty,
+            nested: false,
+            payload: payload.to_vec(),
+        }
+    }
+
+    pub fn u8(ty: u16, v: u8) -> Self {
+        Self::bytes(ty, &[v])
+    }
+
+    /// native order
+    pub fn u16(ty: u16, v: u16) -> Self {
+        Self::bytes(ty, &v.to_ne_bytes())
+    }
+
+    /// native order
+    pub fn u32(ty: u16, v: u32) -> Self {
+        Self::bytes(ty, &v.to_ne_bytes())
+    }
+
+    /// NUL terminated string
+    pub fn str(ty: u16, s: &str) -> Self {
+        let mut payload = s.as_bytes().to_vec();
+        payload.push(0);
+
+        Self {
+            ty,
+            nested: false,
+            payload,
+        }
+    }
+
+    pub fn nested(ty: u16, attrs: &[GenlAttr]) -> Self {
+        let mut payload = vec![0; attrs.iter().map(|a| a.buf_len()).sum()];
+        let mut off = 0;
+
+        for attr in attrs {
+            attr.fill_buf(&mut payload[off..]);
+            off += attr.buf_len();
+        }
+
+        Self {
+            ty,
+            nested: true,
+            payload,
+        }
+    }
+
+    pub fn as_u8(&self) -> Option<u8> {
+        self.payload.first().copied()
+    }
+
+    pub fn as_u16(&self) -> Option<u16> {
+        Some(u16::from_ne_bytes(
+            self.payload.get(..2)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        Some(u32::from_ne_bytes(
+            self.payload.get(..4)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn as_str(&self) -> String {
+        let len = self
+            .payload
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(self.payload.len());
+
+        String::from_utf8_lossy(&self.payload[..len]).into_owned()
+    }
+
+    /// Parse payload as attributes (whether NLA_F_NESTED is set or not,
+    /// since old kernel didn't set it)
+    pub fn as_nested(&self) -> Vec<GenlAttr> {
+        parse_attrs(&self.payload)
+    }
+}
+
+impl FillBuf for GenlAttr {
+    fn buf_len(&self) -> usize {
+        nlmsg_align(rta_len(self.payload.len()))
+    }
+
+    fn fill_buf(&self, buf: &mut [u8]) {
+        assert!(buf.len() >= self.buf_len());
+
+        let mut ty = self.ty;
+
+        if self.nested {
+            ty |= NLA_F_NESTED;
+        }
+
+        let hdr_len = size_of::<RtAttrHdr>();
+        let len = rta_len(self.payload.len());
+
+        buf[..2].copy_from_slice(&(len as u16).to_ne_bytes());
+        buf[2..hdr_len].copy_from_slice(&ty.to_ne_bytes());
+        buf[hdr_len..len].copy_from_slice(&self.payload);
+        buf[len..self.buf_len()].fill(0);
+    }
+}
+
+impl GenlMsg {
+    pub fn new(cmd: u8, version: u8) -> Self {
+        Self {
+            cmd,
+            version,
+            attrs: vec![],
+        }
+    }
+
+    pub fn attr(mut self, attr: GenlAttr) -> Self {
+        self.attrs.push(attr);
+        self
+    }
+
+    /// First attribute of type `ty`
+    pub fn find(&self, ty: u16) -> Option<&GenlAttr> {
+        self.attrs.iter().find(|attr| attr.ty == ty)
+    }
+
+    /// payload: netlink message payload (genlmsghdr included)
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() < size_of::<GenlMsgHdr>() {
+            return None;
+        }
+
+        Some(Self {
+            cmd: payload[0],
+            version: payload[1],
+            attrs: parse_attrs(&payload[size_of::<GenlMsgHdr>()..]),
+        })
+    }
+}
+
+impl FillBuf for GenlMsg {
+    fn buf_len(&self) -> usize {
+        size_of::<GenlMsgHdr>()
+            + self.attrs.iter().map(|attr| attr.buf_len()).sum::<usize>()
+    }
+
+    fn fill_buf(&self, buf: &mut [u8]) {
+        assert!(buf.len() >= self.buf_len());
+
+        buf[..size_of::<GenlMsgHdr>()].copy_from_slice(&[
+            self.cmd,
+            self.version,
+            0,
+            0,
+        ]);
+
+        let mut off = size_of::<GenlMsgHdr>();
+
+        for attr in self.attrs.iter() {
+            attr.fill_buf(&mut buf[off..]);
+            off += attr.buf_len();
+        }
+    }
+}
+
+impl GenlFamily {
+    fn parse(msg: &GenlMsg) -> Option<Self> {
+        let mcast_groups = match msg.find(CTRL_ATTR_MCAST_GROUPS) {
+            Some(groups) => groups
+                .as_nested()
+                .iter()
+                .filter_map(|group| {
+                    let attrs = group.as_nested();
+                    let find = |ty| attrs.iter().find(|attr| attr.ty == ty);
+
+                    Some(GenlMcastGroup {
+                        name: find(CTRL_ATTR_MCAST_GRP_NAME)?.as_str(),
+                        id: find(CTRL_ATTR_MCAST_GRP_ID)?.as_u32()?,
+                    })
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        let as_u32 =
+            |ty| msg.find(ty).and_then(GenlAttr::as_u32).unwrap_or_default();
+
+        Some(Self {
+            id: msg.find(CTRL_ATTR_FAMILY_ID)?.as_u16()?,
+            name: msg.find(CTRL_ATTR_FAMILY_NAME)?.as_str(),
+            version: as_u32(CTRL_ATTR_VERSION),
+            hdr_size: as_u32(CTRL_ATTR_HDRSIZE),
+            max_attr: as_u32(CTRL_ATTR_MAXATTR),
+            mcast_groups,
+        })
+    }
+
+    pub fn mcast_group(&self, name: &str) -> Option<u32> {
+        self.mcast_groups
+            .iter()
+            .find(|group| group.name == name)
+            .map(|group| group.id)
+    }
+}
+
+impl GenlSocket {
+    pub fn new() -> errno::Result<Self> {
+        let sock = socket(
+            AddressFamily::NETLINK,
+            SocketType::RAW,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::NetlinkGeneric,
+        )?;
+
+        bind(sock.as_fd(), SockAddrNL::default().into())?;
+
+        Ok(Self {
+            sock,
+            buf: vec![0; NL_DUMP_BUF_SIZE],
+            seq: 0,
+        })
+    }
+
+    /// Send `msg` to family `family_id` and collect replies
+    ///
+    /// NLM_F_REQUEST and NLM_F_ACK are always set, return on ACK (or
+    /// NLMSG_DONE for dump). Replies of other sequence are dropped.
+    pub fn request(
+        &mut self,
+        family_id: u16,
+        flags: NlMsgFlags,
+        msg: &GenlMsg,
+    ) -> errno::Result<Vec<GenlMsg>> {
+        self.seq = self.seq.wrapping_add(1);
+
+        let len = nlmsg_length(msg.buf_len());
+        let mut req = vec![0u8; len];
+
+        AlignedRawBufRef::from_slice(&mut req, NLMSG_ALIGNTO)
+            .consume::<NlMsgHdr>()
+            .write(NlMsgHdr {
+                len: len as _,
+                ty: NlMsgType(family_id),
+                flags: flags | NlMsgStdFlag::Request | NlMsgStdFlag::Ack,
+                seq: self.seq,
+                pid: 0,
+            });
+        msg.fill_buf(&mut req[nlmsg_length(0)..]);
+
+        send_all(self.sock.as_fd(), &req, Default::default())?;
+
+        let mut replies = vec![];
+
+        loop {
+            let len = recv_datagram(self.sock.as_fd(), &mut self.buf)?;
+
+            let mut nlbuf =
+                AlignedRawBufRef::from_slice(&self.buf[..len], NLMSG_ALIGNTO);
+
+            while nlmsg_ok(&nlbuf) {
+                let hdr = nlbuf.consume::<NlMsgHdr>().read();
+                let payload: RawBufRef =
+                    nlbuf.consume_bytes(hdr.payload_len()).into();
+
+                if hdr.seq != self.seq {
+                    continue;
+                }
+
+                if hdr.ty == NlMsgCtrlType::Done {
+                    return Ok(replies);
+                }
+
+                if hdr.ty == NlMsgCtrlType::Error {
+                    nlmsg_err(payload.cast::<i32>().read_unaligned())?;
+                    return Ok(replies);
+                }
+
+                if hdr.ty.to_bits() != family_id {
+                    continue;
+                }
+
+                if let Some(reply) = GenlMsg::parse(payload.head_slice()) {
+                    replies.push(reply);
+                }
+            }
+        }
+    }
+
+    /// CTRL_CMD_GETFAMILY by name, ENOENT if the family isn't registered
+    /// (module isn't loaded)
+    pub fn resolve_family(&mut self, name: &str) -> errno::Result<GenlFamily> {
+        let msg = GenlMsg::new(CTRL_CMD_GETFAMILY, 1)
+            .attr(GenlAttr::str(CTRL_ATTR_FAMILY_NAME, name));
+
+        self.request(GENL_ID_CTRL, NlMsgFlags::default(), &msg)?
+            .iter()
+            .filter(|reply| reply.cmd == CTRL_CMD_NEWFAMILY)
+            .find_map(GenlFamily::parse)
+            .ok_or(PosixError::ENOENT)
+    }
+
+    /// Dump all registered families
+    pub fn families(&mut self) -> errno::Result<Vec<GenlFamily>> {
+        let msg = GenlMsg::new(CTRL_CMD_GETFAMILY, 1);
+
+        Ok(self
+            .request(
+                GENL_ID_CTRL,
+                NlMsgFlags::default() | NlMsgGetFlag::Dump,
+                &msg,
+            )?
+            .iter()
+            .filter_map(GenlFamily::parse)
+            .collect())
+    }
+}
+
+impl AsFd for GenlSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.sock.as_fd()
+    }
+}
+
+impl AsRawFd for GenlSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Parse a stream of struct nlattr
+pub fn parse_attrs(data: &[u8]) -> Vec<GenlAttr> {
+    parse_rta_raw(AlignedRawBufRef::from_slice(data, NLMSG_ALIGNTO))
+        .into_iter()
+        .map(|rta| {
+            let ty = rta.hdr.ty.to_bits();
+
+            GenlAttr {
+                ty: ty & NLA_TYPE_MASK,
+                nested: ty & NLA_F_NESTED != 0,
+                payload: rta.payload.head_slice().to_vec(),
+            }
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genl_attr() {
+        let msg = GenlMsg::new(CTRL_CMD_GETFAMILY, 1).attr(GenlAttr::nested(
+            CTRL_ATTR_MCAST_GROUPS,
+            &[GenlAttr::str(1, "abc"), GenlAttr::u32(2, 7)],
+        ));
+
+        let mut buf = vec![0; msg.buf_len()];
+        msg.fill_buf(&mut buf);
+
+        assert_eq!(buf.len() % NLMSG_ALIGNTO, 0);
+
+        let parsed = GenlMsg::parse(&buf).unwrap();
+        assert_eq!(parsed, msg);
+
+        let nested = parsed.find(CTRL_ATTR_MCAST_GROUPS).unwrap().as_nested();
+        assert_eq!(nested[0].as_str(), "abc");
+        assert_eq!(nested[1].as_u32(), Some(7));
+    }
+
+    #[test]
+    fn test_resolve_family() {
+        let mut sock = GenlSocket::new().unwrap();
+
+        let ctrl = sock.resolve_family("nlctrl").unwrap();
+        println!("{ctrl:#?}");
+
+        assert_eq!(ctrl.id, GENL_ID_CTRL);
+        assert!(ctrl.mcast_group("notify").is_some());
+
+        assert_eq!(
+            sock.resolve_family("no-such-family").unwrap_err(),
+            PosixError::ENOENT
+        );
+
+        for family in sock.families().unwrap() {
+            println!("{:>4} {}", family.id, family.name);
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! rtnetlink multicast notifications (`ip monitor`)
+//!
+//! Ref [rtnetlink(7)](https://man7.org/linux/man-pages/man7/rtnetlink.7.html)
+
+use std::{
+    fmt::Debug,
+    ops::{BitAnd, BitOr},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd},
+};
+
+use m6io::rawbuf::AlignedRawBufRef;
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    errno::{self, PosixError},
+    iface::IfFlag,
+    netlink::{
+        Address, IfAddrMsgHdr, IfInfoMsgHdr, Link, NL_DUMP_BUF_SIZE,
+        NLMSG_ALIGNTO, NlMsgCtrlType, NlMsgHdr, NlMsgRouteType, NlMsgTypeKind,
+        RtMsg, RtRespAttr, nlmsg_ok, parse_rta_raw, read_rtmsghdr,
+        recv_datagram,
+    },
+    socket::{
+        AddressFamily, ExtraBehavior, SockAddrNL, SocketProtocol, SocketType,
+        bind, set_nonblocking, socket,
+    },
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// RTMGRP_XXX, multicast groups of NETLINK_ROUTE (`SockAddrNL::groups`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(u32)]
+#[repr(u32)]
+pub enum RtnlGroup {
+    Link = 0x1,
+    Notify = 0x2,
+    Neigh = 0x4,
+    Tc = 0x8,
+    Ipv4IfAddr = 0x10,
+    Ipv4Mroute = 0x20,
+    Ipv4Route = 0x40,
+    Ipv4Rule = 0x80,
+    Ipv6IfAddr = 0x100,
+    Ipv6Mroute = 0x200,
+    Ipv6Route = 0x400,
+    Ipv6IfInfo = 0x800,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(u32)]
+#[repr(transparent)]
+pub struct RtnlGroups(u32);
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum NetlinkEvent {
+    /// RTM_NEWLINK with IFF_UP and IFF_RUNNING
+    LinkUp(Link),
+    /// RTM_NEWLINK otherwise
+    LinkDown(Link),
+    /// RTM_DELLINK
+    LinkRemoved(Link),
+    AddrAdded(Address),
+    AddrRemoved(Address),
+    /// RTM_NEWROUTE / RTM_DELROUTE
+    RouteChanged {
+        removed: bool,
+        route: RtMsg,
+    },
+    /// ENOBUFS, kernel dropped notifications (we're too slow), state should
+    /// be re-synchronized by dump (e.g. `get_links`)
+    Overrun,
+}
+
+/// Netlink socket subscribed to rtnetlink groups
+///
+/// Blocking by default, iterate it to wait events. For epoll integration,
+/// `set_nonblocking` and iterate until `None` after readable.
+pub struct NetlinkMonitor {
+    sock: OwnedFd,
+    /// grown to the largest datagram
+    buf: Vec<u8>,
+    /// events decoded from last datagram not yet taken
+    pending: Vec<NetlinkEvent>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl RtnlGroups {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    /// Link, IPv4 address and IPv4 route changes
+    pub fn default_groups() -> Self {
+        RtnlGroup::Link | RtnlGroup::Ipv4IfAddr | RtnlGroup::Ipv4Route
+    }
+}
+
+impl BitOr<RtnlGroup> for RtnlGroups {
+    type Output = Self;
+
+    fn bitor(self, rhs: RtnlGroup) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for RtnlGroup {
+    type Output = RtnlGroups;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        RtnlGroups(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<RtnlGroup> for &RtnlGroups {
+    type Output = bool;
+
+    fn bitand(self, rhs: RtnlGroup) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<RtnlGroups> for RtnlGroup {
+    fn into(self) -> RtnlGroups {
+        RtnlGroups(self.to_bits())
+    }
+}
+
+impl Debug for RtnlGroups {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut builder = &mut f.debug_list();
+
+        for group in RtnlGroup::iter() {
+            if self & group {
+                builder = builder.entry(&group);
+            }
+        }
+
+        builder.finish()
+    }
+}
+
+impl NetlinkMonitor {
+    pub fn new(groups: RtnlGroups) -> errno::Result<Self> {
+        let sock = socket(
+            AddressFamily::NETLINK,
+            SocketType::RAW,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::NetlinkRoute,
+        )?;
+
+        bind(
+            sock.as_fd(),
+            SockAddrNL {
+                groups: groups.to_bits(),
+                ..Default::default()
+            }
+            .into(),
+        )?;
+
+        Ok(Self {
+            sock,
+            buf: vec![0; NL_DUMP_BUF_SIZE],
+            pending: vec![],
+        })
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> errno::Result<()> {
+        set_nonblocking(self.sock.as_fd(), nonblocking)
+    }
+
+    /// Next event, `Ok(None)` if it would block (non-blocking mode)
+    pub fn next_event(&mut self) -> errno::Result<Option<NetlinkEvent>> {
+        while self.pending.is_empty() {
+            let len = match recv_datagram(self.sock.as_fd(), &mut self.buf) {
+                Ok(len) => len,
+                Err(PosixError::EAGAIN) => return Ok(None),
+                Err(PosixError::ENOBUFS) => {
+                    return Ok(Some(NetlinkEvent::Overrun));
+                }
+                Err(err) => Err(err)?,
+            };
+
+            self.pending = parse_events(&self.buf[..len]);
+            // pop from back
+            self.pending.reverse();
+        }
+
+        Ok(self.pending.pop())
+    }
+}
+
+impl Iterator for NetlinkMonitor {
+    type Item = errno::Result<NetlinkEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+impl AsFd for NetlinkMonitor {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.sock.as_fd()
+    }
+}
+
+impl AsRawFd for NetlinkMonitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+fn parse_events(buf: &[u8]) -> Vec<NetlinkEvent> {
+    use NlMsgRouteType::*;
+
+    let mut nlbuf = AlignedRawBufRef::from_slice(buf, NLMSG_ALIGNTO);
+    let mut events = vec![];
+
+    while nlmsg_ok(&nlbuf) {
+        let hdr = nlbuf.consume::<NlMsgHdr>().read();
+        let mut payload = nlbuf.consume_bytes(hdr.payload_len());
+
+        if hdr.ty == NlMsgCtrlType::Done {
+            break;
+        }
+
+        let NlMsgTypeKind::Route(ty) = hdr.ty.to_kind()
+        else {
+            continue;
+        };
+
+        let event = match ty {
+            NewLink | DelLink => {
+                let ifi = payload.consume::<IfInfoMsgHdr>().read();
+                let link = Link::parse(ifi, parse_rta_raw(payload));
+
+                if ty == DelLink {
+                    NetlinkEvent::LinkRemoved(link)
+                }
+                else if &link.flags & IfFlag::Up
+                    && &link.flags & IfFlag::Running
+                {
+                    NetlinkEvent::LinkUp(link)
+                }
+                else {
+                    NetlinkEvent::LinkDown(link)
+                }
+            }
+            NewAddr | DelAddr => {
+                let ifa = payload.consume::<IfAddrMsgHdr>().read();
+
+                let Some(addr) = Address::parse(ifa, parse_rta_raw(payload))
+                else {
+                    continue;
+                };
+
+                if ty == DelAddr {
+                    NetlinkEvent::AddrRemoved(addr)
+                }
+                else {
+                    NetlinkEvent::AddrAdded(addr)
+                }
+            }
+            NewRoute | DelRoute => {
+                let Some(rth) = read_rtmsghdr(&mut payload)
+                else {
+                    continue;
+                };
+
+                let attrs = parse_rta_raw(payload)
+                    .into_iter()
+                    .map(|rta| RtRespAttr::parse_from_raw_rta(rth, rta))
+                    .collect();
+
+                NetlinkEvent::RouteChanged {
+                    removed: ty == DelRoute,
+                    route: RtMsg { hdr: rth, attrs },
+                }
+            }
+            _ => continue,
+        };
+
+        events.push(event);
+    }
+
+    events
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+    use crate::{
+        iface::get_ifindex,
+        netlink::{
+            IfaFlag, LinkKind, add_addr, create_link, del_addr, del_link,
+        },
+    };
+
+    #[test]
+    fn test_monitor() {
+        assert_eq!(
+            format!("{:?}", RtnlGroups::default_groups()),
+            "[Link, Ipv4IfAddr, Ipv4Route]"
+        );
+
+        let mut monitor =
+            NetlinkMonitor::new(RtnlGroups::default_groups()).unwrap();
+
+        monitor.set_nonblocking(true).unwrap();
+
+        // need CAP_NET_ADMIN
+        match create_link("lxtest-mon0", &LinkKind::Dummy) {
+            Ok(()) => (),
+            Err(err) => {
+                assert_eq!(err, PosixError::EPERM);
+                return;
+            }
+        }
+
+        let ifindex = get_ifindex("lxtest-mon0").unwrap();
+        // TEST-NET-2
+        let ip = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7));
+
+        add_addr(ifindex, ip, 32, IfaFlag::NoPrefixRoute.into()).unwrap();
+        del_addr(ifindex, ip, 32).unwrap();
+        del_link(ifindex).unwrap();
+
+        // notified before the requests are acknowledged
+        let events: Vec<_> =
+            monitor.by_ref().collect::<errno::Result<_>>().unwrap();
+
+        for event in events.iter() {
+            println!("{event:?}");
+        }
+
+        assert!(events.iter().any(|event| matches!(
+            event,
+            NetlinkEvent::AddrAdded(addr)
+                if addr.ifindex == ifindex && addr.addr == ip
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            NetlinkEvent::AddrRemoved(addr)
+                if addr.ifindex == ifindex && addr.addr == ip
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            NetlinkEvent::LinkRemoved(link) if link.ifindex == ifindex
+        )));
+    }
+}
@@ -0,0 +1,291 @@
+//! Network namespace aware operations
+//!
+//! Socket belongs to the network namespace where it's created, so switch
+//! the current thread into target namespace (`setns`) to create it and
+//! switch back.
+//!
+//! Ref [network_namespaces(7)](https://man7.org/linux/man-pages/man7/network_namespaces.7.html)
+
+use std::{
+    ffi::{CString, c_int},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    process, thread,
+};
+
+use libc::{O_CLOEXEC, O_RDONLY, pid_t};
+
+use crate::{
+    errno::{self, PosixError},
+    netlink::{AttrBuilder, LinkKind, NetlinkSocket, new_link, set_link},
+    sched::{CloneFlag, unshare},
+    socket::SocketProtocol,
+    unistd::Pid,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+const IFLA_NET_NS_FD: u16 = 28;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Handle of network namespace, it's kept alive by the handle
+#[derive(Debug)]
+pub struct NetNs {
+    fd: OwnedFd,
+}
+
+/// Switch the calling thread back to `origin` on drop (unwinding
+/// included)
+struct NetNsGuard {
+    origin: OwnedFd,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl NetNs {
+    /// Named namespace (`open_netns`)
+    pub fn open(name: &str) -> errno::Result<Self> {
+        Ok(Self {
+            fd: open_netns(name)?,
+        })
+    }
+
+    pub fn of_pid(pid: Pid) -> errno::Result<Self> {
+        Ok(Self {
+            fd: open_netns_of_pid(pid.as_raw())?,
+        })
+    }
+
+    /// Namespace of the calling thread
+    pub fn current() -> errno::Result<Self> {
+        Ok(Self {
+            fd: open_ns_file("/proc/thread-self/ns/net")?,
+        })
+    }
+
+    /// Create new anonymous namespace (only loopback, which is down),
+    /// the calling thread stays where it is (need CAP_SYS_ADMIN)
+    ///
+    /// It's destroyed when the handle and everything in it (sockets,
+    /// processes) is gone.
+    pub fn new() -> errno::Result<Self> {
+        // unshare in a temporary thread, which exits then
+        thread::spawn(|| {
+            unshare(CloneFlag::NewNet.into())?;
+
+            Self::current()
+        })
+        .join()
+        .map_err(|_| PosixError::EIO)?
+    }
+
+    /// Enter namespace, run `f` and return, even if `f` panics
+    /// (`in_netns`)
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> errno::Result<T> {
+        in_netns(self.fd.as_fd(), f)
+    }
+
+    pub fn into_owned_fd(self) -> OwnedFd {
+        self.fd
+    }
+}
+
+impl From<OwnedFd> for NetNs {
+    fn from(fd: OwnedFd) -> Self {
+        Self { fd }
+    }
+}
+
+impl AsFd for NetNs {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl Drop for NetNsGuard {
+    fn drop(&mut self) {
+        // anything run by the thread after would be in wrong namespace
+        if let Err(err) = setns(self.origin.as_fd()) {
+            eprintln!("switch back netns: {err:?}");
+            process::abort();
+        }
+    }
+}
+
+impl NetlinkSocket {
+    /// Netlink socket inside network namespace `netns` (need CAP_SYS_ADMIN)
+    pub fn new_in(
+        protocol: SocketProtocol,
+        netns: BorrowedFd,
+    ) -> errno::Result<Self> {
+        in_netns(netns, || Self::new(protocol))?
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Open named namespace created by `ip netns add` (`/run/netns/<name>`)
+pub fn open_netns(name: &str) -> errno::Result<OwnedFd> {
+    if name.is_empty() || name.contains('/') {
+        Err(PosixError::EINVAL)?
+    }
+
+    open_ns_file(&format!("/run/netns/{name}"))
+}
+
+/// Open network namespace of process `pid`
+pub fn open_netns_of_pid(pid: pid_t) -> errno::Result<OwnedFd> {
+    open_ns_file(&format!("/proc/{pid}/ns/net"))
+}
+
+/// Run `f` with current thread in `netns`, then switch back (even if `f`
+/// panics)
+///
+/// Sockets created by `f` (e.g. by `get_links`, `add_route`) stay in
+/// `netns`. Need CAP_SYS_ADMIN. Process is aborted if it fails to switch
+/// back, rather than leave the thread in `netns`.
+pub fn in_netns<T>(
+    netns: BorrowedFd,
+    f: impl FnOnce() -> T,
+) -> errno::Result<T> {
+    let origin = open_ns_file("/proc/thread-self/ns/net")?;
+
+    setns(netns)?;
+
+    let _guard = NetNsGuard { origin };
+
+    Ok(f())
+}
+
+/// RTM_NEWLINK with IFLA_NET_NS_FD, create link directly in `netns`
+///
+/// For veth, only `name` end is placed in `netns`, peer stays in current
+/// namespace.
+pub fn create_link_in(
+    name: &str,
+    kind: &LinkKind,
+    netns: BorrowedFd,
+) -> errno::Result<()> {
+    new_link(
+        name,
+        kind,
+        AttrBuilder::new().u32(IFLA_NET_NS_FD, netns.as_raw_fd() as u32),
+    )
+}
+
+/// RTM_SETLINK with IFLA_NET_NS_FD, move link to `netns` (`ip link set
+/// netns`)
+pub fn set_link_netns(ifindex: c_int, netns: BorrowedFd) -> errno::Result<()> {
+    set_link(
+        ifindex,
+        0,
+        0,
+        AttrBuilder::new().u32(IFLA_NET_NS_FD, netns.as_raw_fd() as u32),
+    )
+}
+
+fn setns(netns: BorrowedFd) -> errno::Result<()> {
+    crate::sched::setns(netns, CloneFlag::NewNet.into())
+}
+
+fn open_ns_file(path: &str) -> errno::Result<OwnedFd> {
+    let path = CString::new(path).map_err(|_| PosixError::EINVAL)?;
+
+    let fd = unsafe { libc::open(path.as_ptr(), O_RDONLY | O_CLOEXEC) };
+
+    if fd == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs, panic,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+    use crate::netlink::get_links;
+
+    #[test]
+    fn test_in_netns() {
+        let netns = open_netns_of_pid(process::id() as pid_t).unwrap();
+
+        assert_eq!(open_netns("a/b").unwrap_err(), PosixError::EINVAL);
+
+        // need CAP_SYS_ADMIN, enter the same namespace
+        match in_netns(netns.as_fd(), get_links) {
+            Ok(links) => {
+                assert!(links.unwrap().iter().any(|link| link.name == "lo"));
+
+                NetlinkSocket::new_in(
+                    SocketProtocol::NetlinkRoute,
+                    netns.as_fd(),
+                )
+                .unwrap();
+            }
+            Err(err) => println!("in_netns: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_netns_new() {
+        let netns = match NetNs::new() {
+            Ok(netns) => netns,
+            Err(err) => {
+                assert_eq!(err, PosixError::EPERM);
+                return;
+            }
+        };
+
+        let links = netns.run(get_links).unwrap().unwrap();
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].name, "lo");
+
+        // back to where it was
+        assert!(get_links().unwrap().len() >= 1);
+        assert!(NetNs::of_pid(Pid::this()).is_ok());
+        assert_eq!(NetNs::open("").unwrap_err(), PosixError::EINVAL);
+    }
+
+    #[test]
+    fn test_netns_run_panic() {
+        let netns = match NetNs::new() {
+            Ok(netns) => netns,
+            Err(err) => {
+                assert_eq!(err, PosixError::EPERM);
+                return;
+            }
+        };
+
+        let current = || fs::read_link("/proc/thread-self/ns/net").unwrap();
+
+        thread::spawn(move || {
+            let origin = current();
+            let entered = AtomicBool::new(false);
+
+            let res = panic::catch_unwind(|| {
+                netns.run(|| {
+                    entered.store(current() != origin, Ordering::Relaxed);
+                    panic!("lxtest panic in netns");
+                })
+            });
+
+            assert!(res.is_err());
+            assert!(entered.load(Ordering::Relaxed));
+            assert_eq!(current(), origin);
+        })
+        .join()
+        .unwrap();
+    }
+}
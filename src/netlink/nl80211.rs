@@ -0,0 +1,215 @@
+//! Wireless interface query over nl80211 (generic netlink family)
+//!
+//! Only station (client) side information is supported.
+//!
+//! Ref [nl80211.h](https://git.kernel.org/pub/scm/linux/kernel/git/torvalds/linux.git/tree/include/uapi/linux/nl80211.h)
+
+use std::ffi::c_int;
+
+use crate::{
+    errno::{self, PosixError},
+    netlink::{
+        NlMsgFlags, NlMsgGetFlag,
+        genl::{GenlAttr, GenlMsg, GenlSocket},
+    },
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+const NL80211_GENL_NAME: &str = "nl80211";
+
+/* NL80211_CMD_XXX */
+const NL80211_CMD_GET_INTERFACE: u8 = 5;
+const NL80211_CMD_NEW_INTERFACE: u8 = 7;
+const NL80211_CMD_GET_STATION: u8 = 17;
+const NL80211_CMD_NEW_STATION: u8 = 19;
+
+/* NL80211_ATTR_XXX */
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_IFNAME: u16 = 4;
+const NL80211_ATTR_IFTYPE: u16 = 5;
+const NL80211_ATTR_MAC: u16 = 6;
+const NL80211_ATTR_STA_INFO: u16 = 21;
+const NL80211_ATTR_SSID: u16 = 52;
+
+/* NL80211_STA_INFO_XXX (nested in NL80211_ATTR_STA_INFO) */
+const NL80211_STA_INFO_SIGNAL: u16 = 7;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// NL80211_IFTYPE_XXX
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WifiIfType {
+    Adhoc,
+    /// Managed (client)
+    Station,
+    Ap,
+    Monitor,
+    MeshPoint,
+    P2pClient,
+    P2pGo,
+    Oth(u32),
+}
+
+/// Reply of NL80211_CMD_GET_INTERFACE
+#[derive(Clone, Debug)]
+pub struct WifiInterface {
+    pub ifindex: c_int,
+    pub name: String,
+    pub iftype: WifiIfType,
+    /// SSID of connected network (station) or served one (AP)
+    pub ssid: Option<String>,
+}
+
+/// Generic netlink socket with nl80211 family resolved
+pub struct Nl80211 {
+    sock: GenlSocket,
+    family: u16,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl From<u32> for WifiIfType {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Self::Adhoc,
+            2 => Self::Station,
+            3 => Self::Ap,
+            6 => Self::Monitor,
+            7 => Self::MeshPoint,
+            8 => Self::P2pClient,
+            9 => Self::P2pGo,
+            x => Self::Oth(x),
+        }
+    }
+}
+
+impl WifiInterface {
+    fn parse(msg: &GenlMsg) -> Option<Self> {
+        Some(Self {
+            ifindex: msg.find(NL80211_ATTR_IFINDEX)?.as_u32()? as c_int,
+            name: msg
+                .find(NL80211_ATTR_IFNAME)
+                .map(GenlAttr::as_str)
+                .unwrap_or_default(),
+            iftype: msg
+                .find(NL80211_ATTR_IFTYPE)
+                .and_then(GenlAttr::as_u32)
+                .map(WifiIfType::from)?,
+            // not NUL terminated, may be not UTF-8
+            ssid: msg.find(NL80211_ATTR_SSID).map(|attr| {
+                String::from_utf8_lossy(&attr.payload).into_owned()
+            }),
+        })
+    }
+}
+
+impl Nl80211 {
+    /// ENOENT if cfg80211 isn't loaded
+    pub fn new() -> errno::Result<Self> {
+        let mut sock = GenlSocket::new()?;
+        let family = sock.resolve_family(NL80211_GENL_NAME)?.id;
+
+        Ok(Self { sock, family })
+    }
+
+    /// NL80211_CMD_GET_INTERFACE, ENODEV if `ifindex` isn't wireless
+    pub fn interface(
+        &mut self,
+        ifindex: c_int,
+    ) -> errno::Result<WifiInterface> {
+        let msg = GenlMsg::new(NL80211_CMD_GET_INTERFACE, 0)
+            .attr(GenlAttr::u32(NL80211_ATTR_IFINDEX, ifindex as u32));
+
+        self.sock
+            .request(self.family, NlMsgFlags::default(), &msg)?
+            .iter()
+            .filter(|reply| reply.cmd == NL80211_CMD_NEW_INTERFACE)
+            .find_map(WifiInterface::parse)
+            .ok_or(PosixError::ENODEV)
+    }
+
+    /// Dump wireless interfaces
+    pub fn interfaces(&mut self) -> errno::Result<Vec<WifiInterface>> {
+        let msg = GenlMsg::new(NL80211_CMD_GET_INTERFACE, 0);
+
+        Ok(self
+            .sock
+            .request(
+                self.family,
+                NlMsgFlags::default() | NlMsgGetFlag::Dump,
+                &msg,
+            )?
+            .iter()
+            .filter(|reply| reply.cmd == NL80211_CMD_NEW_INTERFACE)
+            .filter_map(WifiInterface::parse)
+            .collect())
+    }
+
+    /// Signal (dBm) of the associated AP, `None` if not connected
+    ///
+    /// For station interface the only station is the AP.
+    pub fn signal(&mut self, ifindex: c_int) -> errno::Result<Option<i8>> {
+        let msg = GenlMsg::new(NL80211_CMD_GET_STATION, 0)
+            .attr(GenlAttr::u32(NL80211_ATTR_IFINDEX, ifindex as u32));
+
+        Ok(self
+            .sock
+            .request(
+                self.family,
+                NlMsgFlags::default() | NlMsgGetFlag::Dump,
+                &msg,
+            )?
+            .iter()
+            .filter(|reply| {
+                reply.cmd == NL80211_CMD_NEW_STATION
+                    && reply.find(NL80211_ATTR_MAC).is_some()
+            })
+            .find_map(|reply| {
+                reply
+                    .find(NL80211_ATTR_STA_INFO)?
+                    .as_nested()
+                    .iter()
+                    .find(|attr| attr.ty == NL80211_STA_INFO_SIGNAL)?
+                    .as_u8()
+                    .map(|v| v as i8)
+            }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iface::get_ifindex;
+
+    #[test]
+    fn test_nl80211() {
+        let msg = GenlMsg::new(NL80211_CMD_NEW_INTERFACE, 0)
+            .attr(GenlAttr::u32(NL80211_ATTR_IFINDEX, 3))
+            .attr(GenlAttr::str(NL80211_ATTR_IFNAME, "wlan0"))
+            .attr(GenlAttr::u32(NL80211_ATTR_IFTYPE, 2))
+            .attr(GenlAttr::bytes(NL80211_ATTR_SSID, b"home"));
+
+        let iface = WifiInterface::parse(&msg).unwrap();
+
+        assert_eq!(iface.iftype, WifiIfType::Station);
+        assert_eq!(iface.ssid.as_deref(), Some("home"));
+
+        // cfg80211 may be not loaded
+        match Nl80211::new() {
+            Ok(mut nl) => {
+                for iface in nl.interfaces().unwrap() {
+                    println!("{iface:?} {:?}", nl.signal(iface.ifindex));
+                }
+
+                assert!(nl.interface(get_ifindex("lo").unwrap()).is_err());
+            }
+            Err(err) => println!("nl80211: {err:?}"),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Socket statistics over NETLINK_SOCK_DIAG (what `ss` does)
+//!
+//! Ref [sock_diag(7)](https://man7.org/linux/man-pages/man7/sock_diag.7.html)
+
+use std::{
+    collections::HashMap,
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use libc::{AF_INET, AF_INET6, IPPROTO_TCP, IPPROTO_UDP, pid_t, uid_t};
+
+use crate::{
+    errno::{self, from_io_error},
+    netlink::{NetlinkSocket, NlMsgFlags, NlMsgGetFlag, NlMsgType, NlRequest},
+    socket::{SocketProtocol, tcp::TcpState},
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/// Message type of sock_diag request/reply
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+
+/// INET_DIAG_NOCOOKIE
+const INET_DIAG_NOCOOKIE: u32 = !0;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// struct inet_diag_sockid
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct InetDiagSockId {
+    /// network order
+    pub sport: u16,
+    /// network order
+    pub dport: u16,
+    /// IPv4 uses the first 4 bytes
+    pub src: [u8; 16],
+    pub dst: [u8; 16],
+    pub ifindex: u32,
+    pub cookie: [u32; 2],
+}
+
+/// struct inet_diag_req_v2
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct InetDiagReqV2 {
+    pub family: u8,
+    pub protocol: u8,
+    /// INET_DIAG_XXX extensions requested (bitmap of `1 << (ext - 1)`)
+    pub ext: u8,
+    pub _pad: u8,
+    /// Bitmap of `1 << TcpState`
+    pub states: u32,
+    pub id: InetDiagSockId,
+}
+
+/// struct inet_diag_msg
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct InetDiagMsg {
+    pub family: u8,
+    pub state: u8,
+    pub timer: u8,
+    pub retrans: u8,
+    pub id: InetDiagSockId,
+    pub expires: u32,
+    pub rqueue: u32,
+    pub wqueue: u32,
+    pub uid: u32,
+    pub inode: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DiagProtocol {
+    Tcp,
+    Udp,
+}
+
+/// An IPv4/IPv6 socket (one line of `ss -tuan`)
+#[derive(Clone, Debug)]
+pub struct InetSock {
+    pub protocol: DiagProtocol,
+    /// UDP socket is Close (unconnected) or Established (connected)
+    pub state: Option<TcpState>,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    /// Bound device, 0 for any
+    pub ifindex: u32,
+    /// Recv-Q (pending connections for listener)
+    pub rqueue: u32,
+    /// Send-Q (backlog for listener)
+    pub wqueue: u32,
+    pub uid: uid_t,
+    /// Socket inode, see `socket_owners`
+    pub inode: u32,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl DiagProtocol {
+    fn to_ipproto(&self) -> u8 {
+        match self {
+            Self::Tcp => IPPROTO_TCP as u8,
+            Self::Udp => IPPROTO_UDP as u8,
+        }
+    }
+}
+
+impl InetDiagSockId {
+    fn addr(family: u8, ip: &[u8; 16], port: u16) -> SocketAddr {
+        let ip = if family == AF_INET as u8 {
+            IpAddr::V4(Ipv4Addr::from_octets(ip[..4].try_into().unwrap()))
+        }
+        else {
+            IpAddr::V6(Ipv6Addr::from_octets(*ip))
+        };
+
+        SocketAddr::new(ip, u16::from_be(port))
+    }
+}
+
+impl InetSock {
+    fn from_diag_msg(protocol: DiagProtocol, msg: &InetDiagMsg) -> Self {
+        Self {
+            protocol,
+            state: TcpState::try_from(msg.state).ok(),
+            local: InetDiagSockId::addr(msg.family, &msg.id.src, msg.id.sport),
+            remote: InetDiagSockId::addr(
+                msg.family,
+                &msg.id.dst,
+                msg.id.dport,
+            ),
+            ifindex: msg.id.ifindex,
+            rqueue: msg.rqueue,
+            wqueue: msg.wqueue,
+            uid: msg.uid,
+            inode: msg.inode,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Dump IPv4 and IPv6 sockets of `protocol` in `states` (empty for all)
+pub fn get_inet_sockets(
+    protocol: DiagProtocol,
+    states: &[TcpState],
+) -> errno::Result<Vec<InetSock>> {
+    let states = if states.is_empty() {
+        !0
+    }
+    else {
+        states
+            .iter()
+            .fold(0u32, |acc, state| acc | 1 << (*state as u8))
+    };
+
+    let mut sock = NetlinkSocket::new(SocketProtocol::NetlinkSockDiag)?;
+    let mut socks = vec![];
+
+    for family in [AF_INET, AF_INET6] {
+        let replies = sock.request(&NlRequest {
+            ty: NlMsgType(SOCK_DIAG_BY_FAMILY),
+            flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+            hdr: InetDiagReqV2 {
+                family: family as u8,
+                protocol: protocol.to_ipproto(),
+                states,
+                id: InetDiagSockId {
+                    cookie: [INET_DIAG_NOCOOKIE; 2],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            attrs: vec![],
+        })?;
+
+        for msg in replies {
+            if msg.hdr.ty.to_bits() != SOCK_DIAG_BY_FAMILY {
+                continue;
+            }
+
+            let diag = msg.payload_ref().consume::<InetDiagMsg>().read();
+
+            socks.push(InetSock::from_diag_msg(protocol, &diag));
+        }
+    }
+
+    Ok(socks)
+}
+
+/// Map socket inode to pids holding it by scanning `/proc/<pid>/fd`
+///
+/// Processes of other users are skipped unless we have the privilege.
+pub fn socket_owners() -> errno::Result<HashMap<u32, Vec<pid_t>>> {
+    let mut owners: HashMap<u32, Vec<pid_t>> = HashMap::new();
+
+    for entry in fs::read_dir("/proc").map_err(from_io_error)? {
+        let Ok(entry) = entry
+        else {
+            continue;
+        };
+
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<pid_t>().ok())
+        else {
+            continue;
+        };
+
+        // process exited or permission denied
+        let Ok(fds) = fs::read_dir(entry.path().join("fd"))
+        else {
+            continue;
+        };
+
+        for fd in fds.flatten() {
+            let Ok(link) = fs::read_link(fd.path())
+            else {
+                continue;
+            };
+
+            // socket:[<inode>]
+            if let Some(inode) = link
+                .to_str()
+                .and_then(|s| s.strip_prefix("socket:["))
+                .and_then(|s| s.strip_suffix(']'))
+                .and_then(|s| s.parse::<u32>().ok())
+            {
+                let pids = owners.entry(inode).or_default();
+
+                if !pids.contains(&pid) {
+                    pids.push(pid);
+                }
+            }
+        }
+    }
+
+    Ok(owners)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, process};
+
+    use super::*;
+
+    #[test]
+    fn test_inet_sockets() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let socks =
+            get_inet_sockets(DiagProtocol::Tcp, &[TcpState::Listen]).unwrap();
+
+        let sock =
+            socks.iter().find(|sock| sock.local.port() == port).unwrap();
+
+        println!("{sock:?}");
+
+        assert_eq!(sock.state, Some(TcpState::Listen));
+        assert_eq!(sock.local.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let owners = socket_owners().unwrap();
+
+        assert!(owners[&sock.inode].contains(&(process::id() as pid_t)));
+
+        for sock in get_inet_sockets(DiagProtocol::Udp, &[]).unwrap() {
+            println!("{sock:?}");
+        }
+    }
+}
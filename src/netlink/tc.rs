@@ -0,0 +1,455 @@
+//! Traffic control (qdisc) over rtnetlink
+//!
+//! Ref [tc(8)](https://man7.org/linux/man-pages/man8/tc.8.html),
+//! [tc-netem(8)](https://man7.org/linux/man-pages/man8/tc-netem.8.html)
+
+use std::{ffi::c_int, time::Duration};
+
+use m6tobytes::derive_to_bits;
+
+use crate::{
+    errno,
+    netlink::{
+        AttrBuilder, NetlinkSocket, NlMsgFlags, NlMsgGetFlag, NlMsgNewFlag,
+        NlMsgRouteType, NlMsgTypeKind, NlRequest, attr_str, parse_rta_raw,
+    },
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/* TCA_XXX */
+const TCA_KIND: u16 = 1;
+const TCA_OPTIONS: u16 = 2;
+
+/* TCA_FQ_CODEL_XXX */
+const TCA_FQ_CODEL_TARGET: u16 = 1;
+const TCA_FQ_CODEL_LIMIT: u16 = 2;
+const TCA_FQ_CODEL_INTERVAL: u16 = 3;
+const TCA_FQ_CODEL_ECN: u16 = 4;
+
+/* TCA_TBF_XXX */
+const TCA_TBF_PARMS: u16 = 1;
+const TCA_TBF_RATE64: u16 = 4;
+const TCA_TBF_BURST: u16 = 6;
+
+/* TCA_NETEM_XXX */
+const TCA_NETEM_LATENCY64: u16 = 10;
+const TCA_NETEM_JITTER64: u16 = 11;
+
+/// TC_LINKLAYER_ETHERNET
+const TC_LINKLAYER_ETHERNET: u8 = 1;
+
+/// psched tick is 64ns (PSCHED_SHIFT)
+const PSCHED_SHIFT: u32 = 6;
+
+/// Default packets limit of netem (same as tc)
+const NETEM_DEFAULT_LIMIT: u32 = 1000;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// `major:minor` qdisc/class handle
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive_to_bits(u32)]
+#[repr(transparent)]
+pub struct TcHandle(u32);
+
+/// struct tcmsg
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct TcMsgHdr {
+    /// AF_UNSPEC
+    pub family: u8,
+    pub _pad1: u8,
+    pub _pad2: u16,
+    pub ifindex: c_int,
+    pub handle: TcHandle,
+    pub parent: TcHandle,
+    pub info: u32,
+}
+
+/// Flow queue CoDel, `None` for kernel default
+#[derive(Default, Clone, Copy, Debug)]
+pub struct FqCodelOpts {
+    /// Packets
+    pub limit: Option<u32>,
+    pub target: Option<Duration>,
+    pub interval: Option<Duration>,
+    pub ecn: Option<bool>,
+}
+
+/// Token bucket filter
+#[derive(Default, Clone, Copy, Debug)]
+pub struct TbfOpts {
+    /// Bytes per second
+    pub rate: u64,
+    /// Bucket size in bytes
+    pub burst: u32,
+    /// Bytes can be queued waiting for tokens
+    pub limit: u32,
+}
+
+/// Network emulator
+#[derive(Default, Clone, Copy, Debug)]
+pub struct NetemOpts {
+    pub delay: Duration,
+    pub jitter: Duration,
+    /// Loss percent (0.0 ~ 100.0)
+    pub loss: f64,
+    /// Packets, 0 for default (1000)
+    pub limit: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum Qdisc {
+    FqCodel(FqCodelOpts),
+    Tbf(TbfOpts),
+    Netem(NetemOpts),
+}
+
+/// Reply of RTM_GETQDISC
+#[derive(Clone, Debug)]
+pub struct QdiscInfo {
+    pub ifindex: c_int,
+    pub handle: TcHandle,
+    pub parent: TcHandle,
+    /// TCA_KIND (e.g. "noqueue", "fq_codel")
+    pub kind: String,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl TcHandle {
+    pub const UNSPEC: Self = Self(0);
+    pub const ROOT: Self = Self(0xFFFF_FFFF);
+    pub const INGRESS: Self = Self(0xFFFF_FFF1);
+
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self(((major as u32) << 16) | minor as u32)
+    }
+
+    pub const fn major(&self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+
+    pub const fn minor(&self) -> u16 {
+        self.0 as u16
+    }
+}
+
+impl Qdisc {
+    /// TCA_KIND
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::FqCodel(..) => "fq_codel",
+            Self::Tbf(..) => "tbf",
+            Self::Netem(..) => "netem",
+        }
+    }
+
+    /// Payload of TCA_OPTIONS
+    fn options(&self) -> Vec<u8> {
+        match self {
+            Self::FqCodel(opts) => {
+                let mut attrs = AttrBuilder::new();
+
+                if let Some(target) = opts.target {
+                    attrs =
+                        attrs.u32(TCA_FQ_CODEL_TARGET, duration_us(target));
+                }
+
+                if let Some(limit) = opts.limit {
+                    attrs = attrs.u32(TCA_FQ_CODEL_LIMIT, limit);
+                }
+
+                if let Some(interval) = opts.interval {
+                    attrs = attrs
+                        .u32(TCA_FQ_CODEL_INTERVAL, duration_us(interval));
+                }
+
+                if let Some(ecn) = opts.ecn {
+                    attrs = attrs.u32(TCA_FQ_CODEL_ECN, ecn as u32);
+                }
+
+                attrs.build()
+            }
+            Self::Tbf(opts) => {
+                let mut attrs = AttrBuilder::new()
+                    .bytes(TCA_TBF_PARMS, &tbf_qopt(opts))
+                    .u32(TCA_TBF_BURST, opts.burst);
+
+                if opts.rate > u32::MAX as u64 {
+                    attrs =
+                        attrs.bytes(TCA_TBF_RATE64, &opts.rate.to_ne_bytes());
+                }
+
+                attrs.build()
+            }
+            Self::Netem(opts) => {
+                // struct tc_netem_qopt followed by attributes
+                let mut buf = netem_qopt(opts);
+
+                buf.extend(
+                    AttrBuilder::new()
+                        .bytes(
+                            TCA_NETEM_LATENCY64,
+                            &(opts.delay.as_nanos() as i64).to_ne_bytes(),
+                        )
+                        .bytes(
+                            TCA_NETEM_JITTER64,
+                            &(opts.jitter.as_nanos() as i64).to_ne_bytes(),
+                        )
+                        .build(),
+                );
+
+                buf
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// RTM_NEWQDISC with NLM_F_CREATE | NLM_F_EXCL (need CAP_NET_ADMIN)
+///
+/// e.g. `add_qdisc(ifindex, TcHandle::ROOT, TcHandle::new(1, 0), &qdisc)`
+pub fn add_qdisc(
+    ifindex: c_int,
+    parent: TcHandle,
+    handle: TcHandle,
+    qdisc: &Qdisc,
+) -> errno::Result<()> {
+    modify_qdisc(
+        NlMsgRouteType::NewQdisc,
+        NlMsgFlags::default() | NlMsgNewFlag::Create | NlMsgNewFlag::Exec,
+        ifindex,
+        parent,
+        handle,
+        Some(qdisc),
+    )
+}
+
+/// RTM_NEWQDISC with NLM_F_CREATE | NLM_F_REPLACE (`tc qdisc replace`)
+pub fn replace_qdisc(
+    ifindex: c_int,
+    parent: TcHandle,
+    handle: TcHandle,
+    qdisc: &Qdisc,
+) -> errno::Result<()> {
+    modify_qdisc(
+        NlMsgRouteType::NewQdisc,
+        NlMsgFlags::default() | NlMsgNewFlag::Create | NlMsgNewFlag::Replace,
+        ifindex,
+        parent,
+        handle,
+        Some(qdisc),
+    )
+}
+
+/// RTM_DELQDISC, restore default qdisc if `parent` is `TcHandle::ROOT`
+pub fn del_qdisc(ifindex: c_int, parent: TcHandle) -> errno::Result<()> {
+    modify_qdisc(
+        NlMsgRouteType::DelQdisc,
+        NlMsgFlags::default(),
+        ifindex,
+        parent,
+        TcHandle::UNSPEC,
+        None,
+    )
+}
+
+/// RTM_GETQDISC dump
+pub fn get_qdiscs() -> errno::Result<Vec<QdiscInfo>> {
+    let replies = NetlinkSocket::route()?.request(&NlRequest {
+        ty: NlMsgRouteType::GetQdisc.into(),
+        flags: NlMsgFlags::default() | NlMsgGetFlag::Dump,
+        hdr: TcMsgHdr::default(),
+        attrs: vec![],
+    })?;
+
+    let mut qdiscs = vec![];
+
+    for msg in replies {
+        if msg.hdr.ty.to_kind()
+            != NlMsgTypeKind::Route(NlMsgRouteType::NewQdisc)
+        {
+            continue;
+        }
+
+        let mut payload = msg.payload_ref();
+        let tcm = payload.consume::<TcMsgHdr>().read();
+
+        let kind = parse_rta_raw(payload)
+            .into_iter()
+            .find(|rta| rta.hdr.ty.to_bits() == TCA_KIND)
+            .map(|rta| attr_str(rta.payload.head_slice()))
+            .unwrap_or_default();
+
+        qdiscs.push(QdiscInfo {
+            ifindex: tcm.ifindex,
+            handle: tcm.handle,
+            parent: tcm.parent,
+            kind,
+        });
+    }
+
+    Ok(qdiscs)
+}
+
+fn modify_qdisc(
+    ty: NlMsgRouteType,
+    flags: NlMsgFlags,
+    ifindex: c_int,
+    parent: TcHandle,
+    handle: TcHandle,
+    qdisc: Option<&Qdisc>,
+) -> errno::Result<()> {
+    let mut attrs = AttrBuilder::new();
+
+    if let Some(qdisc) = qdisc {
+        attrs = attrs
+            .str(TCA_KIND, qdisc.kind())
+            .bytes(TCA_OPTIONS, &qdisc.options());
+    }
+
+    NetlinkSocket::route()?.request(&NlRequest {
+        ty: ty.into(),
+        flags,
+        hdr: TcMsgHdr {
+            ifindex,
+            handle,
+            parent,
+            ..Default::default()
+        },
+        attrs: attrs.build(),
+    })?;
+
+    Ok(())
+}
+
+/// Saturated microseconds
+fn duration_us(d: Duration) -> u32 {
+    d.as_micros().min(u32::MAX as u128) as u32
+}
+
+/// Saturated psched ticks
+fn duration_ticks(d: Duration) -> u32 {
+    (d.as_nanos() >> PSCHED_SHIFT).min(u32::MAX as u128) as u32
+}
+
+/// struct tc_ratespec
+fn ratespec(rate: u64) -> [u8; 12] {
+    let mut buf = [0u8; 12];
+
+    // cell_log, linklayer, overhead, cell_align, mpu
+    buf[1] = TC_LINKLAYER_ETHERNET;
+    buf[8..]
+        .copy_from_slice(&(rate.min(u32::MAX as u64) as u32).to_ne_bytes());
+
+    buf
+}
+
+/// struct tc_tbf_qopt
+fn tbf_qopt(opts: &TbfOpts) -> Vec<u8> {
+    // time to transmit burst on rate
+    let buffer = if opts.rate == 0 {
+        Duration::ZERO
+    }
+    else {
+        Duration::from_nanos(
+            (opts.burst as u128 * 1_000_000_000 / opts.rate as u128)
+                .min(u64::MAX as u128) as u64,
+        )
+    };
+
+    let mut buf = vec![];
+
+    buf.extend(ratespec(opts.rate));
+    // peakrate
+    buf.extend(ratespec(0));
+    buf.extend(opts.limit.to_ne_bytes());
+    buf.extend(duration_ticks(buffer).to_ne_bytes());
+    // mtu
+    buf.extend(0u32.to_ne_bytes());
+
+    buf
+}
+
+/// struct tc_netem_qopt
+fn netem_qopt(opts: &NetemOpts) -> Vec<u8> {
+    let limit = if opts.limit == 0 {
+        NETEM_DEFAULT_LIMIT
+    }
+    else {
+        opts.limit
+    };
+
+    let loss = (opts.loss.clamp(0.0, 100.0) / 100.0 * u32::MAX as f64) as u32;
+
+    let mut buf = vec![];
+
+    buf.extend(duration_ticks(opts.delay).to_ne_bytes());
+    buf.extend(limit.to_ne_bytes());
+    buf.extend(loss.to_ne_bytes());
+    // gap, duplicate
+    buf.extend(0u32.to_ne_bytes());
+    buf.extend(0u32.to_ne_bytes());
+    buf.extend(duration_ticks(opts.jitter).to_ne_bytes());
+
+    buf
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iface::get_ifindex;
+
+    #[test]
+    fn test_qdisc() {
+        let lo = get_ifindex("lo").unwrap();
+
+        for qdisc in get_qdiscs().unwrap() {
+            println!("{qdisc:?}");
+        }
+
+        assert_eq!(TcHandle::new(1, 0).major(), 1);
+        assert_eq!(tbf_qopt(&TbfOpts::default()).len(), 36);
+        assert_eq!(netem_qopt(&NetemOpts::default()).len(), 24);
+
+        let netem = Qdisc::Netem(NetemOpts {
+            delay: Duration::from_millis(10),
+            loss: 1.0,
+            ..Default::default()
+        });
+
+        // need CAP_NET_ADMIN
+        match add_qdisc(lo, TcHandle::ROOT, TcHandle::new(1, 0), &netem) {
+            Ok(()) => {
+                assert!(get_qdiscs().unwrap().iter().any(|qdisc| {
+                    qdisc.ifindex == lo && qdisc.kind == "netem"
+                }));
+
+                replace_qdisc(
+                    lo,
+                    TcHandle::ROOT,
+                    TcHandle::new(1, 0),
+                    &Qdisc::Tbf(TbfOpts {
+                        rate: 1_000_000,
+                        burst: 32 * 1024,
+                        limit: 64 * 1024,
+                    }),
+                )
+                .unwrap();
+
+                del_qdisc(lo, TcHandle::ROOT).unwrap();
+            }
+            Err(err) => println!("add_qdisc: {err:?}"),
+        }
+    }
+}
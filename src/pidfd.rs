@@ -0,0 +1,182 @@
+//! Process referred by file descriptor (pidfd), free of pid reuse race
+//!
+//! Fd is readable when the process terminates, so it can be registered
+//! into `Epoll` to watch its exit.
+//!
+//! Ref [pidfd_open(2)](https://man7.org/linux/man-pages/man2/pidfd_open.2.html)
+
+use std::{
+    mem::zeroed,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    ptr::null,
+};
+
+use libc::{P_PIDFD, WEXITED, WNOHANG, c_uint, id_t, siginfo_t};
+
+use crate::{
+    errno::{self, PosixError},
+    process::WaitStatus,
+    signal::{SigInfo, Signal},
+    unistd::Pid,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+#[derive(Debug)]
+pub struct PidFd {
+    fd: OwnedFd,
+    pid: Pid,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl PidFd {
+    /// pidfd_open (Linux 5.3), fd is always close-on-exec
+    pub fn open(pid: Pid) -> errno::Result<Self> {
+        Self::open_with(pid, 0)
+    }
+
+    /// `wait` returns EAGAIN instead of blocking (Linux 5.10)
+    pub fn open_nonblocking(pid: Pid) -> errno::Result<Self> {
+        Self::open_with(pid, libc::PIDFD_NONBLOCK)
+    }
+
+    fn open_with(pid: Pid, flags: c_uint) -> errno::Result<Self> {
+        let ret = unsafe {
+            libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), flags)
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(ret as RawFd) },
+            pid,
+        })
+    }
+
+    /// Take pidfd of `pid` returned by others (e.g. `clone3`)
+    pub(crate) fn from_raw_parts(fd: OwnedFd, pid: Pid) -> Self {
+        Self { fd, pid }
+    }
+
+    /// Pid when it's opened
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// pidfd_send_signal, ESRCH if the process has terminated
+    pub fn send_signal(&self, sig: Signal) -> errno::Result<()> {
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                self.fd.as_raw_fd(),
+                sig.to_bits(),
+                null::<siginfo_t>(),
+                0,
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(())
+    }
+
+    /// waitid(P_PIDFD), reap the process (should be child of the caller)
+    pub fn wait(&self) -> errno::Result<WaitStatus> {
+        self.waitid(WEXITED)?.ok_or(PosixError::ECHILD)
+    }
+
+    /// `wait` without blocking, `None` if it's running
+    pub fn try_wait(&self) -> errno::Result<Option<WaitStatus>> {
+        self.waitid(WEXITED | WNOHANG)
+    }
+
+    fn waitid(&self, options: i32) -> errno::Result<Option<WaitStatus>> {
+        let mut info: siginfo_t = unsafe { zeroed() };
+
+        let ret = unsafe {
+            libc::waitid(
+                P_PIDFD,
+                self.fd.as_raw_fd() as id_t,
+                &mut info,
+                options,
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        // WNOHANG and no state change
+        if unsafe { info.si_pid() } == 0 {
+            return Ok(None);
+        }
+
+        Ok(WaitStatus::from_siginfo(&SigInfo::from(info)))
+    }
+}
+
+impl AsFd for PidFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for PidFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::epoll::{Epoll, EpollEvent, EpollFlag, EpollToken};
+
+    #[test]
+    fn test_pidfd() {
+        let child = Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = Pid::from_raw(child.id() as _);
+
+        let pidfd = PidFd::open(pid).unwrap();
+
+        assert_eq!(pidfd.try_wait(), Ok(None));
+
+        let mut epoll = Epoll::create().unwrap();
+        let mut events = [EpollEvent::default(); 1];
+
+        epoll
+            .insert(
+                pidfd.as_fd(),
+                EpollEvent::with_token(
+                    EpollFlag::In.into(),
+                    EpollToken::Fd(pidfd.as_raw_fd()),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        assert!(epoll.wait(&mut events, 0).unwrap().is_empty());
+
+        pidfd.send_signal(Signal::SIGKILL).unwrap();
+
+        assert_eq!(epoll.wait(&mut events, 5000).unwrap().len(), 1);
+
+        assert_eq!(
+            pidfd.wait(),
+            Ok(WaitStatus::Signaled(pid, Signal::SIGKILL, false))
+        );
+
+        assert_eq!(pidfd.send_signal(Signal::SIGKILL), Err(PosixError::ESRCH));
+    }
+}
@@ -0,0 +1,195 @@
+//! Operations on attributes of process (thread)
+//!
+//! Ref [prctl(2)](https://man7.org/linux/man-pages/man2/prctl.2.html)
+
+use std::ffi::{CStr, c_int, c_ulong};
+
+use libc::{
+    PR_CAP_AMBIENT, PR_CAP_AMBIENT_CLEAR_ALL, PR_CAP_AMBIENT_IS_SET,
+    PR_CAP_AMBIENT_LOWER, PR_CAP_AMBIENT_RAISE, PR_GET_DUMPABLE, PR_GET_NAME,
+    PR_GET_NO_NEW_PRIVS, PR_GET_PDEATHSIG, PR_SET_DUMPABLE, PR_SET_NAME,
+    PR_SET_NO_NEW_PRIVS, PR_SET_PDEATHSIG,
+};
+
+use crate::{
+    caps::Capability,
+    errno::{self, PosixError},
+    signal::Signal,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/// Max bytes of thread name (exclude nul)
+pub const TASK_COMM_LEN: usize = 15;
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Name of the calling thread (/proc/self/task/TID/comm), truncated to
+/// `TASK_COMM_LEN` bytes, EINVAL if it contains nul
+pub fn set_name(name: &str) -> errno::Result<()> {
+    if name.contains('\0') {
+        Err(PosixError::EINVAL)?
+    }
+
+    let mut buf = [0u8; TASK_COMM_LEN + 1];
+    let len = name.len().min(TASK_COMM_LEN);
+
+    buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+    prctl(PR_SET_NAME, buf.as_ptr() as c_ulong, 0)?;
+
+    Ok(())
+}
+
+pub fn get_name() -> errno::Result<String> {
+    let mut buf = [0u8; TASK_COMM_LEN + 1];
+
+    prctl(PR_GET_NAME, buf.as_mut_ptr() as c_ulong, 0)?;
+
+    Ok(CStr::from_bytes_until_nul(&buf)
+        .unwrap()
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// Signal sent to the calling thread when its parent (thread) terminates,
+/// `None` to clear it
+///
+/// It's cleared by fork (for child), so set it in child and check
+/// `getppid` after that for parent exited before.
+pub fn set_pdeathsig(sig: Option<Signal>) -> errno::Result<()> {
+    prctl(
+        PR_SET_PDEATHSIG,
+        sig.map(|sig| sig.to_bits()).unwrap_or(0) as c_ulong,
+        0,
+    )?;
+
+    Ok(())
+}
+
+pub fn get_pdeathsig() -> errno::Result<Option<Signal>> {
+    let mut signo: c_int = 0;
+
+    prctl(PR_GET_PDEATHSIG, &mut signo as *mut c_int as c_ulong, 0)?;
+
+    if signo == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(Signal::try_from(signo)?))
+}
+
+/// execve can't grant privileges any more (set-user-ID, file
+/// capabilities), required by unprivileged seccomp filter
+///
+/// It's irreversible and inherited by children (and threads created
+/// after).
+pub fn set_no_new_privs() -> errno::Result<()> {
+    prctl(PR_SET_NO_NEW_PRIVS, 1, 0)?;
+
+    Ok(())
+}
+
+pub fn get_no_new_privs() -> errno::Result<bool> {
+    Ok(prctl(PR_GET_NO_NEW_PRIVS, 0, 0)? == 1)
+}
+
+/// Core dump and ptrace by same uid are allowed, it's reset to
+/// /proc/sys/fs/suid_dumpable when credentials change
+pub fn set_dumpable(dumpable: bool) -> errno::Result<()> {
+    prctl(PR_SET_DUMPABLE, dumpable as c_ulong, 0)?;
+
+    Ok(())
+}
+
+pub fn get_dumpable() -> errno::Result<bool> {
+    Ok(prctl(PR_GET_DUMPABLE, 0, 0)? == 1)
+}
+
+/// Capability `cap` is in ambient set, which is kept across
+/// execve of non-privileged program
+pub fn cap_ambient_is_set(cap: Capability) -> errno::Result<bool> {
+    Ok(prctl(
+        PR_CAP_AMBIENT,
+        PR_CAP_AMBIENT_IS_SET as c_ulong,
+        cap.to_bits() as c_ulong,
+    )? == 1)
+}
+
+/// EPERM if `cap` isn't both permitted and inheritable
+pub fn cap_ambient_raise(cap: Capability) -> errno::Result<()> {
+    prctl(
+        PR_CAP_AMBIENT,
+        PR_CAP_AMBIENT_RAISE as c_ulong,
+        cap.to_bits() as c_ulong,
+    )?;
+
+    Ok(())
+}
+
+pub fn cap_ambient_lower(cap: Capability) -> errno::Result<()> {
+    prctl(
+        PR_CAP_AMBIENT,
+        PR_CAP_AMBIENT_LOWER as c_ulong,
+        cap.to_bits() as c_ulong,
+    )?;
+
+    Ok(())
+}
+
+pub fn cap_ambient_clear_all() -> errno::Result<()> {
+    prctl(PR_CAP_AMBIENT, PR_CAP_AMBIENT_CLEAR_ALL as c_ulong, 0)?;
+
+    Ok(())
+}
+
+/// Unused arguments are 0 (required by some options)
+fn prctl(option: c_int, arg2: c_ulong, arg3: c_ulong) -> errno::Result<c_int> {
+    let ret =
+        unsafe { libc::prctl(option, arg2, arg3, 0 as c_ulong, 0 as c_ulong) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_prctl() {
+        // attributes of spawned thread only
+        thread::spawn(|| {
+            set_name("lxtest-prctl-thread-name").unwrap();
+
+            assert_eq!(get_name().unwrap(), "lxtest-prctl-th");
+            assert_eq!(set_name("a\0b"), Err(PosixError::EINVAL));
+
+            assert_eq!(get_pdeathsig().unwrap(), None);
+            set_pdeathsig(Some(Signal::SIGTERM)).unwrap();
+            assert_eq!(get_pdeathsig().unwrap(), Some(Signal::SIGTERM));
+            set_pdeathsig(None).unwrap();
+
+            set_no_new_privs().unwrap();
+            assert!(get_no_new_privs().unwrap());
+        })
+        .join()
+        .unwrap();
+
+        println!("dumpable: {:?}", get_dumpable());
+
+        assert!(!cap_ambient_is_set(Capability::NetRaw).unwrap());
+        cap_ambient_clear_all().unwrap();
+        assert!(cap_ambient_lower(Capability::NetRaw).is_ok());
+    }
+}
@@ -0,0 +1,1062 @@
+//! Child process management
+//!
+//! Ref [fork(2)](https://man7.org/linux/man-pages/man2/fork.2.html),
+//! [clone(2)](https://man7.org/linux/man-pages/man2/clone.2.html),
+//! [execve(2)](https://man7.org/linux/man-pages/man2/execve.2.html),
+//! [wait(2)](https://man7.org/linux/man-pages/man2/wait.2.html)
+
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    env,
+    ffi::{CStr, CString, OsStr, OsString, c_char, c_int},
+    fmt::Debug,
+    marker::PhantomData,
+    mem::zeroed,
+    ops::{BitAnd, BitOr},
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::ffi::{OsStrExt, OsStringExt},
+    },
+    path::Path,
+    ptr::{null, null_mut},
+    sync::mpsc::Sender,
+};
+
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    errno::{self, PosixError},
+    pidfd::PidFd,
+    sched::CloneFlags,
+    signal::{SigInfo, Signal},
+    signalfd::SignalFd,
+    socket::ExtraBehavior,
+    unistd::{self, Pid},
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+const CLONE_PIDFD: u64 = 0x1000;
+const CLONE_INTO_CGROUP: u64 = 0x200000000;
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Return of `fork`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForkResult {
+    Parent { child: Pid },
+    Child,
+}
+
+/// Return of `clone3`
+#[derive(Debug)]
+pub enum CloneResult {
+    Parent {
+        child: Pid,
+        /// If `CloneArgs::pidfd` is set
+        pidfd: Option<PidFd>,
+    },
+    Child,
+}
+
+/// Arguments of `clone3`, which creates process like `fork` (no shared
+/// memory, own stack copy)
+#[derive(Debug, Clone)]
+pub struct CloneArgs<'fd> {
+    namespaces: CloneFlags,
+    pidfd: bool,
+    exit_signal: Option<Signal>,
+    set_tid: Vec<Pid>,
+    cgroup: Option<BorrowedFd<'fd>>,
+}
+
+/// struct clone_args (CLONE_ARGS_SIZE_VER2)
+#[derive(Default)]
+#[repr(C)]
+struct RawCloneArgs {
+    flags: u64,
+    pidfd: u64,
+    child_tid: u64,
+    parent_tid: u64,
+    exit_signal: u64,
+    stack: u64,
+    stack_size: u64,
+    tls: u64,
+    set_tid: u64,
+    set_tid_size: u64,
+    cgroup: u64,
+}
+
+/// NULL terminated array of C strings, argv or envp of `execve`
+///
+/// It's built before `fork`, so that `execve` doesn't allocate in child.
+#[derive(Debug, Clone)]
+pub struct CStrArray<'a> {
+    ptrs: Vec<*const c_char>,
+    _marker: PhantomData<&'a CStr>,
+}
+
+/// Decoded status of `waitpid`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitStatus {
+    /// Exit code
+    Exited(Pid, c_int),
+    /// Killed by signal, true if core dumped
+    Signaled(Pid, Signal, bool),
+    /// Stopped by signal (`WaitFlag::Untraced`)
+    Stopped(Pid, Signal),
+    /// Resumed by SIGCONT (`WaitFlag::Continued`)
+    Continued(Pid),
+    /// Raw status of `waitpid` that can't be decoded (signal is unknown)
+    Unknown(Pid, c_int),
+}
+
+/// WXXX of waitpid
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(i32)]
+#[repr(i32)]
+pub enum WaitFlag {
+    /// Return immediately if no child has changed state
+    NoHang = 1,
+    /// Report stopped children
+    Untraced = 2,
+    /// Report children resumed by SIGCONT
+    Continued = 8,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(i32)]
+#[repr(transparent)]
+pub struct WaitFlags(i32);
+
+/// Builder to launch child process by `fork` and `execve`
+///
+/// Everything (argv, environment, program path) is prepared before `fork`,
+/// so child only makes async-signal-safe calls, in order: reset signal
+/// mask and SIGPIPE, `setsid`, `chdir`, remap fds, `execve`. Failure of
+/// them is reported by `spawn`.
+#[derive(Debug, Clone)]
+pub struct Command {
+    program: CString,
+    /// Program name is the first
+    args: Vec<CString>,
+    /// `None` to remove
+    envs: BTreeMap<OsString, Option<OsString>>,
+    env_clear: bool,
+    cwd: Option<CString>,
+    /// (fd of parent, fd of child)
+    fds: Vec<(RawFd, RawFd)>,
+    setsid: bool,
+    saw_nul: bool,
+}
+
+/// Reap terminated children when SIGCHLD arrives
+///
+/// SIGCHLD is blocked and accepted by signalfd, so fd can be registered
+/// into `Epoll`. Every child is reaped (`waitpid(-1)`), include those
+/// spawned by other code of the process.
+#[derive(Debug)]
+pub struct ChildReaper {
+    sfd: SignalFd,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl ForkResult {
+    pub fn is_child(&self) -> bool {
+        matches!(self, Self::Child)
+    }
+}
+
+impl<'fd> CloneArgs<'fd> {
+    /// SIGCHLD is sent to parent when child exits (like `fork`)
+    pub fn new() -> Self {
+        Self {
+            namespaces: CloneFlags::new(),
+            pidfd: false,
+            exit_signal: Some(Signal::SIGCHLD),
+            set_tid: vec![],
+            cgroup: None,
+        }
+    }
+
+    /// Create child in new namespaces (CLONE_NEWXXX)
+    ///
+    /// Need CAP_SYS_ADMIN except `CloneFlag::NewUser`, with which others
+    /// are created in (and owned by) the new user namespace.
+    pub fn namespaces(&mut self, flags: CloneFlags) -> &mut Self {
+        self.namespaces = flags;
+        self
+    }
+
+    /// CLONE_PIDFD, return pidfd of child
+    pub fn pidfd(&mut self, pidfd: bool) -> &mut Self {
+        self.pidfd = pidfd;
+        self
+    }
+
+    /// Signal to parent when child exits, `None` for no signal (child
+    /// can't be waited by `waitpid` without __WALL then)
+    pub fn exit_signal(&mut self, sig: Option<Signal>) -> &mut Self {
+        self.exit_signal = sig;
+        self
+    }
+
+    /// Pid of child in each pid namespace, from the innermost one (need
+    /// CAP_SYS_ADMIN of them), e.g. for checkpoint/restore
+    pub fn set_tid(&mut self, tids: &[Pid]) -> &mut Self {
+        self.set_tid = tids.to_vec();
+        self
+    }
+
+    /// CLONE_INTO_CGROUP, place child into cgroup v2 directory `fd`
+    pub fn into_cgroup(&mut self, fd: BorrowedFd<'fd>) -> &mut Self {
+        self.cgroup = Some(fd);
+        self
+    }
+}
+
+impl Default for CloneArgs<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> CStrArray<'a> {
+    pub fn new(strs: &[&'a CStr]) -> Self {
+        Self {
+            ptrs: strs.iter().map(|s| s.as_ptr()).chain([null()]).collect(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn as_ptr(&self) -> *const *const c_char {
+        self.ptrs.as_ptr()
+    }
+}
+
+impl WaitStatus {
+    /// Decode `status` of `waitpid`, `None` if signal is unknown
+    pub fn from_raw(pid: Pid, status: c_int) -> Option<Self> {
+        let sig = |signo| Signal::try_from(signo).ok();
+
+        Some(if libc::WIFEXITED(status) {
+            Self::Exited(pid, libc::WEXITSTATUS(status))
+        }
+        else if libc::WIFSIGNALED(status) {
+            Self::Signaled(
+                pid,
+                sig(libc::WTERMSIG(status))?,
+                libc::WCOREDUMP(status),
+            )
+        }
+        else if libc::WIFSTOPPED(status) {
+            Self::Stopped(pid, sig(libc::WSTOPSIG(status))?)
+        }
+        else if libc::WIFCONTINUED(status) {
+            Self::Continued(pid)
+        }
+        else {
+            None?
+        })
+    }
+
+    /// Decode siginfo of waitid (or SIGCHLD), `None` if it isn't of child
+    /// state change
+    pub fn from_siginfo(info: &SigInfo) -> Option<Self> {
+        let pid = Pid::from_raw(info.pid);
+        let sig = || Signal::try_from(info.status).ok();
+
+        Some(match info.code {
+            libc::CLD_EXITED => Self::Exited(pid, info.status),
+            libc::CLD_KILLED => Self::Signaled(pid, sig()?, false),
+            libc::CLD_DUMPED => Self::Signaled(pid, sig()?, true),
+            libc::CLD_STOPPED | libc::CLD_TRAPPED => {
+                Self::Stopped(pid, sig()?)
+            }
+            libc::CLD_CONTINUED => Self::Continued(pid),
+            _ => None?,
+        })
+    }
+
+    pub fn pid(&self) -> Pid {
+        match self {
+            Self::Exited(pid, _)
+            | Self::Signaled(pid, _, _)
+            | Self::Stopped(pid, _)
+            | Self::Continued(pid)
+            | Self::Unknown(pid, _) => *pid,
+        }
+    }
+
+    /// Exited or killed
+    pub fn is_terminated(&self) -> bool {
+        match self {
+            Self::Exited(..) | Self::Signaled(..) => true,
+            Self::Unknown(_, status) => {
+                libc::WIFEXITED(*status) || libc::WIFSIGNALED(*status)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl WaitFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<WaitFlag> for WaitFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: WaitFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for WaitFlag {
+    type Output = WaitFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        WaitFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<WaitFlag> for &WaitFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: WaitFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<WaitFlags> for WaitFlag {
+    fn into(self) -> WaitFlags {
+        WaitFlags(self.to_bits())
+    }
+}
+
+impl Debug for WaitFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in WaitFlag::iter().filter(|e| self & *e).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Command {
+    /// `program` is searched in PATH (of child environment) if it doesn't
+    /// contain '/'
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        let mut saw_nul = false;
+        let program = to_cstring(program.as_ref(), &mut saw_nul);
+
+        Self {
+            args: vec![program.clone()],
+            program,
+            envs: BTreeMap::new(),
+            env_clear: false,
+            cwd: None,
+            fds: vec![],
+            setsid: false,
+            saw_nul,
+        }
+    }
+
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        let arg = to_cstring(arg.as_ref(), &mut self.saw_nul);
+
+        self.args.push(arg);
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+
+        self
+    }
+
+    pub fn env(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        val: impl AsRef<OsStr>,
+    ) -> &mut Self {
+        self.envs
+            .insert(key.as_ref().to_owned(), Some(val.as_ref().to_owned()));
+        self
+    }
+
+    pub fn env_remove(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.envs.insert(key.as_ref().to_owned(), None);
+        self
+    }
+
+    /// Don't inherit environment of the caller
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.envs.clear();
+        self.env_clear = true;
+        self
+    }
+
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.cwd =
+            Some(to_cstring(dir.as_ref().as_os_str(), &mut self.saw_nul));
+        self
+    }
+
+    /// Dup `fd` as `child_fd` of child (e.g. 0, 1, 2 for stdio)
+    ///
+    /// `fd` should be kept open until `spawn`. Other fds are inherited as
+    /// they are, unless they're close-on-exec.
+    pub fn fd(&mut self, child_fd: RawFd, fd: BorrowedFd) -> &mut Self {
+        self.fds.retain(|(_, dst)| *dst != child_fd);
+        self.fds.push((fd.as_raw_fd(), child_fd));
+        self
+    }
+
+    /// Run child in new session (and process group), detached from
+    /// controlling terminal
+    pub fn setsid(&mut self, setsid: bool) -> &mut Self {
+        self.setsid = setsid;
+        self
+    }
+
+    /// Return pid of child, which should be waited by `waitpid`
+    ///
+    /// EINVAL if any string contains nul, ENOENT if program isn't found,
+    /// or errno of the failed step in child (which is reaped then).
+    pub fn spawn(&self) -> errno::Result<Pid> {
+        if self.saw_nul {
+            Err(PosixError::EINVAL)?
+        }
+
+        let envs = self.capture_env();
+        let path = self.resolve_program(&envs)?;
+
+        let envs = envs
+            .into_iter()
+            .map(|(mut key, val)| {
+                key.push("=");
+                key.push(val);
+                // key and value have no nul
+                CString::new(key.into_vec()).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let args = self.args.iter().map(CString::as_c_str).collect::<Vec<_>>();
+        let envs = envs.iter().map(CString::as_c_str).collect::<Vec<_>>();
+
+        let argv = CStrArray::new(&args);
+        let envp = CStrArray::new(&envs);
+        let mut tmp = vec![-1; self.fds.len()];
+
+        let (r, w) = unistd::pipe2(ExtraBehavior::new().close_on_exec())?;
+
+        match unsafe { fork() }? {
+            ForkResult::Child => unsafe {
+                let mut err_fd = w.as_raw_fd();
+                let errno = self.exec_child(
+                    &path,
+                    &argv,
+                    &envp,
+                    &mut tmp,
+                    &mut err_fd,
+                );
+
+                libc::write(
+                    err_fd,
+                    &errno as *const c_int as _,
+                    size_of::<c_int>(),
+                );
+                libc::_exit(127)
+            },
+            ForkResult::Parent { child } => {
+                drop(w);
+
+                let mut buf = [0u8; size_of::<c_int>()];
+
+                let n = loop {
+                    match unistd::read(r.as_fd(), &mut buf, buf.len()) {
+                        Err(PosixError::EINTR) => continue,
+                        res => break res?,
+                    }
+                };
+
+                // pipe is closed by successful exec
+                if n == 0 {
+                    return Ok(child);
+                }
+
+                let _ = waitpid(Some(child), WaitFlags::new());
+
+                Err(PosixError::try_from(c_int::from_ne_bytes(buf))
+                    .unwrap_or(PosixError::EIO))
+            }
+        }
+    }
+
+    /// Return errno of the failed step, `err_fd` is updated if it's moved
+    ///
+    /// Async-signal-safe only.
+    unsafe fn exec_child(
+        &self,
+        path: &CStr,
+        argv: &CStrArray,
+        envp: &CStrArray,
+        tmp: &mut [c_int],
+        err_fd: &mut RawFd,
+    ) -> c_int {
+        unsafe {
+            let errno = || *libc::__errno_location();
+
+            let mut empty: libc::sigset_t = zeroed();
+            libc::sigemptyset(&mut empty);
+
+            if libc::sigprocmask(libc::SIG_SETMASK, &empty, null_mut()) == -1 {
+                return errno();
+            }
+
+            // Rust runtime ignores SIGPIPE, which is kept across execve
+            if libc::signal(libc::SIGPIPE, libc::SIG_DFL) == libc::SIG_ERR {
+                return errno();
+            }
+
+            if self.setsid && libc::setsid() == -1 {
+                return errno();
+            }
+
+            if let Some(cwd) = &self.cwd {
+                if libc::chdir(cwd.as_ptr()) == -1 {
+                    return errno();
+                }
+            }
+
+            // move fds of parent (and error pipe) above all target fds
+            // first, so that dup2 doesn't overwrite any of them
+            let min_fd =
+                self.fds.iter().map(|(_, dst)| *dst + 1).max().unwrap_or(0);
+
+            *err_fd = match libc::fcntl(*err_fd, libc::F_DUPFD_CLOEXEC, min_fd)
+            {
+                -1 => return errno(),
+                fd => fd,
+            };
+
+            for (i, (src, _)) in self.fds.iter().enumerate() {
+                tmp[i] = libc::fcntl(*src, libc::F_DUPFD_CLOEXEC, min_fd);
+
+                if tmp[i] == -1 {
+                    return errno();
+                }
+            }
+
+            // dup2 clears FD_CLOEXEC of target
+            for (i, (_, dst)) in self.fds.iter().enumerate() {
+                if libc::dup2(tmp[i], *dst) == -1 {
+                    return errno();
+                }
+            }
+
+            libc::execve(path.as_ptr(), argv.as_ptr(), envp.as_ptr());
+
+            errno()
+        }
+    }
+
+    fn capture_env(&self) -> BTreeMap<OsString, OsString> {
+        let mut envs = if self.env_clear {
+            BTreeMap::new()
+        }
+        else {
+            env::vars_os().collect()
+        };
+
+        for (key, val) in self.envs.iter() {
+            match val {
+                Some(val) => envs.insert(key.clone(), val.clone()),
+                None => envs.remove(key),
+            };
+        }
+
+        envs
+    }
+
+    fn resolve_program(
+        &self,
+        envs: &BTreeMap<OsString, OsString>,
+    ) -> errno::Result<CString> {
+        if self.program.as_bytes().contains(&b'/') {
+            return Ok(self.program.clone());
+        }
+
+        let path = envs
+            .get(OsStr::new("PATH"))
+            .map(OsString::as_os_str)
+            .unwrap_or(OsStr::new("/bin:/usr/bin"));
+
+        for dir in path.as_bytes().split(|b| *b == b':') {
+            let dir = if dir.is_empty() { b"." } else { dir };
+
+            let mut candidate = dir.to_vec();
+            candidate.push(b'/');
+            candidate.extend_from_slice(self.program.as_bytes());
+
+            let Ok(candidate) = CString::new(candidate)
+            else {
+                continue;
+            };
+
+            if unsafe { libc::access(candidate.as_ptr(), libc::X_OK) } == 0 {
+                return Ok(candidate);
+            }
+        }
+
+        Err(PosixError::ENOENT)
+    }
+}
+
+impl ChildReaper {
+    /// Block SIGCHLD of the calling thread (see `SignalFd::new`)
+    pub fn new() -> errno::Result<Self> {
+        Ok(Self {
+            sfd: SignalFd::new(Signal::SIGCHLD.into())?,
+        })
+    }
+
+    /// Drain SIGCHLD and reap all terminated children, call `f` with each
+    /// status, return the number of them
+    ///
+    /// It doesn't block, and SIGCHLD isn't required to be received (it's
+    /// merged if several children exit at once).
+    pub fn reap(
+        &mut self,
+        mut f: impl FnMut(WaitStatus),
+    ) -> errno::Result<usize> {
+        while self.sfd.read()?.is_some() {}
+
+        let mut n = 0;
+
+        loop {
+            match waitpid(None, WaitFlag::NoHang.into()) {
+                Ok(Some(status)) => {
+                    f(status);
+                    n += 1;
+                }
+                Ok(None) | Err(PosixError::ECHILD) => break Ok(n),
+                Err(PosixError::EINTR) => continue,
+                Err(err) => break Err(err),
+            }
+        }
+    }
+
+    /// `reap` and send statuses to `tx` (receiver being dropped is
+    /// ignored)
+    pub fn reap_to(
+        &mut self,
+        tx: &Sender<WaitStatus>,
+    ) -> errno::Result<usize> {
+        self.reap(|status| {
+            let _ = tx.send(status);
+        })
+    }
+}
+
+impl AsFd for ChildReaper {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.sfd.as_fd()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Create child process of copy of the caller, only the calling thread is
+/// copied
+///
+/// # Safety
+///
+/// Child of multi-threaded process may only call async-signal-safe
+/// functions until `execve` or `_exit` (locks may be held by threads
+/// which are gone, e.g. allocator, stdout), and shouldn't return into
+/// code that would run destructors twice.
+pub unsafe fn fork() -> errno::Result<ForkResult> {
+    let ret = unsafe { libc::fork() };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(if ret == 0 {
+        ForkResult::Child
+    }
+    else {
+        ForkResult::Parent {
+            child: Pid::from_raw(ret),
+        }
+    })
+}
+
+/// clone3 (Linux 5.3) with `args`, like `fork` otherwise
+///
+/// ENOSPC if nesting of namespaces exceeds limit, EEXIST if pid of
+/// `CloneArgs::set_tid` is in use.
+///
+/// # Safety
+///
+/// Stricter than `fork`: raw syscall bypasses bookkeeping of libc (atfork
+/// handlers, cached tid of thread control block), so `raise`,
+/// `pthread_*`, robust or recursive mutexes act on the parent thread in
+/// child. Child may only make raw syscalls until `execve` or `_exit`.
+pub unsafe fn clone3(args: &CloneArgs) -> errno::Result<CloneResult> {
+    let mut pidfd: c_int = -1;
+    let set_tid = args
+        .set_tid
+        .iter()
+        .map(|pid| pid.as_raw())
+        .collect::<Vec<_>>();
+
+    let mut flags = args.namespaces.to_bits() as u32 as u64;
+
+    if args.pidfd {
+        flags |= CLONE_PIDFD;
+    }
+
+    if args.cgroup.is_some() {
+        flags |= CLONE_INTO_CGROUP;
+    }
+
+    let raw = RawCloneArgs {
+        flags,
+        pidfd: &mut pidfd as *mut c_int as u64,
+        exit_signal: args.exit_signal.map(Signal::to_bits).unwrap_or(0) as u64,
+        set_tid: if set_tid.is_empty() {
+            0
+        }
+        else {
+            set_tid.as_ptr() as u64
+        },
+        set_tid_size: set_tid.len() as u64,
+        cgroup: args.cgroup.map(|fd| fd.as_raw_fd()).unwrap_or(0) as u64,
+        ..Default::default()
+    };
+
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_clone3,
+            &raw as *const RawCloneArgs,
+            size_of::<RawCloneArgs>(),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    if ret == 0 {
+        return Ok(CloneResult::Child);
+    }
+
+    let child = Pid::from_raw(ret as _);
+
+    Ok(CloneResult::Parent {
+        child,
+        pidfd: args.pidfd.then(|| {
+            PidFd::from_raw_parts(
+                unsafe { OwnedFd::from_raw_fd(pidfd) },
+                child,
+            )
+        }),
+    })
+}
+
+/// Replace process image with program of `path`, return only on error
+///
+/// `argv[0]` is program name by convention, `envp` is "KEY=VALUE".
+///
+/// It's async-signal-safe, so it can be called in child of `fork`.
+pub fn execve(
+    path: &CStr,
+    argv: &CStrArray,
+    envp: &CStrArray,
+) -> errno::Result<Infallible> {
+    unsafe { libc::execve(path.as_ptr(), argv.as_ptr(), envp.as_ptr()) };
+
+    Err(errno::last_os_error())
+}
+
+/// Like `execve` but `file` is searched in PATH (if it doesn't contain
+/// '/') and environment is inherited
+///
+/// It isn't async-signal-safe (of POSIX), don't call it in child of `fork`
+/// of multi-threaded process, resolve path and `execve` instead.
+pub fn execvp(file: &CStr, argv: &CStrArray) -> errno::Result<Infallible> {
+    unsafe { libc::execvp(file.as_ptr(), argv.as_ptr()) };
+
+    Err(errno::last_os_error())
+}
+
+fn to_cstring(s: &OsStr, saw_nul: &mut bool) -> CString {
+    CString::new(s.as_bytes()).unwrap_or_else(|_| {
+        *saw_nul = true;
+        CString::default()
+    })
+}
+
+/// `pid`: `None` for any child
+///
+/// Return `None` only if `WaitFlag::NoHang` is set and no child has
+/// changed state (status that can't be decoded is `WaitStatus::Unknown`).
+/// ECHILD if there is no child (to wait).
+pub fn waitpid(
+    pid: Option<Pid>,
+    flags: WaitFlags,
+) -> errno::Result<Option<WaitStatus>> {
+    let mut status = 0;
+
+    let ret = unsafe {
+        libc::waitpid(
+            pid.map(Pid::as_raw).unwrap_or(-1),
+            &mut status,
+            flags.to_bits(),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    if ret == 0 {
+        return Ok(None);
+    }
+
+    let pid = Pid::from_raw(ret);
+
+    Ok(Some(
+        WaitStatus::from_raw(pid, status)
+            .unwrap_or(WaitStatus::Unknown(pid, status)),
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process::Command, sync::mpsc, thread, time::Duration};
+
+    use super::*;
+    use crate::sched::CloneFlag;
+
+    #[test]
+    fn test_wait_status() {
+        let pid = Pid::from_raw(42);
+
+        assert_eq!(
+            WaitStatus::from_raw(pid, 3 << 8),
+            Some(WaitStatus::Exited(pid, 3))
+        );
+        assert_eq!(
+            WaitStatus::from_raw(pid, 9),
+            Some(WaitStatus::Signaled(pid, Signal::SIGKILL, false))
+        );
+        assert_eq!(
+            WaitStatus::from_raw(pid, 19 << 8 | 0x7f),
+            Some(WaitStatus::Stopped(pid, Signal::SIGSTOP))
+        );
+        assert_eq!(
+            WaitStatus::from_raw(pid, 0xffff),
+            Some(WaitStatus::Continued(pid))
+        );
+        assert!(WaitStatus::Unknown(pid, 64).is_terminated());
+        assert!(!WaitStatus::Unknown(pid, 64 << 8 | 0x7f).is_terminated());
+    }
+
+    #[test]
+    fn test_fork_exec() {
+        // no allocation in child
+        let argv = CStrArray::new(&[c"sh", c"-c", c"exit $LXTEST"]);
+        let envp = CStrArray::new(&[c"LXTEST=5"]);
+
+        let child = match unsafe { fork() }.unwrap() {
+            ForkResult::Parent { child } => child,
+            ForkResult::Child => {
+                let _ = execve(c"/bin/sh", &argv, &envp);
+
+                unsafe { libc::_exit(127) }
+            }
+        };
+
+        assert_eq!(
+            waitpid(Some(child), WaitFlags::new()),
+            Ok(Some(WaitStatus::Exited(child, 5)))
+        );
+
+        let argv = CStrArray::new(&[c"lxtest"]);
+
+        assert_eq!(
+            execvp(c"lxtest-no-such-program", &argv),
+            Err(PosixError::ENOENT)
+        );
+    }
+
+    #[test]
+    fn test_clone3() {
+        let (child, pidfd) =
+            match unsafe { clone3(CloneArgs::new().pidfd(true)) }.unwrap() {
+                CloneResult::Parent { child, pidfd } => {
+                    (child, pidfd.unwrap())
+                }
+                CloneResult::Child => unsafe { libc::_exit(7) },
+            };
+
+        assert_eq!(pidfd.pid(), child);
+
+        assert_eq!(pidfd.wait(), Ok(WaitStatus::Exited(child, 7)));
+
+        // unprivileged user namespace may be disabled
+        let args = CloneArgs::new()
+            .namespaces(CloneFlag::NewUser | CloneFlag::NewPid)
+            .clone();
+
+        match unsafe { clone3(&args) } {
+            Ok(CloneResult::Parent { child, .. }) => assert_eq!(
+                waitpid(Some(child), WaitFlags::new()),
+                Ok(Some(WaitStatus::Exited(child, 0)))
+            ),
+            // init of new pid namespace
+            Ok(CloneResult::Child) => unsafe {
+                libc::_exit(if libc::getpid() == 1 { 0 } else { 1 })
+            },
+            Err(err) => assert!(matches!(
+                err,
+                PosixError::EPERM | PosixError::EINVAL | PosixError::ENOSPC
+            )),
+        }
+    }
+
+    #[test]
+    fn test_command() {
+        let (r, w) =
+            unistd::pipe2(ExtraBehavior::new().close_on_exec()).unwrap();
+
+        let child = super::Command::new("sh")
+            .args(["-c", "echo $LXTEST $(pwd); exit 4"])
+            .env("LXTEST", "hi")
+            .current_dir("/")
+            .fd(1, w.as_fd())
+            .setsid(true)
+            .spawn()
+            .unwrap();
+
+        drop(w);
+
+        let mut buf = [0u8; 16];
+        let n = unistd::read(r.as_fd(), &mut buf, 16).unwrap();
+
+        assert_eq!(&buf[..n], b"hi /\n");
+
+        assert_eq!(
+            waitpid(Some(child), WaitFlags::new()),
+            Ok(Some(WaitStatus::Exited(child, 4)))
+        );
+
+        assert_eq!(
+            super::Command::new("lxtest-no-such-program").spawn(),
+            Err(PosixError::ENOENT)
+        );
+        assert_eq!(
+            super::Command::new("true")
+                .current_dir("/lxtest-no-such-dir")
+                .spawn(),
+            Err(PosixError::ENOENT)
+        );
+        assert_eq!(
+            super::Command::new("true").arg("a\0b").spawn(),
+            Err(PosixError::EINVAL)
+        );
+    }
+
+    #[test]
+    fn test_command_sigpipe() {
+        let (r, w) =
+            unistd::pipe2(ExtraBehavior::new().close_on_exec()).unwrap();
+
+        let child = super::Command::new("grep")
+            .args(["SigIgn", "/proc/self/status"])
+            .fd(1, w.as_fd())
+            .spawn()
+            .unwrap();
+
+        drop(w);
+
+        let mut buf = [0u8; 64];
+        let n = unistd::read(r.as_fd(), &mut buf, 64).unwrap();
+        let line = String::from_utf8_lossy(&buf[..n]);
+        let ignored =
+            u64::from_str_radix(line["SigIgn:".len()..].trim(), 16).unwrap();
+
+        assert_eq!(ignored & 1 << (libc::SIGPIPE - 1), 0, "{line}");
+        assert_eq!(
+            waitpid(Some(child), WaitFlags::new()),
+            Ok(Some(WaitStatus::Exited(child, 0)))
+        );
+    }
+
+    #[test]
+    fn test_child_reaper() {
+        // it reaps children of other tests too, so run it alone in
+        // subprocess of the test binary
+        if env::var_os("LXTEST_CHILD_REAPER").is_none() {
+            let status = Command::new(env::current_exe().unwrap())
+                .args([
+                    "process::tests::test_child_reaper",
+                    "--exact",
+                    "--nocapture",
+                ])
+                .env("LXTEST_CHILD_REAPER", "1")
+                .status()
+                .unwrap();
+
+            assert!(status.success());
+            return;
+        }
+
+        let mut reaper = ChildReaper::new().unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        let child = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        let pid = Pid::from_raw(child.id() as _);
+
+        // SIGCHLD is process directed, may be discarded by other threads
+        for _ in 0..100 {
+            reaper.reap_to(&tx).unwrap();
+
+            if let Some(status) = rx.try_iter().find(|s| s.pid() == pid) {
+                assert_eq!(status, WaitStatus::Exited(pid, 3));
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("{pid} isn't reaped");
+    }
+}
@@ -0,0 +1,184 @@
+//! Resource limits of process
+//!
+//! Ref [getrlimit(2)](https://man7.org/linux/man-pages/man2/getrlimit.2.html)
+
+use std::ptr::null;
+
+use libc::{RLIM_INFINITY, rlim_t, rlimit};
+use m6tobytes::derive_to_bits;
+
+use crate::{errno, unistd::Pid};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// RLIMIT_XXX
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive_to_bits(u32)]
+#[repr(u32)]
+pub enum Resource {
+    /// CPU time in seconds (SIGXCPU)
+    Cpu = 0,
+    /// Max file size (SIGXFSZ)
+    Fsize = 1,
+    /// Data segment size
+    Data = 2,
+    Stack = 3,
+    /// Core file size
+    Core = 4,
+    Rss = 5,
+    /// Processes (threads) of real uid
+    Nproc = 6,
+    /// Max fd + 1
+    Nofile = 7,
+    /// Bytes of memory locked (mlock, SO_RCVBUFFORCE of packet ring, ...)
+    Memlock = 8,
+    /// Virtual memory size
+    As = 9,
+    Locks = 10,
+    Sigpending = 11,
+    /// Bytes of POSIX message queues
+    Msgqueue = 12,
+    /// Ceiling of nice (20 - rlim)
+    Nice = 13,
+    Rtprio = 14,
+    /// Realtime CPU time in microseconds without blocking
+    Rttime = 15,
+}
+
+/// `None` for unlimited (RLIM_INFINITY)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rlimit {
+    /// Enforced limit, can be raised up to `hard` by unprivileged process
+    pub soft: Option<u64>,
+    /// Ceiling of `soft`, can only be lowered without CAP_SYS_RESOURCE
+    pub hard: Option<u64>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl Rlimit {
+    pub fn new(soft: Option<u64>, hard: Option<u64>) -> Self {
+        Self { soft, hard }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl From<rlimit> for Rlimit {
+    fn from(rlim: rlimit) -> Self {
+        let conv = |v: rlim_t| (v != RLIM_INFINITY).then_some(v as u64);
+
+        Self {
+            soft: conv(rlim.rlim_cur),
+            hard: conv(rlim.rlim_max),
+        }
+    }
+}
+
+impl From<Rlimit> for rlimit {
+    fn from(rlim: Rlimit) -> Self {
+        Self {
+            rlim_cur: rlim.soft.map(|v| v as rlim_t).unwrap_or(RLIM_INFINITY),
+            rlim_max: rlim.hard.map(|v| v as rlim_t).unwrap_or(RLIM_INFINITY),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+pub fn getrlimit(resource: Resource) -> errno::Result<Rlimit> {
+    prlimit(None, resource, None)
+}
+
+/// EINVAL if soft > hard, EPERM for raising hard limit without
+/// CAP_SYS_RESOURCE (or RLIMIT_NOFILE above /proc/sys/fs/nr_open)
+pub fn setrlimit(resource: Resource, rlim: Rlimit) -> errno::Result<()> {
+    prlimit(None, resource, Some(rlim))?;
+
+    Ok(())
+}
+
+/// Get and optionally set limit of process `pid` (`None` for the caller),
+/// return the old limit
+pub fn prlimit(
+    pid: Option<Pid>,
+    resource: Resource,
+    new: Option<Rlimit>,
+) -> errno::Result<Rlimit> {
+    let new = new.map(rlimit::from);
+    let mut old = rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    let ret = unsafe {
+        libc::prlimit(
+            pid.map(Pid::as_raw).unwrap_or(0),
+            resource.to_bits(),
+            new.as_ref()
+                .map(|rlim| rlim as *const rlimit)
+                .unwrap_or(null()),
+            &mut old,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(old.into())
+}
+
+/// Raise soft limit of `resource` to its hard limit, return the new soft
+/// limit (`None` for unlimited)
+///
+/// Usually for RLIMIT_NOFILE of server, whose default soft limit is 1024.
+pub fn raise_to_hard(resource: Resource) -> errno::Result<Option<u64>> {
+    let rlim = getrlimit(resource)?;
+
+    if rlim.soft != rlim.hard {
+        setrlimit(resource, Rlimit::new(rlim.hard, rlim.hard))?;
+    }
+
+    Ok(rlim.hard)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errno::PosixError;
+
+    #[test]
+    fn test_rlimit() {
+        let nofile = getrlimit(Resource::Nofile).unwrap();
+
+        println!("nofile: {nofile:?}");
+
+        assert!(nofile.soft <= nofile.hard || nofile.hard.is_none());
+
+        assert_eq!(
+            setrlimit(Resource::Core, Rlimit::new(Some(2), Some(1))),
+            Err(PosixError::EINVAL)
+        );
+
+        let core = getrlimit(Resource::Core).unwrap();
+
+        // lower soft limit and restore it
+        setrlimit(Resource::Core, Rlimit::new(Some(0), core.hard)).unwrap();
+        assert_eq!(getrlimit(Resource::Core).unwrap().soft, Some(0));
+        setrlimit(Resource::Core, core).unwrap();
+
+        assert_eq!(
+            prlimit(Some(Pid::this()), Resource::Core, None).unwrap(),
+            core
+        );
+        assert_eq!(raise_to_hard(Resource::Nofile).unwrap(), nofile.hard);
+    }
+}
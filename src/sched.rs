@@ -0,0 +1,581 @@
+//! CPU affinity, scheduling policy and priority of process (thread), and
+//! namespaces
+//!
+//! `pid` of functions is thread id actually (`Pid::this_thread`), `None`
+//! for the calling thread.
+//!
+//! Ref [sched(7)](https://man7.org/linux/man-pages/man7/sched.7.html),
+//! [namespaces(7)](https://man7.org/linux/man-pages/man7/namespaces.7.html)
+
+use std::{
+    ffi::c_int,
+    fmt::Debug,
+    mem::{size_of, zeroed},
+    ops::{BitAnd, BitOr},
+    os::fd::{AsRawFd, BorrowedFd},
+    time::Duration,
+};
+
+use int_enum::IntEnum;
+use libc::{CPU_SETSIZE, cpu_set_t, id_t, sched_param};
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    errno::{self, PosixError},
+    unistd::Pid,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/// Policy is reset to `SchedPolicy::Other` for children (flag of policy)
+const SCHED_RESET_ON_FORK: c_int = 0x40000000;
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Set of CPU (up to CPU_SETSIZE, 1024)
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct CpuSet(cpu_set_t);
+
+/// SCHED_XXX
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntEnum)]
+#[repr(i32)]
+pub enum SchedPolicy {
+    /// Default time-sharing (CFS/EEVDF), weighted by nice
+    Other = 0,
+    /// Realtime, run until it blocks or yields (priority 1..=99)
+    Fifo = 1,
+    /// Realtime, like `Fifo` with time slice
+    Rr = 2,
+    /// CPU-bound batch work
+    Batch = 3,
+    /// Lower than nice 19
+    Idle = 5,
+    /// Earliest deadline first (set by `sched_set_deadline`)
+    Deadline = 6,
+}
+
+/// PRIO_XXX, kind of `who` of `setpriority`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum PriorityWhich {
+    /// Process (thread) id
+    Process = 0,
+    /// Process group id
+    Pgrp = 1,
+    /// All processes of uid
+    User = 2,
+}
+
+/// CLONE_NEWXXX, namespaces of unshare, setns
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter)]
+#[derive_to_bits(i32)]
+#[repr(i32)]
+pub enum CloneFlag {
+    /// Time namespace (Linux 5.6), applies to children only
+    NewTime = 0x80,
+    /// Mount namespace
+    NewNs = 0x20000,
+    NewCgroup = 0x02000000,
+    /// Hostname, domainname
+    NewUts = 0x04000000,
+    /// System V IPC, POSIX message queues
+    NewIpc = 0x08000000,
+    /// Caller should be single-threaded for unshare
+    NewUser = 0x10000000,
+    /// Applies to children only, the first one becomes init (pid 1)
+    NewPid = 0x20000000,
+    NewNet = 0x40000000,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(i32)]
+#[repr(transparent)]
+pub struct CloneFlags(i32);
+
+/// struct sched_attr of sched_setattr (SCHED_ATTR_SIZE_VER0)
+#[derive(Default)]
+#[repr(C)]
+struct SchedAttr {
+    size: u32,
+    sched_policy: u32,
+    sched_flags: u64,
+    sched_nice: i32,
+    sched_priority: u32,
+    /// nanoseconds
+    sched_runtime: u64,
+    sched_deadline: u64,
+    sched_period: u64,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl CpuSet {
+    pub const MAX: usize = CPU_SETSIZE as usize;
+
+    pub fn new() -> Self {
+        Self(unsafe { zeroed() })
+    }
+
+    /// EINVAL if `cpu` >= `CpuSet::MAX`
+    pub fn set(&mut self, cpu: usize) -> errno::Result<()> {
+        if cpu >= Self::MAX {
+            Err(PosixError::EINVAL)?
+        }
+
+        unsafe { libc::CPU_SET(cpu, &mut self.0) };
+
+        Ok(())
+    }
+
+    pub fn unset(&mut self, cpu: usize) -> errno::Result<()> {
+        if cpu >= Self::MAX {
+            Err(PosixError::EINVAL)?
+        }
+
+        unsafe { libc::CPU_CLR(cpu, &mut self.0) };
+
+        Ok(())
+    }
+
+    pub fn is_set(&self, cpu: usize) -> bool {
+        cpu < Self::MAX && unsafe { libc::CPU_ISSET(cpu, &self.0) }
+    }
+
+    pub fn count(&self) -> usize {
+        unsafe { libc::CPU_COUNT(&self.0) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..Self::MAX).filter(|cpu| self.is_set(*cpu))
+    }
+}
+
+impl Default for CpuSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for CpuSet {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { libc::CPU_EQUAL(&self.0, &other.0) }
+    }
+}
+
+impl Eq for CpuSet {}
+
+impl FromIterator<usize> for CpuSet {
+    /// CPU out of range is ignored
+    fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
+        let mut set = Self::new();
+
+        for cpu in iter {
+            let _ = set.set(cpu);
+        }
+
+        set
+    }
+}
+
+impl Debug for CpuSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl CloneFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<CloneFlag> for CloneFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: CloneFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for CloneFlag {
+    type Output = CloneFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        CloneFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<CloneFlag> for &CloneFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: CloneFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<CloneFlags> for CloneFlag {
+    fn into(self) -> CloneFlags {
+        CloneFlags(self.to_bits())
+    }
+}
+
+impl Debug for CloneFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in CloneFlag::iter().filter(|e| self & *e).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+pub fn sched_getaffinity(pid: Option<Pid>) -> errno::Result<CpuSet> {
+    let mut set = CpuSet::new();
+
+    let ret = unsafe {
+        libc::sched_getaffinity(
+            pid.map(Pid::as_raw).unwrap_or(0),
+            size_of::<cpu_set_t>(),
+            &mut set.0,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(set)
+}
+
+/// Pin thread to `set`, EINVAL if no CPU of `set` is online (or allowed
+/// by cpuset cgroup)
+pub fn sched_setaffinity(pid: Option<Pid>, set: &CpuSet) -> errno::Result<()> {
+    let ret = unsafe {
+        libc::sched_setaffinity(
+            pid.map(Pid::as_raw).unwrap_or(0),
+            size_of::<cpu_set_t>(),
+            &set.0,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// CPU the calling thread is running on (may be stale immediately)
+pub fn sched_getcpu() -> errno::Result<usize> {
+    let ret = unsafe { libc::sched_getcpu() };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as usize)
+}
+
+/// `priority` should be 0 for non realtime policy, EPERM for realtime
+/// policy without CAP_SYS_NICE (or RLIMIT_RTPRIO)
+///
+/// Use `sched_set_deadline` for `SchedPolicy::Deadline` (EINVAL here).
+pub fn sched_setscheduler(
+    pid: Option<Pid>,
+    policy: SchedPolicy,
+    priority: c_int,
+    reset_on_fork: bool,
+) -> errno::Result<()> {
+    let param = sched_param {
+        sched_priority: priority,
+    };
+    let mut policy = policy as c_int;
+
+    if reset_on_fork {
+        policy |= SCHED_RESET_ON_FORK;
+    }
+
+    let ret = unsafe {
+        libc::sched_setscheduler(
+            pid.map(Pid::as_raw).unwrap_or(0),
+            policy,
+            &param,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+pub fn sched_getscheduler(pid: Option<Pid>) -> errno::Result<SchedPolicy> {
+    let ret =
+        unsafe { libc::sched_getscheduler(pid.map(Pid::as_raw).unwrap_or(0)) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    SchedPolicy::try_from(ret & !SCHED_RESET_ON_FORK)
+        .map_err(|_| PosixError::EINVAL)
+}
+
+/// Realtime priority of policy (0 for non realtime policy)
+pub fn sched_getparam(pid: Option<Pid>) -> errno::Result<c_int> {
+    let mut param = sched_param { sched_priority: 0 };
+
+    let ret = unsafe {
+        libc::sched_getparam(pid.map(Pid::as_raw).unwrap_or(0), &mut param)
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(param.sched_priority)
+}
+
+/// Range of priority of `policy`, e.g. 1..=99 for `SchedPolicy::Fifo`
+pub fn priority_range(policy: SchedPolicy) -> errno::Result<(c_int, c_int)> {
+    let min = unsafe { libc::sched_get_priority_min(policy as c_int) };
+    let max = unsafe { libc::sched_get_priority_max(policy as c_int) };
+
+    if min == -1 || max == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok((min, max))
+}
+
+/// SCHED_DEADLINE by sched_setattr, thread gets `runtime` of CPU every
+/// `period` before `deadline` (runtime <= deadline <= period)
+///
+/// Need CAP_SYS_NICE (EPERM), EBUSY if admission control fails, EINVAL
+/// for wrong parameters.
+pub fn sched_set_deadline(
+    pid: Option<Pid>,
+    runtime: Duration,
+    deadline: Duration,
+    period: Duration,
+) -> errno::Result<()> {
+    let attr = SchedAttr {
+        size: size_of::<SchedAttr>() as u32,
+        sched_policy: SchedPolicy::Deadline as u32,
+        sched_runtime: runtime.as_nanos() as u64,
+        sched_deadline: deadline.as_nanos() as u64,
+        sched_period: period.as_nanos() as u64,
+        ..Default::default()
+    };
+
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_sched_setattr,
+            pid.map(Pid::as_raw).unwrap_or(0),
+            &attr as *const SchedAttr,
+            0,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Move the calling thread into new namespaces of `flags` (need
+/// CAP_SYS_ADMIN, except `CloneFlag::NewUser`)
+///
+/// EINVAL for `CloneFlag::NewUser` of multi-threaded process.
+pub fn unshare(flags: CloneFlags) -> errno::Result<()> {
+    let ret = unsafe { libc::unshare(flags.to_bits()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Move the calling thread into namespace of `fd` (/proc/PID/ns/XXX, or
+/// pidfd with `nstype` of namespaces to join)
+///
+/// `nstype` is the kind of namespace `fd` must be, empty for any.
+pub fn setns(fd: BorrowedFd, nstype: CloneFlags) -> errno::Result<()> {
+    let ret = unsafe { libc::setns(fd.as_raw_fd(), nstype.to_bits()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Nice value (-20..=19, lower is higher priority) of `who` (0 for the
+/// caller), lowering it needs CAP_SYS_NICE (or RLIMIT_NICE)
+pub fn setpriority(
+    which: PriorityWhich,
+    who: id_t,
+    prio: c_int,
+) -> errno::Result<()> {
+    let ret = unsafe { libc::setpriority(which as _, who, prio) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Lowest nice value (highest priority) of processes of `who`
+pub fn getpriority(which: PriorityWhich, who: id_t) -> errno::Result<c_int> {
+    // -1 is legal
+    unsafe { *libc::__errno_location() = 0 };
+
+    let ret = unsafe { libc::getpriority(which as _, who) };
+
+    if ret == -1 && unsafe { *libc::__errno_location() } != 0 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, os::fd::AsFd, thread};
+
+    use super::*;
+    use crate::unistd::{gethostname, sethostname};
+
+    #[test]
+    fn test_affinity() {
+        let allowed = sched_getaffinity(None).unwrap();
+
+        println!("allowed: {allowed:?}");
+
+        assert!(!allowed.is_empty());
+        assert!(allowed.is_set(sched_getcpu().unwrap()));
+
+        let first = allowed.iter().next().unwrap();
+
+        // pin a spawned thread only
+        thread::spawn(move || {
+            let tid = Pid::this_thread();
+            let set = CpuSet::from_iter([first]);
+
+            sched_setaffinity(Some(tid), &set).unwrap();
+
+            assert_eq!(sched_getaffinity(None).unwrap(), set);
+            assert_eq!(sched_getcpu().unwrap(), first);
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(sched_getaffinity(None).unwrap(), allowed);
+        assert_eq!(
+            sched_setaffinity(None, &CpuSet::new()),
+            Err(PosixError::EINVAL)
+        );
+        assert_eq!(CpuSet::new().set(CpuSet::MAX), Err(PosixError::EINVAL));
+    }
+
+    #[test]
+    fn test_scheduler() {
+        assert_eq!(priority_range(SchedPolicy::Fifo).unwrap(), (1, 99));
+
+        thread::spawn(|| {
+            assert_eq!(sched_getscheduler(None).unwrap(), SchedPolicy::Other);
+            assert_eq!(sched_getparam(None).unwrap(), 0);
+
+            let tid = Pid::this_thread().as_raw() as id_t;
+            let nice = getpriority(PriorityWhich::Process, tid).unwrap();
+
+            // raising nice is always permitted
+            setpriority(PriorityWhich::Process, tid, 19).unwrap();
+            assert_eq!(getpriority(PriorityWhich::Process, tid).unwrap(), 19);
+            assert!(nice <= 19);
+
+            match sched_setscheduler(None, SchedPolicy::Rr, 10, true) {
+                Ok(()) => {
+                    assert_eq!(
+                        sched_getscheduler(None).unwrap(),
+                        SchedPolicy::Rr
+                    );
+                    assert_eq!(sched_getparam(None).unwrap(), 10);
+                }
+                Err(err) => assert_eq!(err, PosixError::EPERM),
+            }
+
+            assert_eq!(
+                sched_setscheduler(None, SchedPolicy::Deadline, 0, false),
+                Err(PosixError::EINVAL)
+            );
+            assert!(
+                sched_set_deadline(
+                    None,
+                    Duration::from_millis(10),
+                    Duration::from_millis(5),
+                    Duration::from_millis(20)
+                )
+                .is_err()
+            );
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_namespace() {
+        let hostname = gethostname().unwrap();
+
+        thread::spawn(move || {
+            let uts = File::open("/proc/thread-self/ns/uts").unwrap();
+
+            // need CAP_SYS_ADMIN
+            match unshare(CloneFlag::NewUts.into()) {
+                Ok(()) => {
+                    sethostname("lxtest-uts").unwrap();
+                    assert_eq!(gethostname().unwrap(), "lxtest-uts");
+
+                    setns(uts.as_fd(), CloneFlag::NewUts.into()).unwrap();
+                    assert_eq!(gethostname().unwrap(), hostname);
+                }
+                Err(err) => assert_eq!(err, PosixError::EPERM),
+            }
+
+            assert_eq!(
+                setns(uts.as_fd(), CloneFlag::NewNet.into()).unwrap_err(),
+                PosixError::EINVAL
+            );
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(
+            format!("{:?}", CloneFlag::NewNet | CloneFlag::NewUts),
+            "NewUts NewNet"
+        );
+    }
+}
@@ -1,15 +1,21 @@
 use std::{
-    fmt::Debug,
+    ffi::{c_int, c_void},
+    fmt::{Debug, Display},
+    marker::PhantomData,
     mem::zeroed,
-    ops::{BitAnd, BitOr},
+    ops::{BitAnd, BitOr, Sub},
+    str::FromStr,
+    time::Duration,
 };
 
 use int_enum::IntEnum;
-use libc::sigset_t;
-use m6tobytes::{derive_from_bits, derive_to_bits};
-use strum::{EnumIter, IntoEnumIterator};
+use libc::{pid_t, siginfo_t, sigset_t, sigval, timespec, uid_t};
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
 
-use crate::errno::{self, PosixError};
+use crate::{
+    errno::{self, PosixError},
+    unistd::Pid,
+};
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -20,9 +26,9 @@ use crate::errno::{self, PosixError};
 //// Structures
 
 
-#[derive(Debug, EnumIter, Clone, Copy, PartialEq, Eq, Hash, IntEnum)]
-#[derive_to_bits(i32)]
-#[derive_from_bits(i32)]
+/// `SIGRT` is realtime signal, others are standard signals (of the value
+/// as discriminant)
+#[derive(Debug, EnumIter, IntoStaticStr, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum Signal {
     /// mordern os merged into with SIGIOT
@@ -103,12 +109,54 @@ pub enum Signal {
     /// Window resize signal (4.3BSD, Sun)
     #[cfg(all(target_env = "gnu", target_arch = "x86_64"))]
     SIGWINCH = 28,
+    /// SIGRTMIN + n, queued and delivered in order
+    ///
+    /// SIGRTMIN is resolved at runtime, since libc reserves some of the
+    /// kernel ones (starting from 32) for itself.
+    SIGRT(RtOffset) = 32,
 }
 
+/// Offset of realtime signal from SIGRTMIN, always within SIGRTMAX (made
+/// by `Signal::rt`)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RtOffset(u8);
+
 #[derive(PartialEq, Eq, Clone, Copy, Hash)]
 #[repr(transparent)]
 pub struct SignalSet(sigset_t);
 
+/// union sigval, data sent along with signal (e.g. by `sigqueue`)
+#[derive(Clone, Copy)]
+pub union SigVal {
+    pub int: c_int,
+    pub ptr: *mut c_void,
+}
+
+/// Useful fields of siginfo_t
+///
+/// `pid`, `uid` are valid for signal sent by `kill`/`sigqueue` (or
+/// SIGCHLD), `status` for SIGCHLD, `value` for `sigqueue`.
+#[derive(Debug, Clone, Copy)]
+pub struct SigInfo {
+    pub signo: c_int,
+    /// SI_XXX (e.g. SI_USER, SI_QUEUE) or signal specific code
+    pub code: c_int,
+    pub pid: pid_t,
+    pub uid: uid_t,
+    pub status: c_int,
+    pub value: SigVal,
+}
+
+/// Block signals of the calling thread, restore the previous mask on drop
+///
+/// Not `Send`, signal mask is per thread.
+#[derive(Debug)]
+#[must_use = "signals are unblocked immediately if guard is dropped"]
+pub struct SignalBlockGuard {
+    oldset: SignalSet,
+    _not_send: PhantomData<*const ()>,
+}
+
 #[derive(Debug, IntEnum, Default, Clone, Copy)]
 #[repr(i32)]
 pub enum SigMaskHow {
@@ -121,6 +169,136 @@ pub enum SigMaskHow {
 ////////////////////////////////////////////////////////////////////////////////
 //// Functions
 
+impl RtOffset {
+    pub fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl Signal {
+    /// Realtime signal SIGRTMIN + `n`, `None` if it exceeds SIGRTMAX
+    pub fn rt(n: u8) -> Option<Self> {
+        (libc::SIGRTMIN() + n as c_int <= libc::SIGRTMAX())
+            .then_some(Self::SIGRT(RtOffset(n)))
+    }
+
+    pub fn is_realtime(&self) -> bool {
+        matches!(self, Self::SIGRT(_))
+    }
+
+    pub fn to_bits(self) -> i32 {
+        match self {
+            Self::SIGRT(n) => libc::SIGRTMIN() + n.get() as i32,
+            // `repr(i32)` enum (with fields) starts with its i32 discriminant,
+            // ref "primitive representation of enums with fields"
+            _ => unsafe { *(&self as *const Self).cast::<i32>() },
+        }
+    }
+
+    /// Name like "SIGTERM", "SIGRT" for all realtime signals (see `Display`
+    /// for "SIGRTMIN+n")
+    pub fn as_str(&self) -> &'static str {
+        self.into()
+    }
+
+    /// Parse like kill(1): "SIGTERM", "TERM", "term", "15", "SIGRTMIN+1",
+    /// "RTMAX-2"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+
+        if let Ok(signo) = name.parse::<i32>() {
+            return Self::try_from(signo).ok();
+        }
+
+        let name = name.to_ascii_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+
+        // unsigned decimal only ("+5".parse::<u8>() is accepted)
+        let offset = |n: &str| {
+            n.starts_with(|c: char| c.is_ascii_digit())
+                .then(|| n.parse::<u8>().ok())
+                .flatten()
+        };
+        let max = (libc::SIGRTMAX() - libc::SIGRTMIN()) as u8;
+
+        if let Some(rest) = name.strip_prefix("RTMIN") {
+            return match rest.strip_prefix('+') {
+                Some(n) => Self::rt(offset(n)?),
+                None if rest.is_empty() => Self::rt(0),
+                None => None,
+            };
+        }
+
+        if let Some(rest) = name.strip_prefix("RTMAX") {
+            return match rest.strip_prefix('-') {
+                Some(n) => Self::rt(max.checked_sub(offset(n)?)?),
+                None if rest.is_empty() => Self::rt(max),
+                None => None,
+            };
+        }
+
+        Self::iter()
+            .find(|sig| !sig.is_realtime() && &sig.as_str()[3..] == name)
+    }
+
+    /// Standard signals and every realtime signal, in order of signal
+    /// number
+    pub fn iter_all() -> impl Iterator<Item = Self> {
+        let mut standard = Self::iter()
+            .filter(|sig| !sig.is_realtime())
+            .collect::<Vec<_>>();
+
+        standard.sort_by_key(|sig| sig.to_bits());
+
+        standard.into_iter().chain(
+            (0..=libc::SIGRTMAX() - libc::SIGRTMIN())
+                .map(|n| Self::SIGRT(RtOffset(n as u8))),
+        )
+    }
+}
+
+impl Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SIGRT(RtOffset(0)) => write!(f, "SIGRTMIN"),
+            Self::SIGRT(n) => write!(f, "SIGRTMIN+{}", n.get()),
+            _ => write!(f, "{}", self.as_str()),
+        }
+    }
+}
+
+impl FromStr for Signal {
+    type Err = PosixError;
+
+    /// EINVAL if it isn't a known signal name (see `Signal::from_name`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or(PosixError::EINVAL)
+    }
+}
+
+impl TryFrom<i32> for Signal {
+    type Error = PosixError;
+
+    /// EINVAL if it isn't a known signal
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        if (libc::SIGRTMIN()..=libc::SIGRTMAX()).contains(&value) {
+            return Ok(Self::SIGRT(RtOffset(
+                (value - libc::SIGRTMIN()) as u8,
+            )));
+        }
+
+        Self::iter()
+            .find(|sig| !sig.is_realtime() && sig.to_bits() == value)
+            .ok_or(PosixError::EINVAL)
+    }
+}
+
+impl From<Signal> for i32 {
+    fn from(sig: Signal) -> Self {
+        sig.to_bits()
+    }
+}
+
 impl BitOr<Signal> for Signal {
     type Output = SignalSet;
 
@@ -161,13 +339,64 @@ impl SignalSet {
         Self(sigset)
     }
 
+    /// sigfillset, all signals
+    pub fn fill() -> Self {
+        let mut sigset: sigset_t = unsafe { zeroed() };
+
+        let ret = unsafe { libc::sigfillset(&mut sigset as *mut sigset_t) };
+
+        if ret != 0 {
+            panic!("{:?}", errno::last_os_error());
+        }
+
+        Self(sigset)
+    }
+
     pub const fn is_empty(&self) -> bool {
-        unsafe {
+        let bytes = unsafe {
             std::mem::transmute::<sigset_t, [u8; size_of::<sigset_t>()]>(
                 self.0,
             )
+        };
+
+        let mut i = 0;
+
+        while i < bytes.len() {
+            if bytes[i] != 0 {
+                return false;
+            }
+
+            i += 1;
+        }
+
+        true
+    }
+
+    /// True if signal was member of it
+    pub fn remove(&mut self, sig: Signal) -> bool {
+        let was_member = self.is_member(sig);
+
+        let ret = unsafe {
+            libc::sigdelset(&mut self.0 as *mut sigset_t, sig.to_bits() as _)
+        };
+
+        if ret == -1 {
+            panic!("{:?}", errno::last_os_error());
         }
-        .is_empty()
+
+        was_member
+    }
+
+    /// Members in order of signal number
+    pub fn iter(&self) -> impl Iterator<Item = Signal> + '_ {
+        self.raw_members()
+            .filter_map(|signo| Signal::try_from(signo).ok())
+    }
+
+    fn raw_members(&self) -> impl Iterator<Item = c_int> + '_ {
+        (1..=libc::SIGRTMAX()).filter(|signo| unsafe {
+            libc::sigismember(self.as_ptr(), *signo) == 1
+        })
     }
 
     pub fn is_member(&self, sig: Signal) -> bool {
@@ -218,6 +447,108 @@ impl SignalSet {
 
         Signal::try_from(sig).unwrap()
     }
+
+    /// sigwaitinfo, wait one of pending signals of the set (should be
+    /// blocked)
+    pub fn wait_info(&self) -> errno::Result<SigInfo> {
+        let mut info: siginfo_t = unsafe { zeroed() };
+
+        let ret = unsafe { libc::sigwaitinfo(self.as_ptr(), &mut info) };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(info.into())
+    }
+
+    /// sigtimedwait, `None` if no signal arrives before `timeout`
+    pub fn wait_timeout(
+        &self,
+        timeout: Duration,
+    ) -> errno::Result<Option<SigInfo>> {
+        let mut info: siginfo_t = unsafe { zeroed() };
+        let timeout = timespec {
+            tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as _,
+            tv_nsec: timeout.subsec_nanos() as _,
+        };
+
+        let ret =
+            unsafe { libc::sigtimedwait(self.as_ptr(), &mut info, &timeout) };
+
+        if ret == -1 {
+            return match errno::last_os_error() {
+                PosixError::EAGAIN => Ok(None),
+                err => Err(err),
+            };
+        }
+
+        Ok(Some(info.into()))
+    }
+}
+
+impl SignalBlockGuard {
+    pub fn new(set: SignalSet) -> errno::Result<Self> {
+        Ok(Self {
+            oldset: pthread_sigmask(SigMaskHow::BLOCK, set)?,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Mask before the guard is created
+    pub fn oldset(&self) -> SignalSet {
+        self.oldset
+    }
+}
+
+impl Drop for SignalBlockGuard {
+    fn drop(&mut self) {
+        // only fails on invalid `how`, never panic in drop
+        let _ = pthread_sigmask(SigMaskHow::SETMASK, self.oldset);
+    }
+}
+
+impl SigInfo {
+    /// `None` if it isn't a `Signal` known
+    pub fn signal(&self) -> Option<Signal> {
+        Signal::try_from(self.signo).ok()
+    }
+}
+
+impl From<siginfo_t> for SigInfo {
+    fn from(info: siginfo_t) -> Self {
+        // fields are of union in kernel, these are always readable
+        unsafe {
+            Self {
+                signo: info.si_signo,
+                code: info.si_code,
+                pid: info.si_pid(),
+                uid: info.si_uid(),
+                status: info.si_status(),
+                value: SigVal {
+                    ptr: info.si_value().sival_ptr,
+                },
+            }
+        }
+    }
+}
+
+impl From<c_int> for SigVal {
+    fn from(int: c_int) -> Self {
+        // zero high bits of `ptr`
+        let mut value = Self {
+            ptr: std::ptr::null_mut(),
+        };
+
+        value.int = int;
+        value
+    }
+}
+
+impl Debug for SigVal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", unsafe { self.ptr })
+    }
 }
 
 impl BitAnd<Signal> for &SignalSet {
@@ -237,9 +568,61 @@ impl BitOr<Signal> for SignalSet {
     }
 }
 
+/// Union
+impl BitOr for SignalSet {
+    type Output = Self;
+
+    fn bitor(mut self, rhs: Self) -> Self::Output {
+        for signo in rhs.raw_members() {
+            unsafe { libc::sigaddset(self.as_mut_ptr(), signo) };
+        }
+
+        self
+    }
+}
+
+/// Intersection
+impl BitAnd for SignalSet {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        let mut set = self;
+
+        for signo in self.raw_members() {
+            if unsafe { libc::sigismember(rhs.as_ptr(), signo) } != 1 {
+                unsafe { libc::sigdelset(set.as_mut_ptr(), signo) };
+            }
+        }
+
+        set
+    }
+}
+
+/// Difference
+impl Sub for SignalSet {
+    type Output = Self;
+
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        for signo in rhs.raw_members() {
+            unsafe { libc::sigdelset(self.as_mut_ptr(), signo) };
+        }
+
+        self
+    }
+}
+
+impl Sub<Signal> for SignalSet {
+    type Output = Self;
+
+    fn sub(mut self, rhs: Signal) -> Self::Output {
+        self.remove(rhs);
+        self
+    }
+}
+
 impl Debug for SignalSet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for (i, sig) in Signal::iter().filter(|sig| self & *sig).enumerate() {
+        for (i, sig) in self.iter().enumerate() {
             if i > 0 {
                 write!(f, " ")?;
             }
@@ -254,6 +637,27 @@ impl Debug for SignalSet {
 ////////////////////////////////////////////////////////////////////////////////
 //// Functions
 
+/// return old sigmask
+///
+/// Process signal mask, it's the same as `pthread_sigmask` (mask of the
+/// calling thread) on Linux, use it only in single-threaded process.
+pub fn sigprocmask(
+    how: SigMaskHow,
+    set: SignalSet,
+) -> errno::Result<SignalSet> {
+    let mut oldset = SignalSet::empty();
+
+    let ret = unsafe {
+        libc::sigprocmask(how.into(), set.as_ptr(), oldset.as_mut_ptr())
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(oldset)
+}
+
 /// return old sigmask
 pub fn pthread_sigmask(
     how: SigMaskHow,
@@ -272,6 +676,89 @@ pub fn pthread_sigmask(
     Ok(oldset)
 }
 
+/// Queue `sig` with `value` to process `pid` (receiver gets it from
+/// `SigInfo::value`)
+///
+/// Realtime signals are queued, standard ones aren't (merged if pending).
+pub fn sigqueue(pid: Pid, sig: Signal, value: SigVal) -> errno::Result<()> {
+    unsafe extern "C" {
+        fn sigqueue(pid: pid_t, sig: c_int, value: sigval) -> c_int;
+    }
+
+    let ret = unsafe {
+        sigqueue(
+            pid.as_raw(),
+            sig.to_bits(),
+            sigval {
+                sival_ptr: value.ptr,
+            },
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// sigpending, signals pending for delivery (blocked) of the calling
+/// thread and the process
+pub fn pending() -> errno::Result<SignalSet> {
+    let mut set = SignalSet::empty();
+
+    let ret = unsafe { libc::sigpending(set.as_mut_ptr()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(set)
+}
+
+/// Send `sig` to process `pid`
+pub fn kill(pid: Pid, sig: Signal) -> errno::Result<()> {
+    let ret = unsafe { libc::kill(pid.as_raw(), sig.to_bits()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Send `sig` to every process of process group `pgrp`
+pub fn killpg(pgrp: Pid, sig: Signal) -> errno::Result<()> {
+    let ret = unsafe { libc::killpg(pgrp.as_raw(), sig.to_bits()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Send `sig` to thread `tid` of thread group (process) `tgid`
+///
+/// ESRCH if `tid` doesn't belong to `tgid` (e.g. it has exited and the tid
+/// is reused).
+pub fn tgkill(tgid: Pid, tid: Pid, sig: Signal) -> errno::Result<()> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_tgkill,
+            tgid.as_raw(),
+            tid.as_raw(),
+            sig.to_bits(),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
 pub fn raise(
     sig: Signal,
 ) -> bool {
@@ -281,3 +768,183 @@ pub fn raise(
 
     ret == 0
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigwaitinfo() {
+        let set: SignalSet = Signal::SIGUSR2.into();
+
+        pthread_sigmask(SigMaskHow::BLOCK, set).unwrap();
+
+        assert!(
+            set.wait_timeout(Duration::from_millis(1))
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(raise(Signal::SIGUSR2));
+
+        let info = set.wait_info().unwrap();
+
+        assert_eq!(info.signal(), Some(Signal::SIGUSR2));
+        assert_eq!(info.pid, std::process::id() as pid_t);
+
+        assert!(raise(Signal::SIGUSR2));
+
+        let info = set.wait_timeout(Duration::ZERO).unwrap().unwrap();
+
+        assert_eq!(info.signo, Signal::SIGUSR2.to_bits());
+    }
+
+    #[test]
+    fn test_signal_set() {
+        let a = Signal::SIGINT | Signal::SIGTERM;
+        let b = Signal::SIGTERM | Signal::SIGHUP;
+
+        assert!(SignalSet::empty().is_empty());
+        assert!(!a.is_empty());
+        assert_eq!(
+            (a | b).iter().collect::<Vec<_>>(),
+            [Signal::SIGHUP, Signal::SIGINT, Signal::SIGTERM]
+        );
+        assert_eq!((a & b).iter().collect::<Vec<_>>(), [Signal::SIGTERM]);
+        assert_eq!(a - b, Signal::SIGINT.into());
+        assert!((a - Signal::SIGINT - Signal::SIGTERM).is_empty());
+
+        let mut all = SignalSet::fill();
+
+        assert!(all.is_member(Signal::SIGKILL));
+        assert!(all.remove(Signal::SIGKILL));
+        assert!(!all.remove(Signal::SIGKILL));
+        assert!(!(&all & Signal::SIGKILL));
+
+        // SIGALRM isn't used by other tests
+        let set: SignalSet = Signal::SIGALRM.into();
+
+        pthread_sigmask(SigMaskHow::BLOCK, set).unwrap();
+        assert!(!pending().unwrap().is_member(Signal::SIGALRM));
+
+        assert!(raise(Signal::SIGALRM));
+        assert!(pending().unwrap().is_member(Signal::SIGALRM));
+
+        assert_eq!(set.wait(), Signal::SIGALRM);
+    }
+
+    #[test]
+    fn test_signal_block_guard() {
+        let current =
+            || pthread_sigmask(SigMaskHow::BLOCK, SignalSet::empty());
+
+        let before = current().unwrap();
+
+        assert!(!before.is_member(Signal::SIGTTIN));
+
+        {
+            let guard = SignalBlockGuard::new(Signal::SIGTTIN.into()).unwrap();
+
+            assert_eq!(guard.oldset(), before);
+            assert!(current().unwrap().is_member(Signal::SIGTTIN));
+
+            let _inner =
+                SignalBlockGuard::new(Signal::SIGTTOU | Signal::SIGTTIN)
+                    .unwrap();
+
+            assert!(current().unwrap().is_member(Signal::SIGTTOU));
+        }
+
+        assert_eq!(current().unwrap(), before);
+
+        assert_eq!(
+            sigprocmask(SigMaskHow::UNBLOCK, SignalSet::empty()).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn test_signal_name() {
+        for name in ["SIGTERM", "TERM", "term", " 15"] {
+            assert_eq!(Signal::from_name(name), Some(Signal::SIGTERM));
+        }
+
+        assert_eq!("SIGRTMIN+1".parse::<Signal>().ok(), Signal::rt(1));
+        assert_eq!(
+            Signal::from_name("RTMAX").unwrap().to_bits(),
+            libc::SIGRTMAX()
+        );
+        assert_eq!(
+            Signal::from_name("RTMAX-1").unwrap().to_bits(),
+            libc::SIGRTMAX() - 1
+        );
+        assert_eq!(Signal::from_name("RTMIN-1"), None);
+        assert_eq!(Signal::from_name("SIGRTMIN+-5"), None);
+        assert_eq!(Signal::from_name("RTMIN++5"), None);
+        assert_eq!(Signal::from_name("RTMAX--1"), None);
+        assert_eq!(Signal::from_name("RTMAX-+1"), None);
+        assert_eq!(Signal::from_name("RTMIN+2147483647"), None);
+        assert_eq!(Signal::from_name("RTMAX-255"), None);
+        assert_eq!(Signal::from_name("SIGFOO"), None);
+        assert_eq!(Signal::from_name("0"), None);
+
+        assert_eq!(Signal::SIGKILL.as_str(), "SIGKILL");
+        assert_eq!(Signal::rt(0).unwrap().to_string(), "SIGRTMIN");
+        assert_eq!(Signal::rt(2).unwrap().to_string(), "SIGRTMIN+2");
+
+        let all = Signal::iter_all().collect::<Vec<_>>();
+
+        assert_eq!(all[0], Signal::SIGHUP);
+        assert!(all.is_sorted_by_key(|sig| sig.to_bits()));
+        assert_eq!(all.last().unwrap().to_bits(), libc::SIGRTMAX());
+
+        for sig in all {
+            assert_eq!(sig.to_string().parse(), Ok(sig));
+        }
+    }
+
+    #[test]
+    fn test_sigqueue() {
+        let rt = Signal::rt(2).unwrap();
+
+        assert!(Signal::rt(u8::MAX).is_none());
+        assert_eq!(Signal::try_from(rt.to_bits()), Ok(rt));
+        assert_eq!(Signal::try_from(15), Ok(Signal::SIGTERM));
+        assert_eq!(Signal::try_from(0), Err(PosixError::EINVAL));
+        assert_eq!(i32::from(Signal::SIGKILL), 9);
+        assert_eq!(Signal::SIGUSR1.to_bits(), libc::SIGUSR1);
+        assert_eq!(Signal::SIGWINCH.to_bits(), libc::SIGWINCH);
+        assert_eq!(rt.to_bits(), libc::SIGRTMIN() + 2);
+
+        assert_eq!(unsafe { SigVal::from(-1).int }, -1);
+
+        // process directed signal to self would be delivered to other test
+        // threads (not blocking it)
+        assert_eq!(
+            sigqueue(Pid::from_raw(pid_t::MAX), rt, 1.into()),
+            Err(PosixError::ESRCH)
+        );
+    }
+
+    #[test]
+    fn test_kill() {
+        let set: SignalSet = Signal::SIGWINCH.into();
+
+        pthread_sigmask(SigMaskHow::BLOCK, set).unwrap();
+
+        // thread directed, pending on this thread only
+        tgkill(Pid::this(), Pid::this_thread(), Signal::SIGWINCH).unwrap();
+
+        assert_eq!(set.wait(), Signal::SIGWINCH);
+
+        let nobody = Pid::from_raw(pid_t::MAX);
+
+        assert_eq!(kill(nobody, Signal::SIGTERM), Err(PosixError::ESRCH));
+        assert_eq!(killpg(nobody, Signal::SIGTERM), Err(PosixError::ESRCH));
+        assert_eq!(
+            tgkill(Pid::this(), nobody, Signal::SIGTERM),
+            Err(PosixError::ESRCH)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Accept signals through file descriptor instead of handler
+//!
+//! Signals of the mask are blocked (so not delivered as usual) and read
+//! as `SignalFdInfo` when fd is readable, e.g. registered into `Epoll`.
+//!
+//! Ref [signalfd(2)](https://man7.org/linux/man-pages/man2/signalfd.2.html)
+
+use std::{
+    ffi::c_int,
+    mem::{size_of, zeroed},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use libc::{SFD_CLOEXEC, SFD_NONBLOCK, pid_t, signalfd_siginfo, uid_t};
+
+use crate::{
+    errno::{self, PosixError},
+    signal::{SigMaskHow, Signal, SignalSet, pthread_sigmask},
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Useful fields of struct signalfd_siginfo
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignalFdInfo {
+    pub signo: c_int,
+    /// SI_XXX (e.g. SI_USER, SI_QUEUE) or signal specific CLD_XXX
+    pub code: c_int,
+    /// Sender pid
+    pub pid: pid_t,
+    /// Real uid of sender
+    pub uid: uid_t,
+    /// Exit status or signal (SIGCHLD)
+    pub status: c_int,
+    /// Value sent by `sigqueue`
+    pub int: c_int,
+    pub ptr: u64,
+}
+
+/// Non-blocking signalfd
+#[derive(Debug)]
+pub struct SignalFd {
+    fd: OwnedFd,
+    mask: SignalSet,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl SignalFdInfo {
+    /// `None` if it isn't a `Signal` known (e.g. realtime signal)
+    pub fn signal(&self) -> Option<Signal> {
+        Signal::try_from(self.signo).ok()
+    }
+}
+
+impl From<signalfd_siginfo> for SignalFdInfo {
+    fn from(info: signalfd_siginfo) -> Self {
+        Self {
+            signo: info.ssi_signo as c_int,
+            code: info.ssi_code,
+            pid: info.ssi_pid as pid_t,
+            uid: info.ssi_uid,
+            status: info.ssi_status,
+            int: info.ssi_int,
+            ptr: info.ssi_ptr,
+        }
+    }
+}
+
+impl SignalFd {
+    /// Block `mask` of the calling thread and create signalfd of it
+    ///
+    /// Signals stay blocked after it's dropped. Other threads should
+    /// block them too (e.g. block before spawning), or process directed
+    /// signals may be delivered there.
+    pub fn new(mask: SignalSet) -> errno::Result<Self> {
+        pthread_sigmask(SigMaskHow::BLOCK, mask)?;
+
+        let ret = unsafe {
+            libc::signalfd(-1, mask.as_ptr(), SFD_NONBLOCK | SFD_CLOEXEC)
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(ret) },
+            mask,
+        })
+    }
+
+    pub fn mask(&self) -> SignalSet {
+        self.mask
+    }
+
+    /// Replace mask of signalfd, `mask` is blocked too (old one isn't
+    /// unblocked)
+    pub fn set_mask(&mut self, mask: SignalSet) -> errno::Result<()> {
+        pthread_sigmask(SigMaskHow::BLOCK, mask)?;
+
+        let ret =
+            unsafe { libc::signalfd(self.fd.as_raw_fd(), mask.as_ptr(), 0) };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        self.mask = mask;
+
+        Ok(())
+    }
+
+    /// Dequeue one pending signal, `None` if there is no one
+    pub fn read(&self) -> errno::Result<Option<SignalFdInfo>> {
+        let mut info: signalfd_siginfo = unsafe { zeroed() };
+
+        let ret = unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                &mut info as *mut signalfd_siginfo as _,
+                size_of::<signalfd_siginfo>(),
+            )
+        };
+
+        if ret == -1 {
+            return match errno::last_os_error() {
+                PosixError::EAGAIN => Ok(None),
+                err => Err(err),
+            };
+        }
+
+        Ok(Some(info.into()))
+    }
+}
+
+impl Iterator for SignalFd {
+    type Item = errno::Result<SignalFdInfo>;
+
+    /// Drain pending signals
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+impl AsFd for SignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for SignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        epoll::{Epoll, EpollEvent, EpollFlag, EpollToken},
+        signal::raise,
+    };
+
+    #[test]
+    fn test_signalfd() {
+        let mut sfd = SignalFd::new(Signal::SIGUSR1.into()).unwrap();
+
+        assert_eq!(sfd.read(), Ok(None));
+
+        let mut epoll = Epoll::create().unwrap();
+        let mut events = [EpollEvent::default(); 1];
+
+        epoll
+            .insert(
+                sfd.as_fd(),
+                EpollEvent::with_token(
+                    EpollFlag::In.into(),
+                    EpollToken::Fd(sfd.as_raw_fd()),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        // blocked, pending on this thread
+        assert!(raise(Signal::SIGUSR1));
+
+        let fired = epoll.wait(&mut events, 1000).unwrap();
+
+        assert_eq!(fired.len(), 1);
+
+        let info = sfd.read().unwrap().unwrap();
+
+        assert_eq!(info.signal(), Some(Signal::SIGUSR1));
+        assert_eq!(info.pid, std::process::id() as pid_t);
+        assert!(sfd.next().is_none());
+
+        sfd.set_mask(Signal::SIGUSR1 | Signal::SIGUSR2).unwrap();
+
+        assert!(sfd.mask().is_member(Signal::SIGUSR2));
+    }
+}
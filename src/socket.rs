@@ -1,20 +1,41 @@
 //! Socket Address Family
 
+pub mod bpf;
+pub mod cmsg;
+pub mod errqueue;
+pub mod ip;
+pub mod packet;
+pub mod tcp;
+pub mod timestamp;
+pub mod xdp;
+
 use std::{
-    ffi::{c_int, c_void},
+    ffi::{OsStr, c_int, c_void},
     fmt::Debug,
-    mem::{transmute, transmute_copy},
-    net::{Ipv4Addr, Ipv6Addr},
-    ops::{BitAnd, BitOr},
-    os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    io::{IoSlice, IoSliceMut},
+    mem::{transmute, transmute_copy, zeroed},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown},
+    ops::{BitAnd, BitOr, BitOrAssign},
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
     ptr,
+    time::{Duration, Instant},
 };
 
-use derive_more::derive::{Deref, DerefMut};
+use derive_more::derive::{Deref, DerefMut, Display, Error};
 use int_enum::IntEnum;
 use libc::{
-    SOCK_CLOEXEC, SOCK_NONBLOCK, in_addr, pid_t, sa_family_t, size_t,
-    sockaddr, sockaddr_in, socklen_t,
+    AF_INET, AF_INET6, AF_NETLINK, AF_PACKET, AF_UNIX, ARPHRD_ETHER, IFNAMSIZ,
+    NETLINK_GENERIC, NETLINK_NETFILTER, NETLINK_SOCK_DIAG, O_NONBLOCK,
+    SHUT_RD, SHUT_RDWR, SHUT_WR, SO_BINDTODEVICE, SO_BINDTOIFINDEX, SO_ERROR,
+    SO_LINGER, SO_PASSCRED, SO_PEERCRED, SO_RCVTIMEO, SO_SNDTIMEO,
+    SOCK_CLOEXEC, SOCK_NONBLOCK, SOL_SOCKET, gid_t, in_addr, iovec, mmsghdr,
+    msghdr, pid_t, sa_family_t, size_t, sockaddr, sockaddr_in, sockaddr_ll,
+    sockaddr_storage, socklen_t, suseconds_t, time_t, timespec, timeval,
+    uid_t,
 };
 use m6tobytes::{derive_from_bits, derive_to_bits};
 use osimodel::{
@@ -25,11 +46,14 @@ use osimodel::{
         ip::ProtocolKind,
     },
 };
-use strum::EnumIter;
+use strum::{EnumIter, IntoEnumIterator};
 
 use crate::{
+    epoll::{Epoll, EpollData, EpollEvent, EpollFlag},
     errno::{self, PosixError},
     ether::EthTypeKind,
+    fcntl,
+    socket::cmsg::{CmsgBuffer, ControlMessageOwned},
 };
 
 
@@ -52,6 +76,8 @@ pub enum SaFamily {
     Inet = 2,
     /// AF_INET 10
     Inet6 = 10,
+    /// AF_NETLINK 16
+    Netlink = 16,
     /// AF_PACKET 17 (rx/tx raw packets at the Layer 2)
     Packet = 17,
 }
@@ -93,6 +119,17 @@ pub enum SockAddr {
     Netlink(SockAddrNL),
 }
 
+/// Error of parsing raw sockaddr
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SockAddrParseError {
+    #[display("null sockaddr")]
+    Null,
+    #[display("unsupported address family {_0}")]
+    UnsupportedFamily(#[error(not(source))] sa_family_t),
+    #[display("invalid address length {len} for family {family}")]
+    InvalidLength { family: sa_family_t, len: socklen_t },
+}
+
 /// Synonym libc::sockaddr_in
 #[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Hash)]
 #[repr(C)]
@@ -122,11 +159,17 @@ pub struct SockAddrIn6 {
 #[derive(Default, Clone, Copy, Eq, PartialEq, Hash, Deref)]
 pub struct InAddr6([u8; 16]);
 
+/// Synonym libc::sockaddr_un
+///
+/// Three kinds: pathname (NUL-terminated), abstract (leading NUL,
+/// length-delimited, linux only) and unnamed (no path).
 #[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
 #[repr(C)]
 pub struct SockAddrUn {
     pub family: SaFamily,
     pub path: [u8; 108],
+    /// used length of `sockaddr_un` (family included)
+    len: socklen_t,
 }
 
 ///
@@ -158,8 +201,9 @@ pub struct SockAddrNL {
     pub groups: u32,
 }
 
+/// sa_family_t of `struct sockaddr_nl` (AF_NETLINK)
 #[derive(Debug, Clone, Copy, Default)]
-#[repr(i32)]
+#[repr(u16)]
 pub enum SaNlFamily {
     #[default]
     NetlinkRoute = 16,
@@ -255,10 +299,16 @@ pub enum SocketProtocol {
     Eth(EthTypeKind),
     Zero,
     /// 0
-    NetlinkRoute
+    NetlinkRoute,
+    /// 16
+    NetlinkGeneric,
+    /// 4
+    NetlinkSockDiag,
+    /// 12
+    NetlinkNetfilter,
 }
 
-#[derive(Debug, EnumIter, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, Hash)]
 #[derive_to_bits(i32)]
 #[repr(i32)]
 pub enum Msg {
@@ -281,14 +331,200 @@ pub enum Msg {
     CLOEXEC = 0x40000000,
 }
 
-#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Default, PartialEq, Eq, Hash, Clone, Copy)]
 #[derive_to_bits(i32)]
 #[repr(transparent)]
 pub struct Flags(i32);
 
+/// Result of `recvmsg`
+#[derive(Debug)]
+pub struct RecvMsg {
+    /// received bytes
+    pub len: size_t,
+    /// msg_flags (`Msg::TRUNC`, `Msg::CTRUNC` ...)
+    pub flags: Flags,
+    /// decoded ancillary data
+    pub cmsgs: Vec<ControlMessageOwned>,
+    name: sockaddr_storage,
+    namelen: socklen_t,
+}
+
+/// Socket owning its fd, methods are thin wrappers of free functions
+#[derive(Debug)]
+pub struct Socket {
+    fd: OwnedFd,
+}
+
+/// Synonym libc::ucred (SCM_CREDENTIALS / SO_PEERCRED)
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[repr(C)]
+pub struct UCred {
+    pub pid: pid_t,
+    pub uid: uid_t,
+    pub gid: gid_t,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 //// Implementations
 
+impl RecvMsg {
+    /// source address (for unconnected socket)
+    pub fn addr(&self) -> Option<SockAddr> {
+        if self.namelen == 0 {
+            return None;
+        }
+
+        SockAddr::try_from_raw_parts(
+            &self.name as *const sockaddr_storage as *const sockaddr,
+            self.namelen,
+        )
+        .ok()
+    }
+}
+
+impl Socket {
+    pub fn new(
+        domain: AddressFamily,
+        socktype: SocketType,
+        extra_behavior: ExtraBehavior,
+        protocol: SocketProtocol,
+    ) -> errno::Result<Self> {
+        socket(domain, socktype, extra_behavior, protocol).map(Self::from)
+    }
+
+    pub fn bind(&self, addr: SockAddr) -> errno::Result<()> {
+        bind(self.as_fd(), addr)
+    }
+
+    pub fn connect(&self, addr: SockAddr) -> errno::Result<()> {
+        connect(self.as_fd(), addr)
+    }
+
+    pub fn connect_timeout(
+        &self,
+        addr: SockAddr,
+        timeout: Duration,
+    ) -> errno::Result<()> {
+        connect_timeout(self.as_fd(), addr, timeout)
+    }
+
+    pub fn listen(&self, backlog: c_int) -> errno::Result<()> {
+        listen(self.as_fd(), backlog)
+    }
+
+    /// Accepted socket is close-on-exec
+    pub fn accept(&self) -> errno::Result<(Self, Option<SockAddr>)> {
+        accept(self.as_fd(), ExtraBehavior::new().close_on_exec())
+            .map(|(fd, addr)| (fd.into(), addr))
+    }
+
+    pub fn send(&self, msg: &[u8], flags: Flags) -> errno::Result<size_t> {
+        send(self.as_fd(), msg, flags)
+    }
+
+    pub fn send_to(
+        &self,
+        msg: &[u8],
+        flags: Flags,
+        addr: SockAddr,
+    ) -> errno::Result<size_t> {
+        sendto(self.as_fd(), msg, flags, Some(addr))
+    }
+
+    pub fn recv(&self, buf: &mut [u8], flags: Flags) -> errno::Result<size_t> {
+        recv(self.as_fd(), buf, flags)
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> errno::Result<()> {
+        set_nonblocking(self.as_fd(), nonblocking)
+    }
+
+    pub fn local_addr(&self) -> errno::Result<SockAddr> {
+        getsockname(self.as_fd())
+    }
+
+    pub fn peer_addr(&self) -> errno::Result<SockAddr> {
+        getpeername(self.as_fd())
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> errno::Result<()> {
+        shutdown(self.as_fd(), how)
+    }
+
+    pub fn set_linger(&self, linger: Option<Duration>) -> errno::Result<()> {
+        set_linger(self.as_fd(), linger)
+    }
+
+    pub fn set_recv_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> errno::Result<()> {
+        set_recv_timeout(self.as_fd(), timeout)
+    }
+
+    pub fn set_send_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> errno::Result<()> {
+        set_send_timeout(self.as_fd(), timeout)
+    }
+
+    pub fn close_graceful(self, timeout: Duration) -> errno::Result<()> {
+        close_graceful(self.fd, timeout)
+    }
+
+    pub fn into_fd(self) -> OwnedFd {
+        self.fd
+    }
+}
+
+impl From<OwnedFd> for Socket {
+    fn from(fd: OwnedFd) -> Self {
+        Self { fd }
+    }
+}
+
+impl Into<OwnedFd> for Socket {
+    fn into(self) -> OwnedFd {
+        self.fd
+    }
+}
+
+impl AsFd for Socket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl UCred {
+    /// credentials of current process (the default one kernel would check)
+    pub fn current() -> Self {
+        unsafe {
+            Self {
+                pid: libc::getpid(),
+                uid: libc::getuid(),
+                gid: libc::getgid(),
+            }
+        }
+    }
+}
+
+impl Flags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn contains(&self, flag: Msg) -> bool {
+        self & flag
+    }
+}
+
 impl BitOr<Msg> for Flags {
     type Output = Self;
 
@@ -305,6 +541,46 @@ impl BitAnd<Msg> for &Flags {
     }
 }
 
+impl BitOrAssign<Msg> for Flags {
+    fn bitor_assign(&mut self, rhs: Msg) {
+        self.0 |= rhs.to_bits()
+    }
+}
+
+impl BitOr for Msg {
+    type Output = Flags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Flags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl Into<Flags> for Msg {
+    fn into(self) -> Flags {
+        Flags(self.to_bits())
+    }
+}
+
+impl FromIterator<Msg> for Flags {
+    fn from_iter<T: IntoIterator<Item = Msg>>(iter: T) -> Self {
+        iter.into_iter().fold(Self::new(), |acc, flag| acc | flag)
+    }
+}
+
+impl Debug for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut builder = &mut f.debug_list();
+
+        for flag in Msg::iter() {
+            if self & flag {
+                builder = builder.entry(&flag);
+            }
+        }
+
+        builder.finish()
+    }
+}
+
 impl SocketProtocol {
     /// to raw protocol value:
     ///
@@ -319,6 +595,9 @@ impl SocketProtocol {
             IP(protocol_spec) => protocol_spec.to_bits() as _,
             Eth(eth_type_spec) => eth_type_spec.to_bits().to_be() as _,
             Zero | NetlinkRoute => 0,
+            NetlinkGeneric => NETLINK_GENERIC,
+            NetlinkSockDiag => NETLINK_SOCK_DIAG,
+            NetlinkNetfilter => NETLINK_NETFILTER,
         }
     }
 
@@ -411,25 +690,101 @@ impl ExtraBehavior {
 }
 
 impl SockAddrUn {
+    const PATH_OFFSET: usize = size_of::<sa_family_t>();
+
+    /// Pathname address (ENAMETOOLONG if it can't fit with NUL)
+    pub fn new<P: AsRef<Path>>(path: P) -> errno::Result<Self> {
+        let path = path.as_ref().as_os_str().as_bytes();
+
+        if path.len() >= 108 {
+            Err(PosixError::ENAMETOOLONG)?
+        }
+
+        let mut it = Self::unnamed();
+
+        it.path[..path.len()].copy_from_slice(path);
+        it.len = (Self::PATH_OFFSET + path.len() + 1) as _;
+
+        Ok(it)
+    }
+
+    /// Abstract namespace address, `name` without the leading NUL
+    pub fn new_abstract(name: &[u8]) -> errno::Result<Self> {
+        if name.len() >= 108 {
+            Err(PosixError::ENAMETOOLONG)?
+        }
+
+        let mut it = Self::unnamed();
+
+        it.path[1..1 + name.len()].copy_from_slice(name);
+        it.len = (Self::PATH_OFFSET + 1 + name.len()) as _;
+
+        Ok(it)
+    }
+
+    /// Unnamed address (autobind when be bound)
+    pub fn unnamed() -> Self {
+        Self {
+            family: SaFamily::Local,
+            path: [0; 108],
+            len: Self::PATH_OFFSET as _,
+        }
+    }
+
+    fn path_len(&self) -> usize {
+        (self.len as usize).saturating_sub(Self::PATH_OFFSET)
+    }
+
+    pub fn is_unnamed(&self) -> bool {
+        self.path_len() == 0
+    }
+
+    pub fn as_pathname(&self) -> Option<&Path> {
+        let raw = &self.path[..self.path_len()];
+
+        if raw.is_empty() || raw[0] == 0 {
+            return None;
+        }
+
+        // trailing NUL is optional
+        let end = raw.iter().position(|b| *b == 0).unwrap_or(raw.len());
+
+        Some(Path::new(OsStr::from_bytes(&raw[..end])))
+    }
+
+    pub fn as_abstract(&self) -> Option<&[u8]> {
+        let raw = &self.path[..self.path_len()];
+
+        if raw.first() == Some(&0) {
+            Some(&raw[1..])
+        }
+        else {
+            None
+        }
+    }
+
     pub fn from_raw_parts(
         sockaddr: *const sockaddr,
         addrlen: socklen_t,
     ) -> Self {
-        assert!(addrlen as usize > size_of::<sa_family_t>());
+        assert!(addrlen as usize >= Self::PATH_OFFSET);
 
         let mut it = Self {
             family: unsafe {
                 SaFamily::from_bits(ptr::read(sockaddr as *const sa_family_t))
             },
-            path: [0; 108],
+            ..Self::unnamed()
         };
 
-        it.path.copy_from_slice(unsafe {
+        let path_len = (addrlen as usize - Self::PATH_OFFSET).min(108);
+
+        it.path[..path_len].copy_from_slice(unsafe {
             std::slice::from_raw_parts(
-                sockaddr.byte_add(size_of::<sa_family_t>()) as _,
-                addrlen as usize - size_of::<sa_family_t>(),
+                sockaddr.byte_add(Self::PATH_OFFSET) as _,
+                path_len,
             )
         });
+        it.len = (Self::PATH_OFFSET + path_len) as _;
 
         it
     }
@@ -447,6 +802,18 @@ impl SockAddrIn6 {
     }
 }
 
+impl From<Ipv6Addr> for SockAddrIn6 {
+    fn from(value: Ipv6Addr) -> Self {
+        Self {
+            family: SaFamily::Inet6,
+            port: 0.into(),
+            flowinfo: U32Be::new(0),
+            addr: InAddr6(value.octets()),
+            scope_id: 0,
+        }
+    }
+}
+
 impl Into<SockAddr> for SockAddrIn6 {
     fn into(self) -> SockAddr {
         SockAddr::Inet6(self)
@@ -457,6 +824,36 @@ impl SockAddrLL {
     pub unsafe fn from_raw(raw: *const sockaddr) -> Self {
         unsafe { core::ptr::read(raw as *const Self) }
     }
+
+    /// Destination of `sendto` on AF_PACKET socket (Ethernet interface)
+    pub fn for_send(ifindex: i32, protocol: EthType, dest: Mac) -> Self {
+        let mut ll = Self::raw_eth(ifindex, protocol);
+
+        ll.sll_halen = 6;
+        ll.sll_addr = *PhyAddr::from(dest);
+
+        unsafe { Self::from_raw(&ll as *const sockaddr_ll as _) }
+    }
+
+    /// Address of `bind` on AF_PACKET socket, only receive `protocol`
+    /// packets from `ifindex` (0 for any interface)
+    pub fn for_bind(ifindex: i32, protocol: EthType) -> Self {
+        let ll = Self::raw_eth(ifindex, protocol);
+
+        unsafe { Self::from_raw(&ll as *const sockaddr_ll as _) }
+    }
+
+    /// hatype and pkttype are ignored by kernel for sending and binding
+    fn raw_eth(ifindex: i32, protocol: EthType) -> sockaddr_ll {
+        let mut ll: sockaddr_ll = unsafe { zeroed() };
+
+        ll.sll_family = AF_PACKET as _;
+        ll.sll_protocol = protocol.to_ne().to_be();
+        ll.sll_ifindex = ifindex;
+        ll.sll_hatype = ARPHRD_ETHER;
+
+        ll
+    }
 }
 
 impl Into<SockAddr> for SockAddrLL {
@@ -508,41 +905,126 @@ impl SockAddr {
         match self {
             Inet(..) => size_of::<SockAddrIn>() as _,
             Inet6(..) => size_of::<SockAddrIn6>() as _,
-            Unix(..) => size_of::<SockAddrUn>() as _,
+            Unix(sock_addr_un) => sock_addr_un.len,
             Packet(..) => size_of::<SockAddrLL>() as _,
             #[cfg(target_os = "linux")]
             Netlink(..) => size_of::<SockAddrNL>() as _
         }
     }
 
+    /// IP address, v4-mapped IPv6 address is normalized to IPv4
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self.to_canonical() {
+            Self::Inet(sock_addr_in) => {
+                Some(IpAddr::V4(sock_addr_in.addr.into()))
+            }
+            Self::Inet6(sock_addr_in6) => {
+                Some(IpAddr::V6(sock_addr_in6.addr.into()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Convert v4-mapped (`::ffff:a.b.c.d`) IPv6 address to IPv4 address
+    /// (e.g. peer of dual-stack socket)
+    pub fn to_canonical(self) -> Self {
+        match self {
+            Self::Inet6(sock_addr_in6) => {
+                match Into::<Ipv6Addr>::into(sock_addr_in6.addr)
+                    .to_ipv4_mapped()
+                {
+                    Some(ip) => Self::Inet(SockAddrIn {
+                        port: sock_addr_in6.port,
+                        ..ip.into()
+                    }),
+                    None => self,
+                }
+            }
+            _ => self,
+        }
+    }
+
     /// just copy without heap owneship move (need manually free for sockaddr)
+    ///
+    /// Panic on unsupported family or bad length, see `try_from_raw_parts`
     pub fn from_raw_parts(
         sockaddr: *const sockaddr,
         addrlen: socklen_t,
     ) -> Self {
-        assert!(addrlen >= 2);
-        assert!(!sockaddr.is_null());
+        Self::try_from_raw_parts(sockaddr, addrlen).unwrap()
+    }
+
+    /// `addrlen` is the length filled by kernel (may be shorter than the
+    /// struct, e.g. AF_UNIX and AF_PACKET)
+    pub fn try_from_raw_parts(
+        sockaddr: *const sockaddr,
+        addrlen: socklen_t,
+    ) -> Result<Self, SockAddrParseError> {
+        if sockaddr.is_null() {
+            return Err(SockAddrParseError::Null);
+        }
+
+        if (addrlen as usize) < size_of::<sa_family_t>() {
+            return Err(SockAddrParseError::InvalidLength {
+                family: 0,
+                len: addrlen,
+            });
+        }
 
-        let family = unsafe { SaFamily::from_bits((*sockaddr).sa_family) };
+        // don't use SaFamily::from_bits for it may be unknown value
+        let family = unsafe { (*sockaddr).sa_family };
 
-        match family {
-            SaFamily::UnSpec => panic!("unsupported type sockaddr"),
-            SaFamily::Local => unsafe {
-                assert_eq!(addrlen as usize, size_of::<SockAddrLL>());
-                Self::Packet(SockAddrLL::from_raw(sockaddr))
-            },
-            SaFamily::Inet => unsafe {
-                assert_eq!(addrlen as usize, size_of::<SockAddrIn>());
-                Self::Inet(SockAddrIn::from_raw(sockaddr))
-            },
-            SaFamily::Inet6 => unsafe {
-                assert_eq!(addrlen as usize, size_of::<SockAddrIn6>());
-                Self::Inet6(SockAddrIn6::from_raw(sockaddr))
-            },
-            SaFamily::Packet => {
+        let check_len = |min: usize| {
+            if (addrlen as usize) < min {
+                Err(SockAddrParseError::InvalidLength {
+                    family,
+                    len: addrlen,
+                })
+            }
+            else {
+                Ok(())
+            }
+        };
+
+        Ok(match family as c_int {
+            AF_INET => {
+                check_len(size_of::<SockAddrIn>())?;
+                Self::Inet(unsafe { SockAddrIn::from_raw(sockaddr) })
+            }
+            AF_INET6 => {
+                check_len(size_of::<SockAddrIn6>())?;
+                Self::Inet6(unsafe { SockAddrIn6::from_raw(sockaddr) })
+            }
+            AF_UNIX => {
                 Self::Unix(SockAddrUn::from_raw_parts(sockaddr, addrlen))
             }
-        }
+            AF_PACKET => {
+                // sll_addr is truncated to sll_halen
+                check_len(size_of::<sockaddr_ll>() - 8)?;
+
+                let mut ll: sockaddr_ll = unsafe { zeroed() };
+
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        sockaddr as *const u8,
+                        &mut ll as *mut sockaddr_ll as *mut u8,
+                        (addrlen as usize).min(size_of::<sockaddr_ll>()),
+                    );
+                }
+
+                Self::Packet(unsafe {
+                    SockAddrLL::from_raw(&ll as *const sockaddr_ll as _)
+                })
+            }
+            #[cfg(target_os = "linux")]
+            AF_NETLINK => {
+                check_len(size_of::<SockAddrNL>())?;
+                Self::Netlink(unsafe {
+                    ptr::read_unaligned(sockaddr as *const SockAddrNL)
+                })
+            }
+            _ => Err(SockAddrParseError::UnsupportedFamily(family))?,
+        })
     }
 }
 
@@ -643,6 +1125,35 @@ pub fn socket(
     }
 }
 
+/// Create a pair of connected sockets (usually `AddressFamily::UNIX`)
+///
+/// Handy for local IPC or self-pipe style wakeups of an `Epoll` loop.
+pub fn socketpair(
+    domain: AddressFamily,
+    socktype: SocketType,
+    extra_behavior: ExtraBehavior,
+) -> errno::Result<(OwnedFd, OwnedFd)> {
+    let mut fds: [c_int; 2] = [-1; 2];
+
+    let ret = unsafe {
+        libc::socketpair(
+            Into::<c_int>::into(domain),
+            Into::<c_int>::into(socktype) | extra_behavior.to_bits() as c_int,
+            0,
+            fds.as_mut_ptr(),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())
+    }
+    else {
+        Ok(unsafe {
+            (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]))
+        })
+    }
+}
+
 pub fn bind(sock: BorrowedFd, addr: SockAddr) -> errno::Result<()> {
     let ret = unsafe {
         libc::bind(sock.as_raw_fd(), addr.as_ptr(), addr.address_len())
@@ -655,65 +1166,431 @@ pub fn bind(sock: BorrowedFd, addr: SockAddr) -> errno::Result<()> {
     Ok(())
 }
 
-pub fn recvfrom(
-    sock: BorrowedFd,
-    buf: &mut [u8],
-    flags: Flags,
-    mut addr: Option<SockAddr>,
-) -> errno::Result<size_t> {
-    let mut addrlen = addr.as_ref().map(|addr| addr.address_len());
-
+pub fn connect(sock: BorrowedFd, addr: SockAddr) -> errno::Result<()> {
     let ret = unsafe {
-        libc::recvfrom(
-            sock.as_raw_fd(),
-            buf.as_mut_ptr() as *mut c_void,
-            buf.len(),
-            flags.to_bits() as i32,
-            addr.as_mut()
-                .map(|addr| addr.as_mut_ptr())
-                .unwrap_or_default(),
-            addrlen
-                .as_mut()
-                .map(|addrlen_mut| core::ptr::from_mut(addrlen_mut))
-                .unwrap_or_default(),
-        )
+        libc::connect(sock.as_raw_fd(), addr.as_ptr(), addr.address_len())
     };
 
-    // for now impl, addrlen is just be ignored since we don't handle this complicated case.
-
-    if ret < 0 {
+    if ret == -1 {
         Err(errno::last_os_error())?
     }
 
-    Ok(ret as usize)
+    Ok(())
 }
 
-/// for non-blocking recvfrom all buf
-pub fn recvfrom_all(
+/// Connect with a bound of time (ETIMEDOUT), waiting by `Epoll`
+///
+/// Socket's blocking mode is kept as it was.
+pub fn connect_timeout(
     sock: BorrowedFd,
-    buf: &mut [u8],
-    flags: Flags,
-    addr: Option<SockAddr>,
-) -> errno::Result<size_t> {
-    let mut cnt = 0;
+    addr: SockAddr,
+    timeout: Duration,
+) -> errno::Result<()> {
+    let was_nonblocking = fcntl::get_fl_flags(sock)? & O_NONBLOCK != 0;
 
-    loop {
-        match recvfrom(sock, &mut buf[cnt..], flags, addr) {
-            Ok(0) => break,
-            Ok(n) => cnt += n,
-            Err(ref err) if matches!(err, PosixError::EAGAIN) => break,
-            Err(ref err) if matches!(err, PosixError::EINTR) => continue,
-            Err(err) => Err(err)?,
-        }
+    if !was_nonblocking {
+        set_nonblocking(sock, true)?;
     }
 
-    Ok(cnt)
+    let res = connect_nonblocking_wait(sock, addr, timeout);
+
+    // always restore, but connect error comes first
+    let restored = if !was_nonblocking {
+        set_nonblocking(sock, false)
+    }
+    else {
+        Ok(())
+    };
+
+    res.and(restored)
 }
 
-pub fn recv(
+fn connect_nonblocking_wait(
     sock: BorrowedFd,
-    buf: &mut [u8],
-    flags: Flags,
+    addr: SockAddr,
+    timeout: Duration,
+) -> errno::Result<()> {
+    match connect(sock, addr) {
+        Ok(()) => return Ok(()),
+        Err(PosixError::EINPROGRESS) => (),
+        Err(err) => Err(err)?,
+    }
+
+    let mut epoll = Epoll::create()?;
+
+    epoll.insert(
+        sock,
+        EpollEvent {
+            events: EpollFlag::Out.into(),
+            data: EpollData::new_as_fd(sock.as_raw_fd()),
+        },
+    )?;
+
+    // `None` if it's too far to be represented
+    let deadline = Instant::now().checked_add(timeout);
+    let mut events = [EpollEvent::default(); 1];
+
+    loop {
+        match epoll.pwait(&mut events, ms_until(deadline), None) {
+            Ok(fired) if fired.is_empty() => Err(PosixError::ETIMEDOUT)?,
+            Ok(_) => break,
+            Err(PosixError::EINTR) => continue,
+            Err(err) => Err(err)?,
+        }
+    }
+
+    match get_sock_error(sock)? {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+pub fn listen(sock: BorrowedFd, backlog: c_int) -> errno::Result<()> {
+    let ret = unsafe { libc::listen(sock.as_raw_fd(), backlog) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// accept4, with peer address if it's parsable
+pub fn accept(
+    sock: BorrowedFd,
+    extra_behavior: ExtraBehavior,
+) -> errno::Result<(OwnedFd, Option<SockAddr>)> {
+    let mut addr: sockaddr_storage = unsafe { zeroed() };
+    let mut addrlen = size_of::<sockaddr_storage>() as socklen_t;
+
+    let fd = unsafe {
+        libc::accept4(
+            sock.as_raw_fd(),
+            &mut addr as *mut sockaddr_storage as *mut sockaddr,
+            &mut addrlen,
+            extra_behavior.to_bits(),
+        )
+    };
+
+    if fd == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok((
+        unsafe { OwnedFd::from_raw_fd(fd) },
+        SockAddr::try_from_raw_parts(
+            &addr as *const sockaddr_storage as *const sockaddr,
+            addrlen,
+        )
+        .ok(),
+    ))
+}
+
+/// Local address of socket
+pub fn getsockname(sock: BorrowedFd) -> errno::Result<SockAddr> {
+    sockname_with(sock, libc::getsockname)
+}
+
+/// Address of connected peer
+pub fn getpeername(sock: BorrowedFd) -> errno::Result<SockAddr> {
+    sockname_with(sock, libc::getpeername)
+}
+
+fn sockname_with(
+    sock: BorrowedFd,
+    f: unsafe extern "C" fn(c_int, *mut sockaddr, *mut socklen_t) -> c_int,
+) -> errno::Result<SockAddr> {
+    let mut addr: sockaddr_storage = unsafe { zeroed() };
+    let mut addrlen = size_of::<sockaddr_storage>() as socklen_t;
+
+    let ret = unsafe {
+        f(
+            sock.as_raw_fd(),
+            &mut addr as *mut sockaddr_storage as *mut sockaddr,
+            &mut addrlen,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    SockAddr::try_from_raw_parts(
+        &addr as *const sockaddr_storage as *const sockaddr,
+        addrlen,
+    )
+    .map_err(|_| PosixError::EAFNOSUPPORT)
+}
+
+/// Toggle O_NONBLOCK
+pub fn set_nonblocking(
+    sock: BorrowedFd,
+    nonblocking: bool,
+) -> errno::Result<()> {
+    fcntl::set_nonblocking(sock, nonblocking)
+}
+
+pub fn shutdown(sock: BorrowedFd, how: Shutdown) -> errno::Result<()> {
+    let how = match how {
+        Shutdown::Read => SHUT_RD,
+        Shutdown::Write => SHUT_WR,
+        Shutdown::Both => SHUT_RDWR,
+    };
+
+    let ret = unsafe { libc::shutdown(sock.as_raw_fd(), how) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// SO_LINGER (seconds granularity, round up, saturate at c_int::MAX)
+///
+/// `Some(timeout)`: `close` blocks until pending data sent or timeout,
+/// `Some(ZERO)` resets (RST) the connection on `close`,
+/// `None`: (default) `close` returns immediately and sends in background.
+pub fn set_linger(
+    sock: BorrowedFd,
+    linger: Option<Duration>,
+) -> errno::Result<()> {
+    let linger = libc::linger {
+        l_onoff: linger.is_some() as c_int,
+        l_linger: linger
+            .map(|dur| {
+                dur.as_nanos()
+                    .div_ceil(1_000_000_000)
+                    .min(c_int::MAX as u128) as c_int
+            })
+            .unwrap_or(0),
+    };
+
+    setsockopt(sock, SOL_SOCKET, SO_LINGER, &linger)
+}
+
+pub fn get_linger(sock: BorrowedFd) -> errno::Result<Option<Duration>> {
+    let linger =
+        unsafe { getsockopt::<libc::linger>(sock, SOL_SOCKET, SO_LINGER)? };
+
+    Ok(if linger.l_onoff != 0 {
+        Some(Duration::from_secs(linger.l_linger as u64))
+    }
+    else {
+        None
+    })
+}
+
+/// SO_RCVTIMEO, blocking receive fails with EAGAIN after timeout
+///
+/// `None` blocks forever, zero timeout is EINVAL.
+pub fn set_recv_timeout(
+    sock: BorrowedFd,
+    timeout: Option<Duration>,
+) -> errno::Result<()> {
+    setsockopt(
+        sock,
+        SOL_SOCKET,
+        SO_RCVTIMEO,
+        &duration_to_timeval(timeout)?,
+    )
+}
+
+pub fn get_recv_timeout(sock: BorrowedFd) -> errno::Result<Option<Duration>> {
+    let tv = unsafe { getsockopt::<timeval>(sock, SOL_SOCKET, SO_RCVTIMEO)? };
+
+    Ok(timeval_to_duration(&tv))
+}
+
+/// SO_SNDTIMEO, like `set_recv_timeout` for blocking send
+pub fn set_send_timeout(
+    sock: BorrowedFd,
+    timeout: Option<Duration>,
+) -> errno::Result<()> {
+    setsockopt(
+        sock,
+        SOL_SOCKET,
+        SO_SNDTIMEO,
+        &duration_to_timeval(timeout)?,
+    )
+}
+
+pub fn get_send_timeout(sock: BorrowedFd) -> errno::Result<Option<Duration>> {
+    let tv = unsafe { getsockopt::<timeval>(sock, SOL_SOCKET, SO_SNDTIMEO)? };
+
+    Ok(timeval_to_duration(&tv))
+}
+
+/// zero timeval means no timeout, round up to us to not become zero
+fn duration_to_timeval(timeout: Option<Duration>) -> errno::Result<timeval> {
+    let Some(timeout) = timeout
+    else {
+        return Ok(timeval {
+            tv_sec: 0,
+            tv_usec: 0,
+        });
+    };
+
+    if timeout.is_zero() {
+        Err(PosixError::EINVAL)?
+    }
+
+    let usec = timeout.subsec_nanos().div_ceil(1000);
+
+    Ok(if usec == 1_000_000 {
+        timeval {
+            tv_sec: timeout.as_secs() as time_t + 1,
+            tv_usec: 0,
+        }
+    }
+    else {
+        timeval {
+            tv_sec: timeout.as_secs() as time_t,
+            tv_usec: usec as suseconds_t,
+        }
+    })
+}
+
+fn timeval_to_duration(tv: &timeval) -> Option<Duration> {
+    if tv.tv_sec == 0 && tv.tv_usec == 0 {
+        None
+    }
+    else {
+        Some(Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000))
+    }
+}
+
+/// Milliseconds until `deadline`, -1 for `None` (infinite), round up to not
+/// wake before it, saturate at c_int::MAX
+fn ms_until(deadline: Option<Instant>) -> c_int {
+    match deadline {
+        Some(deadline) => deadline
+            .saturating_duration_since(Instant::now())
+            .as_nanos()
+            .div_ceil(1_000_000)
+            .min(c_int::MAX as u128) as c_int,
+        None => -1,
+    }
+}
+
+/// Send FIN, discard what peer still sends until its FIN or timeout, then
+/// close.
+///
+/// Close with unread data would reset the connection (RST), peer may lose
+/// our last response.
+pub fn close_graceful(sock: OwnedFd, timeout: Duration) -> errno::Result<()> {
+    match shutdown(sock.as_fd(), Shutdown::Write) {
+        Ok(()) => (),
+        // never connected or reset by peer
+        Err(PosixError::ENOTCONN) => return Ok(()),
+        Err(err) => Err(err)?,
+    }
+
+    let mut epoll = Epoll::create()?;
+
+    epoll.insert(
+        sock.as_fd(),
+        EpollEvent {
+            events: EpollFlag::In.into(),
+            data: EpollData::new_as_fd(sock.as_raw_fd()),
+        },
+    )?;
+
+    // `None` if it's too far to be represented
+    let deadline = Instant::now().checked_add(timeout);
+    let mut events = [EpollEvent::default(); 1];
+    let mut buf = [0u8; 4096];
+
+    loop {
+        match recv(sock.as_fd(), &mut buf, Msg::DONTWAIT.into()) {
+            // peer FIN
+            Ok(0) => break,
+            Ok(_) => continue,
+            Err(PosixError::EAGAIN) => (),
+            Err(PosixError::EINTR) => continue,
+            // peer has gone anyway
+            Err(PosixError::ECONNRESET) => break,
+            Err(err) => Err(err)?,
+        }
+
+        let remain_ms = ms_until(deadline);
+
+        if remain_ms == 0 {
+            break;
+        }
+
+        match epoll.pwait(&mut events, remain_ms, None) {
+            Ok(fired) if fired.is_empty() => break,
+            Ok(_) => (),
+            Err(PosixError::EINTR) => (),
+            Err(err) => Err(err)?,
+        }
+    }
+
+    drop(sock);
+
+    Ok(())
+}
+
+pub fn recvfrom(
+    sock: BorrowedFd,
+    buf: &mut [u8],
+    flags: Flags,
+    mut addr: Option<SockAddr>,
+) -> errno::Result<size_t> {
+    let mut addrlen = addr.as_ref().map(|addr| addr.address_len());
+
+    let ret = unsafe {
+        libc::recvfrom(
+            sock.as_raw_fd(),
+            buf.as_mut_ptr() as *mut c_void,
+            buf.len(),
+            flags.to_bits() as i32,
+            addr.as_mut()
+                .map(|addr| addr.as_mut_ptr())
+                .unwrap_or_default(),
+            addrlen
+                .as_mut()
+                .map(|addrlen_mut| core::ptr::from_mut(addrlen_mut))
+                .unwrap_or_default(),
+        )
+    };
+
+    // for now impl, addrlen is just be ignored since we don't handle this complicated case.
+
+    if ret < 0 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as usize)
+}
+
+/// for non-blocking recvfrom all buf
+pub fn recvfrom_all(
+    sock: BorrowedFd,
+    buf: &mut [u8],
+    flags: Flags,
+    addr: Option<SockAddr>,
+) -> errno::Result<size_t> {
+    let mut cnt = 0;
+
+    loop {
+        match recvfrom(sock, &mut buf[cnt..], flags, addr) {
+            Ok(0) => break,
+            Ok(n) => cnt += n,
+            Err(ref err) if matches!(err, PosixError::EAGAIN) => break,
+            Err(ref err) if matches!(err, PosixError::EINTR) => continue,
+            Err(err) => Err(err)?,
+        }
+    }
+
+    Ok(cnt)
+}
+
+pub fn recv(
+    sock: BorrowedFd,
+    buf: &mut [u8],
+    flags: Flags,
 ) -> errno::Result<size_t> {
     let ret = unsafe {
         libc::recv(
@@ -795,6 +1672,214 @@ pub fn sendto_all(
     Ok(cnt)
 }
 
+/// Scatter/gather send with optional ancillary data
+pub fn sendmsg(
+    sock: BorrowedFd,
+    iov: &[IoSlice],
+    cmsg: Option<&CmsgBuffer>,
+    flags: Flags,
+    addr: Option<SockAddr>,
+) -> errno::Result<size_t> {
+    let mut msg: msghdr = unsafe { zeroed() };
+
+    if let Some(ref addr) = addr {
+        msg.msg_name = addr.as_ptr() as *mut c_void;
+        msg.msg_namelen = addr.address_len();
+    }
+
+    // IoSlice is guaranteed to be ABI compatible with iovec
+    msg.msg_iov = iov.as_ptr() as *mut iovec;
+    msg.msg_iovlen = iov.len() as _;
+
+    if let Some(cmsg) = cmsg.filter(|cmsg| !cmsg.is_empty()) {
+        msg.msg_control = cmsg.as_ptr() as *mut c_void;
+        msg.msg_controllen = cmsg.len() as _;
+    }
+
+    let ret = unsafe {
+        libc::sendmsg(sock.as_raw_fd(), &msg as *const msghdr, flags.to_bits())
+    };
+
+    if ret < 0 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as usize)
+}
+
+/// Scatter/gather receive, ancillary data would be decoded from `cmsg` space
+pub fn recvmsg(
+    sock: BorrowedFd,
+    iov: &mut [IoSliceMut],
+    mut cmsg: Option<&mut CmsgBuffer>,
+    flags: Flags,
+) -> errno::Result<RecvMsg> {
+    let mut name: sockaddr_storage = unsafe { zeroed() };
+    let mut msg: msghdr = unsafe { zeroed() };
+
+    msg.msg_name = &mut name as *mut sockaddr_storage as *mut c_void;
+    msg.msg_namelen = size_of::<sockaddr_storage>() as _;
+    msg.msg_iov = iov.as_mut_ptr() as *mut iovec;
+    msg.msg_iovlen = iov.len() as _;
+
+    if let Some(cmsg) = cmsg.as_mut() {
+        msg.msg_control = cmsg.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = cmsg.capacity() as _;
+    }
+
+    let ret = unsafe {
+        libc::recvmsg(
+            sock.as_raw_fd(),
+            &mut msg as *mut msghdr,
+            flags.to_bits(),
+        )
+    };
+
+    if ret < 0 {
+        Err(errno::last_os_error())?
+    }
+
+    let cmsgs = match cmsg {
+        Some(cmsg) => {
+            unsafe { cmsg.set_len(msg.msg_controllen as usize) };
+            cmsg.drain()
+        }
+        None => vec![],
+    };
+
+    Ok(RecvMsg {
+        len: ret as usize,
+        flags: Flags(msg.msg_flags),
+        cmsgs,
+        name,
+        namelen: msg.msg_namelen,
+    })
+}
+
+/// Batch version of `sendto`, each message is `(data, dest_addr)`
+///
+/// Return sent bytes of each message, it may be less than `msgs.len()`
+/// (partial batch)
+pub fn sendmmsg(
+    sock: BorrowedFd,
+    msgs: &[(&[u8], Option<SockAddr>)],
+    flags: Flags,
+) -> errno::Result<Vec<size_t>> {
+    let mut iovs: Vec<iovec> = msgs
+        .iter()
+        .map(|(buf, _)| iovec {
+            iov_base: buf.as_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        })
+        .collect();
+
+    let mut hdrs: Vec<mmsghdr> = msgs
+        .iter()
+        .zip(iovs.iter_mut())
+        .map(|((_, addr), iov)| {
+            let mut hdr: mmsghdr = unsafe { zeroed() };
+
+            if let Some(addr) = addr {
+                hdr.msg_hdr.msg_name = addr.as_ptr() as *mut c_void;
+                hdr.msg_hdr.msg_namelen = addr.address_len();
+            }
+
+            hdr.msg_hdr.msg_iov = iov as *mut iovec;
+            hdr.msg_hdr.msg_iovlen = 1;
+
+            hdr
+        })
+        .collect();
+
+    let ret = unsafe {
+        libc::sendmmsg(
+            sock.as_raw_fd(),
+            hdrs.as_mut_ptr(),
+            hdrs.len() as _,
+            flags.to_bits() as _,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(hdrs[..ret as usize]
+        .iter()
+        .map(|hdr| hdr.msg_len as size_t)
+        .collect())
+}
+
+/// Batch version of `recvfrom`, one datagram per buffer
+///
+/// timeout is only checked after each datagram received (see recvmmsg(2)
+/// BUGS), use `Msg::WAITFORNE` to return as soon as one datagram arrived.
+pub fn recvmmsg(
+    sock: BorrowedFd,
+    bufs: &mut [&mut [u8]],
+    flags: Flags,
+    timeout: Option<Duration>,
+) -> errno::Result<Vec<RecvMsg>> {
+    let mut names: Vec<sockaddr_storage> =
+        vec![unsafe { zeroed() }; bufs.len()];
+
+    let mut iovs: Vec<iovec> = bufs
+        .iter_mut()
+        .map(|buf| iovec {
+            iov_base: buf.as_mut_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        })
+        .collect();
+
+    let mut hdrs: Vec<mmsghdr> = names
+        .iter_mut()
+        .zip(iovs.iter_mut())
+        .map(|(name, iov)| {
+            let mut hdr: mmsghdr = unsafe { zeroed() };
+
+            hdr.msg_hdr.msg_name = name as *mut sockaddr_storage as _;
+            hdr.msg_hdr.msg_namelen = size_of::<sockaddr_storage>() as _;
+            hdr.msg_hdr.msg_iov = iov as *mut iovec;
+            hdr.msg_hdr.msg_iovlen = 1;
+
+            hdr
+        })
+        .collect();
+
+    let mut ts = timeout.map(|timeout| timespec {
+        tv_sec: timeout.as_secs() as _,
+        tv_nsec: timeout.subsec_nanos() as _,
+    });
+
+    let ret = unsafe {
+        libc::recvmmsg(
+            sock.as_raw_fd(),
+            hdrs.as_mut_ptr(),
+            hdrs.len() as _,
+            flags.to_bits() as _,
+            ts.as_mut()
+                .map(|ts| ts as *mut timespec)
+                .unwrap_or_default(),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(hdrs[..ret as usize]
+        .iter()
+        .zip(names)
+        .map(|(hdr, name)| RecvMsg {
+            len: hdr.msg_len as size_t,
+            flags: Flags(hdr.msg_hdr.msg_flags),
+            cmsgs: vec![],
+            name,
+            namelen: hdr.msg_hdr.msg_namelen,
+        })
+        .collect())
+}
+
 pub fn send(
     sock: BorrowedFd,
     msg: &[u8],
@@ -833,3 +1918,621 @@ pub fn send_all(
 
     Ok(cnt)
 }
+
+/// Set socket option with value of `T` (passed as raw bytes)
+pub fn setsockopt<T>(
+    sock: BorrowedFd,
+    level: c_int,
+    name: c_int,
+    val: &T,
+) -> errno::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            sock.as_raw_fd(),
+            level,
+            name,
+            val as *const T as *const c_void,
+            size_of::<T>() as socklen_t,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Get socket option as value of `T`
+///
+/// # Safety
+///
+/// `T` should be plain old data which is valid for any bit pattern
+/// (start from zeroed).
+pub unsafe fn getsockopt<T>(
+    sock: BorrowedFd,
+    level: c_int,
+    name: c_int,
+) -> errno::Result<T> {
+    let mut val: T = unsafe { zeroed() };
+    let mut len = size_of::<T>() as socklen_t;
+
+    let ret = unsafe {
+        libc::getsockopt(
+            sock.as_raw_fd(),
+            level,
+            name,
+            &mut val as *mut T as *mut c_void,
+            &mut len as *mut socklen_t,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(val)
+}
+
+/// SO_ERROR, take pending error of socket (be cleared after read)
+pub fn get_sock_error(sock: BorrowedFd) -> errno::Result<Option<PosixError>> {
+    let err = unsafe { getsockopt::<c_int>(sock, SOL_SOCKET, SO_ERROR)? };
+
+    Ok(if err == 0 {
+        None
+    }
+    else {
+        Some(PosixError::try_from(err).unwrap_or(PosixError::EIO))
+    })
+}
+
+/// SO_BINDTODEVICE, only send/receive packets via interface `ifname`
+/// (same name as `iface` functions), empty name for unbinding
+pub fn bind_to_device(sock: BorrowedFd, ifname: &str) -> errno::Result<()> {
+    // reserve NUL
+    if ifname.len() >= IFNAMSIZ {
+        Err(PosixError::EINVAL)?
+    }
+
+    let mut name = [0u8; IFNAMSIZ];
+
+    name[..ifname.len()].copy_from_slice(ifname.as_bytes());
+
+    setsockopt(sock, SOL_SOCKET, SO_BINDTODEVICE, &name)
+}
+
+/// Interface name bound by `bind_to_device`
+pub fn get_bound_device(sock: BorrowedFd) -> errno::Result<Option<String>> {
+    let name = unsafe {
+        getsockopt::<[u8; IFNAMSIZ]>(sock, SOL_SOCKET, SO_BINDTODEVICE)?
+    };
+
+    let len = name.iter().position(|b| *b == 0).unwrap_or(IFNAMSIZ);
+
+    Ok(if len == 0 {
+        None
+    }
+    else {
+        Some(String::from_utf8_lossy(&name[..len]).into_owned())
+    })
+}
+
+/// SO_BINDTOIFINDEX, like `bind_to_device` by `iface::get_ifindex`,
+/// 0 for unbinding
+pub fn bind_to_ifindex(sock: BorrowedFd, ifindex: c_int) -> errno::Result<()> {
+    setsockopt(sock, SOL_SOCKET, SO_BINDTOIFINDEX, &ifindex)
+}
+
+/// SO_PASSCRED, enable receiving `ControlMessageOwned::ScmCredentials`
+/// (AF_UNIX only)
+pub fn set_pass_cred(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, SOL_SOCKET, SO_PASSCRED, &(enable as c_int))
+}
+
+/// SO_PEERCRED, credentials of peer process at the time of `connect`
+/// or `socketpair` (AF_UNIX only)
+pub fn get_peer_cred(sock: BorrowedFd) -> errno::Result<UCred> {
+    unsafe { getsockopt(sock, SOL_SOCKET, SO_PEERCRED) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsFd;
+
+    use super::*;
+
+    #[test]
+    fn test_socketpair() {
+        let (a, b) = socketpair(
+            AddressFamily::UNIX,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+        )
+        .unwrap();
+
+        send_all(a.as_fd(), b"ping", Default::default()).unwrap();
+
+        let mut buf = [0u8; 4];
+
+        assert_eq!(recv(b.as_fd(), &mut buf, Default::default()).unwrap(), 4);
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[test]
+    fn test_scm_rights() {
+        use cmsg::ControlMessage;
+
+        let (a, b) = socketpair(
+            AddressFamily::UNIX,
+            SocketType::STREAM,
+            Default::default(),
+        )
+        .unwrap();
+        let (x, y) = socketpair(
+            AddressFamily::UNIX,
+            SocketType::STREAM,
+            Default::default(),
+        )
+        .unwrap();
+
+        let cmsg =
+            CmsgBuffer::encode(&[ControlMessage::ScmRights(&[x.as_fd()])]);
+
+        sendmsg(
+            a.as_fd(),
+            &[IoSlice::new(b"fd")],
+            Some(&cmsg),
+            Default::default(),
+            None,
+        )
+        .unwrap();
+
+        let mut buf = [0u8; 2];
+        let mut space = CmsgBuffer::for_fds(1);
+
+        let msg = recvmsg(
+            b.as_fd(),
+            &mut [IoSliceMut::new(&mut buf)],
+            Some(&mut space),
+            Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!(msg.len, 2);
+
+        let Some(ControlMessageOwned::ScmRights(fds)) = msg.cmsgs.first()
+        else {
+            panic!("{:?}", msg.cmsgs)
+        };
+
+        // the passed fd still connect to y
+        send_all(fds[0].as_fd(), b"ok", Default::default()).unwrap();
+        assert_eq!(recv(y.as_fd(), &mut buf, Default::default()).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_peer_cred() {
+        let (a, b) = socketpair(
+            AddressFamily::UNIX,
+            SocketType::STREAM,
+            Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!(get_peer_cred(a.as_fd()).unwrap(), UCred::current());
+
+        set_pass_cred(b.as_fd(), true).unwrap();
+
+        let cmsg =
+            CmsgBuffer::encode(&[cmsg::ControlMessage::ScmCredentials(
+                UCred::current(),
+            )]);
+
+        sendmsg(
+            a.as_fd(),
+            &[IoSlice::new(b"x")],
+            Some(&cmsg),
+            Default::default(),
+            None,
+        )
+        .unwrap();
+
+        let mut buf = [0u8; 1];
+        let mut space = CmsgBuffer::for_cred();
+
+        let msg = recvmsg(
+            b.as_fd(),
+            &mut [IoSliceMut::new(&mut buf)],
+            Some(&mut space),
+            Default::default(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            msg.cmsgs.first(),
+            Some(ControlMessageOwned::ScmCredentials(cred))
+                if *cred == UCred::current()
+        ));
+    }
+
+    #[test]
+    fn test_unix_addr() {
+        let addr = SockAddrUn::new_abstract(b"linuxc-test").unwrap();
+
+        assert_eq!(addr.as_abstract(), Some(&b"linuxc-test"[..]));
+        assert_eq!(Into::<SockAddr>::into(addr).address_len(), 2 + 1 + 11);
+
+        let sock = socket(
+            AddressFamily::UNIX,
+            SocketType::DGRAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        bind(sock.as_fd(), addr.into()).unwrap();
+
+        let path = std::env::temp_dir().join("linuxc-test.sock");
+        let _ = std::fs::remove_file(&path);
+
+        let addr = SockAddrUn::new(&path).unwrap();
+
+        assert_eq!(addr.as_pathname(), Some(path.as_path()));
+
+        let sock = socket(
+            AddressFamily::UNIX,
+            SocketType::DGRAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        bind(sock.as_fd(), addr.into()).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sockaddr_parse() {
+        let addr: SockAddr =
+            SockAddrUn::new_abstract(b"linuxc").unwrap().into();
+
+        assert!(matches!(
+            SockAddr::try_from_raw_parts(addr.as_ptr(), addr.address_len()),
+            Ok(SockAddr::Unix(un)) if un.as_abstract() == Some(&b"linuxc"[..])
+        ));
+
+        let mut raw: sockaddr_storage = unsafe { zeroed() };
+        raw.ss_family = 0xFFF;
+
+        assert_eq!(
+            SockAddr::try_from_raw_parts(
+                &raw as *const sockaddr_storage as _,
+                size_of::<sockaddr_storage>() as _
+            )
+            .unwrap_err(),
+            SockAddrParseError::UnsupportedFamily(0xFFF)
+        );
+
+        raw.ss_family = AF_INET as _;
+
+        assert!(matches!(
+            SockAddr::try_from_raw_parts(
+                &raw as *const sockaddr_storage as _,
+                8
+            ),
+            Err(SockAddrParseError::InvalidLength { .. })
+        ));
+    }
+
+    #[test]
+    fn test_socket_tcp() {
+        let listener = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        listener
+            .bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+        listener.listen(1).unwrap();
+
+        let addr = listener.local_addr().unwrap();
+
+        let client = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        client.connect(addr).unwrap();
+
+        let (server, peer) = listener.accept().unwrap();
+
+        assert!(matches!(peer, Some(SockAddr::Inet(..))));
+
+        client.send(b"ping", Default::default()).unwrap();
+
+        let mut buf = [0u8; 4];
+
+        server.recv(&mut buf, Default::default()).unwrap();
+        assert_eq!(&buf, b"ping");
+
+        server.set_nonblocking(true).unwrap();
+        assert!(matches!(
+            server.recv(&mut buf, Default::default()),
+            Err(PosixError::EAGAIN)
+        ));
+    }
+
+    #[test]
+    fn test_connect_timeout() {
+        let listener = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        listener
+            .bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+        listener.listen(1).unwrap();
+
+        let client = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        // far deadline doesn't overflow
+        client
+            .connect_timeout(listener.local_addr().unwrap(), Duration::MAX)
+            .unwrap();
+
+        // TEST-NET-1 is not routed, may also fail fast without route
+        let client = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        let res = client.connect_timeout(
+            SockAddrIn::from(Ipv4Addr::new(192, 0, 2, 1)).into(),
+            Duration::from_millis(100),
+        );
+
+        println!("{res:?}");
+        assert!(res.is_err());
+        // blocking mode is restored after failure
+        assert_eq!(
+            fcntl::get_fl_flags(client.as_fd()).unwrap() & O_NONBLOCK,
+            0
+        );
+    }
+
+    #[test]
+    fn test_bind_to_device() {
+        let sock = Socket::new(
+            AddressFamily::INET,
+            SocketType::DGRAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        assert_eq!(get_bound_device(sock.as_fd()).unwrap(), None);
+
+        // CAP_NET_RAW is needed before linux 5.7
+        if bind_to_device(sock.as_fd(), "lo").is_ok() {
+            assert_eq!(
+                get_bound_device(sock.as_fd()).unwrap().as_deref(),
+                Some("lo")
+            );
+        }
+
+        assert!(matches!(
+            bind_to_device(sock.as_fd(), "a-very-long-ifname"),
+            Err(PosixError::EINVAL)
+        ));
+    }
+
+    #[test]
+    fn test_msg_flags() {
+        let mut flags = Msg::DONTWAIT | Msg::NOSIGNAL;
+
+        assert!(flags.contains(Msg::DONTWAIT));
+        assert!(!flags.contains(Msg::PEEK));
+
+        flags |= Msg::PEEK;
+
+        assert_eq!(
+            flags,
+            [Msg::PEEK, Msg::DONTWAIT, Msg::NOSIGNAL]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(format!("{flags:?}"), "[PEEK, DONTWAIT, NOSIGNAL]");
+    }
+
+    #[test]
+    fn test_sockaddr_ll() {
+        let dest = Mac::from_bytes(&[0x02, 0, 0, 0, 0, 0x01]);
+        let ll = SockAddrLL::for_send(1, EthTypeKind::IPv4.into(), dest);
+
+        assert_eq!(ll.ifindex, 1);
+        assert_eq!(ll.halen, 6);
+        assert_eq!(&ll.addr[..], &[0x02, 0, 0, 0, 0, 0x01, 0, 0]);
+        assert_eq!(EthTypeKind::try_from(ll.protocol), Ok(EthTypeKind::IPv4));
+
+        let ll = SockAddrLL::for_bind(0, EthTypeKind::ALL.into());
+
+        assert_eq!(ll.halen, 0);
+        assert!(matches!(ll.pkttype, PktType::Host));
+
+        let SockAddr::Packet(parsed) = SockAddr::from_raw_parts(
+            SockAddr::Packet(ll).as_ptr(),
+            size_of::<SockAddrLL>() as _,
+        )
+        else {
+            unreachable!()
+        };
+
+        assert_eq!(parsed.ifindex, 0);
+    }
+
+    #[test]
+    fn test_close_graceful() {
+        let listener = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        listener
+            .bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+        listener.listen(1).unwrap();
+
+        let client = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        client.connect(listener.local_addr().unwrap()).unwrap();
+
+        let (server, _) = listener.accept().unwrap();
+
+        assert_eq!(get_linger(server.as_fd()).unwrap(), None);
+        server.set_linger(Some(Duration::from_secs(1))).unwrap();
+        assert_eq!(
+            get_linger(server.as_fd()).unwrap(),
+            Some(Duration::from_secs(1))
+        );
+        // round up
+        server.set_linger(Some(Duration::from_millis(1))).unwrap();
+        assert_eq!(
+            get_linger(server.as_fd()).unwrap(),
+            Some(Duration::from_secs(1))
+        );
+        server.set_linger(None).unwrap();
+
+        client.send(b"unread", Default::default()).unwrap();
+        server.close_graceful(Duration::from_millis(100)).unwrap();
+
+        // client sees our FIN (EOF) instead of RST
+        let mut buf = [0u8; 8];
+        assert_eq!(client.recv(&mut buf, Default::default()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sock_timeout() {
+        let sock = Socket::new(
+            AddressFamily::INET,
+            SocketType::DGRAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        sock.bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+
+        assert_eq!(get_recv_timeout(sock.as_fd()).unwrap(), None);
+        assert!(matches!(
+            sock.set_recv_timeout(Some(Duration::ZERO)),
+            Err(PosixError::EINVAL)
+        ));
+
+        sock.set_recv_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        sock.set_send_timeout(Some(Duration::from_nanos(1500)))
+            .unwrap();
+
+        // kernel keeps it as jiffies
+        assert_eq!(
+            get_recv_timeout(sock.as_fd()).unwrap(),
+            Some(Duration::from_millis(100))
+        );
+        assert!(
+            get_send_timeout(sock.as_fd()).unwrap().unwrap()
+                >= Duration::from_micros(2)
+        );
+
+        let start = Instant::now();
+        let mut buf = [0u8; 8];
+
+        assert!(matches!(
+            sock.recv(&mut buf, Default::default()),
+            Err(PosixError::EAGAIN)
+        ));
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    fn test_mmsg() {
+        let new_udp = || {
+            Socket::new(
+                AddressFamily::INET,
+                SocketType::DGRAM,
+                ExtraBehavior::new().close_on_exec(),
+                SocketProtocol::Zero,
+            )
+            .unwrap()
+        };
+
+        let receiver = new_udp();
+        receiver
+            .bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+        let dst = receiver.local_addr().unwrap();
+
+        let sender = new_udp();
+        sender
+            .bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+
+        let payloads: [&[u8]; 3] = [b"a", b"bb", b"ccc"];
+        let msgs: Vec<_> = payloads.iter().map(|p| (*p, Some(dst))).collect();
+
+        assert_eq!(
+            sendmmsg(sender.as_fd(), &msgs, Default::default()).unwrap(),
+            [1, 2, 3]
+        );
+
+        // one spare buffer, loopback delivers before sendmmsg returns
+        let mut bufs = [[0u8; 8]; 4];
+        let mut bufs: Vec<&mut [u8]> =
+            bufs.iter_mut().map(|buf| &mut buf[..]).collect();
+
+        let received =
+            recvmmsg(receiver.as_fd(), &mut bufs, Msg::DONTWAIT.into(), None)
+                .unwrap();
+
+        assert_eq!(received.len(), 3);
+
+        let src = format!("{:?}", sender.local_addr().unwrap());
+
+        for (i, msg) in received.iter().enumerate() {
+            assert_eq!(msg.len, payloads[i].len());
+            assert_eq!(&bufs[i][..msg.len], payloads[i]);
+            assert_eq!(format!("{:?}", msg.addr().unwrap()), src);
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! Classic BPF socket filter (SO_ATTACH_FILTER)
+//!
+//! Ref [filter](https://docs.kernel.org/networking/filter.html)
+
+use std::{ffi::c_int, net::Ipv4Addr, os::fd::BorrowedFd};
+
+use libc::{
+    SO_ATTACH_FILTER, SO_DETACH_FILTER, SO_LOCK_FILTER, SOL_SOCKET, sock_fprog,
+};
+
+use crate::{
+    errno::{self, PosixError},
+    ether::EthTypeKind,
+    socket::setsockopt,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/* Instruction classes */
+pub const BPF_LD: u16 = 0x00;
+pub const BPF_LDX: u16 = 0x01;
+pub const BPF_ST: u16 = 0x02;
+pub const BPF_STX: u16 = 0x03;
+pub const BPF_ALU: u16 = 0x04;
+pub const BPF_JMP: u16 = 0x05;
+pub const BPF_RET: u16 = 0x06;
+pub const BPF_MISC: u16 = 0x07;
+
+/* ld/ldx fields */
+pub const BPF_W: u16 = 0x00;
+pub const BPF_H: u16 = 0x08;
+pub const BPF_B: u16 = 0x10;
+pub const BPF_IMM: u16 = 0x00;
+pub const BPF_ABS: u16 = 0x20;
+pub const BPF_IND: u16 = 0x40;
+pub const BPF_MEM: u16 = 0x60;
+pub const BPF_LEN: u16 = 0x80;
+pub const BPF_MSH: u16 = 0xa0;
+
+/* alu/jmp fields */
+pub const BPF_ADD: u16 = 0x00;
+pub const BPF_SUB: u16 = 0x10;
+pub const BPF_MUL: u16 = 0x20;
+pub const BPF_DIV: u16 = 0x30;
+pub const BPF_OR: u16 = 0x40;
+pub const BPF_AND: u16 = 0x50;
+pub const BPF_LSH: u16 = 0x60;
+pub const BPF_RSH: u16 = 0x70;
+pub const BPF_NEG: u16 = 0x80;
+pub const BPF_MOD: u16 = 0x90;
+pub const BPF_XOR: u16 = 0xa0;
+
+pub const BPF_JA: u16 = 0x00;
+pub const BPF_JEQ: u16 = 0x10;
+pub const BPF_JGT: u16 = 0x20;
+pub const BPF_JGE: u16 = 0x30;
+pub const BPF_JSET: u16 = 0x40;
+pub const BPF_K: u16 = 0x00;
+pub const BPF_X: u16 = 0x08;
+
+/* ret fields */
+pub const BPF_A: u16 = 0x10;
+
+/* misc fields */
+pub const BPF_TAX: u16 = 0x00;
+pub const BPF_TXA: u16 = 0x80;
+
+/// Max accepted length returned by filters built by `FilterBuilder`
+pub const SNAPLEN_MAX: u32 = 0x40000;
+
+/* Ethernet frame offsets */
+const ETH_TYPE_OFF: u32 = 12;
+const ETH_HLEN: u32 = 14;
+const IPV4_FRAG_OFF: u32 = ETH_HLEN + 6;
+const IPV4_PROTO_OFF: u32 = ETH_HLEN + 9;
+const IPV4_SRC_OFF: u32 = ETH_HLEN + 12;
+const IPV4_DST_OFF: u32 = ETH_HLEN + 16;
+
+const IPPROTO_TCP: u32 = 6;
+const IPPROTO_UDP: u32 = 17;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Synonym libc::sock_filter, one classic BPF instruction
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[repr(C)]
+pub struct SockFilter {
+    pub code: u16,
+    /// jump offset if true
+    pub jt: u8,
+    /// jump offset if false
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// Build a filter program matching all of conditions (AND) on Ethernet
+/// frames (AF_PACKET SOCK_RAW)
+#[derive(Clone, Debug, Default)]
+pub struct FilterBuilder {
+    conds: Vec<Cond>,
+    snaplen: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Cond {
+    EtherType(u16),
+    /// IPv4 source or destination
+    Host(Ipv4Addr),
+    /// TCP/UDP over IPv4 source or destination port
+    Port(u16),
+}
+
+/// Instruction whose jump target of one branch is the final reject
+#[derive(Clone, Copy)]
+enum Emit {
+    Plain(SockFilter),
+    RejectIfTrue(SockFilter),
+    RejectIfFalse(SockFilter),
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl SockFilter {
+    /// C macro BPF_STMT
+    pub const fn stmt(code: u16, k: u32) -> Self {
+        Self {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    /// C macro BPF_JUMP
+    pub const fn jump(code: u16, k: u32, jt: u8, jf: u8) -> Self {
+        Self { code, jt, jf, k }
+    }
+}
+
+impl FilterBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ether_type(mut self, ety: EthTypeKind) -> Self {
+        self.conds.push(Cond::EtherType(ety.to_bits()));
+        self
+    }
+
+    /// IPv4 packet from or to `host`
+    pub fn host(mut self, host: Ipv4Addr) -> Self {
+        self.conds.push(Cond::Host(host));
+        self
+    }
+
+    /// Unfragmented TCP/UDP (IPv4) packet from or to `port`
+    pub fn port(mut self, port: u16) -> Self {
+        self.conds.push(Cond::Port(port));
+        self
+    }
+
+    /// Truncate accepted packets, default `SNAPLEN_MAX`
+    pub fn snaplen(mut self, snaplen: u32) -> Self {
+        self.snaplen = Some(snaplen);
+        self
+    }
+
+    /// EINVAL if the reject is too far (u8 offset) to jump to
+    pub fn build(&self) -> errno::Result<Vec<SockFilter>> {
+        let mut emits = vec![];
+
+        for cond in self.conds.iter() {
+            cond.emit(&mut emits);
+        }
+
+        // accept, reject
+        let reject = emits.len() + 1;
+
+        let mut prog = emits
+            .into_iter()
+            .enumerate()
+            .map(|(i, emit)| {
+                let off = || {
+                    u8::try_from(reject - i - 1)
+                        .map_err(|_| PosixError::EINVAL)
+                };
+
+                Ok(match emit {
+                    Emit::Plain(insn) => insn,
+                    Emit::RejectIfTrue(insn) => {
+                        SockFilter { jt: off()?, ..insn }
+                    }
+                    Emit::RejectIfFalse(insn) => {
+                        SockFilter { jf: off()?, ..insn }
+                    }
+                })
+            })
+            .collect::<errno::Result<Vec<_>>>()?;
+
+        prog.push(SockFilter::stmt(
+            BPF_RET | BPF_K,
+            self.snaplen.unwrap_or(SNAPLEN_MAX),
+        ));
+        prog.push(SockFilter::stmt(BPF_RET | BPF_K, 0));
+
+        Ok(prog)
+    }
+}
+
+impl Cond {
+    fn emit(&self, emits: &mut Vec<Emit>) {
+        use Emit::*;
+
+        match *self {
+            Self::EtherType(ety) => {
+                emits.push(Plain(ld_abs(BPF_H, ETH_TYPE_OFF)));
+                emits.push(RejectIfFalse(jeq(ety as u32, 0, 0)));
+            }
+            Self::Host(host) => {
+                Self::EtherType(EthTypeKind::IPv4.to_bits()).emit(emits);
+
+                let host = u32::from(host);
+
+                emits.push(Plain(ld_abs(BPF_W, IPV4_SRC_OFF)));
+                // matched: skip checking dst
+                emits.push(Plain(jeq(host, 2, 0)));
+                emits.push(Plain(ld_abs(BPF_W, IPV4_DST_OFF)));
+                emits.push(RejectIfFalse(jeq(host, 0, 0)));
+            }
+            Self::Port(port) => {
+                Self::EtherType(EthTypeKind::IPv4.to_bits()).emit(emits);
+
+                emits.push(Plain(ld_abs(BPF_B, IPV4_PROTO_OFF)));
+                emits.push(Plain(jeq(IPPROTO_TCP, 1, 0)));
+                emits.push(RejectIfFalse(jeq(IPPROTO_UDP, 0, 0)));
+
+                // non-first fragment has no L4 header
+                emits.push(Plain(ld_abs(BPF_H, IPV4_FRAG_OFF)));
+                emits.push(RejectIfTrue(SockFilter::jump(
+                    BPF_JMP | BPF_JSET | BPF_K,
+                    0x1FFF,
+                    0,
+                    0,
+                )));
+
+                // X = IP header length
+                emits.push(Plain(SockFilter::stmt(
+                    BPF_LDX | BPF_B | BPF_MSH,
+                    ETH_HLEN,
+                )));
+                emits.push(Plain(SockFilter::stmt(
+                    BPF_LD | BPF_H | BPF_IND,
+                    ETH_HLEN,
+                )));
+                emits.push(Plain(jeq(port as u32, 2, 0)));
+                emits.push(Plain(SockFilter::stmt(
+                    BPF_LD | BPF_H | BPF_IND,
+                    ETH_HLEN + 2,
+                )));
+                emits.push(RejectIfFalse(jeq(port as u32, 0, 0)));
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// SO_ATTACH_FILTER, replace the filter attached to socket
+pub fn attach_filter(
+    sock: BorrowedFd,
+    filter: &[SockFilter],
+) -> errno::Result<()> {
+    let prog = sock_fprog {
+        len: filter.len() as _,
+        filter: filter.as_ptr() as *mut libc::sock_filter,
+    };
+
+    setsockopt(sock, SOL_SOCKET, SO_ATTACH_FILTER, &prog)
+}
+
+/// SO_DETACH_FILTER
+pub fn detach_filter(sock: BorrowedFd) -> errno::Result<()> {
+    setsockopt(sock, SOL_SOCKET, SO_DETACH_FILTER, &(0 as c_int))
+}
+
+/// SO_LOCK_FILTER, prevent the attached filter from being changed
+pub fn lock_filter(sock: BorrowedFd) -> errno::Result<()> {
+    setsockopt(sock, SOL_SOCKET, SO_LOCK_FILTER, &(1 as c_int))
+}
+
+fn ld_abs(size: u16, off: u32) -> SockFilter {
+    SockFilter::stmt(BPF_LD | size | BPF_ABS, off)
+}
+
+fn jeq(k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter::jump(BPF_JMP | BPF_JEQ | BPF_K, k, jt, jf)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_filter() {
+        let prog = FilterBuilder::new()
+            .ether_type(EthTypeKind::ARP)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            prog,
+            [
+                SockFilter::stmt(BPF_LD | BPF_H | BPF_ABS, 12),
+                SockFilter::jump(BPF_JMP | BPF_JEQ | BPF_K, 0x0806, 0, 1),
+                SockFilter::stmt(BPF_RET | BPF_K, SNAPLEN_MAX),
+                SockFilter::stmt(BPF_RET | BPF_K, 0),
+            ]
+        );
+
+        let prog = FilterBuilder::new()
+            .host(Ipv4Addr::new(10, 0, 0, 1))
+            .port(53)
+            .build()
+            .unwrap();
+
+        // every jump stays in program
+        for (i, insn) in prog.iter().enumerate() {
+            if insn.code & 0x07 == BPF_JMP {
+                assert!(i + 1 + (insn.jt.max(insn.jf) as usize) < prog.len());
+            }
+        }
+
+        for insn in prog {
+            println!("{insn:?}");
+        }
+
+        // reject is out of u8 jump offset
+        let mut builder = FilterBuilder::new();
+
+        for _ in 0..300 {
+            builder = builder.ether_type(EthTypeKind::IPv4);
+        }
+
+        assert_eq!(builder.build(), Err(PosixError::EINVAL));
+
+        // the farthest jump just fits
+        let mut builder = FilterBuilder::new();
+
+        for _ in 0..128 {
+            builder = builder.ether_type(EthTypeKind::IPv4);
+        }
+
+        assert_eq!(builder.build().unwrap()[1].jf, 255);
+    }
+}
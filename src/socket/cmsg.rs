@@ -0,0 +1,451 @@
+//! Ancillary Data (Control Message) for `sendmsg`/`recvmsg`
+//!
+//! Ref [cmsg(3)](https://man7.org/linux/man-pages/man3/cmsg.3.html)
+
+use std::{
+    ffi::{c_int, c_uint},
+    os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    ptr,
+    time::SystemTime,
+};
+
+use libc::{
+    IP_RECVERR, IP_TOS, IP_TTL, IPPROTO_IP, IPPROTO_IPV6, IPV6_RECVERR,
+    SCM_CREDENTIALS, SCM_RIGHTS, SCM_TIMESTAMPING, SCM_TIMESTAMPNS,
+    SO_TIMESTAMP, SOL_SOCKET, cmsghdr, sock_extended_err, timespec, timeval,
+};
+use osimodel::network::ip::ToS;
+
+use crate::socket::{
+    UCred,
+    errqueue::SockExtendedErr,
+    timestamp::{Timestamps, timespec_to_system_time, timeval_to_system_time},
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Control message to be sent by `sendmsg`
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum ControlMessage<'a> {
+    /// SCM_RIGHTS (AF_UNIX only), pass file descriptors to peer
+    ScmRights(&'a [BorrowedFd<'a>]),
+    /// SCM_CREDENTIALS (AF_UNIX only), kernel verifies it unless privileged
+    ScmCredentials(UCred),
+}
+
+/// Control message decoded from `recvmsg`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ControlMessageOwned {
+    /// SCM_RIGHTS, fds installed into our process by kernel
+    ScmRights(Vec<OwnedFd>),
+    /// SCM_CREDENTIALS, need `set_pass_cred` on receiver
+    ScmCredentials(UCred),
+    /// SCM_TIMESTAMP (= SO_TIMESTAMP), us precision
+    Timestamp(SystemTime),
+    /// SCM_TIMESTAMPNS (= SO_TIMESTAMPNS)
+    TimestampNs(SystemTime),
+    /// SCM_TIMESTAMPING (= SO_TIMESTAMPING)
+    Timestamping(Timestamps),
+    /// IP_TTL, need `ip::set_recv_ttl`
+    IpTtl(u8),
+    /// IP_TOS, need `ip::set_recv_tos`
+    IpTos(ToS),
+    /// IP_RECVERR, read from error queue (`errqueue::recv_err`)
+    IpRecvErr(SockExtendedErr),
+    /// IPV6_RECVERR
+    Ipv6RecvErr(SockExtendedErr),
+    /// Unrecognized message (level, type, data)
+    Oth {
+        level: c_int,
+        ty: c_int,
+        data: Vec<u8>,
+    },
+}
+
+/// Ancillary data buffer, aligned for `cmsghdr`
+///
+/// Be used both to encode messages before `sendmsg` and as the receive space
+/// of `recvmsg`.
+#[derive(Debug, Clone, Default)]
+pub struct CmsgBuffer {
+    /// u64 backed for `size_t` alignment of `cmsghdr`
+    buf: Vec<u64>,
+    /// occupied bytes
+    len: usize,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl<'a> ControlMessage<'a> {
+    pub fn level(&self) -> c_int {
+        match self {
+            Self::ScmRights(..) | Self::ScmCredentials(..) => SOL_SOCKET,
+        }
+    }
+
+    pub fn ty(&self) -> c_int {
+        match self {
+            Self::ScmRights(..) => SCM_RIGHTS,
+            Self::ScmCredentials(..) => SCM_CREDENTIALS,
+        }
+    }
+
+    /// payload length (without cmsghdr)
+    pub fn data_len(&self) -> usize {
+        match self {
+            Self::ScmRights(fds) => fds.len() * size_of::<RawFd>(),
+            Self::ScmCredentials(..) => size_of::<UCred>(),
+        }
+    }
+
+    /// dst should be at least `data_len` bytes
+    unsafe fn write_data(&self, dst: *mut u8) {
+        match self {
+            Self::ScmRights(fds) => {
+                for (i, fd) in fds.iter().enumerate() {
+                    unsafe {
+                        ptr::write_unaligned(
+                            (dst as *mut RawFd).add(i),
+                            fd.as_raw_fd(),
+                        );
+                    }
+                }
+            }
+            Self::ScmCredentials(cred) => unsafe {
+                ptr::write_unaligned(dst as *mut UCred, *cred);
+            },
+        }
+    }
+}
+
+impl ControlMessageOwned {
+    /// Take ownership of resources (fds) carried by data
+    unsafe fn decode(level: c_int, ty: c_int, data: &[u8]) -> Self {
+        match (level, ty) {
+            (SOL_SOCKET, SCM_RIGHTS) => Self::ScmRights(
+                data.chunks_exact(size_of::<RawFd>())
+                    .map(|chunk| unsafe {
+                        OwnedFd::from_raw_fd(RawFd::from_ne_bytes(
+                            chunk.try_into().unwrap(),
+                        ))
+                    })
+                    .collect(),
+            ),
+            (SOL_SOCKET, SCM_CREDENTIALS)
+                if data.len() >= size_of::<UCred>() =>
+            {
+                Self::ScmCredentials(unsafe {
+                    ptr::read_unaligned(data.as_ptr() as *const UCred)
+                })
+            }
+            (SOL_SOCKET, SO_TIMESTAMP)
+                if data.len() >= size_of::<timeval>() =>
+            {
+                match timeval_to_system_time(&unsafe {
+                    ptr::read_unaligned(data.as_ptr() as *const timeval)
+                }) {
+                    Some(time) => Self::Timestamp(time),
+                    None => Self::oth(level, ty, data),
+                }
+            }
+            (SOL_SOCKET, SCM_TIMESTAMPNS)
+                if data.len() >= size_of::<timespec>() =>
+            {
+                match timespec_to_system_time(&unsafe {
+                    ptr::read_unaligned(data.as_ptr() as *const timespec)
+                }) {
+                    Some(time) => Self::TimestampNs(time),
+                    None => Self::oth(level, ty, data),
+                }
+            }
+            (SOL_SOCKET, SCM_TIMESTAMPING)
+                if data.len() >= size_of::<[timespec; 3]>() =>
+            {
+                Self::Timestamping(Timestamps::from_raw(&unsafe {
+                    ptr::read_unaligned(data.as_ptr() as *const [timespec; 3])
+                }))
+            }
+            (IPPROTO_IP, IP_TTL) if data.len() >= size_of::<c_int>() => {
+                Self::IpTtl(c_int::from_ne_bytes(
+                    data[..size_of::<c_int>()].try_into().unwrap(),
+                ) as u8)
+            }
+            // one byte
+            (IPPROTO_IP, IP_TOS) if !data.is_empty() => {
+                Self::IpTos(ToS::from_bits(data[0]))
+            }
+            (IPPROTO_IP, IP_RECVERR) | (IPPROTO_IPV6, IPV6_RECVERR)
+                if data.len() >= size_of::<sock_extended_err>() =>
+            {
+                let ee = SockExtendedErr::from_cmsg_data(data).unwrap();
+
+                if level == IPPROTO_IP {
+                    Self::IpRecvErr(ee)
+                }
+                else {
+                    Self::Ipv6RecvErr(ee)
+                }
+            }
+            _ => Self::oth(level, ty, data),
+        }
+    }
+
+    fn oth(level: c_int, ty: c_int, data: &[u8]) -> Self {
+        Self::Oth {
+            level,
+            ty,
+            data: data.to_vec(),
+        }
+    }
+}
+
+impl CmsgBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Empty buffer with at least `cap` bytes space (receive side)
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            buf: vec![0; cap.div_ceil(size_of::<u64>())],
+            len: 0,
+        }
+    }
+
+    /// Receive space for at most `n` fds
+    pub fn for_fds(n: usize) -> Self {
+        Self::with_capacity(cmsg_space(n * size_of::<RawFd>()))
+    }
+
+    /// Receive space for any one of timestamp messages
+    pub fn for_timestamp() -> Self {
+        Self::with_capacity(cmsg_space(size_of::<[timespec; 3]>()))
+    }
+
+    /// Receive space for SCM_CREDENTIALS
+    pub fn for_cred() -> Self {
+        Self::with_capacity(cmsg_space(size_of::<UCred>()))
+    }
+
+    pub fn encode(msgs: &[ControlMessage]) -> Self {
+        let mut it = Self::new();
+
+        for msg in msgs {
+            it.push(*msg);
+        }
+
+        it
+    }
+
+    /// Append message, grow buffer if needed
+    pub fn push(&mut self, msg: ControlMessage) {
+        let data_len = msg.data_len();
+        let space = cmsg_space(data_len);
+
+        if self.len + space > self.capacity() {
+            self.buf
+                .resize((self.len + space).div_ceil(size_of::<u64>()), 0);
+        }
+
+        unsafe {
+            let hdr = self.as_mut_ptr().add(self.len) as *mut cmsghdr;
+
+            // zero padding
+            ptr::write_bytes(hdr as *mut u8, 0, space);
+
+            (*hdr).cmsg_len = cmsg_len(data_len) as _;
+            (*hdr).cmsg_level = msg.level();
+            (*hdr).cmsg_type = msg.ty();
+
+            msg.write_data(libc::CMSG_DATA(hdr));
+        }
+
+        self.len += space;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len() * size_of::<u64>()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn as_ptr(&self) -> *const u8 {
+        self.buf.as_ptr() as _
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.buf.as_mut_ptr() as _
+    }
+
+    /// Be called after kernel filled `msg_controllen` bytes
+    pub(crate) unsafe fn set_len(&mut self, len: usize) {
+        debug_assert!(len <= self.capacity());
+
+        self.len = len;
+    }
+
+    /// Decode and take away all messages (buffer is cleared)
+    pub(crate) fn drain(&mut self) -> Vec<ControlMessageOwned> {
+        let mut msgs = vec![];
+        let mut offset = 0;
+
+        while offset + size_of::<cmsghdr>() <= self.len {
+            let hdr = unsafe {
+                ptr::read(self.as_ptr().add(offset) as *const cmsghdr)
+            };
+
+            let hdr_len = cmsg_len(0);
+
+            if (hdr.cmsg_len as usize) < hdr_len {
+                break;
+            }
+
+            // truncated (MSG_CTRUNC) message keep what we got
+            let data_len = (hdr.cmsg_len as usize - hdr_len)
+                .min(self.len - offset - hdr_len);
+
+            let data = unsafe {
+                std::slice::from_raw_parts(
+                    self.as_ptr().add(offset + hdr_len),
+                    data_len,
+                )
+            };
+
+            msgs.push(unsafe {
+                ControlMessageOwned::decode(
+                    hdr.cmsg_level,
+                    hdr.cmsg_type,
+                    data,
+                )
+            });
+
+            offset += cmsg_space(data_len);
+        }
+
+        self.clear();
+
+        msgs
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// C macro CMSG_SPACE
+pub const fn cmsg_space(data_len: usize) -> usize {
+    unsafe { libc::CMSG_SPACE(data_len as c_uint) as usize }
+}
+
+/// C macro CMSG_LEN
+pub const fn cmsg_len(data_len: usize) -> usize {
+    unsafe { libc::CMSG_LEN(data_len as c_uint) as usize }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        slice,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use super::*;
+
+    fn bytes_of<T>(val: &T) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(val as *const T as *const u8, size_of::<T>())
+        }
+    }
+
+    fn ts(tv_sec: i64, tv_nsec: i64) -> timespec {
+        timespec { tv_sec, tv_nsec }
+    }
+
+    #[test]
+    fn test_decode_timestamp() {
+        let decode = |ty, data: &[u8]| unsafe {
+            ControlMessageOwned::decode(SOL_SOCKET, ty, data)
+        };
+
+        let msg = decode(SCM_TIMESTAMPNS, bytes_of(&ts(1_700_000_000, 123)));
+        assert!(matches!(
+            msg,
+            ControlMessageOwned::TimestampNs(time)
+                if time == UNIX_EPOCH + Duration::new(1_700_000_000, 123)
+        ));
+
+        // -1s + 250ms
+        let msg = decode(SCM_TIMESTAMPNS, bytes_of(&ts(-1, 250_000_000)));
+        assert!(matches!(
+            msg,
+            ControlMessageOwned::TimestampNs(time)
+                if time == UNIX_EPOCH - Duration::from_millis(750)
+        ));
+
+        // invalid nsec is kept raw
+        let msg = decode(SCM_TIMESTAMPNS, bytes_of(&ts(1, 1_000_000_000)));
+        assert!(matches!(
+            msg,
+            ControlMessageOwned::Oth {
+                ty: SCM_TIMESTAMPNS,
+                ..
+            }
+        ));
+
+        let tv = timeval {
+            tv_sec: -2,
+            tv_usec: 500_000,
+        };
+        let msg = decode(SO_TIMESTAMP, bytes_of(&tv));
+        assert!(matches!(
+            msg,
+            ControlMessageOwned::Timestamp(time)
+                if time == UNIX_EPOCH - Duration::from_millis(1500)
+        ));
+
+        // usec overflow
+        let tv = timeval {
+            tv_sec: 1,
+            tv_usec: 5_000_000,
+        };
+        let msg = decode(SO_TIMESTAMP, bytes_of(&tv));
+        assert!(matches!(msg, ControlMessageOwned::Oth { .. }));
+
+        // software, deprecated, raw hardware
+        let raw = [ts(-1, 0), ts(0, 0), ts(5, 1)];
+        let msg = decode(SCM_TIMESTAMPING, bytes_of(&raw));
+        assert!(matches!(
+            msg,
+            ControlMessageOwned::Timestamping(Timestamps {
+                software: Some(sw),
+                hardware: Some(hw),
+            }) if sw == UNIX_EPOCH - Duration::from_secs(1)
+                && hw == UNIX_EPOCH + Duration::new(5, 1)
+        ));
+
+        let raw = [ts(1, 0), ts(0, 0), ts(0, 0)];
+        let msg = decode(SCM_TIMESTAMPING, bytes_of(&raw));
+        assert!(matches!(
+            msg,
+            ControlMessageOwned::Timestamping(Timestamps {
+                software: Some(_),
+                hardware: None,
+            })
+        ));
+    }
+}
@@ -0,0 +1,308 @@
+//! Socket error queue (IP_RECVERR, MSG_ERRQUEUE)
+//!
+//! Ref [ip(7)](https://man7.org/linux/man-pages/man7/ip.7.html),
+//! [msg_zerocopy](https://docs.kernel.org/networking/msg_zerocopy.html)
+
+use std::{ffi::c_int, io::IoSliceMut, os::fd::BorrowedFd, ptr};
+
+use libc::{
+    IP_RECVERR, IPPROTO_IP, IPPROTO_IPV6, IPV6_RECVERR, SO_EE_ORIGIN_ICMP,
+    SO_EE_ORIGIN_ICMP6, SO_EE_ORIGIN_LOCAL, SO_EE_ORIGIN_NONE,
+    SO_EE_ORIGIN_TXSTATUS, sock_extended_err, sockaddr, sockaddr_in6,
+    timespec,
+};
+
+use crate::{
+    errno::{self, PosixError},
+    socket::{
+        Flags, Msg, RecvMsg, SockAddr,
+        cmsg::{CmsgBuffer, ControlMessageOwned, cmsg_space},
+        recvmsg, setsockopt,
+    },
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+/// SO_EE_ORIGIN_ZEROCOPY (not in libc)
+const SO_EE_ORIGIN_ZEROCOPY: u8 = 5;
+
+/* ICMP type/code */
+const ICMP_DEST_UNREACH: u8 = 3;
+const ICMP_FRAG_NEEDED: u8 = 4;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const ICMPV6_DEST_UNREACH: u8 = 1;
+const ICMPV6_PKT_TOOBIG: u8 = 2;
+const ICMPV6_TIME_EXCEED: u8 = 3;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// SO_EE_ORIGIN_XXX
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ErrOrigin {
+    None,
+    /// Generated by local stack (e.g. EMSGSIZE with path MTU)
+    Local,
+    Icmp,
+    Icmp6,
+    /// SO_EE_ORIGIN_TXSTATUS (= SO_EE_ORIGIN_TIMESTAMPING)
+    TxStatus,
+    /// MSG_ZEROCOPY completion
+    ZeroCopy,
+    Oth(u8),
+}
+
+/// Decoded `struct sock_extended_err` with the offender address
+#[derive(Clone, Copy, Debug)]
+pub struct SockExtendedErr {
+    /// ee_errno, `None` for 0 (completion notification)
+    pub errno: Option<PosixError>,
+    pub origin: ErrOrigin,
+    /// ICMP type
+    pub ty: u8,
+    /// ICMP code
+    pub code: u8,
+    pub info: u32,
+    pub data: u32,
+    /// SO_EE_OFFENDER, the node that generated the error (ICMP sender)
+    pub offender: Option<SockAddr>,
+}
+
+/// Typed view of `SockExtendedErr`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ExtendedErrKind {
+    /// ICMP(v6) destination unreachable with its code
+    DestUnreachable {
+        code: u8,
+    },
+    /// ICMP fragmentation needed / ICMPv6 packet too big
+    PacketTooBig {
+        mtu: u32,
+    },
+    /// TTL (hop limit) exceeded in transit (traceroute)
+    TtlExceeded,
+    /// Raised by local stack, `info` is the path MTU for EMSGSIZE
+    Local {
+        info: u32,
+    },
+    /// MSG_ZEROCOPY sends of range `lo..=hi` were completed
+    ZeroCopy {
+        lo: u32,
+        hi: u32,
+    },
+    /// Tx timestamp, `key` is the SOF_TIMESTAMPING_OPT_ID counter
+    TxTimestamp {
+        key: u32,
+    },
+    Oth,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl From<u8> for ErrOrigin {
+    fn from(value: u8) -> Self {
+        match value {
+            SO_EE_ORIGIN_NONE => Self::None,
+            SO_EE_ORIGIN_LOCAL => Self::Local,
+            SO_EE_ORIGIN_ICMP => Self::Icmp,
+            SO_EE_ORIGIN_ICMP6 => Self::Icmp6,
+            SO_EE_ORIGIN_TXSTATUS => Self::TxStatus,
+            SO_EE_ORIGIN_ZEROCOPY => Self::ZeroCopy,
+            x => Self::Oth(x),
+        }
+    }
+}
+
+impl SockExtendedErr {
+    /// data: payload of IP_RECVERR/IPV6_RECVERR control message
+    pub(crate) fn from_cmsg_data(data: &[u8]) -> Option<Self> {
+        if data.len() < size_of::<sock_extended_err>() {
+            return None;
+        }
+
+        let ee = unsafe {
+            ptr::read_unaligned(data.as_ptr() as *const sock_extended_err)
+        };
+
+        let rem = &data[size_of::<sock_extended_err>()..];
+
+        // offender family is AF_UNSPEC if it isn't available
+        let offender = if rem.is_empty() {
+            None
+        }
+        else {
+            SockAddr::try_from_raw_parts(
+                rem.as_ptr() as *const sockaddr,
+                rem.len() as _,
+            )
+            .ok()
+        };
+
+        Some(Self {
+            errno: PosixError::try_from(ee.ee_errno as i32).ok(),
+            origin: ee.ee_origin.into(),
+            ty: ee.ee_type,
+            code: ee.ee_code,
+            info: ee.ee_info,
+            data: ee.ee_data,
+            offender,
+        })
+    }
+
+    pub fn kind(&self) -> ExtendedErrKind {
+        use ExtendedErrKind::*;
+
+        match (self.origin, self.ty, self.code) {
+            (ErrOrigin::Icmp, ICMP_DEST_UNREACH, ICMP_FRAG_NEEDED)
+            | (ErrOrigin::Icmp6, ICMPV6_PKT_TOOBIG, _) => {
+                PacketTooBig { mtu: self.info }
+            }
+            (ErrOrigin::Icmp, ICMP_DEST_UNREACH, code)
+            | (ErrOrigin::Icmp6, ICMPV6_DEST_UNREACH, code) => {
+                DestUnreachable { code }
+            }
+            (ErrOrigin::Icmp, ICMP_TIME_EXCEEDED, _)
+            | (ErrOrigin::Icmp6, ICMPV6_TIME_EXCEED, _) => TtlExceeded,
+            (ErrOrigin::Local, ..) => Local { info: self.info },
+            (ErrOrigin::ZeroCopy, ..) => ZeroCopy {
+                lo: self.info,
+                hi: self.data,
+            },
+            (ErrOrigin::TxStatus, ..) => TxTimestamp { key: self.data },
+            _ => Oth,
+        }
+    }
+}
+
+impl RecvMsg {
+    /// First IP_RECVERR/IPV6_RECVERR message
+    pub fn extended_err(&self) -> Option<&SockExtendedErr> {
+        self.cmsgs.iter().find_map(|cmsg| match cmsg {
+            ControlMessageOwned::IpRecvErr(ee)
+            | ControlMessageOwned::Ipv6RecvErr(ee) => Some(ee),
+            _ => None,
+        })
+    }
+}
+
+impl CmsgBuffer {
+    /// Receive space for an extended error and its offender (and a few
+    /// more messages like tx timestamp)
+    pub fn for_errqueue() -> Self {
+        Self::with_capacity(
+            cmsg_space(
+                size_of::<sock_extended_err>() + size_of::<sockaddr_in6>(),
+            ) + cmsg_space(size_of::<[timespec; 3]>()),
+        )
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// IP_RECVERR, queue extended errors (ICMP errors included) on error queue
+pub fn set_recv_err(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IP, IP_RECVERR, &(enable as c_int))
+}
+
+/// IPV6_RECVERR
+pub fn set_recv_err_v6(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IPV6, IPV6_RECVERR, &(enable as c_int))
+}
+
+/// Read one message from error queue (never block)
+///
+/// `buf` receives the original packet (may be truncated), return `None` if
+/// queue is empty.
+pub fn recv_err(
+    sock: BorrowedFd,
+    buf: &mut [u8],
+) -> errno::Result<Option<RecvMsg>> {
+    let mut cmsg = CmsgBuffer::for_errqueue();
+
+    match recvmsg(
+        sock,
+        &mut [IoSliceMut::new(buf)],
+        Some(&mut cmsg),
+        Flags::default() | Msg::ERRQUEUE | Msg::DONTWAIT,
+    ) {
+        Ok(msg) => Ok(Some(msg)),
+        Err(PosixError::EAGAIN) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Ipv4Addr,
+        os::fd::{AsFd, AsRawFd},
+    };
+
+    use libc::{POLLERR, pollfd};
+
+    use super::*;
+    use crate::socket::{
+        AddressFamily, ExtraBehavior, SockAddrIn, Socket, SocketProtocol,
+        SocketType,
+    };
+
+    #[test]
+    fn test_recv_err() {
+        let new_udp = || {
+            Socket::new(
+                AddressFamily::INET,
+                SocketType::DGRAM,
+                ExtraBehavior::new().close_on_exec(),
+                SocketProtocol::Zero,
+            )
+            .unwrap()
+        };
+
+        // take a free port and close it
+        let closed = new_udp();
+        closed
+            .bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+        let addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let sock = new_udp();
+
+        set_recv_err(sock.as_fd(), true).unwrap();
+
+        let mut buf = [0u8; 64];
+
+        assert!(recv_err(sock.as_fd(), &mut buf).unwrap().is_none());
+
+        sock.connect(addr).unwrap();
+        sock.send(b"hello", Default::default()).unwrap();
+
+        // ICMP port unreachable is queued as error (POLLERR)
+        let mut pfd = pollfd {
+            fd: sock.as_raw_fd(),
+            events: 0,
+            revents: 0,
+        };
+
+        assert_eq!(unsafe { libc::poll(&mut pfd, 1, 1000) }, 1);
+        assert_ne!(pfd.revents & POLLERR, 0);
+
+        let msg = recv_err(sock.as_fd(), &mut buf).unwrap().unwrap();
+        let ee = msg.extended_err().unwrap();
+
+        println!("{ee:?} {:?}", ee.kind());
+
+        assert_eq!(ee.origin, ErrOrigin::Icmp);
+        assert_eq!(ee.errno, Some(PosixError::ECONNREFUSED));
+        assert_eq!(ee.kind(), ExtendedErrKind::DestUnreachable { code: 3 });
+        // original payload
+        assert_eq!(&buf[..msg.len], b"hello");
+
+        assert!(recv_err(sock.as_fd(), &mut buf).unwrap().is_none());
+    }
+}
@@ -0,0 +1,444 @@
+//! IP level (IPPROTO_IP, IPPROTO_IPV6) socket options
+//!
+//! Ref [ip(7)](https://man7.org/linux/man-pages/man7/ip.7.html),
+//! [ipv6(7)](https://man7.org/linux/man-pages/man7/ipv6.7.html)
+
+use std::{
+    ffi::c_int,
+    net::{Ipv4Addr, Ipv6Addr},
+    os::fd::{AsFd, BorrowedFd},
+};
+
+use libc::{
+    IP_ADD_MEMBERSHIP, IP_ADD_SOURCE_MEMBERSHIP, IP_DROP_MEMBERSHIP,
+    IP_DROP_SOURCE_MEMBERSHIP, IP_MULTICAST_IF, IP_MULTICAST_LOOP,
+    IP_MULTICAST_TTL, IP_RECVTOS, IP_RECVTTL, IP_TOS, IP_TTL, IPPROTO_IP,
+    IPPROTO_IPV6, IPV6_ADD_MEMBERSHIP, IPV6_DROP_MEMBERSHIP,
+    IPV6_MULTICAST_HOPS, IPV6_MULTICAST_IF, IPV6_MULTICAST_LOOP,
+    IPV6_UNICAST_HOPS, IPV6_V6ONLY, in_addr, in6_addr, ip_mreq_source,
+    ip_mreqn, ipv6_mreq,
+};
+use osimodel::network::ip::ToS;
+
+use crate::{
+    errno,
+    socket::{
+        AddressFamily, ExtraBehavior, SockAddrIn6, Socket, SocketProtocol,
+        SocketType, getsockopt, setsockopt,
+    },
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// Multicast group membership
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum McastMembership {
+    /// IP_ADD_MEMBERSHIP (ip_mreqn), ifindex 0 for kernel choosing
+    V4 { group: Ipv4Addr, ifindex: c_int },
+    /// IP_ADD_SOURCE_MEMBERSHIP, source-specific multicast (SSM)
+    ///
+    /// iface: local address of the interface, `UNSPECIFIED` for any
+    SourceV4 {
+        group: Ipv4Addr,
+        source: Ipv4Addr,
+        iface: Ipv4Addr,
+    },
+    /// IPV6_ADD_MEMBERSHIP (= IPV6_JOIN_GROUP), ifindex 0 for kernel
+    /// choosing
+    V6 { group: Ipv6Addr, ifindex: u32 },
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl McastMembership {
+    fn apply(&self, sock: BorrowedFd, join: bool) -> errno::Result<()> {
+        match *self {
+            Self::V4 { group, ifindex } => setsockopt(
+                sock,
+                IPPROTO_IP,
+                if join {
+                    IP_ADD_MEMBERSHIP
+                }
+                else {
+                    IP_DROP_MEMBERSHIP
+                },
+                &ip_mreqn {
+                    imr_multiaddr: to_in_addr(group),
+                    imr_address: to_in_addr(Ipv4Addr::UNSPECIFIED),
+                    imr_ifindex: ifindex,
+                },
+            ),
+            Self::SourceV4 {
+                group,
+                source,
+                iface,
+            } => setsockopt(
+                sock,
+                IPPROTO_IP,
+                if join {
+                    IP_ADD_SOURCE_MEMBERSHIP
+                }
+                else {
+                    IP_DROP_SOURCE_MEMBERSHIP
+                },
+                &ip_mreq_source {
+                    imr_multiaddr: to_in_addr(group),
+                    imr_interface: to_in_addr(iface),
+                    imr_sourceaddr: to_in_addr(source),
+                },
+            ),
+            Self::V6 { group, ifindex } => setsockopt(
+                sock,
+                IPPROTO_IPV6,
+                if join {
+                    IPV6_ADD_MEMBERSHIP
+                }
+                else {
+                    IPV6_DROP_MEMBERSHIP
+                },
+                &ipv6_mreq {
+                    ipv6mr_multiaddr: to_in6_addr(group),
+                    ipv6mr_interface: ifindex as _,
+                },
+            ),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+pub fn join_multicast(
+    sock: BorrowedFd,
+    membership: McastMembership,
+) -> errno::Result<()> {
+    membership.apply(sock, true)
+}
+
+pub fn leave_multicast(
+    sock: BorrowedFd,
+    membership: McastMembership,
+) -> errno::Result<()> {
+    membership.apply(sock, false)
+}
+
+pub fn join_multicast_v4(
+    sock: BorrowedFd,
+    group: Ipv4Addr,
+    ifindex: c_int,
+) -> errno::Result<()> {
+    join_multicast(sock, McastMembership::V4 { group, ifindex })
+}
+
+pub fn join_multicast_v6(
+    sock: BorrowedFd,
+    group: Ipv6Addr,
+    ifindex: u32,
+) -> errno::Result<()> {
+    join_multicast(sock, McastMembership::V6 { group, ifindex })
+}
+
+/// IP_MULTICAST_TTL (default 1, stay in local network)
+pub fn set_multicast_ttl(sock: BorrowedFd, ttl: u8) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IP, IP_MULTICAST_TTL, &(ttl as c_int))
+}
+
+/// IPV6_MULTICAST_HOPS, -1 for route default
+pub fn set_multicast_hops_v6(
+    sock: BorrowedFd,
+    hops: c_int,
+) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IPV6, IPV6_MULTICAST_HOPS, &hops)
+}
+
+/// IP_MULTICAST_LOOP, whether sent datagrams are looped back to local
+/// sockets (default true)
+pub fn set_multicast_loop(
+    sock: BorrowedFd,
+    enable: bool,
+) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IP, IP_MULTICAST_LOOP, &(enable as c_int))
+}
+
+/// IPV6_MULTICAST_LOOP
+pub fn set_multicast_loop_v6(
+    sock: BorrowedFd,
+    enable: bool,
+) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IPV6, IPV6_MULTICAST_LOOP, &(enable as c_int))
+}
+
+/// IP_MULTICAST_IF, outgoing interface of multicast by ifindex
+pub fn set_multicast_if_v4(
+    sock: BorrowedFd,
+    ifindex: c_int,
+) -> errno::Result<()> {
+    setsockopt(
+        sock,
+        IPPROTO_IP,
+        IP_MULTICAST_IF,
+        &ip_mreqn {
+            imr_multiaddr: to_in_addr(Ipv4Addr::UNSPECIFIED),
+            imr_address: to_in_addr(Ipv4Addr::UNSPECIFIED),
+            imr_ifindex: ifindex,
+        },
+    )
+}
+
+/// IPV6_MULTICAST_IF
+pub fn set_multicast_if_v6(
+    sock: BorrowedFd,
+    ifindex: u32,
+) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IPV6, IPV6_MULTICAST_IF, &(ifindex as c_int))
+}
+
+/// IP_TTL of outgoing unicast packets
+pub fn set_ttl(sock: BorrowedFd, ttl: u8) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IP, IP_TTL, &(ttl as c_int))
+}
+
+pub fn get_ttl(sock: BorrowedFd) -> errno::Result<u8> {
+    unsafe { getsockopt::<c_int>(sock, IPPROTO_IP, IP_TTL) }.map(|v| v as u8)
+}
+
+/// IP_TOS (DSCP + ECN) of outgoing packets
+pub fn set_tos(sock: BorrowedFd, tos: ToS) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IP, IP_TOS, &(tos.to_bits() as c_int))
+}
+
+pub fn get_tos(sock: BorrowedFd) -> errno::Result<ToS> {
+    unsafe { getsockopt::<c_int>(sock, IPPROTO_IP, IP_TOS) }
+        .map(|v| ToS::from_bits(v as u8))
+}
+
+/// IPV6_UNICAST_HOPS, -1 for route default
+pub fn set_unicast_hops_v6(
+    sock: BorrowedFd,
+    hops: c_int,
+) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IPV6, IPV6_UNICAST_HOPS, &hops)
+}
+
+pub fn get_unicast_hops_v6(sock: BorrowedFd) -> errno::Result<c_int> {
+    unsafe { getsockopt(sock, IPPROTO_IPV6, IPV6_UNICAST_HOPS) }
+}
+
+/// IP_RECVTTL, receive `ControlMessageOwned::IpTtl`
+pub fn set_recv_ttl(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IP, IP_RECVTTL, &(enable as c_int))
+}
+
+/// IP_RECVTOS, receive `ControlMessageOwned::IpTos`
+pub fn set_recv_tos(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IP, IP_RECVTOS, &(enable as c_int))
+}
+
+/// IPV6_V6ONLY, restrict AF_INET6 socket to IPv6 only (default by sysctl
+/// `net.ipv6.bindv6only`, usually false)
+pub fn set_ipv6_only(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_IPV6, IPV6_V6ONLY, &(enable as c_int))
+}
+
+pub fn get_ipv6_only(sock: BorrowedFd) -> errno::Result<bool> {
+    unsafe { getsockopt::<c_int>(sock, IPPROTO_IPV6, IPV6_V6ONLY) }
+        .map(|v| v != 0)
+}
+
+/// TCP listener on `[::]:port` accepting both IPv4 and IPv6 clients
+///
+/// IPv4 peers come as v4-mapped address, see `SockAddr::to_canonical`.
+pub fn listen_dual_stack(port: u16, backlog: c_int) -> errno::Result<Socket> {
+    let sock = Socket::new(
+        AddressFamily::INET6,
+        SocketType::STREAM,
+        ExtraBehavior::new().close_on_exec(),
+        SocketProtocol::Zero,
+    )?;
+
+    set_ipv6_only(sock.as_fd(), false)?;
+
+    sock.bind(
+        SockAddrIn6 {
+            port: port.into(),
+            ..Ipv6Addr::UNSPECIFIED.into()
+        }
+        .into(),
+    )?;
+    sock.listen(backlog)?;
+
+    Ok(sock)
+}
+
+pub(crate) fn to_in_addr(ip: Ipv4Addr) -> in_addr {
+    in_addr {
+        s_addr: u32::from_ne_bytes(ip.octets()),
+    }
+}
+
+pub(crate) fn to_in6_addr(ip: Ipv6Addr) -> in6_addr {
+    in6_addr {
+        s6_addr: ip.octets(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{io::IoSliceMut, net::IpAddr};
+
+    use super::*;
+    use crate::{
+        iface::{get_ifindex, get_mcast_groups},
+        socket::{
+            SockAddr, SockAddrIn,
+            cmsg::{CmsgBuffer, ControlMessageOwned},
+            recvmsg,
+        },
+    };
+
+    #[test]
+    fn test_recv_ttl() {
+        let udp = || {
+            Socket::new(
+                AddressFamily::INET,
+                SocketType::DGRAM,
+                ExtraBehavior::new().close_on_exec(),
+                SocketProtocol::Zero,
+            )
+            .unwrap()
+        };
+
+        let (rx, tx) = (udp(), udp());
+
+        rx.bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+        set_recv_ttl(rx.as_fd(), true).unwrap();
+        set_recv_tos(rx.as_fd(), true).unwrap();
+
+        set_ttl(tx.as_fd(), 42).unwrap();
+        assert_eq!(get_ttl(tx.as_fd()).unwrap(), 42);
+
+        tx.send_to(b"ping", Default::default(), rx.local_addr().unwrap())
+            .unwrap();
+
+        let mut buf = [0u8; 4];
+        let mut cmsg = CmsgBuffer::with_capacity(64);
+
+        let msg = recvmsg(
+            rx.as_fd(),
+            &mut [IoSliceMut::new(&mut buf)],
+            Some(&mut cmsg),
+            Default::default(),
+        )
+        .unwrap();
+
+        assert!(
+            msg.cmsgs
+                .iter()
+                .any(|cmsg| matches!(cmsg, ControlMessageOwned::IpTtl(42)))
+        );
+        assert!(
+            msg.cmsgs
+                .iter()
+                .any(|cmsg| matches!(cmsg, ControlMessageOwned::IpTos(..)))
+        );
+    }
+
+    #[test]
+    fn test_dual_stack() {
+        let listener = listen_dual_stack(0, 1).unwrap();
+
+        assert!(!get_ipv6_only(listener.as_fd()).unwrap());
+
+        let SockAddr::Inet6(local) = listener.local_addr().unwrap()
+        else {
+            unreachable!()
+        };
+
+        let client = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        client
+            .connect(
+                SockAddrIn {
+                    port: local.port,
+                    ..Ipv4Addr::LOCALHOST.into()
+                }
+                .into(),
+            )
+            .unwrap();
+
+        let (_server, peer) = listener.accept().unwrap();
+        let peer = peer.unwrap();
+
+        assert!(matches!(peer, SockAddr::Inet6(..)));
+        assert!(matches!(peer.to_canonical(), SockAddr::Inet(..)));
+        assert_eq!(peer.ip(), Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    }
+
+    #[test]
+    fn test_multicast_membership() {
+        let lo = get_ifindex("lo").unwrap();
+
+        let joined = |group: IpAddr| {
+            get_mcast_groups()
+                .unwrap()
+                .iter()
+                .any(|m| m.ifindex == lo && m.group == group)
+        };
+
+        let group = Ipv4Addr::new(224, 0, 0, 123);
+        let sock = Socket::new(
+            AddressFamily::INET,
+            SocketType::DGRAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        assert!(!joined(group.into()));
+        join_multicast_v4(sock.as_fd(), group, lo).unwrap();
+        assert!(joined(group.into()));
+        leave_multicast(
+            sock.as_fd(),
+            McastMembership::V4 { group, ifindex: lo },
+        )
+        .unwrap();
+        assert!(!joined(group.into()));
+
+        // IPv6 is disabled
+        let Ok(sock) = Socket::new(
+            AddressFamily::INET6,
+            SocketType::DGRAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        else {
+            return;
+        };
+
+        let group = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x123);
+
+        assert!(!joined(group.into()));
+        join_multicast_v6(sock.as_fd(), group, lo as u32).unwrap();
+        assert!(joined(group.into()));
+        leave_multicast(
+            sock.as_fd(),
+            McastMembership::V6 {
+                group,
+                ifindex: lo as u32,
+            },
+        )
+        .unwrap();
+        assert!(!joined(group.into()));
+    }
+}
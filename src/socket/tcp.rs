@@ -0,0 +1,420 @@
+//! TCP level (IPPROTO_TCP) socket options
+//!
+//! Ref [tcp(7)](https://man7.org/linux/man-pages/man7/tcp.7.html)
+
+use std::{ffi::c_int, os::fd::BorrowedFd, time::Duration};
+
+use int_enum::IntEnum;
+use libc::{
+    IPPROTO_TCP, SO_KEEPALIVE, SOL_SOCKET, SOMAXCONN, TCP_FASTOPEN,
+    TCP_FASTOPEN_CONNECT, TCP_INFO, TCP_KEEPCNT, TCP_KEEPIDLE, TCP_KEEPINTVL,
+    size_t,
+};
+
+use crate::{
+    errno,
+    socket::{Flags, Msg, SockAddr, getsockopt, listen, sendto, setsockopt},
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// TCP_XXX connection state (`tcpi_state`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntEnum)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum TcpState {
+    Established = 1,
+    SynSent = 2,
+    SynRecv = 3,
+    FinWait1 = 4,
+    FinWait2 = 5,
+    TimeWait = 6,
+    Close = 7,
+    CloseWait = 8,
+    LastAck = 9,
+    Listen = 10,
+    Closing = 11,
+    NewSynRecv = 12,
+}
+
+/// Synonym linux/tcp.h `struct tcp_info` (up to `tcpi_snd_wnd`, linux 5.4)
+///
+/// Fields added by newer kernel than running one are left zero.
+/// Time fields are in us unless noted.
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[repr(C)]
+pub struct TcpInfo {
+    /// see `TcpInfo::state`
+    pub state: u8,
+    pub ca_state: u8,
+    pub retransmits: u8,
+    pub probes: u8,
+    pub backoff: u8,
+    pub options: u8,
+    /// snd_wscale:4, rcv_wscale:4
+    pub wscale: u8,
+    /// delivery_rate_app_limited:1, fastopen_client_fail:2
+    pub app_limited_fastopen: u8,
+
+    pub rto: u32,
+    pub ato: u32,
+    pub snd_mss: u32,
+    pub rcv_mss: u32,
+
+    pub unacked: u32,
+    pub sacked: u32,
+    pub lost: u32,
+    pub retrans: u32,
+    pub fackets: u32,
+
+    /* Times (ms) */
+    pub last_data_sent: u32,
+    pub last_ack_sent: u32,
+    pub last_data_recv: u32,
+    pub last_ack_recv: u32,
+
+    /* Metrics */
+    pub pmtu: u32,
+    pub rcv_ssthresh: u32,
+    pub rtt: u32,
+    pub rttvar: u32,
+    pub snd_ssthresh: u32,
+    pub snd_cwnd: u32,
+    pub advmss: u32,
+    pub reordering: u32,
+
+    pub rcv_rtt: u32,
+    pub rcv_space: u32,
+
+    pub total_retrans: u32,
+
+    /// bytes per second
+    pub pacing_rate: u64,
+    pub max_pacing_rate: u64,
+    pub bytes_acked: u64,
+    pub bytes_received: u64,
+    pub segs_out: u32,
+    pub segs_in: u32,
+
+    pub notsent_bytes: u32,
+    pub min_rtt: u32,
+    pub data_segs_in: u32,
+    pub data_segs_out: u32,
+
+    /// bytes per second
+    pub delivery_rate: u64,
+
+    pub busy_time: u64,
+    pub rwnd_limited: u64,
+    pub sndbuf_limited: u64,
+
+    pub delivered: u32,
+    pub delivered_ce: u32,
+
+    pub bytes_sent: u64,
+    pub bytes_retrans: u64,
+    pub dsack_dups: u32,
+    pub reord_seen: u32,
+
+    pub rcv_ooopack: u32,
+
+    pub snd_wnd: u32,
+}
+
+/// Keepalive probe parameters (second granularity)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct KeepaliveConfig {
+    /// TCP_KEEPIDLE, idle time before the first probe
+    pub idle: Duration,
+    /// TCP_KEEPINTVL, time between probes
+    pub interval: Duration,
+    /// TCP_KEEPCNT, unacknowledged probes before dropping the connection
+    pub count: u32,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl Default for KeepaliveConfig {
+    /// Linux default (net.ipv4.tcp_keepalive_xxx)
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(7200),
+            interval: Duration::from_secs(75),
+            count: 9,
+        }
+    }
+}
+
+impl TcpInfo {
+    pub fn state(&self) -> Option<TcpState> {
+        TcpState::try_from(self.state).ok()
+    }
+
+    /// smoothed round trip time
+    pub fn rtt(&self) -> Duration {
+        Duration::from_micros(self.rtt as _)
+    }
+
+    pub fn rttvar(&self) -> Duration {
+        Duration::from_micros(self.rttvar as _)
+    }
+
+    pub fn min_rtt(&self) -> Duration {
+        Duration::from_micros(self.min_rtt as _)
+    }
+
+    /// retransmission timeout
+    pub fn rto(&self) -> Duration {
+        Duration::from_micros(self.rto as _)
+    }
+
+    pub fn snd_wscale(&self) -> u8 {
+        self.wscale & 0xF
+    }
+
+    pub fn rcv_wscale(&self) -> u8 {
+        self.wscale >> 4
+    }
+
+    pub fn delivery_rate_app_limited(&self) -> bool {
+        self.app_limited_fastopen & 0x1 != 0
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// TCP_INFO
+pub fn get_tcp_info(sock: BorrowedFd) -> errno::Result<TcpInfo> {
+    unsafe { getsockopt(sock, IPPROTO_TCP, TCP_INFO) }
+}
+
+/// SO_KEEPALIVE
+pub fn set_keepalive(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, SOL_SOCKET, SO_KEEPALIVE, &(enable as c_int))
+}
+
+pub fn get_keepalive(sock: BorrowedFd) -> errno::Result<bool> {
+    unsafe { getsockopt::<c_int>(sock, SOL_SOCKET, SO_KEEPALIVE) }
+        .map(|v| v != 0)
+}
+
+/// Enable SO_KEEPALIVE and tune TCP_KEEPIDLE, TCP_KEEPINTVL, TCP_KEEPCNT
+///
+/// Durations are truncated to seconds (at least 1s).
+pub fn set_tcp_keepalive(
+    sock: BorrowedFd,
+    config: KeepaliveConfig,
+) -> errno::Result<()> {
+    let secs = |d: Duration| d.as_secs().clamp(1, c_int::MAX as u64) as c_int;
+
+    setsockopt(sock, IPPROTO_TCP, TCP_KEEPIDLE, &secs(config.idle))?;
+    setsockopt(sock, IPPROTO_TCP, TCP_KEEPINTVL, &secs(config.interval))?;
+    setsockopt(sock, IPPROTO_TCP, TCP_KEEPCNT, &(config.count as c_int))?;
+
+    set_keepalive(sock, true)
+}
+
+/// Current keepalive parameters (effective even if SO_KEEPALIVE is off)
+pub fn get_tcp_keepalive(sock: BorrowedFd) -> errno::Result<KeepaliveConfig> {
+    let get = |name| unsafe { getsockopt::<c_int>(sock, IPPROTO_TCP, name) };
+
+    Ok(KeepaliveConfig {
+        idle: Duration::from_secs(get(TCP_KEEPIDLE)? as _),
+        interval: Duration::from_secs(get(TCP_KEEPINTVL)? as _),
+        count: get(TCP_KEEPCNT)? as _,
+    })
+}
+
+/// Enable TCP_FASTOPEN with `qlen` pending TFO requests then listen
+/// (backlog SOMAXCONN)
+///
+/// Server side also needs sysctl `net.ipv4.tcp_fastopen` bit 0x2.
+pub fn listen_tfo(sock: BorrowedFd, qlen: c_int) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_TCP, TCP_FASTOPEN, &qlen)?;
+
+    listen(sock, SOMAXCONN)
+}
+
+/// Connect and send `data` in SYN (MSG_FASTOPEN)
+///
+/// Fall back to a normal handshake if no cookie cached or TFO disabled.
+pub fn sendto_fastopen(
+    sock: BorrowedFd,
+    data: &[u8],
+    addr: SockAddr,
+) -> errno::Result<size_t> {
+    sendto(sock, data, Flags::default() | Msg::FASTOPEN, Some(addr))
+}
+
+/// TCP_FASTOPEN_CONNECT, make the following `connect` deferred to the first
+/// `send`/`write` carrying data in SYN
+pub fn set_fastopen_connect(
+    sock: BorrowedFd,
+    enable: bool,
+) -> errno::Result<()> {
+    setsockopt(sock, IPPROTO_TCP, TCP_FASTOPEN_CONNECT, &(enable as c_int))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, os::fd::AsFd};
+
+    use super::*;
+    use crate::{
+        errno::PosixError,
+        socket::{
+            AddressFamily, ExtraBehavior, SockAddrIn, Socket, SocketProtocol,
+            SocketType,
+        },
+    };
+
+    #[test]
+    fn test_tfo() {
+        let listener = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        listener
+            .bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+        listen_tfo(listener.as_fd(), 16).unwrap();
+
+        let client = Socket::new(
+            AddressFamily::INET,
+            SocketType::STREAM,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )
+        .unwrap();
+
+        sendto_fastopen(
+            client.as_fd(),
+            b"ping",
+            listener.local_addr().unwrap(),
+        )
+        .unwrap();
+
+        let (server, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4];
+
+        server.recv(&mut buf, Default::default()).unwrap();
+        assert_eq!(&buf, b"ping");
+
+        println!("{:?}", get_tcp_info(client.as_fd()).unwrap().state());
+    }
+
+    #[test]
+    fn test_tcp_info() {
+        let (client, server) = connected_pair();
+        let mut buf = [0u8; 4];
+
+        // a round trip of data
+        client.send(b"ping", Default::default()).unwrap();
+        server.recv(&mut buf, Default::default()).unwrap();
+        server.send(b"pong", Default::default()).unwrap();
+        client.recv(&mut buf, Default::default()).unwrap();
+
+        let info = get_tcp_info(client.as_fd()).unwrap();
+
+        println!("{info:?}");
+
+        assert_eq!(info.state(), Some(TcpState::Established));
+        assert!(info.rtt() > Duration::ZERO);
+        assert!(info.snd_mss > 0);
+    }
+
+    #[test]
+    fn test_keepalive() {
+        let (client, _server) = connected_pair();
+        let sock = client.as_fd();
+
+        assert!(!get_keepalive(sock).unwrap());
+
+        let config = KeepaliveConfig {
+            idle: Duration::from_secs(30),
+            interval: Duration::from_secs(5),
+            count: 3,
+        };
+
+        set_tcp_keepalive(sock, config).unwrap();
+        assert!(get_keepalive(sock).unwrap());
+        assert_eq!(get_tcp_keepalive(sock).unwrap(), config);
+
+        // at least 1s
+        set_tcp_keepalive(
+            sock,
+            KeepaliveConfig {
+                idle: Duration::from_millis(10),
+                ..config
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            get_tcp_keepalive(sock).unwrap().idle,
+            Duration::from_secs(1)
+        );
+
+        set_keepalive(sock, false).unwrap();
+        assert!(!get_keepalive(sock).unwrap());
+
+        // kernel limits idle to 32767s and count to 127
+        assert!(matches!(
+            set_tcp_keepalive(
+                sock,
+                KeepaliveConfig {
+                    idle: Duration::from_secs(40000),
+                    ..config
+                }
+            ),
+            Err(PosixError::EINVAL)
+        ));
+        assert!(matches!(
+            set_tcp_keepalive(
+                sock,
+                KeepaliveConfig {
+                    count: 128,
+                    ..config
+                }
+            ),
+            Err(PosixError::EINVAL)
+        ));
+        assert!(!get_keepalive(sock).unwrap());
+    }
+
+    /// (client, server) connected on localhost
+    fn connected_pair() -> (Socket, Socket) {
+        let new_tcp = || {
+            Socket::new(
+                AddressFamily::INET,
+                SocketType::STREAM,
+                ExtraBehavior::new().close_on_exec(),
+                SocketProtocol::Zero,
+            )
+            .unwrap()
+        };
+
+        let listener = new_tcp();
+
+        listener
+            .bind(SockAddrIn::from(Ipv4Addr::LOCALHOST).into())
+            .unwrap();
+        listener.listen(1).unwrap();
+
+        let client = new_tcp();
+
+        client.connect(listener.local_addr().unwrap()).unwrap();
+
+        let (server, _) = listener.accept().unwrap();
+
+        (client, server)
+    }
+}
@@ -0,0 +1,227 @@
+//! Packet Timestamping (SO_TIMESTAMP, SO_TIMESTAMPNS, SO_TIMESTAMPING)
+//!
+//! Ref [timestamping](https://docs.kernel.org/networking/timestamping.html)
+
+use std::{
+    ffi::c_int,
+    fmt::Debug,
+    ops::{BitAnd, BitOr},
+    os::fd::BorrowedFd,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use libc::{
+    SO_TIMESTAMP, SO_TIMESTAMPING, SO_TIMESTAMPNS, SOL_SOCKET, time_t,
+    timespec, timeval,
+};
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{errno, socket::setsockopt};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// SOF_TIMESTAMPING_XXX
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(u32)]
+#[repr(u32)]
+pub enum TimestampingFlag {
+    /// Generation: hardware timestamp on transmit
+    TxHardware = 0x1,
+    /// Generation: software timestamp when leaving the kernel
+    TxSoftware = 0x2,
+    /// Generation: hardware timestamp on receive
+    RxHardware = 0x4,
+    /// Generation: software timestamp when entering the kernel
+    RxSoftware = 0x8,
+    /// Reporting: software timestamps
+    Software = 0x10,
+    /// Deprecated
+    SysHardware = 0x20,
+    /// Reporting: hardware timestamps
+    RawHardware = 0x40,
+    /// Unique identifier for each packet (in error queue)
+    OptId = 0x80,
+    /// Generation: before entering the packet scheduler
+    TxSched = 0x100,
+    /// Generation: when all data acknowledged (TCP)
+    TxAck = 0x200,
+    /// Always report cmsg (also for non-errqueue recv)
+    OptCmsg = 0x400,
+    /// Tx timestamps without packet payload
+    OptTsonly = 0x800,
+    OptStats = 0x1000,
+    OptPktinfo = 0x2000,
+    /// Both software and hardware Tx timestamps
+    OptTxSwhw = 0x4000,
+    BindPhc = 0x8000,
+    OptIdTcp = 0x10000,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(u32)]
+#[repr(transparent)]
+pub struct TimestampingFlags(u32);
+
+/// Decoded SCM_TIMESTAMPING (struct scm_timestamping)
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Timestamps {
+    /// ts\[0\], software timestamp
+    pub software: Option<SystemTime>,
+    /// ts\[2\], raw hardware timestamp (NIC clock domain)
+    pub hardware: Option<SystemTime>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl TimestampingFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    /// Software Rx/Tx timestamps with reporting
+    pub fn software() -> Self {
+        TimestampingFlag::RxSoftware
+            | TimestampingFlag::TxSoftware
+            | TimestampingFlag::Software
+    }
+
+    /// Hardware Rx/Tx timestamps with reporting (need NIC enabled, see
+    /// SIOCSHWTSTAMP)
+    pub fn hardware() -> Self {
+        TimestampingFlag::RxHardware
+            | TimestampingFlag::TxHardware
+            | TimestampingFlag::RawHardware
+    }
+}
+
+impl BitOr<TimestampingFlag> for TimestampingFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: TimestampingFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for TimestampingFlag {
+    type Output = TimestampingFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        TimestampingFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitOr for TimestampingFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd<TimestampingFlag> for &TimestampingFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: TimestampingFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<TimestampingFlags> for TimestampingFlag {
+    fn into(self) -> TimestampingFlags {
+        TimestampingFlags(self.to_bits())
+    }
+}
+
+impl Debug for TimestampingFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in
+            TimestampingFlag::iter().filter(|e| self & *e).enumerate()
+        {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Timestamps {
+    pub(crate) fn from_raw(ts: &[timespec; 3]) -> Self {
+        Self {
+            software: timespec_to_system_time_opt(&ts[0]),
+            hardware: timespec_to_system_time_opt(&ts[2]),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// SO_TIMESTAMP, receive `ControlMessageOwned::Timestamp` (us precision)
+pub fn set_timestamp(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, SOL_SOCKET, SO_TIMESTAMP, &(enable as c_int))
+}
+
+/// SO_TIMESTAMPNS, receive `ControlMessageOwned::TimestampNs`
+pub fn set_timestamp_ns(sock: BorrowedFd, enable: bool) -> errno::Result<()> {
+    setsockopt(sock, SOL_SOCKET, SO_TIMESTAMPNS, &(enable as c_int))
+}
+
+/// SO_TIMESTAMPING, receive `ControlMessageOwned::Timestamping`
+///
+/// Tx timestamps are reported by error queue (`Msg::ERRQUEUE`).
+pub fn set_timestamping(
+    sock: BorrowedFd,
+    flags: TimestampingFlags,
+) -> errno::Result<()> {
+    setsockopt(sock, SOL_SOCKET, SO_TIMESTAMPING, &flags.to_bits())
+}
+
+/// `None` if nsec is out of range or time isn't representable
+pub(crate) fn timespec_to_system_time(ts: &timespec) -> Option<SystemTime> {
+    let nsec = u32::try_from(ts.tv_nsec)
+        .ok()
+        .filter(|nsec| *nsec < 1_000_000_000)?;
+
+    since_epoch(ts.tv_sec, nsec)
+}
+
+/// `None` if usec is out of range or time isn't representable
+pub(crate) fn timeval_to_system_time(tv: &timeval) -> Option<SystemTime> {
+    let usec = u32::try_from(tv.tv_usec)
+        .ok()
+        .filter(|usec| *usec < 1_000_000)?;
+
+    since_epoch(tv.tv_sec, usec * 1000)
+}
+
+/// `sec` may be negative (before epoch), `nsec` always goes forward
+fn since_epoch(sec: time_t, nsec: u32) -> Option<SystemTime> {
+    let secs = Duration::from_secs(sec.unsigned_abs());
+
+    let base = if sec >= 0 {
+        UNIX_EPOCH.checked_add(secs)
+    }
+    else {
+        UNIX_EPOCH.checked_sub(secs)
+    };
+
+    base?.checked_add(Duration::from_nanos(nsec as u64))
+}
+
+/// all zero timespec means the timestamp isn't available
+fn timespec_to_system_time_opt(ts: &timespec) -> Option<SystemTime> {
+    if ts.tv_sec == 0 && ts.tv_nsec == 0 {
+        None
+    }
+    else {
+        timespec_to_system_time(ts)
+    }
+}
@@ -0,0 +1,1045 @@
+//! AF_XDP socket (XSK)
+//!
+//! Ref [af_xdp](https://docs.kernel.org/networking/af_xdp.html)
+//!
+//! Packets are redirected to the socket by an XDP program attached to the
+//! interface (`bpf_redirect_map` on an XSKMAP), which is out of scope here.
+
+use std::{
+    ffi::{c_int, c_void},
+    fmt::Debug,
+    marker::PhantomData,
+    ops::{BitAnd, BitOr},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd},
+    ptr::null_mut,
+    slice,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use libc::{
+    AF_XDP, MAP_ANONYMOUS, MAP_FAILED, MAP_POPULATE, MAP_PRIVATE, MAP_SHARED,
+    PROT_READ, PROT_WRITE, SOL_XDP, XDP_MMAP_OFFSETS, XDP_PGOFF_RX_RING,
+    XDP_PGOFF_TX_RING, XDP_RING_NEED_WAKEUP, XDP_RX_RING, XDP_STATISTICS,
+    XDP_TX_RING, XDP_UMEM_COMPLETION_RING, XDP_UMEM_FILL_RING,
+    XDP_UMEM_PGOFF_COMPLETION_RING, XDP_UMEM_PGOFF_FILL_RING, XDP_UMEM_REG,
+    off_t, sockaddr_xdp, socklen_t, xdp_mmap_offsets, xdp_ring_offset,
+    xdp_statistics, xdp_umem_reg,
+};
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    errno::{self, PosixError},
+    socket::{
+        AddressFamily, ExtraBehavior, Flags, Msg, SocketProtocol, SocketType,
+        getsockopt, setsockopt, socket,
+    },
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// XDP_XXX bind flags (`sxdp_flags`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(u16)]
+#[repr(u16)]
+pub enum XdpBindFlag {
+    SharedUmem = 1 << 0,
+    /// Force copy mode
+    Copy = 1 << 1,
+    /// Force zero-copy mode (fail if driver doesn't support)
+    ZeroCopy = 1 << 2,
+    /// Kernel only processes rings after a syscall when the ring flag asks
+    UseNeedWakeup = 1 << 3,
+    /// Multi-buffer (scatter-gather) packets
+    UseSg = 1 << 4,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(u16)]
+#[repr(transparent)]
+pub struct XdpBindFlags(u16);
+
+/// Synonym libc::xdp_desc, a packet in UMEM
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[repr(C)]
+pub struct XdpDesc {
+    /// Offset in UMEM
+    pub addr: u64,
+    pub len: u32,
+    pub options: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct XdpConfig<'fd> {
+    /// Number of UMEM chunks
+    pub frame_count: u32,
+    /// UMEM chunk size, 2048 or 4096 (aligned mode)
+    pub frame_size: u32,
+    /// Reserved bytes ahead of packet data in each chunk
+    pub headroom: u32,
+    /* Ring sizes, must be power of 2 */
+    pub fill_size: u32,
+    pub comp_size: u32,
+    pub rx_size: u32,
+    pub tx_size: u32,
+    pub bind_flags: XdpBindFlags,
+    /// Share UMEM (with its fill and completion rings) of this socket bound
+    /// to the same `(ifindex, queue_id)`, XDP_SHARED_UMEM is implied
+    pub shared_umem: Option<BorrowedFd<'fd>>,
+}
+
+/// Synonym libc::xdp_statistics
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct XdpStats {
+    pub rx_dropped: u64,
+    pub rx_invalid_descs: u64,
+    pub tx_invalid_descs: u64,
+    pub rx_ring_full: u64,
+    pub rx_fill_ring_empty_descs: u64,
+    pub tx_ring_empty_descs: u64,
+}
+
+/// Packet buffer area shared with kernel
+pub struct Umem {
+    area: *mut u8,
+    len: usize,
+    frame_size: u32,
+}
+
+/// Single producer single consumer ring mmaped from socket
+struct Ring<T> {
+    map: *mut u8,
+    map_len: usize,
+    producer: *const AtomicU32,
+    consumer: *const AtomicU32,
+    flags: *const AtomicU32,
+    descs: *mut T,
+    size: u32,
+    /// local copy of producer (we produce) or peer's producer (we consume)
+    cached_prod: u32,
+    /// local copy of consumer (we consume) or peer's consumer + size (we
+    /// produce)
+    cached_cons: u32,
+    _marker: PhantomData<T>,
+}
+
+/// AF_XDP socket bound to one queue of an interface
+pub struct XdpSocket {
+    fd: OwnedFd,
+    /// `None` for shared UMEM
+    umem: Option<Umem>,
+    fill: Option<Ring<u64>>,
+    comp: Option<Ring<u64>>,
+    rx: Ring<XdpDesc>,
+    tx: Ring<XdpDesc>,
+    need_wakeup: bool,
+    /// owner of each chunk of own UMEM
+    chunks: Vec<Chunk>,
+    /// chunks of `Chunk::Free`
+    free: Vec<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Chunk {
+    /// In free list of user
+    Free,
+    /// Taken by user, allocated or received and not yet given back
+    User,
+    /// In fill ring, rx ring or in flight of tx
+    Kernel,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl XdpBindFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl BitOr<XdpBindFlag> for XdpBindFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: XdpBindFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for XdpBindFlag {
+    type Output = XdpBindFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        XdpBindFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<XdpBindFlag> for &XdpBindFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: XdpBindFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<XdpBindFlags> for XdpBindFlag {
+    fn into(self) -> XdpBindFlags {
+        XdpBindFlags(self.to_bits())
+    }
+}
+
+impl Debug for XdpBindFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in XdpBindFlag::iter().filter(|e| self & *e).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for XdpConfig<'_> {
+    /// Same as libxdp defaults
+    fn default() -> Self {
+        Self {
+            frame_count: 4096,
+            frame_size: 4096,
+            headroom: 0,
+            fill_size: 2048,
+            comp_size: 2048,
+            rx_size: 2048,
+            tx_size: 2048,
+            bind_flags: XdpBindFlag::UseNeedWakeup.into(),
+            shared_umem: None,
+        }
+    }
+}
+
+impl From<xdp_statistics> for XdpStats {
+    fn from(value: xdp_statistics) -> Self {
+        Self {
+            rx_dropped: value.rx_dropped,
+            rx_invalid_descs: value.rx_invalid_descs,
+            tx_invalid_descs: value.tx_invalid_descs,
+            rx_ring_full: value.rx_ring_full,
+            rx_fill_ring_empty_descs: value.rx_fill_ring_empty_descs,
+            tx_ring_empty_descs: value.tx_ring_empty_descs,
+        }
+    }
+}
+
+impl Umem {
+    /// Allocate page aligned area of `frame_count * frame_size` bytes
+    pub fn new(frame_count: u32, frame_size: u32) -> errno::Result<Self> {
+        let len = frame_count as usize * frame_size as usize;
+
+        let area = unsafe {
+            libc::mmap(
+                null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS | MAP_POPULATE,
+                -1,
+                0,
+            )
+        };
+
+        if area == MAP_FAILED {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(Self {
+            area: area as _,
+            len,
+            frame_size,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn frame_size(&self) -> u32 {
+        self.frame_size
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        (self.len / self.frame_size as usize) as u32
+    }
+
+    /// Start of the chunk containing `addr`
+    pub fn chunk_of(&self, addr: u64) -> u64 {
+        addr - addr % self.frame_size as u64
+    }
+
+    /// # Safety
+    ///
+    /// The range must not be owned by kernel (in fill ring, rx ring or in
+    /// flight of tx) during the borrow, as kernel may write it meanwhile.
+    pub unsafe fn get(&self, addr: u64, len: usize) -> &[u8] {
+        assert!(addr as usize + len <= self.len);
+
+        unsafe { slice::from_raw_parts(self.area.add(addr as usize), len) }
+    }
+
+    /// From `addr` to the end of its chunk
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Umem::get`].
+    pub unsafe fn get_mut(&mut self, addr: u64) -> &mut [u8] {
+        let end = self.chunk_of(addr) as usize + self.frame_size as usize;
+
+        assert!(end <= self.len);
+
+        unsafe {
+            slice::from_raw_parts_mut(
+                self.area.add(addr as usize),
+                end - addr as usize,
+            )
+        }
+    }
+
+    fn register(&self, sock: BorrowedFd, headroom: u32) -> errno::Result<()> {
+        setsockopt(
+            sock,
+            SOL_XDP,
+            XDP_UMEM_REG,
+            &xdp_umem_reg {
+                addr: self.area as u64,
+                len: self.len as u64,
+                chunk_size: self.frame_size,
+                headroom,
+                flags: 0,
+                tx_metadata_len: 0,
+            },
+        )
+    }
+}
+
+impl Drop for Umem {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.area as _, self.len);
+        }
+    }
+}
+
+impl<T: Copy> Ring<T> {
+    fn map(
+        sock: BorrowedFd,
+        size: u32,
+        off: &xdp_ring_offset,
+        pgoff: off_t,
+        is_producer: bool,
+    ) -> errno::Result<Self> {
+        let map_len = off.desc as usize + size as usize * size_of::<T>();
+
+        let map = unsafe {
+            libc::mmap(
+                null_mut(),
+                map_len,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED | MAP_POPULATE,
+                sock.as_raw_fd(),
+                pgoff,
+            )
+        };
+
+        if map == MAP_FAILED {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(unsafe {
+            Self::from_map(map as _, map_len, size, off, is_producer)
+        })
+    }
+
+    /// # Safety
+    ///
+    /// `map` is a mapping of `map_len` bytes laid out as `off` and owned by
+    /// the returned ring.
+    unsafe fn from_map(
+        map: *mut u8,
+        map_len: usize,
+        size: u32,
+        off: &xdp_ring_offset,
+        is_producer: bool,
+    ) -> Self {
+        let mut it = unsafe {
+            Self {
+                map,
+                map_len,
+                producer: map.add(off.producer as usize) as _,
+                consumer: map.add(off.consumer as usize) as _,
+                flags: map.add(off.flags as usize) as _,
+                descs: map.add(off.desc as usize) as _,
+                size,
+                cached_prod: 0,
+                cached_cons: 0,
+                _marker: PhantomData,
+            }
+        };
+
+        it.cached_prod = it.producer().load(Ordering::Relaxed);
+        it.cached_cons = it.consumer().load(Ordering::Relaxed);
+
+        if is_producer {
+            it.cached_cons = it.cached_cons.wrapping_add(size);
+        }
+
+        it
+    }
+
+    fn producer(&self) -> &AtomicU32 {
+        unsafe { &*self.producer }
+    }
+
+    fn consumer(&self) -> &AtomicU32 {
+        unsafe { &*self.consumer }
+    }
+
+    fn need_wakeup(&self) -> bool {
+        unsafe { &*self.flags }.load(Ordering::Relaxed) & XDP_RING_NEED_WAKEUP
+            != 0
+    }
+
+    fn slot(&self, idx: u32) -> *mut T {
+        unsafe { self.descs.add((idx & (self.size - 1)) as usize) }
+    }
+
+    /* Producer side */
+
+    fn free_entries(&mut self, n: u32) -> u32 {
+        let mut free = self.cached_cons.wrapping_sub(self.cached_prod);
+
+        if free < n {
+            self.cached_cons = self
+                .consumer()
+                .load(Ordering::Acquire)
+                .wrapping_add(self.size);
+            free = self.cached_cons.wrapping_sub(self.cached_prod);
+        }
+
+        free.min(n)
+    }
+
+    /// Return produced number
+    fn produce(&mut self, items: impl ExactSizeIterator<Item = T>) -> u32 {
+        let n = self.free_entries(items.len() as u32);
+
+        for item in items.take(n as usize) {
+            unsafe { self.slot(self.cached_prod).write(item) };
+            self.cached_prod = self.cached_prod.wrapping_add(1);
+        }
+
+        self.producer().store(self.cached_prod, Ordering::Release);
+
+        n
+    }
+
+    /* Consumer side */
+
+    fn avail_entries(&mut self, n: u32) -> u32 {
+        let mut avail = self.cached_prod.wrapping_sub(self.cached_cons);
+
+        if avail == 0 {
+            self.cached_prod = self.producer().load(Ordering::Acquire);
+            avail = self.cached_prod.wrapping_sub(self.cached_cons);
+        }
+
+        avail.min(n)
+    }
+
+    fn consume(&mut self, max: u32, out: &mut Vec<T>) -> u32 {
+        let n = self.avail_entries(max);
+
+        for _ in 0..n {
+            out.push(unsafe { self.slot(self.cached_cons).read() });
+            self.cached_cons = self.cached_cons.wrapping_add(1);
+        }
+
+        self.consumer().store(self.cached_cons, Ordering::Release);
+
+        n
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.map as _, self.map_len);
+        }
+    }
+}
+
+impl XdpSocket {
+    /// Create socket, register UMEM, map rings, bind to `(ifindex, queue_id)`
+    /// and fill RX frames
+    ///
+    /// With `config.shared_umem`, UMEM and fill/completion rings of that
+    /// socket are used instead, frames and chunk sizes config are ignored.
+    /// Its descriptors are read through `Umem::get` of that socket.
+    pub fn new(
+        ifindex: u32,
+        queue_id: u32,
+        config: XdpConfig,
+    ) -> errno::Result<Self> {
+        let mut bind_flags = config.bind_flags;
+
+        if config.shared_umem.is_some() {
+            bind_flags = bind_flags | XdpBindFlag::SharedUmem;
+        }
+        else if &bind_flags & XdpBindFlag::SharedUmem {
+            Err(PosixError::EINVAL)?
+        }
+
+        let fd = socket(
+            AddressFamily::XDP,
+            SocketType::RAW,
+            ExtraBehavior::new().close_on_exec(),
+            SocketProtocol::Zero,
+        )?;
+        let sock = fd.as_fd();
+
+        let umem = match config.shared_umem {
+            Some(_) => None,
+            None => {
+                let umem = Umem::new(config.frame_count, config.frame_size)?;
+
+                umem.register(sock, config.headroom)?;
+
+                Some(umem)
+            }
+        };
+
+        let ring_size = |name, size: u32| {
+            setsockopt(sock, SOL_XDP, name, &(size as c_int))
+        };
+
+        if umem.is_some() {
+            ring_size(XDP_UMEM_FILL_RING, config.fill_size)?;
+            ring_size(XDP_UMEM_COMPLETION_RING, config.comp_size)?;
+        }
+        ring_size(XDP_RX_RING, config.rx_size)?;
+        ring_size(XDP_TX_RING, config.tx_size)?;
+
+        // need linux 5.4+ for flags field
+        let off: xdp_mmap_offsets =
+            unsafe { getsockopt(sock, SOL_XDP, XDP_MMAP_OFFSETS)? };
+
+        let (fill, comp) = match umem {
+            Some(_) => (
+                Some(Ring::map(
+                    sock,
+                    config.fill_size,
+                    &off.fr,
+                    XDP_UMEM_PGOFF_FILL_RING as _,
+                    true,
+                )?),
+                Some(Ring::map(
+                    sock,
+                    config.comp_size,
+                    &off.cr,
+                    XDP_UMEM_PGOFF_COMPLETION_RING as _,
+                    false,
+                )?),
+            ),
+            None => (None, None),
+        };
+        let rx = Ring::map(
+            sock,
+            config.rx_size,
+            &off.rx,
+            XDP_PGOFF_RX_RING,
+            false,
+        )?;
+        let tx =
+            Ring::map(sock, config.tx_size, &off.tx, XDP_PGOFF_TX_RING, true)?;
+
+        let addr = sockaddr_xdp {
+            sxdp_family: AF_XDP as _,
+            sxdp_flags: bind_flags.to_bits(),
+            sxdp_ifindex: ifindex,
+            sxdp_queue_id: queue_id,
+            sxdp_shared_umem_fd: config
+                .shared_umem
+                .map(|fd| fd.as_raw_fd() as u32)
+                .unwrap_or_default(),
+        };
+
+        let ret = unsafe {
+            libc::bind(
+                sock.as_raw_fd(),
+                &addr as *const sockaddr_xdp as _,
+                size_of::<sockaddr_xdp>() as socklen_t,
+            )
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        let frame_count = match umem {
+            Some(ref umem) => umem.frame_count(),
+            None => 0,
+        };
+
+        let mut it = Self {
+            free: (0..frame_count)
+                .rev()
+                .map(|i| i as u64 * config.frame_size as u64)
+                .collect(),
+            chunks: vec![Chunk::Free; frame_count as usize],
+            fd,
+            umem,
+            fill,
+            comp,
+            rx,
+            tx,
+            need_wakeup: &bind_flags & XdpBindFlag::UseNeedWakeup,
+        };
+
+        if it.umem.is_some() {
+            let n = (config.fill_size as usize).min(it.free.len());
+            let addrs = it.free.split_off(it.free.len() - n);
+
+            for addr in addrs.iter() {
+                it.chunks[it.chunk_index(*addr).unwrap()] = Chunk::User;
+            }
+
+            it.fill(&addrs)?;
+        }
+
+        Ok(it)
+    }
+
+    /// `None` for shared UMEM
+    pub fn umem(&self) -> Option<&Umem> {
+        self.umem.as_ref()
+    }
+
+    /// Packet data of received descriptor
+    ///
+    /// `None` unless the chunk is taken by user.
+    pub fn frame(&self, desc: &XdpDesc) -> Option<&[u8]> {
+        let umem = self.user_chunk(desc.addr)?;
+        let len = desc.len as u64;
+
+        if desc.addr + len > umem.chunk_of(desc.addr) + umem.frame_size as u64
+        {
+            return None;
+        }
+
+        Some(unsafe { umem.get(desc.addr, len as usize) })
+    }
+
+    /// Writable chunk space from `addr` (for building tx packet)
+    ///
+    /// `None` unless the chunk is taken by user.
+    pub fn frame_mut(&mut self, addr: u64) -> Option<&mut [u8]> {
+        self.user_chunk(addr)?;
+
+        Some(unsafe { self.umem.as_mut()?.get_mut(addr) })
+    }
+
+    /// Take a free chunk for tx
+    pub fn alloc_frame(&mut self) -> Option<u64> {
+        let addr = self.free.pop()?;
+        let i = self.chunk_index(addr)?;
+
+        self.chunks[i] = Chunk::User;
+
+        Some(addr)
+    }
+
+    /// Give back a chunk (e.g. a received frame not to be refilled), return
+    /// false if it isn't taken by user
+    pub fn free_frame(&mut self, addr: u64) -> bool {
+        match self.chunk_index(addr) {
+            Some(i) if self.chunks[i] == Chunk::User => {
+                self.chunks[i] = Chunk::Free;
+                self.free.push(i as u64 * self.frame_size());
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Non-blocking receive at most `max` descriptors
+    ///
+    /// Frames are owned by user until `fill` or `free_frame` them.
+    pub fn recv(&mut self, max: usize) -> Vec<XdpDesc> {
+        let mut descs = Vec::with_capacity(max);
+
+        self.rx.consume(max as u32, &mut descs);
+
+        for desc in descs.iter() {
+            if let Some(i) = self.chunk_index(desc.addr) {
+                self.chunks[i] = Chunk::User;
+            }
+        }
+
+        descs
+    }
+
+    /// Hand chunks taken by user to kernel for receiving, return the number
+    /// filled
+    ///
+    /// EINVAL if any chunk isn't taken by user or UMEM is shared.
+    pub fn fill(&mut self, addrs: &[u64]) -> errno::Result<usize> {
+        if self.fill.is_none() {
+            Err(PosixError::EINVAL)?
+        }
+
+        self.claim(addrs.iter().copied())?;
+
+        let frame_size = self.frame_size();
+        let fill = self.fill.as_mut().unwrap();
+
+        let n = fill.produce(addrs.iter().map(|addr| addr - addr % frame_size))
+            as usize;
+
+        // keep the rest
+        for addr in addrs[n..].iter() {
+            let i = self.chunk_index(*addr).unwrap();
+
+            self.chunks[i] = Chunk::Free;
+            self.free.push(i as u64 * frame_size);
+        }
+
+        if !self.need_wakeup || self.fill.as_ref().unwrap().need_wakeup() {
+            self.kick_rx()?;
+        }
+
+        Ok(n)
+    }
+
+    /// Queue packets for transmitting, return the number queued
+    ///
+    /// Chunks come back to free list by `complete` after sent. EINVAL if
+    /// any chunk of own UMEM isn't taken by user.
+    pub fn send(&mut self, descs: &[XdpDesc]) -> errno::Result<usize> {
+        self.claim(descs.iter().map(|desc| desc.addr))?;
+
+        let n = self.tx.produce(descs.iter().copied()) as usize;
+
+        // the rest stay with user
+        for desc in descs[n..].iter() {
+            if let Some(i) = self.chunk_index(desc.addr) {
+                self.chunks[i] = Chunk::User;
+            }
+        }
+
+        if n > 0 && (!self.need_wakeup || self.tx.need_wakeup()) {
+            self.kick_tx()?;
+        }
+
+        Ok(n)
+    }
+
+    /// Reclaim at most `max` transmitted chunks into free list
+    pub fn complete(&mut self, max: usize) -> usize {
+        let Some(comp) = self.comp.as_mut()
+        else {
+            return 0;
+        };
+
+        let mut addrs = Vec::with_capacity(max);
+
+        comp.consume(max as u32, &mut addrs);
+
+        for addr in addrs.iter() {
+            if let Some(i) = self.chunk_index(*addr) {
+                self.chunks[i] = Chunk::Free;
+                self.free.push(i as u64 * self.frame_size());
+            }
+        }
+
+        addrs.len()
+    }
+
+    /// XDP_STATISTICS
+    pub fn statistics(&self) -> errno::Result<XdpStats> {
+        unsafe {
+            getsockopt::<xdp_statistics>(
+                self.fd.as_fd(),
+                SOL_XDP,
+                XDP_STATISTICS,
+            )
+        }
+        .map(Into::into)
+    }
+
+    fn frame_size(&self) -> u64 {
+        self.umem
+            .as_ref()
+            .map(|umem| umem.frame_size as u64)
+            .unwrap_or(1)
+    }
+
+    /// Index of chunk containing `addr` of own UMEM
+    fn chunk_index(&self, addr: u64) -> Option<usize> {
+        let umem = self.umem.as_ref()?;
+
+        if addr as usize >= umem.len {
+            return None;
+        }
+
+        Some((addr / umem.frame_size as u64) as usize)
+    }
+
+    /// Own UMEM if chunk containing `addr` is taken by user
+    fn user_chunk(&self, addr: u64) -> Option<&Umem> {
+        match self.chunk_index(addr) {
+            Some(i) if self.chunks[i] == Chunk::User => self.umem.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Pass chunks taken by user to kernel, all or none (EINVAL)
+    ///
+    /// No-op for shared UMEM.
+    fn claim(
+        &mut self,
+        addrs: impl Iterator<Item = u64>,
+    ) -> errno::Result<()> {
+        if self.umem.is_none() {
+            return Ok(());
+        }
+
+        let mut claimed = vec![];
+
+        for addr in addrs {
+            match self.chunk_index(addr) {
+                Some(i) if self.chunks[i] == Chunk::User => {
+                    self.chunks[i] = Chunk::Kernel;
+                    claimed.push(i);
+                }
+                _ => {
+                    for i in claimed {
+                        self.chunks[i] = Chunk::User;
+                    }
+
+                    Err(PosixError::EINVAL)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn kick_tx(&self) -> errno::Result<()> {
+        let ret = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                null_mut::<c_void>(),
+                0,
+                (Flags::default() | Msg::DONTWAIT).to_bits(),
+                null_mut(),
+                0,
+            )
+        };
+
+        if ret == -1 {
+            match errno::last_os_error() {
+                // busy, retry next time
+                PosixError::EAGAIN
+                | PosixError::EBUSY
+                | PosixError::ENOBUFS
+                | PosixError::ENETDOWN => (),
+                err => Err(err)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn kick_rx(&self) -> errno::Result<()> {
+        let ret = unsafe {
+            libc::recvfrom(
+                self.fd.as_raw_fd(),
+                null_mut(),
+                0,
+                (Flags::default() | Msg::DONTWAIT).to_bits(),
+                null_mut(),
+                null_mut(),
+            )
+        };
+
+        if ret == -1 {
+            match errno::last_os_error() {
+                PosixError::EAGAIN | PosixError::EBUSY => (),
+                err => Err(err)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AsFd for XdpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::mem::ManuallyDrop;
+
+    use super::*;
+    use crate::iface::get_ifindex;
+
+    #[test]
+    fn test_ring() {
+        let off = xdp_ring_offset {
+            producer: 0,
+            consumer: 64,
+            flags: 128,
+            desc: 192,
+        };
+        let size = 4;
+        let map_len = off.desc as usize + size as usize * size_of::<u64>();
+
+        let map = unsafe {
+            libc::mmap(
+                null_mut(),
+                map_len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(map, MAP_FAILED);
+
+        let mut prod = unsafe {
+            Ring::<u64>::from_map(map as _, map_len, size, &off, true)
+        };
+        // peer view of the same mapping (unmapped by `prod`)
+        let mut cons = ManuallyDrop::new(unsafe {
+            Ring::<u64>::from_map(map as _, map_len, size, &off, false)
+        });
+
+        let mut out = vec![];
+
+        assert_eq!(cons.consume(4, &mut out), 0);
+
+        assert_eq!(prod.produce([1, 2, 3].into_iter()), 3);
+        // full
+        assert_eq!(prod.produce([4, 5].into_iter()), 1);
+
+        assert_eq!(cons.consume(2, &mut out), 2);
+        assert_eq!(out, [1, 2]);
+
+        // wrap around
+        assert_eq!(prod.produce([5, 6, 7].into_iter()), 2);
+
+        out.clear();
+        assert_eq!(cons.consume(8, &mut out), 2);
+        assert_eq!(cons.consume(8, &mut out), 2);
+        assert_eq!(out, [3, 4, 5, 6]);
+        assert_eq!(cons.consume(8, &mut out), 0);
+    }
+
+    #[test]
+    fn test_chunk_owner() {
+        let lo = get_ifindex("lo").unwrap() as u32;
+        let config = XdpConfig {
+            frame_count: 8,
+            fill_size: 4,
+            comp_size: 4,
+            rx_size: 4,
+            tx_size: 4,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            XdpSocket::new(
+                lo,
+                0,
+                XdpConfig {
+                    bind_flags: XdpBindFlag::SharedUmem.into(),
+                    ..config
+                }
+            ),
+            Err(PosixError::EINVAL)
+        ));
+
+        let mut sock = match XdpSocket::new(lo, 0, config) {
+            Ok(sock) => sock,
+            Err(err) => {
+                // need CAP_NET_RAW and CONFIG_XDP_SOCKETS
+                assert!(
+                    matches!(
+                        err,
+                        PosixError::EPERM
+                            | PosixError::EAFNOSUPPORT
+                            | PosixError::EOPNOTSUPP
+                    ),
+                    "{err:?}"
+                );
+                return;
+            }
+        };
+
+        // chunk 0..4 in fill ring, 4..8 free
+        assert_eq!(sock.free.len(), 4);
+
+        let desc = |addr, len| XdpDesc {
+            addr,
+            len,
+            options: 0,
+        };
+
+        // owned by kernel
+        assert!(sock.frame_mut(0).is_none());
+        assert!(sock.frame(&desc(0, 1)).is_none());
+        assert!(!sock.free_frame(0));
+        assert!(matches!(sock.fill(&[0]), Err(PosixError::EINVAL)));
+        assert!(matches!(sock.send(&[desc(0, 1)]), Err(PosixError::EINVAL)));
+
+        let addr = sock.alloc_frame().unwrap();
+
+        sock.frame_mut(addr).unwrap()[..4].copy_from_slice(b"ping");
+        assert_eq!(sock.frame(&desc(addr, 4)).unwrap(), b"ping");
+
+        // out of chunk or UMEM
+        assert!(sock.frame(&desc(addr, 4097)).is_none());
+        assert!(sock.frame_mut(8 * 4096).is_none());
+
+        // all or none
+        assert!(matches!(sock.fill(&[addr, 0]), Err(PosixError::EINVAL)));
+        assert!(matches!(sock.fill(&[addr, addr]), Err(PosixError::EINVAL)));
+        assert!(sock.frame_mut(addr).is_some());
+
+        assert!(sock.free_frame(addr + 1));
+        assert!(!sock.free_frame(addr));
+        assert!(sock.frame_mut(addr).is_none());
+        assert_eq!(sock.free.len(), 4);
+
+        // rx/tx rings only, UMEM of `sock`
+        let mut shared = XdpSocket::new(
+            lo,
+            0,
+            XdpConfig {
+                shared_umem: Some(sock.as_fd()),
+                ..config
+            },
+        )
+        .unwrap();
+
+        assert!(shared.umem().is_none());
+        assert!(matches!(shared.fill(&[addr]), Err(PosixError::EINVAL)));
+        assert_eq!(shared.complete(4), 0);
+    }
+}
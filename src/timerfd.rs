@@ -0,0 +1,220 @@
+//! Timer notified through file descriptor (readable on expiration)
+//!
+//! Fd is non-blocking, so it can be registered into `Epoll` and drained
+//! with `TimerFd::read_expirations` when it fires.
+//!
+//! Ref [timerfd_create(2)](https://man7.org/linux/man-pages/man2/timerfd_create.2.html)
+
+use std::{
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    ptr::null_mut,
+    time::Duration,
+};
+
+use int_enum::IntEnum;
+use libc::{TFD_CLOEXEC, TFD_NONBLOCK, itimerspec, timespec};
+
+use crate::{
+    errno::{self, PosixError},
+    unistd::read,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+/// CLOCK_XXX supported by timerfd
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, IntEnum)]
+#[repr(i32)]
+pub enum ClockId {
+    /// Settable system-wide wall clock
+    Realtime = 0,
+    /// Not affected by wall clock change, stopped during suspend
+    Monotonic = 1,
+    /// Like `Monotonic`, but includes suspend
+    Boottime = 7,
+    /// `Realtime`, wake system from suspend (need CAP_WAKE_ALARM)
+    RealtimeAlarm = 8,
+    /// `Boottime`, wake system from suspend (need CAP_WAKE_ALARM)
+    BoottimeAlarm = 9,
+}
+
+#[derive(Debug)]
+pub struct TimerFd {
+    fd: OwnedFd,
+    clock: ClockId,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl TimerFd {
+    /// Disarmed timer with TFD_NONBLOCK and TFD_CLOEXEC
+    pub fn new(clock: ClockId) -> errno::Result<Self> {
+        let ret = unsafe {
+            libc::timerfd_create(clock.into(), TFD_NONBLOCK | TFD_CLOEXEC)
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(ret) },
+            clock,
+        })
+    }
+
+    pub fn clock(&self) -> ClockId {
+        self.clock
+    }
+
+    /// Expire once after `timeout` (relative)
+    ///
+    /// Zero `timeout` is rounded up to 1ns, or it would disarm the timer.
+    pub fn set_oneshot(&self, timeout: Duration) -> errno::Result<()> {
+        self.settime(timeout.max(Duration::from_nanos(1)), Duration::ZERO)
+    }
+
+    /// Expire every `interval`, first time after `interval`
+    ///
+    /// EINVAL for zero `interval`.
+    pub fn set_interval(&self, interval: Duration) -> errno::Result<()> {
+        if interval.is_zero() {
+            Err(PosixError::EINVAL)?
+        }
+
+        self.settime(interval, interval)
+    }
+
+    pub fn disarm(&self) -> errno::Result<()> {
+        self.settime(Duration::ZERO, Duration::ZERO)
+    }
+
+    /// Time until next expiration, `None` if disarmed
+    pub fn remaining(&self) -> errno::Result<Option<Duration>> {
+        let mut curr: itimerspec = unsafe { std::mem::zeroed() };
+
+        let ret =
+            unsafe { libc::timerfd_gettime(self.fd.as_raw_fd(), &mut curr) };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        let remaining = timespec_to_duration(curr.it_value);
+
+        Ok((!remaining.is_zero()).then_some(remaining))
+    }
+
+    /// Number of expirations since last read (or set), 0 if none
+    pub fn read_expirations(&self) -> errno::Result<u64> {
+        let mut buf = [0u8; 8];
+
+        match read(self.fd.as_fd(), &mut buf, buf.len()) {
+            Ok(_) => Ok(u64::from_ne_bytes(buf)),
+            Err(PosixError::EAGAIN) => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn settime(
+        &self,
+        value: Duration,
+        interval: Duration,
+    ) -> errno::Result<()> {
+        let new = itimerspec {
+            it_interval: duration_to_timespec(interval),
+            it_value: duration_to_timespec(value),
+        };
+
+        let ret = unsafe {
+            libc::timerfd_settime(self.fd.as_raw_fd(), 0, &new, null_mut())
+        };
+
+        if ret == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        Ok(())
+    }
+}
+
+impl AsFd for TimerFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for TimerFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Saturate at time_t::MAX seconds
+fn duration_to_timespec(d: Duration) -> timespec {
+    timespec {
+        tv_sec: d.as_secs().min(libc::time_t::MAX as u64) as _,
+        tv_nsec: d.subsec_nanos() as _,
+    }
+}
+
+fn timespec_to_duration(ts: timespec) -> Duration {
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoll::{Epoll, EpollEvent, EpollFlag, EpollToken};
+
+    #[test]
+    fn test_timerfd() {
+        let timer = TimerFd::new(ClockId::Monotonic).unwrap();
+
+        assert_eq!(timer.remaining(), Ok(None));
+        assert_eq!(timer.read_expirations(), Ok(0));
+        assert_eq!(
+            timer.set_interval(Duration::ZERO),
+            Err(PosixError::EINVAL)
+        );
+
+        let mut epoll = Epoll::create().unwrap();
+        let mut events = [EpollEvent::default(); 1];
+
+        epoll
+            .insert(
+                timer.as_fd(),
+                EpollEvent::with_token(
+                    EpollFlag::In.into(),
+                    EpollToken::Index(1),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        timer.set_oneshot(Duration::from_millis(1)).unwrap();
+
+        let fired = epoll.wait(&mut events, 1000).unwrap();
+
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].token(), Some(EpollToken::Index(1)));
+        assert_eq!(timer.read_expirations(), Ok(1));
+        assert_eq!(timer.remaining(), Ok(None));
+
+        timer.set_interval(Duration::from_secs(60)).unwrap();
+
+        assert!(
+            timer.remaining().unwrap().unwrap() <= Duration::from_secs(60)
+        );
+
+        timer.disarm().unwrap();
+
+        assert_eq!(timer.remaining(), Ok(None));
+    }
+}
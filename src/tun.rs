@@ -0,0 +1,387 @@
+//! TUN (IP packet) / TAP (Ethernet frame) virtual network device
+//!
+//! Ref [tuntap](https://docs.kernel.org/networking/tuntap.html)
+
+use std::{
+    ffi::{CStr, c_char, c_short},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use libc::{O_CLOEXEC, O_NONBLOCK, O_RDWR, gid_t, size_t, uid_t};
+use osimodel::datalink::Mac;
+
+use crate::{
+    errno::{self, PosixError},
+    iface::ifreq,
+    ioctl::{IoctlOpcode, ioctl, ioctl_val},
+    socket::set_nonblocking,
+    unistd::{read, write},
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+//// Constants
+
+const TUN_CLONE_DEV: &CStr = c"/dev/net/tun";
+
+/* IFF_XXX of TUNSETIFF */
+const IFF_TUN: c_short = 0x0001;
+const IFF_TAP: c_short = 0x0002;
+/// No struct tun_pi before packet
+const IFF_NO_PI: c_short = 0x1000;
+
+////////////////////////////////////////////////////////////////////////////////
+//// Structures
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TunMode {
+    /// Layer 3, read/write IP packets
+    Tun,
+    /// Layer 2, read/write Ethernet frames
+    Tap,
+}
+
+/// Options to create (or attach to persistent) TUN/TAP device
+#[derive(Clone, Debug)]
+pub struct TunConfig {
+    /// Empty for kernel assigned `tunN`/`tapN`
+    pub name: String,
+    pub mode: TunMode,
+    /// Prefix every packet with struct tun_pi (`TunPacketInfo`)
+    pub packet_info: bool,
+    /// Keep device after fd is closed
+    pub persist: bool,
+    /// User allowed to attach without CAP_NET_ADMIN
+    pub owner: Option<uid_t>,
+    /// Group allowed to attach without CAP_NET_ADMIN
+    pub group: Option<gid_t>,
+    pub nonblocking: bool,
+}
+
+/// Attached TUN/TAP device, read/write one packet (frame) per call
+#[derive(Debug)]
+pub struct Tun {
+    fd: OwnedFd,
+    name: String,
+    mode: TunMode,
+}
+
+/// struct tun_pi
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TunPacketInfo {
+    /// TUN_PKT_STRIP if packet was truncated
+    pub flags: u16,
+    /// Ethernet type (e.g. 0x0800 for IPv4)
+    pub proto: u16,
+}
+
+/// Ethernet header of TAP frame
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EthFrameHdr {
+    pub dst: Mac,
+    pub src: Mac,
+    pub ethertype: u16,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl TunConfig {
+    pub fn new(mode: TunMode) -> Self {
+        Self {
+            name: String::new(),
+            mode,
+            packet_info: false,
+            persist: false,
+            owner: None,
+            group: None,
+            nonblocking: false,
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    pub fn packet_info(mut self, packet_info: bool) -> Self {
+        self.packet_info = packet_info;
+        self
+    }
+
+    pub fn persist(mut self, persist: bool) -> Self {
+        self.persist = persist;
+        self
+    }
+
+    pub fn owner(mut self, uid: uid_t) -> Self {
+        self.owner = Some(uid);
+        self
+    }
+
+    pub fn group(mut self, gid: gid_t) -> Self {
+        self.group = Some(gid);
+        self
+    }
+
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Open `/dev/net/tun` and TUNSETIFF (need CAP_NET_ADMIN unless we're
+    /// owner of persistent device)
+    pub fn open(&self) -> errno::Result<Tun> {
+        let name = if self.name.is_empty() {
+            match self.mode {
+                TunMode::Tun => "tun%d",
+                TunMode::Tap => "tap%d",
+            }
+        }
+        else {
+            &self.name
+        };
+
+        let mut ifr = ifreq(name)?;
+
+        let mut flags = match self.mode {
+            TunMode::Tun => IFF_TUN,
+            TunMode::Tap => IFF_TAP,
+        };
+
+        if !self.packet_info {
+            flags |= IFF_NO_PI;
+        }
+
+        ifr.ifr_ifru.ifr_flags = flags;
+
+        let mut oflags = O_RDWR | O_CLOEXEC;
+
+        if self.nonblocking {
+            oflags |= O_NONBLOCK;
+        }
+
+        let fd = unsafe { libc::open(TUN_CLONE_DEV.as_ptr(), oflags) };
+
+        if fd == -1 {
+            Err(errno::last_os_error())?
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        ioctl(fd.as_fd(), IoctlOpcode::TunSetIff, Some(&mut ifr))?;
+
+        if let Some(uid) = self.owner {
+            ioctl_val(fd.as_fd(), IoctlOpcode::TunSetOwner, uid as usize)?;
+        }
+
+        if let Some(gid) = self.group {
+            ioctl_val(fd.as_fd(), IoctlOpcode::TunSetGroup, gid as usize)?;
+        }
+
+        let tun = Tun {
+            // kernel fills the real name (`%d` is replaced)
+            name: unsafe {
+                CStr::from_ptr(ifr.ifr_name.as_ptr() as *const c_char)
+            }
+            .to_string_lossy()
+            .into_owned(),
+            fd,
+            mode: self.mode,
+        };
+
+        if self.persist {
+            tun.set_persist(true)?;
+        }
+
+        Ok(tun)
+    }
+}
+
+impl Tun {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mode(&self) -> TunMode {
+        self.mode
+    }
+
+    /// Read one packet (frame), buffer should be larger than MTU (plus
+    /// headers), or packet is truncated
+    pub fn read(&self, buf: &mut [u8]) -> errno::Result<size_t> {
+        loop {
+            match read(self.fd.as_fd(), buf, buf.len()) {
+                Err(PosixError::EINTR) => continue,
+                res => break res,
+            }
+        }
+    }
+
+    /// Write one packet (frame)
+    pub fn write(&self, buf: &[u8]) -> errno::Result<size_t> {
+        loop {
+            match write(self.fd.as_fd(), buf) {
+                Err(PosixError::EINTR) => continue,
+                res => break res,
+            }
+        }
+    }
+
+    /// TUNSETPERSIST, keep device after fd is closed (or not)
+    pub fn set_persist(&self, persist: bool) -> errno::Result<()> {
+        ioctl_val(
+            self.fd.as_fd(),
+            IoctlOpcode::TunSetPersist,
+            persist as usize,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> errno::Result<()> {
+        set_nonblocking(self.fd.as_fd(), nonblocking)
+    }
+}
+
+impl AsFd for Tun {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for Tun {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl TunPacketInfo {
+    /// Split struct tun_pi (`TunConfig::packet_info`) and the packet
+    pub fn split(buf: &[u8]) -> Option<(Self, &[u8])> {
+        if buf.len() < 4 {
+            return None;
+        }
+
+        let pi = Self {
+            flags: u16::from_ne_bytes([buf[0], buf[1]]),
+            proto: u16::from_be_bytes([buf[2], buf[3]]),
+        };
+
+        Some((pi, &buf[4..]))
+    }
+}
+
+impl EthFrameHdr {
+    /// Split Ethernet header and payload of TAP frame
+    pub fn split(frame: &[u8]) -> Option<(Self, &[u8])> {
+        if frame.len() < 14 {
+            return None;
+        }
+
+        let hdr = Self {
+            dst: Mac::from_bytes(&frame[..6]),
+            src: Mac::from_bytes(&frame[6..12]),
+            ethertype: u16::from_be_bytes([frame[12], frame[13]]),
+        };
+
+        Some((hdr, &frame[14..]))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+//// Functions
+
+/// Source and destination address of IPv4/IPv6 packet (TUN)
+pub fn ip_packet_addrs(packet: &[u8]) -> Option<(IpAddr, IpAddr)> {
+    match packet.first()? >> 4 {
+        4 if packet.len() >= 20 => {
+            let src: [u8; 4] = packet[12..16].try_into().unwrap();
+            let dst: [u8; 4] = packet[16..20].try_into().unwrap();
+
+            Some((
+                IpAddr::V4(Ipv4Addr::from_octets(src)),
+                IpAddr::V4(Ipv4Addr::from_octets(dst)),
+            ))
+        }
+        6 if packet.len() >= 40 => {
+            let src: [u8; 16] = packet[8..24].try_into().unwrap();
+            let dst: [u8; 16] = packet[24..40].try_into().unwrap();
+
+            Some((
+                IpAddr::V6(Ipv6Addr::from_octets(src)),
+                IpAddr::V6(Ipv6Addr::from_octets(dst)),
+            ))
+        }
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iface::{IfFlag, get_ifflags};
+
+    #[test]
+    fn test_packet_parse() {
+        let mut packet = [0u8; 20];
+
+        packet[0] = 0x45;
+        packet[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        packet[16..20].copy_from_slice(&[10, 0, 0, 2]);
+
+        assert_eq!(
+            ip_packet_addrs(&packet),
+            Some((
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))
+            ))
+        );
+        assert_eq!(ip_packet_addrs(&packet[..19]), None);
+
+        let (pi, rest) =
+            TunPacketInfo::split(&[0, 0, 0x08, 0x00, 0x45]).unwrap();
+
+        assert_eq!(pi.proto, 0x0800);
+        assert_eq!(rest, [0x45]);
+
+        let mut frame = [0xffu8; 16];
+        frame[12..14].copy_from_slice(&[0x08, 0x06]);
+
+        let (hdr, payload) = EthFrameHdr::split(&frame).unwrap();
+
+        assert_eq!(hdr.ethertype, 0x0806);
+        assert_eq!(payload.len(), 2);
+    }
+
+    #[test]
+    fn test_tun() {
+        // need CAP_NET_ADMIN
+        match TunConfig::new(TunMode::Tun)
+            .name("lxtest-tun0")
+            .nonblocking(true)
+            .open()
+        {
+            Ok(tun) => {
+                assert_eq!(tun.name(), "lxtest-tun0");
+                assert!(get_ifflags(tun.name()).is_ok());
+                assert!(!(get_ifflags(tun.name()).unwrap() & IfFlag::Up));
+
+                // device is down, nothing to read
+                let mut buf = [0u8; 2048];
+
+                assert_eq!(tun.read(&mut buf), Err(PosixError::EAGAIN));
+            }
+            Err(err) => println!("open tun: {err:?}"),
+        }
+
+        assert!(
+            TunConfig::new(TunMode::Tap)
+                .name(&"x".repeat(16))
+                .open()
+                .is_err()
+        );
+    }
+}
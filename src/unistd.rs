@@ -1,12 +1,213 @@
-use std::os::fd::{AsRawFd, BorrowedFd};
+use std::{
+    ffi::{CStr, c_char},
+    fmt::{Debug, Display},
+    ops::{BitAnd, BitOr},
+    os::fd::{AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd},
+    ptr::null_mut,
+};
 
-use libc::{size_t};
+use libc::{c_long, loff_t, off_t, pid_t, size_t};
+use m6tobytes::derive_to_bits;
+use strum::{EnumIter, IntoEnumIterator};
 
-use crate::errno;
+use crate::{
+    errno::{self, PosixError},
+    socket::ExtraBehavior,
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 //// Structures
 
+/// SPLICE_F_XXX
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive_to_bits(u32)]
+#[repr(u32)]
+pub enum SpliceFlag {
+    /// Hint to move pages instead of copying
+    Move = 0x01,
+    /// Don't block on pipe I/O
+    NonBlock = 0x02,
+    /// More data will be coming (like `Msg::MORE`)
+    More = 0x04,
+    /// Unused for `splice` (vmsplice only)
+    Gift = 0x08,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive_to_bits(u32)]
+#[repr(transparent)]
+pub struct SpliceFlags(u32);
+
+/// SEEK_XXX of lseek
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive_to_bits(i32)]
+#[repr(i32)]
+pub enum Whence {
+    /// Offset from start of file
+    Set = 0,
+    /// Offset from current position
+    Cur = 1,
+    /// Offset from end of file
+    End = 2,
+    /// Next data region at or after offset (sparse file)
+    Data = 3,
+    /// Next hole at or after offset (sparse file)
+    Hole = 4,
+}
+
+/// _SC_XXX of sysconf
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive_to_bits(i32)]
+#[repr(i32)]
+pub enum Sysconf {
+    /// Max bytes of argv and environ of exec
+    ArgMax = 0,
+    /// Max processes of real uid (RLIMIT_NPROC)
+    ChildMax = 1,
+    /// Clock ticks per second (of `times`, /proc/PID/stat)
+    ClkTck = 2,
+    NgroupsMax = 3,
+    /// Max fd + 1 (RLIMIT_NOFILE)
+    OpenMax = 4,
+    PageSize = 30,
+    LineMax = 43,
+    /// Max iovec of readv, writev, sendmsg
+    IovMax = 60,
+    LoginNameMax = 71,
+    NprocessorsConf = 83,
+    /// Processors online, may be less than `NprocessorsConf`
+    NprocessorsOnln = 84,
+    PhysPages = 85,
+    /// Free pages of physical memory
+    AvphysPages = 86,
+    HostNameMax = 180,
+}
+
+/// struct utsname, of UTS namespace of the caller
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UtsName {
+    /// "Linux"
+    pub sysname: String,
+    /// Hostname
+    pub nodename: String,
+    /// Kernel release, e.g. "6.1.0-13-amd64"
+    pub release: String,
+    /// Kernel build version
+    pub version: String,
+    /// Hardware, e.g. "x86_64", "aarch64"
+    pub machine: String,
+    /// NIS domain name
+    pub domainname: String,
+}
+
+/// Process (or thread) id
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Pid(pid_t);
+
+////////////////////////////////////////////////////////////////////////////////
+//// Implementations
+
+impl SpliceFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+}
+
+impl Pid {
+    pub const fn from_raw(pid: pid_t) -> Self {
+        Self(pid)
+    }
+
+    pub const fn as_raw(self) -> pid_t {
+        self.0
+    }
+
+    /// getpid
+    pub fn this() -> Self {
+        Self(unsafe { libc::getpid() })
+    }
+
+    /// gettid, id of the calling thread
+    pub fn this_thread() -> Self {
+        Self(unsafe { libc::gettid() })
+    }
+}
+
+impl From<libc::utsname> for UtsName {
+    fn from(uts: libc::utsname) -> Self {
+        Self {
+            sysname: from_c_chars(&uts.sysname),
+            nodename: from_c_chars(&uts.nodename),
+            release: from_c_chars(&uts.release),
+            version: from_c_chars(&uts.version),
+            machine: from_c_chars(&uts.machine),
+            domainname: from_c_chars(&uts.domainname),
+        }
+    }
+}
+
+impl From<pid_t> for Pid {
+    fn from(pid: pid_t) -> Self {
+        Self(pid)
+    }
+}
+
+impl From<Pid> for pid_t {
+    fn from(pid: Pid) -> Self {
+        pid.0
+    }
+}
+
+impl Display for Pid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl BitOr<SpliceFlag> for SpliceFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: SpliceFlag) -> Self::Output {
+        Self(self.0 | rhs.to_bits())
+    }
+}
+
+impl BitOr for SpliceFlag {
+    type Output = SpliceFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        SpliceFlags(self.to_bits() | rhs.to_bits())
+    }
+}
+
+impl BitAnd<SpliceFlag> for &SpliceFlags {
+    type Output = bool;
+
+    fn bitand(self, rhs: SpliceFlag) -> Self::Output {
+        self.0 & rhs.to_bits() != 0
+    }
+}
+
+impl Into<SpliceFlags> for SpliceFlag {
+    fn into(self) -> SpliceFlags {
+        SpliceFlags(self.to_bits())
+    }
+}
+
+impl Debug for SpliceFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in SpliceFlag::iter().filter(|e| self & *e).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{e:?}")?;
+        }
+
+        Ok(())
+    }
+}
 
 ////////////////////////////////////////////////////////////////////////////////
 //// Functions
@@ -27,3 +228,579 @@ pub fn read(
 
     Ok(ret as size_t)
 }
+
+/// Return (read end, write end)
+///
+/// SOCK_NONBLOCK, SOCK_CLOEXEC of `extra_behavior` are the same as
+/// O_NONBLOCK, O_CLOEXEC, apply to both ends.
+pub fn pipe2(
+    extra_behavior: ExtraBehavior,
+) -> errno::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+
+    let ret =
+        unsafe { libc::pipe2(fds.as_mut_ptr(), extra_behavior.to_bits()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(
+        unsafe {
+            (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]))
+        },
+    )
+}
+
+/// Return bytes written, may be less than `buf.len()`
+pub fn write(fd: BorrowedFd, buf: &[u8]) -> errno::Result<size_t> {
+    let ret =
+        unsafe { libc::write(fd.as_raw_fd(), buf.as_ptr() as _, buf.len()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as size_t)
+}
+
+/// Read at `offset` without changing file offset, 0 for EOF
+pub fn pread(
+    fd: BorrowedFd,
+    buf: &mut [u8],
+    offset: off_t,
+) -> errno::Result<size_t> {
+    let ret = unsafe {
+        libc::pread(fd.as_raw_fd(), buf.as_mut_ptr() as _, buf.len(), offset)
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as size_t)
+}
+
+/// Write at `offset` without changing file offset (appended if file is
+/// opened with O_APPEND)
+pub fn pwrite(
+    fd: BorrowedFd,
+    buf: &[u8],
+    offset: off_t,
+) -> errno::Result<size_t> {
+    let ret = unsafe {
+        libc::pwrite(fd.as_raw_fd(), buf.as_ptr() as _, buf.len(), offset)
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as size_t)
+}
+
+/// Reposition file offset, return the new one (from start of file)
+///
+/// ENXIO for `Whence::Data`/`Whence::Hole` if `offset` is beyond EOF,
+/// ESPIPE for pipe, socket.
+pub fn lseek(
+    fd: BorrowedFd,
+    offset: off_t,
+    whence: Whence,
+) -> errno::Result<off_t> {
+    let ret = unsafe { libc::lseek(fd.as_raw_fd(), offset, whence.to_bits()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret)
+}
+
+/// Close explicitly to observe error (e.g. EIO of delayed write on NFS)
+/// that is dropped silently by `OwnedFd`
+///
+/// Fd is released even if it fails, it mustn't be retried (fd may be
+/// reused by other threads), and EINTR is taken as success on Linux.
+pub fn close(fd: OwnedFd) -> errno::Result<()> {
+    let ret = unsafe { libc::close(fd.into_raw_fd()) };
+
+    if ret == -1 {
+        match errno::last_os_error() {
+            PosixError::EINTR => (),
+            err => Err(err)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Flush data and metadata of file to device, retry on EINTR
+///
+/// Error (e.g. EIO) may be reported only once for failed writeback, pages
+/// are marked clean then, so data should be taken as lost instead of
+/// syncing again.
+pub fn fsync(fd: BorrowedFd) -> errno::Result<()> {
+    retry_eintr(|| unsafe { libc::fsync(fd.as_raw_fd()) })
+}
+
+/// Like `fsync` but skip metadata unneeded for reading data back (e.g.
+/// mtime), retry on EINTR
+pub fn fdatasync(fd: BorrowedFd) -> errno::Result<()> {
+    retry_eintr(|| unsafe { libc::fdatasync(fd.as_raw_fd()) })
+}
+
+/// Truncate or extend (with zero) file to `len` bytes, retry on EINTR
+///
+/// File offset is unchanged, EINVAL for fd not opened for writing.
+pub fn ftruncate(fd: BorrowedFd, len: off_t) -> errno::Result<()> {
+    retry_eintr(|| unsafe { libc::ftruncate(fd.as_raw_fd(), len) })
+}
+
+fn retry_eintr(mut f: impl FnMut() -> i32) -> errno::Result<()> {
+    loop {
+        if f() != -1 {
+            break Ok(());
+        }
+
+        match errno::last_os_error() {
+            PosixError::EINTR => continue,
+            err => break Err(err),
+        }
+    }
+}
+
+/// Copy `count` bytes from `in_fd` (mmap-able, e.g. regular file) to
+/// `out_fd` (any file, usually socket) inside kernel
+///
+/// offset: read from it and update it instead of file offset of `in_fd`.
+///
+/// Return bytes transferred, 0 for EOF
+pub fn sendfile(
+    out_fd: BorrowedFd,
+    in_fd: BorrowedFd,
+    offset: Option<&mut off_t>,
+    count: size_t,
+) -> errno::Result<size_t> {
+    let ret = unsafe {
+        libc::sendfile(
+            out_fd.as_raw_fd(),
+            in_fd.as_raw_fd(),
+            offset.map(|off| off as *mut off_t).unwrap_or(null_mut()),
+            count,
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as size_t)
+}
+
+/// Move at most `len` bytes between two fds, one of them must be a pipe
+///
+/// Offset must be `None` for pipe side.
+pub fn splice(
+    fd_in: BorrowedFd,
+    off_in: Option<&mut loff_t>,
+    fd_out: BorrowedFd,
+    off_out: Option<&mut loff_t>,
+    len: size_t,
+    flags: SpliceFlags,
+) -> errno::Result<size_t> {
+    let ret = unsafe {
+        libc::splice(
+            fd_in.as_raw_fd(),
+            off_in.map(|off| off as *mut loff_t).unwrap_or(null_mut()),
+            fd_out.as_raw_fd(),
+            off_out.map(|off| off as *mut loff_t).unwrap_or(null_mut()),
+            len,
+            flags.to_bits(),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as size_t)
+}
+
+/// Duplicate at most `len` bytes from pipe `fd_in` to pipe `fd_out` without
+/// consuming them
+pub fn tee(
+    fd_in: BorrowedFd,
+    fd_out: BorrowedFd,
+    len: size_t,
+    flags: SpliceFlags,
+) -> errno::Result<size_t> {
+    let ret = unsafe {
+        libc::tee(fd_in.as_raw_fd(), fd_out.as_raw_fd(), len, flags.to_bits())
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(ret as size_t)
+}
+
+pub fn getpid() -> Pid {
+    Pid::this()
+}
+
+/// Pid of parent, may change into reaper (e.g. init) if parent exits
+pub fn getppid() -> Pid {
+    Pid(unsafe { libc::getppid() })
+}
+
+pub fn gettid() -> Pid {
+    Pid::this_thread()
+}
+
+/// Create new session and process group led by the caller, which has no
+/// controlling terminal then, return the session id (pid of caller)
+///
+/// EPERM if caller is already a process group leader (fork first).
+pub fn setsid() -> errno::Result<Pid> {
+    let ret = unsafe { libc::setsid() };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(Pid(ret))
+}
+
+/// Session id of `pid` (`None` for the caller)
+pub fn getsid(pid: Option<Pid>) -> errno::Result<Pid> {
+    let ret = unsafe { libc::getsid(pid.map(Pid::as_raw).unwrap_or(0)) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(Pid(ret))
+}
+
+/// Move `pid` (`None` for the caller) into process group `pgid` (`None`
+/// for new group led by `pid`) of the same session
+pub fn setpgid(pid: Option<Pid>, pgid: Option<Pid>) -> errno::Result<()> {
+    let ret = unsafe {
+        libc::setpgid(
+            pid.map(Pid::as_raw).unwrap_or(0),
+            pgid.map(Pid::as_raw).unwrap_or(0),
+        )
+    };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Process group id of `pid` (`None` for the caller)
+pub fn getpgid(pid: Option<Pid>) -> errno::Result<Pid> {
+    let ret = unsafe { libc::getpgid(pid.map(Pid::as_raw).unwrap_or(0)) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(Pid(ret))
+}
+
+/// Make `pgid` the foreground process group of terminal `fd` (controlling
+/// terminal of caller)
+///
+/// Caller of background group gets SIGTTOU unless it's blocked or
+/// ignored, ENOTTY if `fd` isn't the controlling terminal.
+pub fn tcsetpgrp(fd: BorrowedFd, pgid: Pid) -> errno::Result<()> {
+    let ret = unsafe { libc::tcsetpgrp(fd.as_raw_fd(), pgid.as_raw()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Foreground process group of terminal `fd`
+pub fn tcgetpgrp(fd: BorrowedFd) -> errno::Result<Pid> {
+    let ret = unsafe { libc::tcgetpgrp(fd.as_raw_fd()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(Pid(ret))
+}
+
+/// `None` if there is no definite limit
+pub fn sysconf(name: Sysconf) -> errno::Result<Option<c_long>> {
+    unsafe { *libc::__errno_location() = 0 };
+
+    let ret = unsafe { libc::sysconf(name.to_bits()) };
+
+    if ret == -1 {
+        if unsafe { *libc::__errno_location() } == 0 {
+            return Ok(None);
+        }
+
+        Err(errno::last_os_error())?
+    }
+
+    Ok(Some(ret))
+}
+
+/// Size of memory page, e.g. for mmap and ring buffer of packet socket
+pub fn page_size() -> usize {
+    sysconf(Sysconf::PageSize).unwrap().unwrap() as usize
+}
+
+pub fn uname() -> errno::Result<UtsName> {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+
+    let ret = unsafe { libc::uname(&mut uts) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(uts.into())
+}
+
+/// Hostname of UTS namespace of the caller
+pub fn gethostname() -> errno::Result<String> {
+    // HOST_NAME_MAX (64) + nul
+    let mut buf = [0 as c_char; 65];
+
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(from_c_chars(&buf))
+}
+
+/// Need CAP_SYS_ADMIN (of UTS namespace, EPERM), EINVAL if `name` is
+/// longer than 64 bytes
+pub fn sethostname(name: &str) -> errno::Result<()> {
+    let ret = unsafe { libc::sethostname(name.as_ptr() as _, name.len()) };
+
+    if ret == -1 {
+        Err(errno::last_os_error())?
+    }
+
+    Ok(())
+}
+
+/// Nul terminated (or full) char array
+fn from_c_chars(chars: &[c_char]) -> String {
+    let bytes = unsafe {
+        std::slice::from_raw_parts(chars.as_ptr() as *const u8, chars.len())
+    };
+
+    CStr::from_bytes_until_nul(bytes)
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(bytes).into_owned())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        os::fd::{AsFd, FromRawFd, OwnedFd},
+    };
+
+    use super::*;
+
+    fn pipe() -> (OwnedFd, OwnedFd) {
+        pipe2(ExtraBehavior::new().close_on_exec()).unwrap()
+    }
+
+    #[test]
+    fn test_splice_tee() {
+        let (r1, w1) = pipe();
+        let (r2, w2) = pipe();
+        let (r3, w3) = pipe();
+
+        File::from(w1).write_all(b"zero-copy").unwrap();
+
+        // r1 -> w2 (kept in r1) then r1 -> w3
+        assert_eq!(
+            tee(r1.as_fd(), w2.as_fd(), 64, SpliceFlags::new()).unwrap(),
+            9
+        );
+        assert_eq!(
+            splice(
+                r1.as_fd(),
+                None,
+                w3.as_fd(),
+                None,
+                64,
+                SpliceFlag::Move.into()
+            )
+            .unwrap(),
+            9
+        );
+
+        drop((w2, w3));
+
+        for r in [r2, r3] {
+            let mut s = String::new();
+            File::from(r).read_to_string(&mut s).unwrap();
+            assert_eq!(s, "zero-copy");
+        }
+    }
+
+    #[test]
+    fn test_write_seek() {
+        let fd = unsafe {
+            libc::memfd_create(c"lxtest".as_ptr(), libc::MFD_CLOEXEC)
+        };
+        assert!(fd >= 0);
+        let file = unsafe { OwnedFd::from_raw_fd(fd) };
+        let fd = file.as_fd();
+
+        assert_eq!(write(fd, b"hello world").unwrap(), 11);
+        assert_eq!(lseek(fd, 0, Whence::Cur).unwrap(), 11);
+
+        assert_eq!(pwrite(fd, b"W", 6).unwrap(), 1);
+
+        let mut buf = [0u8; 5];
+
+        assert_eq!(pread(fd, &mut buf, 6).unwrap(), 5);
+        assert_eq!(&buf, b"World");
+        // file offset is unchanged
+        assert_eq!(lseek(fd, 0, Whence::Cur).unwrap(), 11);
+
+        assert_eq!(lseek(fd, -5, Whence::End).unwrap(), 6);
+        assert_eq!(read(fd, &mut buf, 5).unwrap(), 5);
+        assert_eq!(&buf, b"World");
+
+        assert_eq!(lseek(fd, 0, Whence::Set).unwrap(), 0);
+        assert_eq!(lseek(fd, 12, Whence::Data), Err(errno::PosixError::ENXIO));
+
+        let (r, _w) = pipe();
+
+        assert_eq!(
+            lseek(r.as_fd(), 0, Whence::Cur),
+            Err(errno::PosixError::ESPIPE)
+        );
+    }
+
+    #[test]
+    fn test_sync_truncate() {
+        let fd = unsafe {
+            libc::memfd_create(c"lxtest".as_ptr(), libc::MFD_CLOEXEC)
+        };
+        assert!(fd >= 0);
+        let file = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        write(file.as_fd(), b"durable").unwrap();
+
+        fsync(file.as_fd()).unwrap();
+        fdatasync(file.as_fd()).unwrap();
+
+        ftruncate(file.as_fd(), 3).unwrap();
+        assert_eq!(lseek(file.as_fd(), 0, Whence::End).unwrap(), 3);
+
+        ftruncate(file.as_fd(), 4096).unwrap();
+        assert_eq!(lseek(file.as_fd(), 0, Whence::End).unwrap(), 4096);
+
+        close(file).unwrap();
+
+        let (r, _w) = pipe();
+
+        assert_eq!(ftruncate(r.as_fd(), 0), Err(PosixError::EINVAL));
+        close(r).unwrap();
+    }
+
+    #[test]
+    fn test_ids() {
+        let pid = getpid();
+
+        assert_eq!(pid.as_raw() as u32, std::process::id());
+        assert_ne!(getppid(), pid);
+        assert!(gettid().as_raw() > 0);
+
+        assert_eq!(getpgid(None).unwrap(), getpgid(Some(pid)).unwrap());
+        assert_eq!(getsid(None).unwrap(), getsid(Some(pid)).unwrap());
+        assert_eq!(
+            getpgid(Some(Pid::from_raw(pid_t::MAX))),
+            Err(PosixError::ESRCH)
+        );
+
+        let (r, _w) = pipe();
+
+        assert_eq!(tcgetpgrp(r.as_fd()), Err(PosixError::ENOTTY));
+        assert_eq!(tcsetpgrp(r.as_fd(), pid), Err(PosixError::ENOTTY));
+    }
+
+    #[test]
+    fn test_sysconf() {
+        assert_eq!(page_size() % 4096, 0);
+        assert!(sysconf(Sysconf::NprocessorsOnln).unwrap().unwrap() >= 1);
+        assert_eq!(sysconf(Sysconf::ClkTck).unwrap(), Some(100));
+        assert!(sysconf(Sysconf::OpenMax).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_uname() {
+        let uts = uname().unwrap();
+
+        assert_eq!(uts.sysname, "Linux");
+        assert!(!uts.release.is_empty());
+        assert_eq!(gethostname().unwrap(), uts.nodename);
+
+        // EPERM is checked first
+        assert!(matches!(
+            sethostname(&"x".repeat(65)),
+            Err(PosixError::EINVAL | PosixError::EPERM)
+        ));
+    }
+
+    #[test]
+    fn test_pipe2() {
+        let (r, w) =
+            pipe2(ExtraBehavior::new().non_block().close_on_exec()).unwrap();
+        let mut buf = [0u8; 4];
+
+        assert_eq!(
+            read(r.as_fd(), &mut buf, 4),
+            Err(errno::PosixError::EAGAIN)
+        );
+        assert_eq!(write(w.as_fd(), b"ping").unwrap(), 4);
+        assert_eq!(read(r.as_fd(), &mut buf, 4).unwrap(), 4);
+
+        let fl = unsafe { libc::fcntl(w.as_raw_fd(), libc::F_GETFL) };
+        let fd = unsafe { libc::fcntl(w.as_raw_fd(), libc::F_GETFD) };
+
+        assert_ne!(fl & libc::O_NONBLOCK, 0);
+        assert_ne!(fd & libc::FD_CLOEXEC, 0);
+    }
+
+    #[test]
+    fn test_sendfile() {
+        let (r, w) = pipe();
+
+        let file = File::open("/proc/self/exe").unwrap();
+        let mut offset = 0;
+
+        let n =
+            sendfile(w.as_fd(), file.as_fd(), Some(&mut offset), 4).unwrap();
+
+        assert_eq!(n, 4);
+        assert_eq!(offset, 4);
+
+        let mut magic = [0u8; 4];
+        File::from(r).read_exact(&mut magic).unwrap();
+        assert_eq!(&magic, b"\x7fELF");
+    }
+}